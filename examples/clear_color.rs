@@ -0,0 +1,31 @@
+//! Sets the clear colour from 8-bit sRGB, the way a colour picker would, and reads it back
+//!
+//! Run with `cargo run --example clear_color`, it checks every value comes back unchanged and the alpha is left alone
+
+use wgpu::Color;
+use wgpu_thing::state::State;
+
+fn main() {
+    env_logger::init();
+    let mut state = pollster::block_on(State::new_headless(320, 240))
+        .expect("there should be an adapter to draw with");
+    state.set_clear_color(Color {
+        a: 0.5,
+        ..Color::BLACK
+    });
+
+    for v in 0..=255 {
+        state.set_clear_color_srgb([v, 255 - v, v / 2]);
+        assert_eq!(
+            state.clear_color_srgb(),
+            [v, 255 - v, v / 2],
+            "{v} should come back the same as it went in"
+        );
+    }
+    assert_eq!(
+        state.clear_color().a,
+        0.5,
+        "setting the colour shouldn't touch the alpha"
+    );
+    println!("Every sRGB clear colour comes back the same as it went in");
+}
@@ -0,0 +1,31 @@
+//! Runs the built-in compute shader over some data, then reads it back to check every element was doubled
+//!
+//! Run with `cargo run --example compute_readback`, it renders headlessly so the dispatch goes through `render()` like it would with a window
+
+use wgpu_thing::{compute::COMPUTE_WORKGROUP_SIZE, state::State};
+
+/// Not a multiple of the workgroup size, so the last workgroup hangs off the end
+const LEN: u32 = 100;
+
+fn main() {
+    env_logger::init();
+    let mut state = pollster::block_on(State::new_headless(320, 240))
+        .expect("there should be an adapter to draw with");
+    let data: Vec<f32> = (0..LEN).map(|i| i as f32).collect();
+    state.set_compute_data(&data);
+    if state.read_compute_data().is_empty() {
+        log::error!("Compute shaders aren't supported here");
+        return;
+    }
+    assert_eq!(
+        state.read_compute_data(),
+        data,
+        "nothing should change before the dispatch"
+    );
+
+    state.dispatch_compute(LEN.div_ceil(COMPUTE_WORKGROUP_SIZE), 1, 1);
+    state.render(1.0).expect("the frame should render");
+    let doubled: Vec<f32> = data.iter().map(|value| value * 2.0).collect();
+    assert_eq!(state.read_compute_data(), doubled);
+    println!("The compute shader doubled all {LEN} elements");
+}
@@ -0,0 +1,67 @@
+//! Lights a floor with a few hundred moving point lights, using the deferred lighting pass
+//!
+//! Run with `cargo run --example deferred_lights`
+
+use std::f32::consts::TAU;
+
+use glam::{Quat, Vec3};
+use wgpu::Color;
+use wgpu_thing::{instance::Instance, light::Light, run::run_with};
+
+/// How many lights there are along each side of the grid they start out in
+const LIGHTS_PER_SIDE: usize = 16;
+/// How many quads there are along each side of the floor, each one 1 unit across
+const FLOOR_SIZE: i32 = 16;
+
+fn main() {
+    let mut time = 0.0;
+    let mut set_up = false;
+    pollster::block_on(run_with(None, None, move |state, dt| {
+        if !set_up {
+            set_up = true;
+            state.enable_deferred_lighting(true);
+            // Just enough to see the floor by, so the point lights stand out
+            state.light = Light::new([0.0, 5.0, 0.0], [0.1, 0.1, 0.1]);
+            // Lying flat, facing up
+            state.set_instances(
+                (-FLOOR_SIZE / 2..FLOOR_SIZE / 2)
+                    .flat_map(|x| (-FLOOR_SIZE / 2..FLOOR_SIZE / 2).map(move |z| (x, z)))
+                    .map(|(x, z)| Instance {
+                        position: Vec3::new(x as f32 + 0.5, -0.5, z as f32 + 0.5),
+                        rotation: Quat::from_rotation_x(-TAU / 4.0),
+                        scale: Vec3::ONE,
+                    })
+                    .collect(),
+            );
+            for i in 0..LIGHTS_PER_SIDE * LIGHTS_PER_SIDE {
+                // A different colour around the hue circle for each light
+                let hue = i as f32 / (LIGHTS_PER_SIDE * LIGHTS_PER_SIDE) as f32 * TAU;
+                let color = Color {
+                    r: (0.5 + 0.5 * hue.cos()) as f64,
+                    g: (0.5 + 0.5 * (hue + TAU / 3.0).cos()) as f64,
+                    b: (0.5 + 0.5 * (hue + 2.0 * TAU / 3.0).cos()) as f64,
+                    a: 1.0,
+                };
+                state.add_light(Vec3::ZERO, color, 1.0);
+            }
+        }
+        if !state.is_deferred_lighting() {
+            // Not supported here, which has already been logged
+            return;
+        }
+
+        time += dt.as_secs_f32();
+        // Each light circles around its own spot on the grid
+        for (i, light) in state.point_lights.iter_mut().enumerate() {
+            let (row, column) = (i / LIGHTS_PER_SIDE, i % LIGHTS_PER_SIDE);
+            let spacing = FLOOR_SIZE as f32 / LIGHTS_PER_SIDE as f32;
+            let centre = Vec3::new(
+                (column as f32 + 0.5) * spacing - FLOOR_SIZE as f32 / 2.0,
+                -0.3,
+                (row as f32 + 0.5) * spacing - FLOOR_SIZE as f32 / 2.0,
+            );
+            let angle = time + i as f32;
+            light.position = (centre + 0.4 * Vec3::new(angle.cos(), 0.0, angle.sin())).to_array();
+        }
+    }));
+}
@@ -0,0 +1,66 @@
+//! Draws the scene in a higher precision HDR format, multisampled where the adapter can, and checks colours above 1 survive until tonemapping
+//!
+//! Run with `cargo run --example hdr_format`, it renders headlessly and only checks the background so MSAA doesn't change what's expected
+
+use wgpu::{Color, TextureFormat};
+use wgpu_thing::{anti_aliasing::AntiAliasing, color, state::State};
+
+/// Tried in order, the first one the adapter can draw the scene in is used
+const FORMATS: [TextureFormat; 2] = [TextureFormat::Rgba32Float, TextureFormat::Rg11b10Float];
+
+fn main() {
+    env_logger::init();
+    let mut state = pollster::block_on(State::new_headless(320, 240))
+        .expect("there should be an adapter to draw with");
+    // Twice as bright as the target can show, then halved before it's written out
+    state.set_clear_color(Color {
+        r: 2.0,
+        g: 0.5,
+        b: 0.0,
+        a: 1.0,
+    });
+    state.set_exposure(0.5);
+
+    let hdr_format = state.hdr_format();
+    state.set_hdr_format(TextureFormat::Rgba8Unorm);
+    assert_eq!(
+        state.hdr_format(),
+        hdr_format,
+        "a unorm format would clip the scene at 1, so it shouldn't be accepted"
+    );
+
+    // Multisampled first, since the resolve into the HDR texture is what's being checked
+    // Without it there's still the format itself to check
+    let combination = [AntiAliasing::Msaa(4), AntiAliasing::None]
+        .into_iter()
+        .flat_map(|anti_aliasing| FORMATS.map(|format| (anti_aliasing, format)))
+        .find(|&(anti_aliasing, format)| {
+            state.set_anti_aliasing(anti_aliasing);
+            state.set_hdr_format(format);
+            state.anti_aliasing() == anti_aliasing && state.hdr_format() == format
+        });
+    let (anti_aliasing, format) = match combination {
+        Some(combination) => combination,
+        None => {
+            log::error!("None of {FORMATS:?} can be drawn into here");
+            return;
+        }
+    };
+
+    let frame = state.capture_frame();
+    let expected = color::to_srgb(Color {
+        r: 1.0,
+        g: 0.25,
+        b: 0.0,
+        a: 1.0,
+    });
+    let background = frame.get_pixel(0, 0).0;
+    assert!(
+        background
+            .iter()
+            .zip(expected)
+            .all(|(&actual, expected)| actual.abs_diff(expected) <= 1),
+        "the background should be {expected:?} in {format:?} with {anti_aliasing:?}, not {background:?}"
+    );
+    println!("{format:?} with {anti_aliasing:?} keeps colours above 1 until they're tonemapped");
+}
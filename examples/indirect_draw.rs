@@ -0,0 +1,167 @@
+//! Lets a compute shader decide how many instances get drawn, through an indirect draw
+//!
+//! Run with `cargo run --example indirect_draw`, it draws headlessly and checks the result is the same as drawing directly
+
+use std::{sync::mpsc, time::Duration};
+
+use glam::{Quat, Vec3};
+use wgpu::{
+    BindGroupDescriptor, BindGroupEntry, BufferDescriptor, BufferUsages, CommandEncoderDescriptor,
+    ComputePassDescriptor, ComputePipelineDescriptor, Maintain, MapMode, ShaderModuleDescriptor,
+    ShaderSource,
+};
+use wgpu_thing::{
+    anti_aliasing::AntiAliasing, indirect::DrawIndexedIndirectArgs, instance::Instance,
+    state::State,
+};
+
+/// Only lets the first instance through, standing in for something like culling on the GPU
+const SHADER: &str = "
+struct DrawIndexedIndirectArgs {
+    index_count: u32,
+    instance_count: u32,
+    first_index: u32,
+    base_vertex: i32,
+    first_instance: u32,
+};
+
+@group(0) @binding(0)
+var<storage, read_write> args: DrawIndexedIndirectArgs;
+
+@compute @workgroup_size(1)
+fn cs_main() {
+    args.instance_count = 1u;
+}
+";
+
+fn main() {
+    env_logger::init();
+    let mut state = pollster::block_on(State::new_headless(320, 240))
+        .expect("there should be an adapter to draw with");
+    let instances: Vec<Instance> = (0..3)
+        .map(|i| Instance {
+            position: Vec3::new(i as f32 - 1.0, 0.0, -(i as f32)),
+            rotation: Quat::from_rotation_y(0.3 * i as f32),
+            scale: Vec3::ONE,
+        })
+        .collect();
+    // Multisampled captures come back empty with some GL drivers, and anti-aliasing isn't what's being checked
+    state.set_anti_aliasing(AntiAliasing::None);
+    state.update(Duration::ZERO);
+
+    // What the compute shader should end up drawing, done the usual way
+    state.set_instances(instances[..1].to_vec());
+    let expected = state.capture_frame();
+    state.set_instances(instances[..2].to_vec());
+    let two = state.capture_frame();
+
+    state.set_instances(instances.clone());
+    let everything = state.capture_frame();
+    state.enable_draw_indirect(true);
+    let draw_indirect = match state.draw_indirect() {
+        Some(draw_indirect) => draw_indirect,
+        None => {
+            log::error!("Indirect draws aren't supported here");
+            return;
+        }
+    };
+
+    let shader = state.device.create_shader_module(ShaderModuleDescriptor {
+        label: Some("Indirect Args Shader"),
+        source: ShaderSource::Wgsl(SHADER.into()),
+    });
+    // Laid out from the shader, since there's only the one binding
+    let pipeline = state
+        .device
+        .create_compute_pipeline(&ComputePipelineDescriptor {
+            label: Some("Indirect Args Pipeline"),
+            layout: None,
+            module: &shader,
+            entry_point: "cs_main",
+        });
+    let bind_group = state.device.create_bind_group(&BindGroupDescriptor {
+        label: Some("Indirect Args Bind Group"),
+        layout: &pipeline.get_bind_group_layout(0),
+        entries: &[BindGroupEntry {
+            binding: 0,
+            resource: draw_indirect.buffer().as_entire_binding(),
+        }],
+    });
+    let mut encoder = state
+        .device
+        .create_command_encoder(&CommandEncoderDescriptor {
+            label: Some("Indirect Args Encoder"),
+        });
+    {
+        let mut compute_pass = encoder.begin_compute_pass(&ComputePassDescriptor {
+            label: Some("Indirect Args Pass"),
+        });
+        compute_pass.set_pipeline(&pipeline);
+        compute_pass.set_bind_group(0, &bind_group, &[]);
+        compute_pass.dispatch_workgroups(1, 1, 1);
+    }
+
+    // Copied out to check what the shader wrote
+    let size = draw_indirect.buffer().size();
+    let staging_buffer = state.device.create_buffer(&BufferDescriptor {
+        label: Some("Indirect Args Staging Buffer"),
+        size,
+        usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+    encoder.copy_buffer_to_buffer(draw_indirect.buffer(), 0, &staging_buffer, 0, size);
+    state.queue.submit(std::iter::once(encoder.finish()));
+    let (sender, receiver) = mpsc::channel();
+    staging_buffer
+        .slice(..)
+        .map_async(MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+    state.device.poll(Maintain::Wait);
+    receiver
+        .recv()
+        .expect("the map callback should have run")
+        .expect("the staging buffer should be mappable");
+    let args: DrawIndexedIndirectArgs =
+        bytemuck::pod_read_unaligned(&staging_buffer.slice(..).get_mapped_range());
+    assert_eq!(
+        args,
+        DrawIndexedIndirectArgs {
+            index_count: state.num_indices,
+            instance_count: 1,
+            ..DrawIndexedIndirectArgs::default()
+        }
+    );
+
+    let actual = state.capture_frame();
+    assert!(
+        actual == expected,
+        "the indirect draw should only draw the first instance"
+    );
+    assert!(
+        actual != everything,
+        "the indirect draw shouldn't draw every instance"
+    );
+    println!("The compute shader's indirect draw matches drawing 1 of 3 instances directly");
+
+    // Still drawing 1 from the compute shader, but the 2 instances left should both be drawn once they change
+    state.set_instances(instances[..2].to_vec());
+    assert!(
+        state.capture_frame() == two,
+        "the args should be reset to draw every instance that's left"
+    );
+    // Args for more instances than there are get ignored, leaving whatever was set before
+    let args = |instance_count| DrawIndexedIndirectArgs {
+        index_count: state.num_indices,
+        instance_count,
+        ..DrawIndexedIndirectArgs::default()
+    };
+    let (one, three) = (args(1), args(3));
+    state.set_draw_indirect_args(one);
+    state.set_draw_indirect_args(three);
+    assert!(
+        state.capture_frame() == expected,
+        "args past the last instance shouldn't be used"
+    );
+    println!("Changing the instances resets the indirect draw to draw all of them, and args past them are ignored");
+}
@@ -0,0 +1,89 @@
+//! Recreates the device as if it had been lost, and checks the settings and texture from beforehand survive it
+//!
+//! Run with `cargo run --example recreate_device`, it draws headlessly before and after to make sure the new device works too
+
+use std::{thread, time::Duration};
+
+use image::{Rgba, RgbaImage};
+use wgpu::{Color, CompareFunction, PresentMode};
+use wgpu_thing::{
+    anti_aliasing::AntiAliasing, assets::AssetState, post_process::Tonemap, state::State,
+};
+
+fn main() {
+    env_logger::init();
+    let mut state = pollster::block_on(State::new_headless(320, 240))
+        .expect("there should be an adapter to draw with");
+    state.update(Duration::ZERO);
+    let checker = state.capture_frame();
+
+    // Anything but the checkerboard, so going back to it shows up
+    let path = std::env::temp_dir().join("recreate_device_texture.png");
+    RgbaImage::from_pixel(4, 4, Rgba([255, 0, 0, 255]))
+        .save(&path)
+        .expect("the texture should be saved");
+    let handle = state.load_texture_async(&path);
+    while state.asset_state(handle) == Some(&AssetState::Loading) {
+        thread::sleep(Duration::from_millis(1));
+        state.update(Duration::ZERO);
+    }
+    assert_eq!(state.asset_state(handle), Some(&AssetState::Ready));
+
+    // Away from the defaults, so carrying them over can't pass by accident
+    state.set_anti_aliasing(AntiAliasing::None);
+    state.set_clear_color(Color {
+        r: 0.25,
+        g: 0.5,
+        b: 0.75,
+        a: 1.0,
+    });
+    state.set_present_mode(PresentMode::Immediate);
+    state.set_tonemap(Tonemap::Aces);
+    state.set_exposure(2.0);
+    state.set_cull_mode(None);
+    state
+        .set_depth_compare(CompareFunction::LessEqual)
+        .expect("the pipelines should build with another depth compare");
+    let before = state.capture_frame();
+    assert!(
+        before != checker,
+        "the loaded texture should be drawn instead of the checkerboard"
+    );
+    // Headless targets only have Fifo, whatever was asked for
+    let present_mode = state.primary_target().config.present_mode;
+    let adapter = state.adapter_info().clone();
+
+    state
+        .recreate_device()
+        .expect("the device should be recreated");
+
+    assert_eq!(state.anti_aliasing(), AntiAliasing::None);
+    assert_eq!(
+        state.clear_color(),
+        Color {
+            r: 0.25,
+            g: 0.5,
+            b: 0.75,
+            a: 1.0,
+        }
+    );
+    assert_eq!(state.primary_target().config.present_mode, present_mode);
+    assert_eq!(state.tonemap(), Tonemap::Aces);
+    assert_eq!(state.exposure(), 2.0);
+    assert_eq!(state.cull_mode(), None);
+    assert_eq!(state.depth_compare(), CompareFunction::LessEqual);
+    assert_eq!(
+        (
+            state.adapter_info().name.as_str(),
+            state.adapter_info().backend
+        ),
+        (adapter.name.as_str(), adapter.backend),
+        "the new device should come from the same adapter"
+    );
+    let after = state.capture_frame();
+    assert!(
+        after == before,
+        "the new device should draw the same as the old one, with the same texture"
+    );
+    println!("The settings carried over to the new device, and it draws the same as the old one");
+}
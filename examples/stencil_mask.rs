@@ -0,0 +1,36 @@
+//! Only draws the scene inside a rectangle in the middle of the window, using the stencil buffer
+//!
+//! Run with `cargo run --example stencil_mask`, hold space to cut a hole instead
+
+use wgpu_thing::{run::run_with, stencil, stencil::MaskRect};
+use winit::event::VirtualKeyCode;
+
+fn main() {
+    // Whether the scene is currently drawn inside the mask (`true`) or outside it
+    let mut inside = None;
+    pollster::block_on(run_with(None, None, move |state, _| {
+        let size = state.primary_target().size;
+        // Half the window, in the middle, following it as it's resized
+        state.set_stencil_mask(Some(MaskRect {
+            x: size.width / 4,
+            y: size.height / 4,
+            width: size.width / 2,
+            height: size.height / 2,
+        }));
+        // Anything but 0, which is what the rest of the stencil gets cleared to
+        state.set_stencil_reference(1);
+
+        let want_inside = !state.input_state.is_key_down(VirtualKeyCode::Space);
+        if inside != Some(want_inside) {
+            let stencil = if want_inside {
+                stencil::inside_mask()
+            } else {
+                stencil::outside_mask()
+            };
+            if let Err(err) = state.set_stencil(stencil) {
+                log::error!("Failed to set the stencil: {err}");
+            }
+            inside = Some(want_inside);
+        }
+    }));
+}
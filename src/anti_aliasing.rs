@@ -0,0 +1,161 @@
+use wgpu::{
+    BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor,
+    BindGroupLayoutEntry, BindingResource, BindingType, Color, ColorTargetState, ColorWrites,
+    CommandEncoder, Device, FragmentState, LoadOp, MultisampleState, Operations, PipelineLayout,
+    PipelineLayoutDescriptor, PrimitiveState, RenderPassColorAttachment, RenderPassDescriptor,
+    RenderPipeline, RenderPipelineDescriptor, SamplerBindingType, ShaderModule,
+    ShaderModuleDescriptor, ShaderSource, ShaderStages, TextureFormat, TextureSampleType,
+    TextureView, TextureViewDimension, VertexState,
+};
+
+use crate::texture::Texture;
+
+/// How the jagged edges of triangles get smoothed out, see `State::set_anti_aliasing()`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AntiAliasing {
+    None,
+    /// Multisampling with this many samples per pixel, only the edges of triangles get extra samples
+    ///
+    /// Looks the best, but costs memory and bandwidth for every sample
+    Msaa(u32),
+    /// Fast approximate anti-aliasing, a pass over the finished scene that blurs along any edges it can find
+    ///
+    /// Costs about the same at any resolution and catches edges MSAA can't (e.g. inside textures), but softens everything a little
+    Fxaa,
+}
+
+impl AntiAliasing {
+    /// One of each, e.g. for listing them in a UI
+    pub const ALL: [AntiAliasing; 3] = [
+        AntiAliasing::None,
+        AntiAliasing::Msaa(4),
+        AntiAliasing::Fxaa,
+    ];
+
+    /// How many samples per pixel the scene gets drawn with
+    pub fn sample_count(self) -> u32 {
+        match self {
+            AntiAliasing::Msaa(count) => count,
+            AntiAliasing::None | AntiAliasing::Fxaa => 1,
+        }
+    }
+}
+
+/// Draws a texture onto the target with FXAA applied, after everything else the scene goes through
+pub struct Fxaa {
+    shader: ShaderModule,
+    layout: PipelineLayout,
+    bind_group_layout: BindGroupLayout,
+}
+
+impl Fxaa {
+    pub fn new(device: &Device) -> Self {
+        let shader = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("FXAA Shader"),
+            source: ShaderSource::Wgsl(include_str!("fxaa.wgsl").into()),
+        });
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("FXAA Bind Group Layout"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: true },
+                        view_dimension: TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                // Has to filter, the samples along the edge land between pixels
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+        let layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("FXAA Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        Self {
+            shader,
+            layout,
+            bind_group_layout,
+        }
+    }
+
+    /// Build the pipeline for a target with `format`, never multisampled since FXAA stands in for that
+    pub fn create_pipeline(&self, device: &Device, format: TextureFormat) -> RenderPipeline {
+        device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("FXAA Pipeline"),
+            layout: Some(&self.layout),
+            vertex: VertexState {
+                module: &self.shader,
+                entry_point: "vs_main",
+                // The triangle is made up in the vertex shader
+                buffers: &[],
+            },
+            fragment: Some(FragmentState {
+                module: &self.shader,
+                entry_point: "fs_main",
+                targets: &[Some(ColorTargetState {
+                    format,
+                    blend: None,
+                    write_mask: ColorWrites::ALL,
+                })],
+            }),
+            primitive: PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: MultisampleState::default(),
+            multiview: None,
+        })
+    }
+
+    /// Bind `input` for sampling, this has to be redone whenever it's recreated
+    pub fn create_bind_group(&self, device: &Device, input: &Texture) -> BindGroup {
+        device.create_bind_group(&BindGroupDescriptor {
+            label: Some("FXAA Bind Group"),
+            layout: &self.bind_group_layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::TextureView(&input.view),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::Sampler(&input.sampler),
+                },
+            ],
+        })
+    }
+
+    /// Record drawing the texture from `bind_group` into `view` with `pipeline` from `create_pipeline()`
+    pub fn apply(
+        &self,
+        encoder: &mut CommandEncoder,
+        view: &TextureView,
+        pipeline: &RenderPipeline,
+        bind_group: &BindGroup,
+    ) {
+        let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+            label: Some("FXAA Pass"),
+            color_attachments: &[Some(RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: Operations {
+                    // Every pixel gets drawn over
+                    load: LoadOp::Clear(Color::BLACK),
+                    store: true,
+                },
+            })],
+            depth_stencil_attachment: None,
+        });
+        render_pass.set_pipeline(pipeline);
+        render_pass.set_bind_group(0, bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
@@ -0,0 +1,100 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Receiver, TryRecvError},
+};
+
+use image::DynamicImage;
+
+use crate::mesh::{self, MeshData};
+
+/// Refers to something passed to one of `State`'s `load_*_async()`s, see `State::asset_state()`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct AssetHandle(pub(crate) u64);
+
+/// How an asset from one of `State`'s `load_*_async()`s is getting on
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AssetState {
+    /// Still being read and decoded, whatever it's replacing is still in use
+    Loading,
+    /// Uploaded to the GPU and in use
+    Ready,
+    /// Couldn't be read or decoded, with why (which has been logged too)
+    Failed(String),
+    /// Finished after a later load that replaces the same thing was asked for, so it was never used
+    Superseded,
+}
+
+/// What a loader thread sends back, decoded but not uploaded
+pub(crate) enum Decoded {
+    Mesh(MeshData),
+    Texture(DynamicImage),
+}
+
+/// An asset being loaded off the main thread, checked on by `State::update()`
+///
+/// Only the reading and decoding happens elsewhere, anything on the GPU is created by whoever polls it
+pub(crate) struct PendingAsset {
+    pub handle: AssetHandle,
+    /// Where it's being loaded from, for logging
+    pub path: PathBuf,
+    result: Receiver<Result<Decoded, String>>,
+}
+
+impl PendingAsset {
+    /// Read the OBJ file at `path` in the background
+    pub fn mesh(handle: AssetHandle, path: &Path) -> Self {
+        Self::spawn(handle, path, |path| {
+            mesh::read_obj(path)
+                .map(Decoded::Mesh)
+                .map_err(|err| err.to_string())
+        })
+    }
+
+    /// Read and decode the image at `path` in the background
+    pub fn texture(handle: AssetHandle, path: &Path) -> Self {
+        Self::spawn(handle, path, |path| {
+            image::open(path)
+                .map(Decoded::Texture)
+                .map_err(|err| err.to_string())
+        })
+    }
+
+    /// `load` runs on a thread of its own, except on the web where there aren't any threads so it runs straight away
+    fn spawn(
+        handle: AssetHandle,
+        path: &Path,
+        load: impl FnOnce(&Path) -> Result<Decoded, String> + Send + 'static,
+    ) -> Self {
+        let (sender, result) = mpsc::channel();
+        let thread_path = path.to_owned();
+        let run = move || {
+            // If the receiver is gone then nobody cares anymore
+            let _ = sender.send(load(&thread_path));
+        };
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Err(err) = std::thread::Builder::new()
+            .name(format!("Loading {}", path.display()))
+            .spawn(run)
+        {
+            // The closure went with the failed spawn, and the sender with it, so `poll()` reports this as a failure
+            log::error!("Failed to start loading {}: {err}", path.display());
+        }
+        #[cfg(target_arch = "wasm32")]
+        run();
+        Self {
+            handle,
+            path: path.to_owned(),
+            result,
+        }
+    }
+
+    /// What was loaded if it's finished, `None` while it's still going
+    pub fn poll(&self) -> Option<Result<Decoded, String>> {
+        match self.result.try_recv() {
+            Ok(result) => Some(result),
+            Err(TryRecvError::Empty) => None,
+            // The thread panicked (or never started) before it could send anything
+            Err(TryRecvError::Disconnected) => Some(Err("the loader thread stopped".to_owned())),
+        }
+    }
+}
@@ -0,0 +1,39 @@
+use wgpu::{BlendComponent, BlendFactor, BlendOperation, BlendState};
+
+/// How the colours we draw get combined with what's already in the target
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BlendMode {
+    /// Overwrite whatever was there
+    #[default]
+    Replace,
+    /// Mix with what was there according to alpha, for transparent geometry
+    Alpha,
+    /// Add to what was there, handy for glowy things like particles
+    Additive,
+}
+
+impl BlendMode {
+    /// Every mode, in the same order as the pipelines built for them
+    pub const ALL: [BlendMode; 3] = [BlendMode::Replace, BlendMode::Alpha, BlendMode::Additive];
+
+    /// Where this mode's pipeline is in `BlendMode::ALL`
+    pub fn index(self) -> usize {
+        self as usize
+    }
+
+    pub fn blend_state(self) -> BlendState {
+        match self {
+            BlendMode::Replace => BlendState::REPLACE,
+            BlendMode::Alpha => BlendState::ALPHA_BLENDING,
+            BlendMode::Additive => BlendState {
+                color: BlendComponent {
+                    src_factor: BlendFactor::SrcAlpha,
+                    dst_factor: BlendFactor::One,
+                    operation: BlendOperation::Add,
+                },
+                // Leave the target's alpha the same as it would be with normal blending
+                alpha: BlendComponent::OVER,
+            },
+        }
+    }
+}
@@ -0,0 +1,303 @@
+use std::num::NonZeroU32;
+
+use wgpu::{
+    AddressMode, BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout,
+    BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingResource, BindingType, BlendComponent,
+    BlendFactor, BlendOperation, BlendState, Color, ColorTargetState, ColorWrites, CommandEncoder,
+    Device, Extent3d, FilterMode, FragmentState, LoadOp, MultisampleState, Operations,
+    PipelineLayout, PipelineLayoutDescriptor, PrimitiveState, RenderPassColorAttachment,
+    RenderPassDescriptor, RenderPipeline, RenderPipelineDescriptor, Sampler, SamplerBindingType,
+    SamplerDescriptor, ShaderModule, ShaderModuleDescriptor, ShaderSource, ShaderStages, Texture,
+    TextureDescriptor, TextureDimension, TextureFormat, TextureSampleType, TextureUsages,
+    TextureView, TextureViewDescriptor, TextureViewDimension, VertexState,
+};
+use winit::dpi::PhysicalSize;
+
+use crate::texture::HDR_FORMAT;
+
+/// How many times the bright parts get halved in size and blurred, more spreads the glow further
+const MAX_LEVELS: u32 = 5;
+/// Bloom is blurred and added together in HDR, so faint glows don't get lost to rounding
+const FORMAT: TextureFormat = HDR_FORMAT;
+
+/// Adds a glow around the bright parts of the scene: they're picked out, blurred at a few sizes, and added back on top
+///
+/// The pipelines are shared, every target gets its own `BloomTextures`
+pub struct Bloom {
+    bind_group_layout: BindGroupLayout,
+    sampler: Sampler,
+    bright_pass: RenderPipeline,
+    downsample: RenderPipeline,
+    /// Like `downsample`, but adds to what's there
+    upsample: RenderPipeline,
+    blur_horizontal: RenderPipeline,
+    blur_vertical: RenderPipeline,
+}
+
+/// Where a target's bloom gets built up, recreated whenever the target is resized
+pub struct BloomTextures {
+    /// Starts out at half the target's size, each mip level halving it again
+    ///
+    /// Mip 0 ends up holding the finished bloom
+    _texture: Texture,
+    /// Where each level goes between the horizontal and vertical blurs, the same size as `texture`
+    _scratch: Texture,
+    /// A view of each mip level of `texture`, for drawing into
+    views: Vec<TextureView>,
+    scratch_views: Vec<TextureView>,
+    /// A bind group for sampling each mip level of `texture`
+    bind_groups: Vec<BindGroup>,
+    scratch_bind_groups: Vec<BindGroup>,
+    /// Samples the scene for the bright pass
+    scene_bind_group: BindGroup,
+}
+
+impl BloomTextures {
+    /// The finished bloom, to be added to the scene
+    pub fn output(&self) -> &TextureView {
+        &self.views[0]
+    }
+}
+
+impl Bloom {
+    pub fn new(device: &Device) -> Self {
+        let shader = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("Bloom Shader"),
+            source: ShaderSource::Wgsl(include_str!("bloom.wgsl").into()),
+        });
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Bloom Bind Group Layout"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: true },
+                        view_dimension: TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+        let layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Bloom Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        // Linear, so resampling averages neighbouring pixels, and clamped so the edges don't pick up the other side
+        let sampler = device.create_sampler(&SamplerDescriptor {
+            label: Some("Bloom Sampler"),
+            address_mode_u: AddressMode::ClampToEdge,
+            address_mode_v: AddressMode::ClampToEdge,
+            address_mode_w: AddressMode::ClampToEdge,
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            mipmap_filter: FilterMode::Nearest,
+            ..Default::default()
+        });
+        let additive = BlendState {
+            color: BlendComponent {
+                src_factor: BlendFactor::One,
+                dst_factor: BlendFactor::One,
+                operation: BlendOperation::Add,
+            },
+            alpha: BlendComponent::REPLACE,
+        };
+        Self {
+            bright_pass: create_pipeline(device, &layout, &shader, "bright_pass", None),
+            downsample: create_pipeline(device, &layout, &shader, "resample", None),
+            upsample: create_pipeline(device, &layout, &shader, "resample", Some(additive)),
+            blur_horizontal: create_pipeline(device, &layout, &shader, "blur_horizontal", None),
+            blur_vertical: create_pipeline(device, &layout, &shader, "blur_vertical", None),
+            bind_group_layout,
+            sampler,
+        }
+    }
+
+    /// Create the textures for a target that's `size` big, with `scene` being what the scene gets drawn into
+    pub fn create_textures(
+        &self,
+        device: &Device,
+        scene: &TextureView,
+        size: PhysicalSize<u32>,
+    ) -> BloomTextures {
+        let size = Extent3d {
+            width: (size.width / 2).max(1),
+            height: (size.height / 2).max(1),
+            depth_or_array_layers: 1,
+        };
+        // Stop before a level would be smaller than a pixel
+        let levels = MAX_LEVELS.min(size.max_mips(TextureDimension::D2));
+        let create_texture = |label| {
+            let texture = device.create_texture(&TextureDescriptor {
+                label: Some(label),
+                size,
+                mip_level_count: levels,
+                sample_count: 1,
+                dimension: TextureDimension::D2,
+                format: FORMAT,
+                usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+            });
+            let views: Vec<_> = (0..levels)
+                .map(|level| {
+                    texture.create_view(&TextureViewDescriptor {
+                        base_mip_level: level,
+                        mip_level_count: NonZeroU32::new(1),
+                        ..Default::default()
+                    })
+                })
+                .collect();
+            let bind_groups = views
+                .iter()
+                .map(|view| self.create_bind_group(device, view))
+                .collect();
+            (texture, views, bind_groups)
+        };
+        let (texture, views, bind_groups) = create_texture("Bloom Texture");
+        let (scratch, scratch_views, scratch_bind_groups) = create_texture("Bloom Scratch Texture");
+        BloomTextures {
+            _texture: texture,
+            _scratch: scratch,
+            views,
+            scratch_views,
+            bind_groups,
+            scratch_bind_groups,
+            scene_bind_group: self.create_bind_group(device, scene),
+        }
+    }
+
+    fn create_bind_group(&self, device: &Device, view: &TextureView) -> BindGroup {
+        device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Bloom Bind Group"),
+            layout: &self.bind_group_layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::TextureView(view),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::Sampler(&self.sampler),
+                },
+            ],
+        })
+    }
+
+    /// Record building the bloom from the scene, which has to have been drawn already
+    pub fn encode(&self, encoder: &mut CommandEncoder, textures: &BloomTextures) {
+        let levels = textures.views.len();
+        draw(
+            encoder,
+            &textures.views[0],
+            &self.bright_pass,
+            &textures.scene_bind_group,
+            false,
+        );
+        // Each level is half the size of the one before
+        for level in 1..levels {
+            draw(
+                encoder,
+                &textures.views[level],
+                &self.downsample,
+                &textures.bind_groups[level - 1],
+                false,
+            );
+        }
+        // Blurring the smaller levels spreads the glow further for the same number of taps
+        for level in 0..levels {
+            draw(
+                encoder,
+                &textures.scratch_views[level],
+                &self.blur_horizontal,
+                &textures.bind_groups[level],
+                false,
+            );
+            draw(
+                encoder,
+                &textures.views[level],
+                &self.blur_vertical,
+                &textures.scratch_bind_groups[level],
+                false,
+            );
+        }
+        // Add each level onto the one above it, from the smallest up, so mip 0 ends up with all of them
+        for level in (1..levels).rev() {
+            draw(
+                encoder,
+                &textures.views[level - 1],
+                &self.upsample,
+                &textures.bind_groups[level],
+                true,
+            );
+        }
+    }
+}
+
+/// Build one of the bloom pipelines, they only differ in the fragment shader and blending
+fn create_pipeline(
+    device: &Device,
+    layout: &PipelineLayout,
+    shader: &ShaderModule,
+    entry_point: &str,
+    blend: Option<BlendState>,
+) -> RenderPipeline {
+    device.create_render_pipeline(&RenderPipelineDescriptor {
+        label: Some(&format!("Bloom {entry_point} Pipeline")),
+        layout: Some(layout),
+        vertex: VertexState {
+            module: shader,
+            entry_point: "vs_main",
+            // The triangle is made up in the vertex shader
+            buffers: &[],
+        },
+        fragment: Some(FragmentState {
+            module: shader,
+            entry_point,
+            targets: &[Some(ColorTargetState {
+                format: FORMAT,
+                blend,
+                write_mask: ColorWrites::ALL,
+            })],
+        }),
+        primitive: PrimitiveState::default(),
+        depth_stencil: None,
+        multisample: MultisampleState::default(),
+        multiview: None,
+    })
+}
+
+/// Draw a fullscreen triangle into `view`, either replacing or keeping (`load`) what's there
+fn draw(
+    encoder: &mut CommandEncoder,
+    view: &TextureView,
+    pipeline: &RenderPipeline,
+    bind_group: &BindGroup,
+    load: bool,
+) {
+    let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+        label: Some("Bloom Pass"),
+        color_attachments: &[Some(RenderPassColorAttachment {
+            view,
+            resolve_target: None,
+            ops: Operations {
+                load: if load {
+                    LoadOp::Load
+                } else {
+                    LoadOp::Clear(Color::BLACK)
+                },
+                store: true,
+            },
+        })],
+        depth_stencil_attachment: None,
+    });
+    render_pass.set_pipeline(pipeline);
+    render_pass.set_bind_group(0, bind_group, &[]);
+    render_pass.draw(0..3, 0..1);
+}
@@ -0,0 +1,63 @@
+// The passes that make up bloom, each one reads a texture and draws a fullscreen triangle into another
+
+// How bright (out of 1) a pixel has to be before it starts to glow
+let THRESHOLD: f32 = 0.8;
+
+@group(0) @binding(0)
+var source: texture_2d<f32>;
+@group(0) @binding(1)
+var source_sampler: sampler;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+};
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    // One triangle big enough to cover the whole target, with corners at (-1, -1), (3, -1) and (-1, 3)
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+    // Texture coordinates start at the top left, clip space at the bottom left
+    out.tex_coords = vec2<f32>(uv.x, 1.0 - uv.y);
+    return out;
+}
+
+// Keep only the parts of the scene bright enough to glow, fading in above the threshold so there's no hard edge
+@fragment
+fn bright_pass(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(source, source_sampler, in.tex_coords).rgb;
+    let brightness = max(color.r, max(color.g, color.b));
+    let contribution = max(brightness - THRESHOLD, 0.0) / max(brightness, 0.0001);
+    return vec4<f32>(color * contribution, 1.0);
+}
+
+// Halving (or doubling) the size with linear filtering averages neighbouring pixels for us
+@fragment
+fn resample(in: VertexOutput) -> @location(0) vec4<f32> {
+    return textureSample(source, source_sampler, in.tex_coords);
+}
+
+// A 9 tap Gaussian blur along `direction` (in texels), the weights are for offsets 0 to 4 on each side
+fn blur(tex_coords: vec2<f32>, direction: vec2<f32>) -> vec4<f32> {
+    var weights = array<f32, 5>(0.227027, 0.1945946, 0.1216216, 0.054054, 0.016216);
+    let texel = direction / vec2<f32>(textureDimensions(source));
+    var color = textureSample(source, source_sampler, tex_coords).rgb * weights[0];
+    for (var i = 1; i < 5; i = i + 1) {
+        let offset = texel * f32(i);
+        color = color + textureSample(source, source_sampler, tex_coords + offset).rgb * weights[i];
+        color = color + textureSample(source, source_sampler, tex_coords - offset).rgb * weights[i];
+    }
+    return vec4<f32>(color, 1.0);
+}
+
+@fragment
+fn blur_horizontal(in: VertexOutput) -> @location(0) vec4<f32> {
+    return blur(in.tex_coords, vec2<f32>(1.0, 0.0));
+}
+
+@fragment
+fn blur_vertical(in: VertexOutput) -> @location(0) vec4<f32> {
+    return blur(in.tex_coords, vec2<f32>(0.0, 1.0));
+}
@@ -0,0 +1,278 @@
+use bytemuck::{Pod, Zeroable};
+use glam::{Mat4, Vec3, Vec4};
+use winit::event::{ElementState, KeyboardInput, MouseButton, VirtualKeyCode, WindowEvent};
+
+use crate::frustum::Frustum;
+
+/// `glam`'s `_gl` projections map depth to -1..1 like OpenGL does, but wgpu (like DirectX/Metal/Vulkan) wants 0..1
+/// This squashes the z axis into that range so things don't get clipped or squished
+#[rustfmt::skip]
+pub const OPENGL_TO_WGPU_MATRIX: Mat4 = Mat4::from_cols(
+    Vec4::new(1.0, 0.0, 0.0, 0.0),
+    Vec4::new(0.0, 1.0, 0.0, 0.0),
+    Vec4::new(0.0, 0.0, 0.5, 0.0),
+    Vec4::new(0.0, 0.0, 0.5, 1.0),
+);
+
+/// How a `Camera` flattens the scene onto the screen
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Projection {
+    /// Things further away look smaller, `fovy` is the vertical field of view in radians
+    Perspective { fovy: f32 },
+    /// Things stay the same size however far away they are, for 2D work
+    ///
+    /// `height` is how many world units fit on screen vertically, the width follows from the aspect ratio
+    Orthographic { height: f32 },
+}
+
+impl Projection {
+    /// The matrix that takes a point in view space to clip space (OpenGL's -1..1 depth), for a target `aspect` wide
+    fn matrix(self, aspect: f32, znear: f32, zfar: f32) -> Mat4 {
+        match self {
+            // Warps the scene to give the effect of depth
+            Projection::Perspective { fovy } => Mat4::perspective_rh_gl(fovy, aspect, znear, zfar),
+            Projection::Orthographic { height } => {
+                let half_height = height / 2.0;
+                let half_width = half_height * aspect;
+                Mat4::orthographic_rh_gl(
+                    -half_width,
+                    half_width,
+                    -half_height,
+                    half_height,
+                    znear,
+                    zfar,
+                )
+            }
+        }
+    }
+}
+
+/// A camera looking from `eye` to `target`
+#[derive(Debug, Clone, Copy)]
+pub struct Camera {
+    pub eye: Vec3,
+    pub target: Vec3,
+    /// Which way is up, almost always `Vec3::Y`
+    pub up: Vec3,
+    /// Width divided by height of whatever we're rendering to
+    pub aspect: f32,
+    /// Perspective by default
+    pub projection: Projection,
+    /// Anything closer than this won't be drawn
+    pub znear: f32,
+    /// Anything further than this won't be drawn
+    pub zfar: f32,
+}
+
+impl Camera {
+    /// A camera a little above and in front of the origin, looking at it
+    pub fn new(aspect: f32) -> Self {
+        Self {
+            eye: Vec3::new(0.0, 1.0, 2.0),
+            target: Vec3::ZERO,
+            up: Vec3::Y,
+            aspect,
+            projection: Projection::Perspective {
+                fovy: 45f32.to_radians(),
+            },
+            znear: 0.1,
+            zfar: 100.0,
+        }
+    }
+
+    /// Partway from `self` (at `t = 0`) to `other` (at `t = 1`), for drawing between two fixed timesteps
+    ///
+    /// Only the position and orientation are blended, everything else comes from `other`
+    pub fn lerp(&self, other: &Camera, t: f32) -> Camera {
+        Camera {
+            eye: self.eye.lerp(other.eye, t),
+            target: self.target.lerp(other.target, t),
+            // Shorter than 1 partway through a turn, and zero if it flips right round
+            up: self
+                .up
+                .lerp(other.up, t)
+                .try_normalize()
+                .unwrap_or(other.up),
+            ..*other
+        }
+    }
+
+    /// The matrix that takes a point in world space to clip space
+    pub fn build_view_projection_matrix(&self) -> Mat4 {
+        // Moves the world to be at the position and rotation of the camera
+        let view = Mat4::look_at_rh(self.eye, self.target, self.up);
+        let proj = self.projection.matrix(self.aspect, self.znear, self.zfar);
+        OPENGL_TO_WGPU_MATRIX * proj * view
+    }
+
+    /// The planes around everything the camera can see, for leaving out what it can't before drawing
+    pub fn frustum(&self) -> Frustum {
+        Frustum::from_view_projection(self.build_view_projection_matrix())
+    }
+
+    /// Takes a point in clip space back to world space as if the camera were at the origin, i.e. to the direction it's looking in
+    ///
+    /// For things that are infinitely far away like the sky, which shouldn't move when the camera does
+    pub fn build_inverse_direction_matrix(&self) -> Mat4 {
+        let view = Mat4::look_at_rh(Vec3::ZERO, self.target - self.eye, self.up);
+        let proj = self.projection.matrix(self.aspect, self.znear, self.zfar);
+        (OPENGL_TO_WGPU_MATRIX * proj * view).inverse()
+    }
+}
+
+/// What actually gets uploaded to the GPU for a `Camera`
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+pub struct CameraUniform {
+    // `Mat4` isn't `Pod`, so we store it as nested arrays
+    pub view_proj: [[f32; 4]; 4],
+    /// Where the camera is, for specular lighting, `w` is just padding
+    pub view_position: [f32; 4],
+}
+
+impl CameraUniform {
+    pub fn new(camera: &Camera) -> Self {
+        Self {
+            view_proj: camera.build_view_projection_matrix().to_cols_array_2d(),
+            view_position: camera.eye.extend(1.0).to_array(),
+        }
+    }
+}
+
+/// Moves a `Camera` around with WASD, and turns it by dragging with the left mouse button (or just moving it, with mouse-look)
+#[derive(Debug)]
+pub struct CameraController {
+    /// Units per second
+    pub speed: f32,
+    /// Radians per unit of raw mouse movement, roughly a pixel
+    pub sensitivity: f32,
+    forward: bool,
+    backward: bool,
+    left: bool,
+    right: bool,
+    /// Whether the mouse button is held down
+    rotating: bool,
+    /// Whether every mouse movement turns the camera, see `set_mouse_look()`
+    mouse_look: bool,
+    /// How far the mouse has moved since the last `update_camera()`
+    yaw_delta: f32,
+    pitch_delta: f32,
+}
+
+impl CameraController {
+    pub fn new(speed: f32, sensitivity: f32) -> Self {
+        Self {
+            speed,
+            sensitivity,
+            forward: false,
+            backward: false,
+            left: false,
+            right: false,
+            rotating: false,
+            mouse_look: false,
+            yaw_delta: 0.0,
+            pitch_delta: 0.0,
+        }
+    }
+
+    /// Returns whether the event was used to control the camera
+    pub fn process_event(&mut self, event: &WindowEvent) -> bool {
+        match event {
+            WindowEvent::KeyboardInput {
+                input:
+                    KeyboardInput {
+                        state,
+                        virtual_keycode: Some(keycode),
+                        ..
+                    },
+                ..
+            } => {
+                let pressed = *state == ElementState::Pressed;
+                match keycode {
+                    VirtualKeyCode::W | VirtualKeyCode::Up => self.forward = pressed,
+                    VirtualKeyCode::S | VirtualKeyCode::Down => self.backward = pressed,
+                    VirtualKeyCode::A | VirtualKeyCode::Left => self.left = pressed,
+                    VirtualKeyCode::D | VirtualKeyCode::Right => self.right = pressed,
+                    _ => return false,
+                }
+                true
+            }
+            WindowEvent::MouseInput {
+                state,
+                button: MouseButton::Left,
+                ..
+            } => {
+                self.rotating = *state == ElementState::Pressed;
+                true
+            }
+            // The button might be let go in another window, where we'd never hear about it
+            WindowEvent::Focused(false) => {
+                self.rotating = false;
+                false
+            }
+            // Turning is done with the raw movement in `process_mouse_motion()`, the cursor is left for the UI and picking
+            _ => false,
+        }
+    }
+
+    /// Turn the camera with every mouse movement rather than only while dragging, for when the cursor's grabbed (see `CursorGrab`)
+    pub fn set_mouse_look(&mut self, on: bool) {
+        self.mouse_look = on;
+    }
+
+    pub fn is_mouse_looking(&self) -> bool {
+        self.mouse_look
+    }
+
+    /// Turn by how far the mouse moved, from `DeviceEvent::MouseMotion`, while dragging or with mouse-look on
+    ///
+    /// Unlike `CursorMoved` this keeps going when the cursor hits the edge of the window (or screen)
+    pub fn process_mouse_motion(&mut self, (dx, dy): (f64, f64)) {
+        if self.rotating || self.mouse_look {
+            self.yaw_delta += dx as f32 * self.sensitivity;
+            self.pitch_delta += dy as f32 * self.sensitivity;
+        }
+    }
+
+    /// Move and turn `camera` based on what's been pressed, `dt` is the time since the last update in seconds
+    pub fn update_camera(&mut self, camera: &mut Camera, dt: f32) {
+        let offset = camera.target - camera.eye;
+        let distance = offset.length();
+        let mut forward = offset.normalize();
+
+        // Turn by converting the direction into angles, nudging them, and converting back
+        if self.yaw_delta != 0.0 || self.pitch_delta != 0.0 {
+            let yaw = forward.z.atan2(forward.x) + self.yaw_delta;
+            // Stop just short of straight up/down, otherwise `look_at` gets confused about which way is up
+            let pitch = (forward.y.asin() - self.pitch_delta).clamp(-MAX_PITCH, MAX_PITCH);
+            forward = Vec3::new(
+                pitch.cos() * yaw.cos(),
+                pitch.sin(),
+                pitch.cos() * yaw.sin(),
+            );
+            self.yaw_delta = 0.0;
+            self.pitch_delta = 0.0;
+        }
+
+        let right = forward.cross(camera.up).normalize();
+        let mut movement = Vec3::ZERO;
+        if self.forward {
+            movement += forward;
+        }
+        if self.backward {
+            movement -= forward;
+        }
+        if self.right {
+            movement += right;
+        }
+        if self.left {
+            movement -= right;
+        }
+        camera.eye += movement.normalize_or_zero() * self.speed * dt;
+        // Keep the target the same distance in front of the eye
+        camera.target = camera.eye + forward * distance;
+    }
+}
+
+/// Just under 90 degrees
+const MAX_PITCH: f32 = std::f32::consts::FRAC_PI_2 - 0.01;
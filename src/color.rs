@@ -0,0 +1,77 @@
+use wgpu::Color;
+
+/// Convert an 8-bit sRGB colour (like you'd get from a colour picker) into the linear `Color` wgpu expects
+///
+/// This is what makes a colour look the same on screen as it did in the picker, as long as the surface is sRGB (see `State::is_srgb()`)
+/// Alpha isn't gamma encoded, so it's just scaled to 0-1
+pub fn from_srgb(r: u8, g: u8, b: u8, a: u8) -> Color {
+    Color {
+        r: srgb_to_linear(r),
+        g: srgb_to_linear(g),
+        b: srgb_to_linear(b),
+        a: a as f64 / 255.0,
+    }
+}
+
+/// The sRGB transfer function, see https://en.wikipedia.org/wiki/SRGB#From_sRGB_to_CIE_XYZ
+fn srgb_to_linear(value: u8) -> f64 {
+    let value = value as f64 / 255.0;
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// The inverse of `from_srgb()`, rounding to the nearest 8-bit value
+pub fn to_srgb(color: Color) -> [u8; 4] {
+    [
+        linear_to_srgb(color.r),
+        linear_to_srgb(color.g),
+        linear_to_srgb(color.b),
+        (color.a.clamp(0.0, 1.0) * 255.0).round() as u8,
+    ]
+}
+
+/// The inverse of `srgb_to_linear()`
+fn linear_to_srgb(value: f64) -> u8 {
+    let value = value.clamp(0.0, 1.0);
+    let value = if value <= 0.0031308 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    };
+    (value * 255.0).round() as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn srgb_round_trips() {
+        for v in 0..=255 {
+            assert_eq!(
+                to_srgb(from_srgb(v, v, v, v)),
+                [v; 4],
+                "{v} didn't round trip"
+            );
+        }
+    }
+
+    #[test]
+    fn channels_stay_separate() {
+        assert_eq!(to_srgb(from_srgb(255, 128, 0, 64)), [255, 128, 0, 64]);
+    }
+
+    #[test]
+    fn out_of_range_is_clamped() {
+        let color = Color {
+            r: -1.0,
+            g: 2.0,
+            b: 0.5,
+            a: 1.5,
+        };
+        assert_eq!(to_srgb(color), [0, 255, 188, 255]);
+    }
+}
@@ -0,0 +1,514 @@
+use std::{error::Error, fmt, io};
+
+use ddsfile::{D3DFormat, Dds, DxgiFormat};
+use image::{ImageError, RgbaImage};
+use wgpu::{AstcBlock, AstcChannel, Extent3d, TextureFormat};
+
+/// What every KTX2 file starts with
+const KTX2_MAGIC: [u8; 12] = [
+    0xAB, 0x4B, 0x54, 0x58, 0x20, 0x32, 0x30, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A,
+];
+
+/// Everything that can go wrong loading a compressed texture
+#[derive(Debug)]
+pub enum CompressedTextureError {
+    Io(io::Error),
+    Dds(ddsfile::Error),
+    Ktx2(ktx2::ParseError),
+    /// Neither a DDS nor a KTX2 file
+    UnknownContainer,
+    /// Not one of the BC or ASTC formats
+    UnsupportedFormat(String),
+    /// KTX2's supercompression (e.g. Zstandard or Basis Universal) isn't handled
+    Supercompressed,
+    /// Only plain 2D textures are handled, not arrays, cube maps or 3D textures
+    Not2d,
+    /// A mip level is shorter than its size says it should be
+    Truncated,
+    /// The device doesn't support the format, and decoding it on the CPU failed
+    Decode(String),
+    /// The decoded image couldn't be uploaded, e.g. it's too big for the device
+    Image(ImageError),
+}
+
+impl fmt::Display for CompressedTextureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CompressedTextureError::Io(err) => write!(f, "failed to read the file: {err}"),
+            CompressedTextureError::Dds(err) => write!(f, "failed to parse the DDS file: {err}"),
+            CompressedTextureError::Ktx2(err) => write!(f, "failed to parse the KTX2 file: {err}"),
+            CompressedTextureError::UnknownContainer => {
+                write!(f, "the file is neither a DDS nor a KTX2 file")
+            }
+            CompressedTextureError::UnsupportedFormat(format) => {
+                write!(f, "{format} isn't a supported BC or ASTC format")
+            }
+            CompressedTextureError::Supercompressed => {
+                write!(f, "supercompressed KTX2 files aren't supported")
+            }
+            CompressedTextureError::Not2d => {
+                write!(
+                    f,
+                    "only 2D textures are supported, not arrays, cube maps or 3D textures"
+                )
+            }
+            CompressedTextureError::Truncated => write!(f, "the file is missing some of its data"),
+            CompressedTextureError::Decode(err) => write!(f, "failed to decode the texture: {err}"),
+            CompressedTextureError::Image(err) => write!(f, "failed to upload the texture: {err}"),
+        }
+    }
+}
+
+impl Error for CompressedTextureError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            CompressedTextureError::Io(err) => Some(err),
+            CompressedTextureError::Dds(err) => Some(err),
+            CompressedTextureError::Ktx2(err) => Some(err),
+            CompressedTextureError::Image(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for CompressedTextureError {
+    fn from(err: io::Error) -> Self {
+        CompressedTextureError::Io(err)
+    }
+}
+
+impl From<ddsfile::Error> for CompressedTextureError {
+    fn from(err: ddsfile::Error) -> Self {
+        CompressedTextureError::Dds(err)
+    }
+}
+
+impl From<ktx2::ParseError> for CompressedTextureError {
+    fn from(err: ktx2::ParseError) -> Self {
+        CompressedTextureError::Ktx2(err)
+    }
+}
+
+impl From<ImageError> for CompressedTextureError {
+    fn from(err: ImageError) -> Self {
+        CompressedTextureError::Image(err)
+    }
+}
+
+/// A texture still in the blocks it was compressed into, read from a DDS or KTX2 file
+pub struct CompressedImage {
+    /// One of the BC or ASTC formats
+    pub format: TextureFormat,
+    pub width: u32,
+    pub height: u32,
+    /// The blocks of each mip level, starting with the full size one
+    pub levels: Vec<Vec<u8>>,
+}
+
+impl CompressedImage {
+    /// Read a DDS or KTX2 file that's already in memory, telling them apart by how they start
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, CompressedTextureError> {
+        if bytes.starts_with(b"DDS ") {
+            Self::from_dds(bytes)
+        } else if bytes.starts_with(&KTX2_MAGIC) {
+            Self::from_ktx2(bytes)
+        } else {
+            Err(CompressedTextureError::UnknownContainer)
+        }
+    }
+
+    fn from_dds(bytes: &[u8]) -> Result<Self, CompressedTextureError> {
+        let dds = Dds::read(bytes)?;
+        // Older files only have the D3D format, newer ones (including everything BC4 and up) the DXGI one
+        let format = match (dds.get_dxgi_format(), dds.get_d3d_format()) {
+            (Some(format), _) => dxgi_format(format).ok_or_else(|| unsupported_format(format))?,
+            (None, Some(format)) => d3d_format(format).ok_or_else(|| unsupported_format(format))?,
+            (None, None) => return Err(unsupported_format("The file's format")),
+        };
+        if dds.get_num_array_layers() > 1 || dds.get_depth() > 1 {
+            return Err(CompressedTextureError::Not2d);
+        }
+        let (width, height) = (dds.get_width(), dds.get_height());
+        // The levels are packed one after the other, each half the size of the last
+        // Only fails if the file's shorter than its header says
+        let mut data = dds.get_data(0).map_err(|err| match err {
+            ddsfile::Error::OutOfBounds => CompressedTextureError::Truncated,
+            err => err.into(),
+        })?;
+        let mut levels = Vec::new();
+        for level in 0..dds.get_num_mipmap_levels().max(1) {
+            let size = level_byte_size(format, width, height, level);
+            if data.len() < size {
+                return Err(CompressedTextureError::Truncated);
+            }
+            let (level_data, rest) = data.split_at(size);
+            levels.push(level_data.to_vec());
+            data = rest;
+        }
+        Ok(Self {
+            format,
+            width,
+            height,
+            levels,
+        })
+    }
+
+    fn from_ktx2(bytes: &[u8]) -> Result<Self, CompressedTextureError> {
+        let reader = ktx2::Reader::new(bytes)?;
+        let header = reader.header();
+        if header.supercompression_scheme.is_some() {
+            return Err(CompressedTextureError::Supercompressed);
+        }
+        if header.layer_count > 1 || header.face_count > 1 || header.pixel_depth > 0 {
+            return Err(CompressedTextureError::Not2d);
+        }
+        let format = match header.format {
+            Some(format) => ktx2_format(format).ok_or_else(|| unsupported_format(format))?,
+            None => return Err(unsupported_format("VK_FORMAT_UNDEFINED")),
+        };
+        let (width, height) = (header.pixel_width, header.pixel_height.max(1));
+        let levels = reader
+            .levels()
+            .enumerate()
+            .map(|(level, data)| {
+                let size = level_byte_size(format, width, height, level as u32);
+                match data.data.get(..size) {
+                    Some(data) => Ok(data.to_vec()),
+                    None => Err(CompressedTextureError::Truncated),
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self {
+            format,
+            width,
+            height,
+            levels,
+        })
+    }
+
+    /// How big `level` is in pixels, not rounded up to whole blocks
+    pub fn level_size(&self, level: u32) -> Extent3d {
+        Extent3d {
+            width: (self.width >> level).max(1),
+            height: (self.height >> level).max(1),
+            depth_or_array_layers: 1,
+        }
+    }
+
+    /// Decode the full size level into plain RGBA, for devices that can't sample the format themselves
+    ///
+    /// Signed formats can't be decoded, and HDR ones get clamped
+    pub fn decode(&self) -> Result<RgbaImage, CompressedTextureError> {
+        let (width, height) = (self.width as usize, self.height as usize);
+        let data = &self.levels[0];
+        // Each pixel comes out as BGRA packed into a `u32`
+        let mut pixels = vec![0; width * height];
+        let result = match self.format {
+            // BC1 can have 1-bit alpha, which the plain BC1 decoder ignores
+            TextureFormat::Bc1RgbaUnorm | TextureFormat::Bc1RgbaUnormSrgb => {
+                texture2ddecoder::decode_bc1a(data, width, height, &mut pixels)
+            }
+            TextureFormat::Bc2RgbaUnorm | TextureFormat::Bc2RgbaUnormSrgb => {
+                texture2ddecoder::decode_bc2(data, width, height, &mut pixels)
+            }
+            TextureFormat::Bc3RgbaUnorm | TextureFormat::Bc3RgbaUnormSrgb => {
+                texture2ddecoder::decode_bc3(data, width, height, &mut pixels)
+            }
+            TextureFormat::Bc4RUnorm => {
+                texture2ddecoder::decode_bc4(data, width, height, &mut pixels)
+            }
+            TextureFormat::Bc5RgUnorm => {
+                texture2ddecoder::decode_bc5(data, width, height, &mut pixels)
+            }
+            TextureFormat::Bc6hRgbUfloat => {
+                texture2ddecoder::decode_bc6_unsigned(data, width, height, &mut pixels)
+            }
+            TextureFormat::Bc7RgbaUnorm | TextureFormat::Bc7RgbaUnormSrgb => {
+                texture2ddecoder::decode_bc7(data, width, height, &mut pixels)
+            }
+            TextureFormat::Astc {
+                block,
+                channel: AstcChannel::Unorm | AstcChannel::UnormSrgb,
+            } => {
+                let (block_width, block_height) = astc_block_dimensions(block);
+                texture2ddecoder::decode_astc(
+                    data,
+                    width,
+                    height,
+                    block_width as usize,
+                    block_height as usize,
+                    &mut pixels,
+                )
+            }
+            format => {
+                return Err(CompressedTextureError::Decode(format!(
+                    "there's no decoder for {format:?}"
+                )))
+            }
+        };
+        result.map_err(|err| CompressedTextureError::Decode(err.to_owned()))?;
+        let rgba = pixels
+            .into_iter()
+            .flat_map(|pixel| {
+                let [b, g, r, a] = pixel.to_le_bytes();
+                [r, g, b, a]
+            })
+            .collect();
+        Ok(RgbaImage::from_raw(self.width, self.height, rgba)
+            .expect("there's a pixel for every one in the image"))
+    }
+}
+
+/// The same format, but sampled as sRGB (or not) according to `srgb`
+///
+/// Formats without an sRGB version (e.g. BC4, BC5 and BC6H) are left as they are
+pub fn with_srgb(format: TextureFormat, srgb: bool) -> TextureFormat {
+    use TextureFormat::*;
+    match (format, srgb) {
+        (Bc1RgbaUnorm | Bc1RgbaUnormSrgb, true) => Bc1RgbaUnormSrgb,
+        (Bc1RgbaUnorm | Bc1RgbaUnormSrgb, false) => Bc1RgbaUnorm,
+        (Bc2RgbaUnorm | Bc2RgbaUnormSrgb, true) => Bc2RgbaUnormSrgb,
+        (Bc2RgbaUnorm | Bc2RgbaUnormSrgb, false) => Bc2RgbaUnorm,
+        (Bc3RgbaUnorm | Bc3RgbaUnormSrgb, true) => Bc3RgbaUnormSrgb,
+        (Bc3RgbaUnorm | Bc3RgbaUnormSrgb, false) => Bc3RgbaUnorm,
+        (Bc7RgbaUnorm | Bc7RgbaUnormSrgb, true) => Bc7RgbaUnormSrgb,
+        (Bc7RgbaUnorm | Bc7RgbaUnormSrgb, false) => Bc7RgbaUnorm,
+        (
+            Astc {
+                block,
+                channel: AstcChannel::Unorm | AstcChannel::UnormSrgb,
+            },
+            srgb,
+        ) => Astc {
+            block,
+            channel: if srgb {
+                AstcChannel::UnormSrgb
+            } else {
+                AstcChannel::Unorm
+            },
+        },
+        (format, _) => format,
+    }
+}
+
+/// How many bytes `level` of a `width` by `height` texture takes up, rounded up to whole blocks
+fn level_byte_size(format: TextureFormat, width: u32, height: u32, level: u32) -> usize {
+    let info = format.describe();
+    let (block_width, block_height) = info.block_dimensions;
+    let blocks_wide = (width >> level).max(1).div_ceil(block_width as u32);
+    let blocks_high = (height >> level).max(1).div_ceil(block_height as u32);
+    blocks_wide as usize * blocks_high as usize * info.block_size as usize
+}
+
+fn unsupported_format(format: impl fmt::Debug) -> CompressedTextureError {
+    CompressedTextureError::UnsupportedFormat(format!("{format:?}"))
+}
+
+fn dxgi_format(format: DxgiFormat) -> Option<TextureFormat> {
+    Some(match format {
+        DxgiFormat::BC1_Typeless | DxgiFormat::BC1_UNorm => TextureFormat::Bc1RgbaUnorm,
+        DxgiFormat::BC1_UNorm_sRGB => TextureFormat::Bc1RgbaUnormSrgb,
+        DxgiFormat::BC2_Typeless | DxgiFormat::BC2_UNorm => TextureFormat::Bc2RgbaUnorm,
+        DxgiFormat::BC2_UNorm_sRGB => TextureFormat::Bc2RgbaUnormSrgb,
+        DxgiFormat::BC3_Typeless | DxgiFormat::BC3_UNorm => TextureFormat::Bc3RgbaUnorm,
+        DxgiFormat::BC3_UNorm_sRGB => TextureFormat::Bc3RgbaUnormSrgb,
+        DxgiFormat::BC4_Typeless | DxgiFormat::BC4_UNorm => TextureFormat::Bc4RUnorm,
+        DxgiFormat::BC4_SNorm => TextureFormat::Bc4RSnorm,
+        DxgiFormat::BC5_Typeless | DxgiFormat::BC5_UNorm => TextureFormat::Bc5RgUnorm,
+        DxgiFormat::BC5_SNorm => TextureFormat::Bc5RgSnorm,
+        DxgiFormat::BC6H_Typeless | DxgiFormat::BC6H_UF16 => TextureFormat::Bc6hRgbUfloat,
+        DxgiFormat::BC6H_SF16 => TextureFormat::Bc6hRgbSfloat,
+        DxgiFormat::BC7_Typeless | DxgiFormat::BC7_UNorm => TextureFormat::Bc7RgbaUnorm,
+        DxgiFormat::BC7_UNorm_sRGB => TextureFormat::Bc7RgbaUnormSrgb,
+        _ => return None,
+    })
+}
+
+fn d3d_format(format: D3DFormat) -> Option<TextureFormat> {
+    Some(match format {
+        D3DFormat::DXT1 => TextureFormat::Bc1RgbaUnorm,
+        // DXT2 and DXT4 are the premultiplied versions, which is up to the shader
+        D3DFormat::DXT2 | D3DFormat::DXT3 => TextureFormat::Bc2RgbaUnorm,
+        D3DFormat::DXT4 | D3DFormat::DXT5 => TextureFormat::Bc3RgbaUnorm,
+        _ => return None,
+    })
+}
+
+fn ktx2_format(format: ktx2::Format) -> Option<TextureFormat> {
+    use ktx2::Format as Vk;
+    let astc = |block, srgb| TextureFormat::Astc {
+        block,
+        channel: if srgb {
+            AstcChannel::UnormSrgb
+        } else {
+            AstcChannel::Unorm
+        },
+    };
+    Some(match format {
+        // BC1 without alpha decodes the same, the alpha just always comes out opaque
+        Vk::BC1_RGB_UNORM_BLOCK | Vk::BC1_RGBA_UNORM_BLOCK => TextureFormat::Bc1RgbaUnorm,
+        Vk::BC1_RGB_SRGB_BLOCK | Vk::BC1_RGBA_SRGB_BLOCK => TextureFormat::Bc1RgbaUnormSrgb,
+        Vk::BC2_UNORM_BLOCK => TextureFormat::Bc2RgbaUnorm,
+        Vk::BC2_SRGB_BLOCK => TextureFormat::Bc2RgbaUnormSrgb,
+        Vk::BC3_UNORM_BLOCK => TextureFormat::Bc3RgbaUnorm,
+        Vk::BC3_SRGB_BLOCK => TextureFormat::Bc3RgbaUnormSrgb,
+        Vk::BC4_UNORM_BLOCK => TextureFormat::Bc4RUnorm,
+        Vk::BC4_SNORM_BLOCK => TextureFormat::Bc4RSnorm,
+        Vk::BC5_UNORM_BLOCK => TextureFormat::Bc5RgUnorm,
+        Vk::BC5_SNORM_BLOCK => TextureFormat::Bc5RgSnorm,
+        Vk::BC6H_UFLOAT_BLOCK => TextureFormat::Bc6hRgbUfloat,
+        Vk::BC6H_SFLOAT_BLOCK => TextureFormat::Bc6hRgbSfloat,
+        Vk::BC7_UNORM_BLOCK => TextureFormat::Bc7RgbaUnorm,
+        Vk::BC7_SRGB_BLOCK => TextureFormat::Bc7RgbaUnormSrgb,
+        Vk::ASTC_4x4_UNORM_BLOCK => astc(AstcBlock::B4x4, false),
+        Vk::ASTC_4x4_SRGB_BLOCK => astc(AstcBlock::B4x4, true),
+        Vk::ASTC_5x4_UNORM_BLOCK => astc(AstcBlock::B5x4, false),
+        Vk::ASTC_5x4_SRGB_BLOCK => astc(AstcBlock::B5x4, true),
+        Vk::ASTC_5x5_UNORM_BLOCK => astc(AstcBlock::B5x5, false),
+        Vk::ASTC_5x5_SRGB_BLOCK => astc(AstcBlock::B5x5, true),
+        Vk::ASTC_6x5_UNORM_BLOCK => astc(AstcBlock::B6x5, false),
+        Vk::ASTC_6x5_SRGB_BLOCK => astc(AstcBlock::B6x5, true),
+        Vk::ASTC_6x6_UNORM_BLOCK => astc(AstcBlock::B6x6, false),
+        Vk::ASTC_6x6_SRGB_BLOCK => astc(AstcBlock::B6x6, true),
+        Vk::ASTC_8x5_UNORM_BLOCK => astc(AstcBlock::B8x5, false),
+        Vk::ASTC_8x5_SRGB_BLOCK => astc(AstcBlock::B8x5, true),
+        Vk::ASTC_8x6_UNORM_BLOCK => astc(AstcBlock::B8x6, false),
+        Vk::ASTC_8x6_SRGB_BLOCK => astc(AstcBlock::B8x6, true),
+        Vk::ASTC_8x8_UNORM_BLOCK => astc(AstcBlock::B8x8, false),
+        Vk::ASTC_8x8_SRGB_BLOCK => astc(AstcBlock::B8x8, true),
+        Vk::ASTC_10x5_UNORM_BLOCK => astc(AstcBlock::B10x5, false),
+        Vk::ASTC_10x5_SRGB_BLOCK => astc(AstcBlock::B10x5, true),
+        Vk::ASTC_10x6_UNORM_BLOCK => astc(AstcBlock::B10x6, false),
+        Vk::ASTC_10x6_SRGB_BLOCK => astc(AstcBlock::B10x6, true),
+        Vk::ASTC_10x8_UNORM_BLOCK => astc(AstcBlock::B10x8, false),
+        Vk::ASTC_10x8_SRGB_BLOCK => astc(AstcBlock::B10x8, true),
+        Vk::ASTC_10x10_UNORM_BLOCK => astc(AstcBlock::B10x10, false),
+        Vk::ASTC_10x10_SRGB_BLOCK => astc(AstcBlock::B10x10, true),
+        Vk::ASTC_12x10_UNORM_BLOCK => astc(AstcBlock::B12x10, false),
+        Vk::ASTC_12x10_SRGB_BLOCK => astc(AstcBlock::B12x10, true),
+        Vk::ASTC_12x12_UNORM_BLOCK => astc(AstcBlock::B12x12, false),
+        Vk::ASTC_12x12_SRGB_BLOCK => astc(AstcBlock::B12x12, true),
+        _ => return None,
+    })
+}
+
+fn astc_block_dimensions(block: AstcBlock) -> (u8, u8) {
+    TextureFormat::Astc {
+        block,
+        channel: AstcChannel::Unorm,
+    }
+    .describe()
+    .block_dimensions
+}
+
+#[cfg(test)]
+mod tests {
+    use ddsfile::{AlphaMode, D3D10ResourceDimension, NewDxgiParams};
+
+    use super::*;
+
+    /// A blank BC1 texture with every mip level down to 1x1, as a DDS file
+    fn bc1_dds(width: u32, height: u32, mipmap_levels: u32) -> Dds {
+        Dds::new_dxgi(NewDxgiParams {
+            height,
+            width,
+            depth: None,
+            format: DxgiFormat::BC1_UNorm,
+            mipmap_levels: Some(mipmap_levels),
+            array_layers: None,
+            caps2: None,
+            is_cubemap: false,
+            resource_dimension: D3D10ResourceDimension::Texture2D,
+            alpha_mode: AlphaMode::Unknown,
+        })
+        .expect("the parameters should make a valid DDS")
+    }
+
+    fn to_bytes(dds: &Dds) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        dds.write(&mut bytes).expect("writing to a Vec can't fail");
+        bytes
+    }
+
+    #[test]
+    fn level_byte_size_rounds_up_to_whole_blocks() {
+        // BC1 is 8 bytes per 4x4 block
+        let format = TextureFormat::Bc1RgbaUnorm;
+        assert_eq!(level_byte_size(format, 64, 64, 0), 16 * 16 * 8);
+        assert_eq!(level_byte_size(format, 64, 64, 1), 8 * 8 * 8);
+        assert_eq!(level_byte_size(format, 62, 62, 0), 16 * 16 * 8);
+        // The smallest levels still take a whole block
+        assert_eq!(level_byte_size(format, 64, 64, 6), 8);
+        assert_eq!(level_byte_size(format, 64, 16, 5), 8);
+        // BC7 is 16 bytes per block
+        assert_eq!(
+            level_byte_size(TextureFormat::Bc7RgbaUnorm, 8, 4, 0),
+            2 * 16
+        );
+    }
+
+    #[test]
+    fn with_srgb_switches_both_ways() {
+        use TextureFormat::*;
+        assert_eq!(with_srgb(Bc1RgbaUnorm, true), Bc1RgbaUnormSrgb);
+        assert_eq!(with_srgb(Bc1RgbaUnormSrgb, false), Bc1RgbaUnorm);
+        assert_eq!(with_srgb(Bc7RgbaUnorm, false), Bc7RgbaUnorm);
+        let astc = |channel| Astc {
+            block: AstcBlock::B6x6,
+            channel,
+        };
+        assert_eq!(
+            with_srgb(astc(AstcChannel::Unorm), true),
+            astc(AstcChannel::UnormSrgb)
+        );
+        assert_eq!(
+            with_srgb(astc(AstcChannel::UnormSrgb), false),
+            astc(AstcChannel::Unorm)
+        );
+    }
+
+    #[test]
+    fn with_srgb_leaves_formats_without_an_srgb_version() {
+        use TextureFormat::*;
+        assert_eq!(with_srgb(Bc4RUnorm, true), Bc4RUnorm);
+        assert_eq!(with_srgb(Bc6hRgbUfloat, true), Bc6hRgbUfloat);
+        let hdr = Astc {
+            block: AstcBlock::B4x4,
+            channel: AstcChannel::Hdr,
+        };
+        assert_eq!(with_srgb(hdr, true), hdr);
+    }
+
+    #[test]
+    fn unknown_container() {
+        assert!(matches!(
+            CompressedImage::from_bytes(b"\x89PNG\r\n\x1a\n"),
+            Err(CompressedTextureError::UnknownContainer)
+        ));
+        assert!(matches!(
+            CompressedImage::from_bytes(&[]),
+            Err(CompressedTextureError::UnknownContainer)
+        ));
+    }
+
+    #[test]
+    fn reads_every_level_of_a_dds() {
+        let image = CompressedImage::from_bytes(&to_bytes(&bc1_dds(16, 8, 5)))
+            .expect("the DDS should be readable");
+        assert_eq!(image.format, TextureFormat::Bc1RgbaUnorm);
+        assert_eq!((image.width, image.height), (16, 8));
+        let sizes: Vec<_> = image.levels.iter().map(Vec::len).collect();
+        assert_eq!(sizes, [4 * 2 * 8, 2 * 8, 8, 8, 8]);
+    }
+
+    #[test]
+    fn truncated_dds() {
+        let mut dds = bc1_dds(16, 16, 3);
+        // Part of the last level is missing
+        dds.data.truncate(dds.data.len() - 4);
+        assert!(matches!(
+            CompressedImage::from_bytes(&to_bytes(&dds)),
+            Err(CompressedTextureError::Truncated)
+        ));
+    }
+}
@@ -0,0 +1,108 @@
+use wgpu::{
+    util::{BufferInitDescriptor, DeviceExt},
+    BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor,
+    BindGroupLayoutEntry, BindingType, Buffer, BufferBindingType, BufferUsages, CommandEncoder,
+    ComputePassDescriptor, ComputePipeline, ComputePipelineDescriptor, Device,
+    PipelineLayoutDescriptor, Queue, ShaderModuleDescriptor, ShaderSource, ShaderStages,
+};
+
+/// How many invocations are in each workgroup, has to match `@workgroup_size` in compute.wgsl
+pub const COMPUTE_WORKGROUP_SIZE: u32 = 64;
+
+/// A compute pipeline and the storage buffer it works on
+pub struct Compute {
+    pub pipeline: ComputePipeline,
+    pub bind_group_layout: BindGroupLayout,
+    /// The data the compute shader reads and writes, as `array<f32>`
+    pub storage_buffer: Buffer,
+    pub bind_group: BindGroup,
+    /// How many `f32`s are in `storage_buffer`
+    len: usize,
+}
+
+impl Compute {
+    /// Build the compute pipeline from compute.wgsl, with `data` as the initial contents of the storage buffer
+    ///
+    /// `data` can't be empty, since empty buffers can't be bound
+    pub fn new(device: &Device, data: &[f32]) -> Self {
+        let shader = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("Compute Shader"),
+            source: ShaderSource::Wgsl(include_str!("compute.wgsl").into()),
+        });
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Compute Bind Group Layout"),
+            entries: &[BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::COMPUTE,
+                ty: BindingType::Buffer {
+                    // `read_only: false` since the shader writes its results back into it
+                    ty: BufferBindingType::Storage { read_only: false },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Compute Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_compute_pipeline(&ComputePipelineDescriptor {
+            label: Some("Compute Pipeline"),
+            layout: Some(&layout),
+            module: &shader,
+            // The function we marked with `@compute`
+            entry_point: "cs_main",
+        });
+        let (storage_buffer, bind_group) = create_storage(device, &bind_group_layout, data);
+        Self {
+            pipeline,
+            bind_group_layout,
+            storage_buffer,
+            bind_group,
+            len: data.len(),
+        }
+    }
+
+    /// Replace the contents of the storage buffer, recreating it if the length changed
+    pub fn set_data(&mut self, device: &Device, queue: &Queue, data: &[f32]) {
+        if data.len() == self.len {
+            queue.write_buffer(&self.storage_buffer, 0, bytemuck::cast_slice(data));
+        } else {
+            (self.storage_buffer, self.bind_group) =
+                create_storage(device, &self.bind_group_layout, data);
+            self.len = data.len();
+        }
+    }
+
+    /// Record a compute pass running `workgroups` (x, y, z) workgroups of `COMPUTE_WORKGROUP_SIZE` invocations
+    pub fn encode(&self, encoder: &mut CommandEncoder, [x, y, z]: [u32; 3]) {
+        let mut compute_pass = encoder.begin_compute_pass(&ComputePassDescriptor {
+            label: Some("Compute Pass"),
+        });
+        compute_pass.set_pipeline(&self.pipeline);
+        compute_pass.set_bind_group(0, &self.bind_group, &[]);
+        compute_pass.dispatch_workgroups(x, y, z);
+    }
+}
+
+/// Upload `data` into a new storage buffer, and make a bind group for it
+fn create_storage(device: &Device, layout: &BindGroupLayout, data: &[f32]) -> (Buffer, BindGroup) {
+    assert!(!data.is_empty(), "the compute data can't be empty");
+    let buffer = device.create_buffer_init(&BufferInitDescriptor {
+        label: Some("Compute Storage Buffer"),
+        contents: bytemuck::cast_slice(data),
+        // `COPY_SRC` so the results can be read back, `COPY_DST` so `set_data()` can overwrite it
+        usage: BufferUsages::STORAGE | BufferUsages::COPY_SRC | BufferUsages::COPY_DST,
+    });
+    let bind_group = device.create_bind_group(&BindGroupDescriptor {
+        label: Some("Compute Bind Group"),
+        layout,
+        entries: &[BindGroupEntry {
+            binding: 0,
+            resource: buffer.as_entire_binding(),
+        }],
+    });
+    (buffer, bind_group)
+}
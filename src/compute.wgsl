@@ -0,0 +1,20 @@
+// How many invocations each workgroup has, `dispatch_compute()` is in workgroups so this has to match `COMPUTE_WORKGROUP_SIZE`
+let WORKGROUP_SIZE: u32 = 64u;
+
+@group(0) @binding(0)
+var<storage, read_write> data: array<f32>;
+
+// Each invocation doubles one element of `data`, with the y and z dimensions laid out one after another
+@compute @workgroup_size(64)
+fn cs_main(
+    @builtin(global_invocation_id) id: vec3<u32>,
+    @builtin(num_workgroups) num_workgroups: vec3<u32>,
+) {
+    let width = num_workgroups.x * WORKGROUP_SIZE;
+    let index = id.x + width * (id.y + num_workgroups.y * id.z);
+    // The last workgroup can hang off the end of the array
+    if index >= arrayLength(&data) {
+        return;
+    }
+    data[index] = data[index] * 2.0;
+}
@@ -0,0 +1,62 @@
+use winit::{
+    dpi::PhysicalPosition,
+    window::{CursorGrabMode, Window},
+};
+
+/// Hides the cursor and keeps it in the window, so mouse-look doesn't stop at the edge of the screen
+///
+/// Mouse movement then has to come from `DeviceEvent::MouseMotion`, since the cursor itself doesn't go anywhere useful
+#[derive(Debug, Default)]
+pub struct CursorGrab {
+    /// How the cursor is grabbed, `None` when it isn't
+    mode: Option<CursorGrabMode>,
+}
+
+impl CursorGrab {
+    /// Grab and hide the cursor, returns `false` if the platform won't let us
+    ///
+    /// `Locked` keeps the cursor where it is, but isn't supported everywhere (e.g. Windows and X11)
+    /// so we fall back to `Confined`, keeping the cursor in the window and putting it back in the middle whenever it moves
+    pub fn grab(&mut self, window: &Window) -> bool {
+        for mode in [CursorGrabMode::Locked, CursorGrabMode::Confined] {
+            match window.set_cursor_grab(mode) {
+                Ok(()) => {
+                    window.set_cursor_visible(false);
+                    self.mode = Some(mode);
+                    self.recenter(window);
+                    return true;
+                }
+                Err(err) => log::debug!("Couldn't grab the cursor with {mode:?}: {err}"),
+            }
+        }
+        log::warn!("Couldn't grab the cursor, this platform doesn't support it");
+        false
+    }
+
+    /// Let go of the cursor and show it again
+    pub fn release(&mut self, window: &Window) {
+        if self.mode.take().is_some() {
+            if let Err(err) = window.set_cursor_grab(CursorGrabMode::None) {
+                log::error!("Failed to release the cursor: {err}");
+            }
+            window.set_cursor_visible(true);
+        }
+    }
+
+    pub fn is_grabbed(&self) -> bool {
+        self.mode.is_some()
+    }
+
+    /// Put the cursor back in the middle of the window if it's only confined, call this whenever the mouse moves
+    ///
+    /// Otherwise it would end up stuck against the edge of the window
+    pub fn recenter(&self, window: &Window) {
+        if self.mode == Some(CursorGrabMode::Confined) {
+            let size = window.inner_size();
+            let center = PhysicalPosition::new(size.width / 2, size.height / 2);
+            if let Err(err) = window.set_cursor_position(center) {
+                log::debug!("Couldn't recenter the cursor: {err}");
+            }
+        }
+    }
+}
@@ -0,0 +1,180 @@
+use std::mem;
+
+use bytemuck::{Pod, Zeroable};
+use glam::Vec3;
+use wgpu::{
+    vertex_attr_array, BindGroup, BindGroupLayout, BlendState, Buffer, BufferAddress,
+    BufferDescriptor, BufferUsages, Color, ColorTargetState, ColorWrites, CompareFunction,
+    DepthBiasState, DepthStencilState, Device, FragmentState, MultisampleState, PipelineLayout,
+    PipelineLayoutDescriptor, PrimitiveState, PrimitiveTopology, Queue, RenderPass, RenderPipeline,
+    RenderPipelineDescriptor, ShaderModule, ShaderModuleDescriptor, ShaderSource, StencilState,
+    TextureFormat, VertexAttribute, VertexBufferLayout, VertexState, VertexStepMode,
+};
+
+use crate::texture::DEPTH_FORMAT;
+
+/// One end of a line, matches `VertexInput` in debug_lines.wgsl
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+struct LineVertex {
+    position: [f32; 3],
+    color: [f32; 4],
+}
+
+impl LineVertex {
+    const ATTRIBUTES: [VertexAttribute; 2] = vertex_attr_array![0 => Float32x3, 1 => Float32x4];
+
+    fn desc<'a>() -> VertexBufferLayout<'a> {
+        VertexBufferLayout {
+            array_stride: mem::size_of::<Self>() as BufferAddress,
+            step_mode: VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+}
+
+/// Collects line segments over a frame (for axes, bounding boxes, normals...) and draws them all in one go
+pub struct DebugLines {
+    shader: ShaderModule,
+    layout: PipelineLayout,
+    /// `None` until something's drawn, grows to fit the busiest frame
+    vertex_buffer: Option<Buffer>,
+    /// What's been queued with `push()` since the last `prepare()`, two per line
+    vertices: Vec<LineVertex>,
+    /// How many vertices `prepare()` last uploaded, and `draw()` draws
+    num_vertices: u32,
+}
+
+impl DebugLines {
+    /// `camera_bind_group_layout` is the same layout the scene uses
+    pub fn new(device: &Device, camera_bind_group_layout: &BindGroupLayout) -> Self {
+        let shader = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("Debug Line Shader"),
+            source: ShaderSource::Wgsl(include_str!("debug_lines.wgsl").into()),
+        });
+        let layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Debug Line Pipeline Layout"),
+            bind_group_layouts: &[camera_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        Self {
+            shader,
+            layout,
+            vertex_buffer: None,
+            vertices: Vec::new(),
+            num_vertices: 0,
+        }
+    }
+
+    /// Queue a line from `a` to `b` in world space
+    pub fn push(&mut self, a: Vec3, b: Vec3, color: Color) {
+        let color = [color.r, color.g, color.b, color.a].map(|channel| channel as f32);
+        self.vertices.extend([
+            LineVertex {
+                position: a.to_array(),
+                color,
+            },
+            LineVertex {
+                position: b.to_array(),
+                color,
+            },
+        ]);
+    }
+
+    /// Upload everything queued since the last call, replacing what `draw()` draws, and start a new frame
+    pub fn prepare(&mut self, device: &Device, queue: &Queue) {
+        self.num_vertices = self.vertices.len() as u32;
+        if self.vertices.is_empty() {
+            return;
+        }
+        let size = mem::size_of_val(self.vertices.as_slice()) as BufferAddress;
+        let vertex_buffer = match &self.vertex_buffer {
+            Some(buffer) if buffer.size() >= size => buffer,
+            // Doubling, so a slowly growing number of lines doesn't mean a new buffer every frame
+            _ => self
+                .vertex_buffer
+                .insert(device.create_buffer(&BufferDescriptor {
+                    label: Some("Debug Line Vertex Buffer"),
+                    size: size.next_power_of_two(),
+                    usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                })),
+        };
+        queue.write_buffer(vertex_buffer, 0, bytemuck::cast_slice(&self.vertices));
+        self.vertices.clear();
+    }
+
+    /// Whether `draw()` has anything to draw
+    pub fn is_empty(&self) -> bool {
+        self.num_vertices == 0
+    }
+
+    /// Build the pipeline for a target with `format` and `sample_count`
+    ///
+    /// With `depth_test` lines behind the scene are hidden, otherwise they're drawn over everything
+    pub fn create_pipeline(
+        &self,
+        device: &Device,
+        format: TextureFormat,
+        sample_count: u32,
+        depth_test: bool,
+    ) -> RenderPipeline {
+        device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("Debug Line Pipeline"),
+            layout: Some(&self.layout),
+            vertex: VertexState {
+                module: &self.shader,
+                entry_point: "vs_main",
+                buffers: &[LineVertex::desc()],
+            },
+            fragment: Some(FragmentState {
+                module: &self.shader,
+                entry_point: "fs_main",
+                targets: &[Some(ColorTargetState {
+                    format,
+                    blend: Some(BlendState::ALPHA_BLENDING),
+                    write_mask: ColorWrites::ALL,
+                })],
+            }),
+            primitive: PrimitiveState {
+                topology: PrimitiveTopology::LineList,
+                ..PrimitiveState::default()
+            },
+            // Lines never hide anything, so they don't write depth either way
+            depth_stencil: Some(DepthStencilState {
+                format: DEPTH_FORMAT,
+                depth_write_enabled: false,
+                depth_compare: if depth_test {
+                    CompareFunction::LessEqual
+                } else {
+                    CompareFunction::Always
+                },
+                stencil: StencilState::default(),
+                bias: DepthBiasState::default(),
+            }),
+            // Multisampling is what smooths out the lines' edges
+            multisample: MultisampleState {
+                count: sample_count,
+                ..MultisampleState::default()
+            },
+            multiview: None,
+        })
+    }
+
+    /// Draw what was last `prepare()`d with `pipeline` from `create_pipeline()`
+    pub fn draw<'a>(
+        &'a self,
+        render_pass: &mut RenderPass<'a>,
+        pipeline: &'a RenderPipeline,
+        camera_bind_group: &'a BindGroup,
+    ) {
+        let vertex_buffer = match &self.vertex_buffer {
+            Some(vertex_buffer) if self.num_vertices > 0 => vertex_buffer,
+            _ => return,
+        };
+        render_pass.set_pipeline(pipeline);
+        render_pass.set_bind_group(0, camera_bind_group, &[]);
+        render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+        render_pass.draw(0..self.num_vertices, 0..1);
+    }
+}
@@ -0,0 +1,43 @@
+/// Which fragment shader the scene's geometry is drawn with, for seeing what goes into the lighting
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DebugView {
+    /// The normal, fully lit scene
+    #[default]
+    Lit,
+    /// The vertex colour and texture without any lighting
+    Unlit,
+    /// The world space normals, with each axis mapped from -1..1 to 0..1
+    Normals,
+    /// The texture coordinates, with `u` in red and `v` in green
+    TexCoords,
+}
+
+impl DebugView {
+    /// Every view, in the order `next()` goes through them
+    pub const ALL: [DebugView; 4] = [
+        DebugView::Lit,
+        DebugView::Unlit,
+        DebugView::Normals,
+        DebugView::TexCoords,
+    ];
+
+    /// Where this view is in `DebugView::ALL`
+    pub fn index(self) -> usize {
+        self as usize
+    }
+
+    /// The view after this one, going back to `Lit` after the last one
+    pub fn next(self) -> Self {
+        Self::ALL[(self.index() + 1) % Self::ALL.len()]
+    }
+
+    /// The `@fragment` function in the shader that draws this view
+    pub fn entry_point(self) -> &'static str {
+        match self {
+            DebugView::Lit => "fs_main",
+            DebugView::Unlit => "fs_unlit",
+            DebugView::Normals => "fs_normals",
+            DebugView::TexCoords => "fs_tex_coords",
+        }
+    }
+}
@@ -0,0 +1,206 @@
+use std::mem;
+
+use bytemuck::{Pod, Zeroable};
+use glam::Vec3;
+use wgpu::{
+    BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor,
+    BindGroupLayoutEntry, BindingType, Buffer, BufferAddress, BufferBindingType, BufferDescriptor,
+    BufferUsages, Color, ColorTargetState, ColorWrites, CompareFunction, DepthBiasState,
+    DepthStencilState, Device, FragmentState, MultisampleState, PipelineLayoutDescriptor,
+    PrimitiveState, Queue, RenderPass, RenderPipeline, RenderPipelineDescriptor, ShaderModule,
+    ShaderModuleDescriptor, ShaderSource, ShaderStages, StencilState, TextureFormat, VertexState,
+};
+
+use crate::texture::DEPTH_FORMAT;
+
+/// The count at the start of the light buffer, padded out to where the array starts
+const HEADER_SIZE: BufferAddress = 16;
+
+/// A light that only the deferred lighting pass uses, see `State::add_light()`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Pod, Zeroable)]
+pub struct PointLight {
+    /// Where the light is in world space
+    pub position: [f32; 3],
+    /// How far the light reaches, fading out to nothing at the edge
+    pub radius: f32,
+    /// Linear RGB, can go above 1 for a brighter light
+    pub color: [f32; 3],
+    // Each light in a storage buffer array is padded out to 16 bytes
+    _padding: u32,
+}
+
+impl PointLight {
+    pub fn new(position: Vec3, color: Color, radius: f32) -> Self {
+        Self {
+            position: position.to_array(),
+            radius,
+            color: [color.r, color.g, color.b].map(|channel| channel as f32),
+            _padding: 0,
+        }
+    }
+}
+
+/// Lights the G-buffer with a fullscreen pass, so the cost of each light depends on the pixels it reaches instead of the geometry
+///
+/// Draws into the scene's pass in place of its geometry, setting the depth from the G-buffer so what's drawn after is still hidden
+pub struct DeferredLighting {
+    shader: ShaderModule,
+    lights_layout: BindGroupLayout,
+    /// A `u32` count followed by the lights, grown as needed
+    lights_buffer: Buffer,
+    lights_bind_group: BindGroup,
+}
+
+impl DeferredLighting {
+    /// Needs storage buffers in fragment shaders, which WebGL doesn't have
+    pub fn new(device: &Device) -> Self {
+        let shader = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("Deferred Lighting Shader"),
+            source: ShaderSource::Wgsl(include_str!("deferred.wgsl").into()),
+        });
+        let lights_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Point Light Bind Group Layout"),
+            entries: &[BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::FRAGMENT,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Storage { read_only: true },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        // Room for one light, since the array in the shader can't be empty
+        let (lights_buffer, lights_bind_group) = create_lights_buffer(
+            device,
+            &lights_layout,
+            HEADER_SIZE + mem::size_of::<PointLight>() as BufferAddress,
+        );
+        Self {
+            shader,
+            lights_layout,
+            lights_buffer,
+            lights_bind_group,
+        }
+    }
+
+    /// Upload `lights`, replacing the ones from the last call
+    pub fn prepare(&mut self, device: &Device, queue: &Queue, lights: &[PointLight]) {
+        let size = HEADER_SIZE + mem::size_of_val(lights) as BufferAddress;
+        if size > self.lights_buffer.size() {
+            // Doubling, so a slowly growing number of lights doesn't mean a new buffer every frame
+            (self.lights_buffer, self.lights_bind_group) =
+                create_lights_buffer(device, &self.lights_layout, size.next_power_of_two());
+        }
+        queue.write_buffer(
+            &self.lights_buffer,
+            0,
+            bytemuck::bytes_of(&[lights.len() as u32, 0, 0, 0]),
+        );
+        if !lights.is_empty() {
+            queue.write_buffer(
+                &self.lights_buffer,
+                HEADER_SIZE,
+                bytemuck::cast_slice(lights),
+            );
+        }
+    }
+
+    /// Build the pipeline for a target with `format` and `sample_count`
+    ///
+    /// `gbuffer_layout` has to bind the albedo, normal and position at 0, 1 and 2, like `gbuffer::DEFAULT_ATTACHMENTS`
+    pub fn create_pipeline(
+        &self,
+        device: &Device,
+        globals_layout: &BindGroupLayout,
+        camera_layout: &BindGroupLayout,
+        gbuffer_layout: &BindGroupLayout,
+        format: TextureFormat,
+        sample_count: u32,
+    ) -> RenderPipeline {
+        let layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Deferred Lighting Pipeline Layout"),
+            bind_group_layouts: &[
+                globals_layout,
+                camera_layout,
+                gbuffer_layout,
+                &self.lights_layout,
+            ],
+            push_constant_ranges: &[],
+        });
+        device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("Deferred Lighting Pipeline"),
+            layout: Some(&layout),
+            vertex: VertexState {
+                module: &self.shader,
+                entry_point: "vs_main",
+                // The triangle is made up in the vertex shader
+                buffers: &[],
+            },
+            fragment: Some(FragmentState {
+                module: &self.shader,
+                entry_point: "fs_main",
+                targets: &[Some(ColorTargetState {
+                    format,
+                    blend: None,
+                    write_mask: ColorWrites::ALL,
+                })],
+            }),
+            primitive: PrimitiveState::default(),
+            // The G-buffer pass already kept the closest surfaces, this just puts their depth back for everything drawn after
+            depth_stencil: Some(DepthStencilState {
+                format: DEPTH_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: CompareFunction::Always,
+                stencil: StencilState::default(),
+                bias: DepthBiasState::default(),
+            }),
+            multisample: MultisampleState {
+                count: sample_count,
+                ..MultisampleState::default()
+            },
+            multiview: None,
+        })
+    }
+
+    /// Light the G-buffer bound by `gbuffer_bind_group` with `pipeline` from `create_pipeline()`
+    pub fn draw<'a>(
+        &'a self,
+        render_pass: &mut RenderPass<'a>,
+        pipeline: &'a RenderPipeline,
+        globals_bind_group: &'a BindGroup,
+        camera_bind_group: &'a BindGroup,
+        gbuffer_bind_group: &'a BindGroup,
+    ) {
+        render_pass.set_pipeline(pipeline);
+        render_pass.set_bind_group(0, globals_bind_group, &[]);
+        render_pass.set_bind_group(1, camera_bind_group, &[]);
+        render_pass.set_bind_group(2, gbuffer_bind_group, &[]);
+        render_pass.set_bind_group(3, &self.lights_bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
+
+fn create_lights_buffer(
+    device: &Device,
+    layout: &BindGroupLayout,
+    size: BufferAddress,
+) -> (Buffer, BindGroup) {
+    let buffer = device.create_buffer(&BufferDescriptor {
+        label: Some("Point Light Buffer"),
+        size,
+        usage: BufferUsages::STORAGE | BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+    let bind_group = device.create_bind_group(&BindGroupDescriptor {
+        label: Some("Point Light Bind Group"),
+        layout,
+        entries: &[BindGroupEntry {
+            binding: 0,
+            resource: buffer.as_entire_binding(),
+        }],
+    });
+    (buffer, bind_group)
+}
@@ -0,0 +1,150 @@
+// Lights what the G-buffer pass drew, with the scene's light plus every point light, a pixel at a time
+
+struct Globals {
+    time: f32,
+    resolution: vec2<f32>,
+    inverse_resolution: vec2<f32>,
+};
+@group(0) @binding(0)
+var<uniform> globals: Globals;
+
+struct Light {
+    position: vec3<f32>,
+    color: vec3<f32>,
+};
+@group(0) @binding(1)
+var<uniform> light: Light;
+
+struct Shadow {
+    view_proj: mat4x4<f32>,
+    enabled: u32,
+};
+@group(0) @binding(2)
+var<uniform> shadow: Shadow;
+@group(0) @binding(3)
+var t_shadow: texture_depth_2d;
+@group(0) @binding(4)
+var s_shadow: sampler_comparison;
+
+// The same as `shadow_factor` in shader.wgsl, from 0 in full shadow to 1 fully lit
+fn shadow_factor(world_position: vec3<f32>) -> f32 {
+    if (shadow.enabled == 0u) {
+        return 1.0;
+    }
+    let light_clip = shadow.view_proj * vec4<f32>(world_position, 1.0);
+    let ndc = light_clip.xyz / light_clip.w;
+    let uv = ndc.xy * vec2<f32>(0.5, -0.5) + 0.5;
+    if (any(uv < vec2<f32>(0.0)) || any(uv > vec2<f32>(1.0)) || ndc.z > 1.0) {
+        return 1.0;
+    }
+    let texel = 1.0 / vec2<f32>(textureDimensions(t_shadow));
+    var lit = 0.0;
+    for (var x = -1; x <= 1; x = x + 1) {
+        for (var y = -1; y <= 1; y = y + 1) {
+            let offset = vec2<f32>(f32(x), f32(y)) * texel;
+            lit = lit + textureSampleCompareLevel(t_shadow, s_shadow, uv + offset, ndc.z);
+        }
+    }
+    return lit / 9.0;
+}
+
+struct Camera {
+    view_proj: mat4x4<f32>,
+    view_position: vec4<f32>,
+};
+@group(1) @binding(0)
+var<uniform> camera: Camera;
+
+// What `fs_gbuffer` in shader.wgsl wrote, read a pixel at a time so there's no filtering
+@group(2) @binding(0)
+var t_albedo: texture_2d<f32>;
+@group(2) @binding(1)
+var t_normal: texture_2d<f32>;
+@group(2) @binding(2)
+var t_position: texture_2d<f32>;
+
+struct PointLight {
+    position: vec3<f32>,
+    radius: f32,
+    color: vec3<f32>,
+};
+struct PointLights {
+    // The buffer can be bigger than the lights in it
+    count: u32,
+    lights: array<PointLight>,
+};
+@group(3) @binding(0)
+var<storage, read> point_lights: PointLights;
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+    // One triangle big enough to cover the whole viewport, with corners at (-1, -1), (3, -1) and (-1, 3)
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    return vec4<f32>(uv * 2.0 - 1.0, 1.0, 1.0);
+}
+
+struct FragmentOutput {
+    @location(0) color: vec4<f32>,
+    // The depth of the surface that was lit, so whatever's drawn after is still hidden behind it
+    @builtin(frag_depth) depth: f32,
+};
+
+// Blinn-Phong, the same as `fs_main` in shader.wgsl but without the ambient
+fn shade(
+    albedo: vec3<f32>,
+    normal: vec3<f32>,
+    position: vec3<f32>,
+    view_dir: vec3<f32>,
+    light_position: vec3<f32>,
+    light_color: vec3<f32>,
+) -> vec3<f32> {
+    let light_dir = normalize(light_position - position);
+    let half_dir = normalize(view_dir + light_dir);
+    let diffuse = max(dot(normal, light_dir), 0.0);
+    let specular = pow(max(dot(normal, half_dir), 0.0), 32.0);
+    return (diffuse * albedo + specular) * light_color;
+}
+
+@fragment
+fn fs_main(@builtin(position) clip_position: vec4<f32>) -> FragmentOutput {
+    // The G-buffer is the same size as the target, so the pixel we're drawing is the one to read
+    let pixel = vec2<i32>(clip_position.xy);
+    let position = textureLoad(t_position, pixel, 0);
+    // Nothing got drawn here, so whatever's behind (the clear colour or the sky) shows through
+    if (position.w == 0.0) {
+        discard;
+    }
+    let albedo = textureLoad(t_albedo, pixel, 0);
+    let normal = normalize(textureLoad(t_normal, pixel, 0).xyz);
+    let view_dir = normalize(camera.view_position.xyz - position.xyz);
+
+    // The scene's light comes with the same bit of ambient as when drawing forwards
+    // Only the scene's light casts shadows
+    var color = 0.1 * albedo.rgb * light.color
+        + shade(albedo.rgb, normal, position.xyz, view_dir, light.position, light.color)
+        * shadow_factor(position.xyz);
+    for (var i = 0u; i < point_lights.count; i = i + 1u) {
+        let point_light = point_lights.lights[i];
+        let distance = length(point_light.position - position.xyz);
+        // Out of reach, which is most of them for most pixels
+        if (distance >= point_light.radius) {
+            continue;
+        }
+        // Fades out smoothly to nothing at the edge of its radius
+        let falloff = 1.0 - distance / point_light.radius;
+        color = color + shade(
+            albedo.rgb,
+            normal,
+            position.xyz,
+            view_dir,
+            point_light.position,
+            point_light.color,
+        ) * falloff * falloff;
+    }
+
+    var out: FragmentOutput;
+    out.color = vec4<f32>(color, albedo.a);
+    let clip = camera.view_proj * vec4<f32>(position.xyz, 1.0);
+    out.depth = clip.z / clip.w;
+    return out;
+}
@@ -0,0 +1,105 @@
+use glam::{Mat4, Vec3, Vec4};
+
+/// A sphere that everything in some geometry fits inside, cheap to test against a `Frustum`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoundingSphere {
+    pub center: Vec3,
+    pub radius: f32,
+}
+
+impl BoundingSphere {
+    /// The sphere around the middle of `points`' bounding box, `None` if there aren't any points
+    ///
+    /// Not the smallest possible sphere, but close enough and quick to work out
+    pub fn from_points(points: &[Vec3]) -> Option<Self> {
+        let (min, max) = points.iter().fold(None, |bounds, &point| {
+            Some(match bounds {
+                Some((min, max)) => (point.min(min), point.max(max)),
+                None => (point, point),
+            })
+        })?;
+        let center = (min + max) / 2.0;
+        let radius = points
+            .iter()
+            .map(|point| point.distance(center))
+            .fold(0.0, f32::max);
+        Some(Self { center, radius })
+    }
+
+    /// The smallest sphere that fits both `self` and `other`
+    pub fn merge(self, other: Self) -> Self {
+        let offset = other.center - self.center;
+        let distance = offset.length();
+        if distance + other.radius <= self.radius {
+            return self;
+        }
+        if distance + self.radius <= other.radius {
+            return other;
+        }
+        let radius = (distance + self.radius + other.radius) / 2.0;
+        Self {
+            // Moved from `self`'s centre towards `other`'s, so both just touch the new edge
+            center: self.center + offset * ((radius - self.radius) / distance),
+            radius,
+        }
+    }
+
+    /// The sphere around the same geometry after it's been moved, rotated and scaled by `matrix`
+    ///
+    /// With non-uniform scaling the sphere gets the biggest scale on every axis, so it can end up bigger than it needs to be
+    pub fn transformed(&self, matrix: Mat4) -> Self {
+        let scale = matrix
+            .x_axis
+            .truncate()
+            .length()
+            .max(matrix.y_axis.truncate().length())
+            .max(matrix.z_axis.truncate().length());
+        Self {
+            center: matrix.transform_point3(self.center),
+            radius: self.radius * scale,
+        }
+    }
+}
+
+/// How many instances were drawn and how many were left out by the last frustum culling, see `State::culling_stats()`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CullingStats {
+    pub drawn: u32,
+    pub culled: u32,
+}
+
+/// The six planes around what a camera can see, see `Camera::frustum()`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Frustum {
+    /// Left, right, bottom, top, near and far, each with its normal (`xyz`) pointing inwards and normalised
+    ///
+    /// A point `p` is on the inside of a plane when `plane.xyz.dot(p) + plane.w >= 0`
+    pub planes: [Vec4; 6],
+}
+
+impl Frustum {
+    /// Pull the planes out of a matrix taking world space to wgpu's clip space, where depth goes from 0 to 1
+    ///
+    /// See Gribb and Hartmann's "Fast Extraction of Viewing Frustum Planes from the World-View-Projection Matrix"
+    pub fn from_view_projection(view_proj: Mat4) -> Self {
+        let [row_x, row_y, row_z, row_w] = [0, 1, 2, 3].map(|index| view_proj.row(index));
+        let planes = [
+            row_w + row_x,
+            row_w - row_x,
+            row_w + row_y,
+            row_w - row_y,
+            // Not `row_w + row_z` like OpenGL, since depth starts at 0 rather than -1
+            row_z,
+            row_w - row_z,
+        ]
+        .map(|plane| plane / plane.truncate().length());
+        Self { planes }
+    }
+
+    /// Whether any of `sphere` could be inside, it might not be if it's just outside a corner
+    pub fn intersects_sphere(&self, sphere: &BoundingSphere) -> bool {
+        self.planes
+            .iter()
+            .all(|plane| plane.truncate().dot(sphere.center) + plane.w >= -sphere.radius)
+    }
+}
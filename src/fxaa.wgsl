@@ -0,0 +1,77 @@
+// Fast approximate anti-aliasing: finds edges by their contrast in brightness, then blurs along them
+// Based on the simpler "console" version of Timothy Lottes' FXAA
+
+@group(0) @binding(0)
+var input: texture_2d<f32>;
+@group(0) @binding(1)
+var input_sampler: sampler;
+
+// The least the blur gets pulled in by, so flat areas don't divide by zero
+let REDUCE_MIN: f32 = 0.0078125;
+// How much the blur gets pulled in on bright edges, so they don't smear
+let REDUCE_MUL: f32 = 0.125;
+// The furthest along the edge the blur reaches, in pixels
+let SPAN_MAX: f32 = 8.0;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+};
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    // One triangle big enough to cover the whole target, with corners at (-1, -1), (3, -1) and (-1, 3)
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+    // Texture coordinates start at the top left, clip space at the bottom left
+    out.tex_coords = vec2<f32>(uv.x, 1.0 - uv.y);
+    return out;
+}
+
+// How bright `color` looks, roughly gamma corrected since an sRGB texture gets sampled as linear
+fn luma(color: vec3<f32>) -> f32 {
+    return sqrt(dot(color, vec3<f32>(0.2126, 0.7152, 0.0722)));
+}
+
+fn sample_at(uv: vec2<f32>) -> vec3<f32> {
+    return textureSample(input, input_sampler, uv).rgb;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let texel = 1.0 / vec2<f32>(textureDimensions(input));
+    let center = textureSample(input, input_sampler, in.tex_coords);
+    // The four diagonal neighbours, named as if up is towards the top of the screen
+    let luma_nw = luma(sample_at(in.tex_coords + vec2<f32>(-1.0, -1.0) * texel));
+    let luma_ne = luma(sample_at(in.tex_coords + vec2<f32>(1.0, -1.0) * texel));
+    let luma_sw = luma(sample_at(in.tex_coords + vec2<f32>(-1.0, 1.0) * texel));
+    let luma_se = luma(sample_at(in.tex_coords + vec2<f32>(1.0, 1.0) * texel));
+    let luma_m = luma(center.rgb);
+    let luma_min = min(luma_m, min(min(luma_nw, luma_ne), min(luma_sw, luma_se)));
+    let luma_max = max(luma_m, max(max(luma_nw, luma_ne), max(luma_sw, luma_se)));
+
+    // Along the edge, at right angles to the way the brightness changes
+    var direction = vec2<f32>(
+        (luma_sw + luma_se) - (luma_nw + luma_ne),
+        (luma_nw + luma_sw) - (luma_ne + luma_se),
+    );
+    let reduce = max((luma_nw + luma_ne + luma_sw + luma_se) * 0.25 * REDUCE_MUL, REDUCE_MIN);
+    // Scaled so the shorter side is about a pixel long
+    let scale = 1.0 / (min(abs(direction.x), abs(direction.y)) + reduce);
+    direction = clamp(direction * scale, vec2<f32>(-SPAN_MAX), vec2<f32>(SPAN_MAX)) * texel;
+
+    // Two samples close in along the edge, and two more further out
+    let near = 0.5 * (
+        sample_at(in.tex_coords + direction * (1.0 / 3.0 - 0.5))
+        + sample_at(in.tex_coords + direction * (2.0 / 3.0 - 0.5))
+    );
+    let far = near * 0.5 + 0.25 * (
+        sample_at(in.tex_coords - direction * 0.5)
+        + sample_at(in.tex_coords + direction * 0.5)
+    );
+    // Reaching further picked up something that isn't part of the edge, so stick to the closer samples
+    let luma_far = luma(far);
+    let outside = luma_far < luma_min || luma_far > luma_max;
+    return vec4<f32>(select(far, near, outside), center.a);
+}
@@ -0,0 +1,291 @@
+use wgpu::{
+    BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor,
+    BindGroupLayoutEntry, BindingResource, BindingType, Color, ColorTargetState, ColorWrites,
+    CommandEncoder, DepthStencilState, Device, Extent3d, Face, FragmentState, LoadOp,
+    MultisampleState, Operations, PipelineLayout, PrimitiveState, RenderPass,
+    RenderPassColorAttachment, RenderPassDepthStencilAttachment, RenderPassDescriptor,
+    RenderPipeline, RenderPipelineDescriptor, ShaderModule, ShaderStages, TextureDescriptor,
+    TextureDimension, TextureFormat, TextureSampleType, TextureUsages, TextureViewDescriptor,
+    TextureViewDimension, VertexState,
+};
+use winit::dpi::PhysicalSize;
+
+use crate::instance::Instance;
+use crate::stencil::{self, MaskRect};
+use crate::texture::{self, Texture};
+use crate::vertex::Vertex;
+
+/// One of the textures the G-buffer pass writes to, at `@location(n)` for the `n`th attachment
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GBufferAttachment {
+    pub label: &'static str,
+    pub format: TextureFormat,
+}
+
+/// What `fs_gbuffer` in `shader.wgsl` writes: the surface's colour, its normal and its position, all in world space
+///
+/// Normals and positions go outside 0..1, so they need a float format
+pub const DEFAULT_ATTACHMENTS: [GBufferAttachment; 3] = [
+    GBufferAttachment {
+        label: "Albedo",
+        format: TextureFormat::Rgba8Unorm,
+    },
+    GBufferAttachment {
+        label: "Normal",
+        format: TextureFormat::Rgba16Float,
+    },
+    GBufferAttachment {
+        label: "Position",
+        format: TextureFormat::Rgba16Float,
+    },
+];
+
+/// Draws the scene's geometry into several textures at once, for a later pass to light or otherwise make use of
+///
+/// The layout is shared, every target gets its own `GBufferTextures`
+pub struct GBuffer {
+    attachments: Vec<GBufferAttachment>,
+    /// Binds each attachment at the `@binding()` matching its `@location()`
+    bind_group_layout: BindGroupLayout,
+    /// The same as `State`'s, but for the G-buffer's own depth texture, which is never multisampled
+    stencil_mask_pipeline: RenderPipeline,
+}
+
+/// Where a target's G-buffer gets drawn, recreated whenever the target is resized
+pub struct GBufferTextures {
+    /// One for each attachment, in the same order
+    textures: Vec<Texture>,
+    /// Never multisampled like the target's own depth texture might be, so it can't be shared with the scene
+    depth_texture: Texture,
+    bind_group: BindGroup,
+}
+
+impl GBuffer {
+    pub fn new(device: &Device, attachments: &[GBufferAttachment]) -> Self {
+        let entries: Vec<_> = (0..attachments.len() as u32)
+            .map(|binding| BindGroupLayoutEntry {
+                binding,
+                visibility: ShaderStages::FRAGMENT,
+                ty: BindingType::Texture {
+                    // Read with `textureLoad()` a pixel at a time, so any float format will do
+                    sample_type: match attachments[binding as usize].format.describe().sample_type {
+                        TextureSampleType::Float { .. } => {
+                            TextureSampleType::Float { filterable: false }
+                        }
+                        sample_type => sample_type,
+                    },
+                    view_dimension: TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                count: None,
+            })
+            .collect();
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("GBuffer Bind Group Layout"),
+            entries: &entries,
+        });
+        Self {
+            attachments: attachments.to_vec(),
+            bind_group_layout,
+            stencil_mask_pipeline: stencil::create_mask_pipeline(device, 1),
+        }
+    }
+
+    pub fn attachments(&self) -> &[GBufferAttachment] {
+        &self.attachments
+    }
+
+    /// For writing the stencil mask with `GBufferTextures::draw_stencil_mask()`
+    pub fn stencil_mask_pipeline(&self) -> &RenderPipeline {
+        &self.stencil_mask_pipeline
+    }
+
+    /// For building a pipeline that samples the textures through `GBufferTextures::bind_group()`
+    pub fn bind_group_layout(&self) -> &BindGroupLayout {
+        &self.bind_group_layout
+    }
+
+    /// Build the pipeline that draws our vertices into the attachments, with the `fs_gbuffer` entry point of `shader`
+    ///
+    /// `fs_gbuffer` has to return something for each attachment, at the same `@location()`
+    /// Culled and depth tested like the scene's pipelines, so the same surfaces end up in the G-buffer as would be drawn directly
+    pub fn create_pipeline(
+        &self,
+        device: &Device,
+        layout: &PipelineLayout,
+        shader: &ShaderModule,
+        cull_mode: Option<Face>,
+        depth_stencil: &DepthStencilState,
+    ) -> RenderPipeline {
+        // Nothing gets blended, the closest surface just overwrites whatever's there
+        let targets: Vec<_> = self
+            .attachments
+            .iter()
+            .map(|attachment| {
+                Some(ColorTargetState {
+                    format: attachment.format,
+                    blend: None,
+                    write_mask: ColorWrites::ALL,
+                })
+            })
+            .collect();
+        device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("GBuffer Pipeline"),
+            layout: Some(layout),
+            vertex: VertexState {
+                module: shader,
+                entry_point: "vs_main",
+                buffers: &[Vertex::desc(), Instance::desc()],
+            },
+            fragment: Some(FragmentState {
+                module: shader,
+                entry_point: "fs_gbuffer",
+                targets: &targets,
+            }),
+            primitive: PrimitiveState {
+                cull_mode,
+                ..PrimitiveState::default()
+            },
+            depth_stencil: Some(depth_stencil.clone()),
+            // Averaging normals and positions between surfaces would make up ones that aren't there
+            multisample: MultisampleState::default(),
+            multiview: None,
+        })
+    }
+
+    /// Create the textures for a target that's `size` big
+    pub fn create_textures(&self, device: &Device, size: PhysicalSize<u32>) -> GBufferTextures {
+        let extent = Extent3d {
+            width: size.width,
+            height: size.height,
+            depth_or_array_layers: 1,
+        };
+        let textures: Vec<_> = self
+            .attachments
+            .iter()
+            .map(|attachment| {
+                let texture = device.create_texture(&TextureDescriptor {
+                    label: Some(attachment.label),
+                    size: extent,
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: TextureDimension::D2,
+                    format: attachment.format,
+                    // Drawn into by the G-buffer pass, then sampled by whatever comes after (or copied out to look at)
+                    usage: TextureUsages::RENDER_ATTACHMENT
+                        | TextureUsages::TEXTURE_BINDING
+                        | TextureUsages::COPY_SRC,
+                });
+                let view = texture.create_view(&TextureViewDescriptor::default());
+                Texture {
+                    texture,
+                    view,
+                    sampler: texture::create_clamped_sampler(device, None),
+                }
+            })
+            .collect();
+        let entries: Vec<_> = textures
+            .iter()
+            .enumerate()
+            .map(|(binding, texture)| BindGroupEntry {
+                binding: binding as u32,
+                resource: BindingResource::TextureView(&texture.view),
+            })
+            .collect();
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("GBuffer Bind Group"),
+            layout: &self.bind_group_layout,
+            entries: &entries,
+        });
+        let depth_texture = texture::create_depth_texture(device, size, 1);
+        GBufferTextures {
+            textures,
+            depth_texture,
+            bind_group,
+        }
+    }
+}
+
+impl GBufferTextures {
+    /// One for each of `GBuffer::attachments()`, in the same order
+    pub fn textures(&self) -> &[Texture] {
+        &self.textures
+    }
+
+    /// Binds every texture at the `@binding()` matching its `@location()`, laid out by `GBuffer::bind_group_layout()`
+    pub fn bind_group(&self) -> &BindGroup {
+        &self.bind_group
+    }
+
+    /// Write `reference` into the stencil wherever `rect` covers, in a pass of its own that clears the depth and stencil first
+    ///
+    /// The G-buffer has its own depth texture, so the mask the scene's pass is drawn with has to be written into this one as well
+    pub fn draw_stencil_mask(
+        &self,
+        encoder: &mut CommandEncoder,
+        pipeline: &RenderPipeline,
+        rect: MaskRect,
+        reference: u32,
+    ) {
+        let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+            label: Some("GBuffer Stencil Mask Pass"),
+            color_attachments: &[],
+            depth_stencil_attachment: Some(RenderPassDepthStencilAttachment {
+                view: &self.depth_texture.view,
+                depth_ops: Some(Operations {
+                    load: LoadOp::Clear(1.0),
+                    store: true,
+                }),
+                stencil_ops: Some(Operations {
+                    load: LoadOp::Clear(0),
+                    store: true,
+                }),
+            }),
+        });
+        stencil::draw_mask(&mut render_pass, pipeline, rect, reference);
+    }
+
+    /// Start a pass drawing into every attachment, with everything cleared to 0 so uncovered pixels can be told apart
+    ///
+    /// The stencil is kept if `draw_stencil_mask()` just wrote to it, otherwise it's cleared to 0 as well
+    pub fn begin_pass<'a>(
+        &'a self,
+        encoder: &'a mut CommandEncoder,
+        keep_stencil: bool,
+    ) -> RenderPass<'a> {
+        let color_attachments: Vec<_> = self
+            .textures
+            .iter()
+            .map(|texture| {
+                Some(RenderPassColorAttachment {
+                    view: &texture.view,
+                    resolve_target: None,
+                    ops: Operations {
+                        load: LoadOp::Clear(Color::TRANSPARENT),
+                        store: true,
+                    },
+                })
+            })
+            .collect();
+        encoder.begin_render_pass(&RenderPassDescriptor {
+            label: Some("GBuffer Pass"),
+            color_attachments: &color_attachments,
+            depth_stencil_attachment: Some(RenderPassDepthStencilAttachment {
+                view: &self.depth_texture.view,
+                depth_ops: Some(Operations {
+                    load: LoadOp::Clear(1.0),
+                    store: true,
+                }),
+                // Tested by the G-buffer pipelines with the scene's stencil state, the same as the forward pass
+                stencil_ops: Some(Operations {
+                    load: if keep_stencil {
+                        LoadOp::Load
+                    } else {
+                        LoadOp::Clear(0)
+                    },
+                    store: true,
+                }),
+            }),
+        })
+    }
+}
@@ -0,0 +1,30 @@
+use bytemuck::{Pod, Zeroable};
+use winit::dpi::PhysicalSize;
+
+/// Values that every shader can read from `@group(0) @binding(0)`
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, Pod, Zeroable)]
+pub struct Globals {
+    /// Seconds since the `State` was created
+    pub time: f32,
+    // `vec2`s have to start on a multiple of 8 bytes
+    _padding: f32,
+    /// The size of the target being drawn into in physical pixels, rewritten for each target before it's drawn
+    pub resolution: [f32; 2],
+    /// `1 / resolution`, to save a division in every fragment
+    pub inverse_resolution: [f32; 2],
+    // Uniform buffers need to be 16 byte aligned on some platforms (looking at you WebGL)
+    _padding2: [f32; 2],
+}
+
+impl Globals {
+    /// The same globals, for drawing into a target that's `size` big
+    pub fn with_resolution(self, size: PhysicalSize<u32>) -> Self {
+        let resolution = [size.width as f32, size.height as f32];
+        Self {
+            resolution,
+            inverse_resolution: resolution.map(|dimension| 1.0 / dimension.max(1.0)),
+            ..self
+        }
+    }
+}
@@ -0,0 +1,119 @@
+use std::{
+    sync::mpsc::{self, Receiver, TryRecvError},
+    time::Duration,
+};
+
+use wgpu::{
+    Buffer, BufferAsyncError, BufferDescriptor, BufferUsages, CommandEncoder, Device, Maintain,
+    MapMode, QuerySet, QuerySetDescriptor, QueryType, Queue, QUERY_SIZE,
+};
+
+/// One timestamp at the start of the frame, one at the end
+const QUERY_COUNT: u32 = 2;
+const BUFFER_SIZE: u64 = (QUERY_SIZE * QUERY_COUNT) as u64;
+
+/// Measures how long the GPU spends on a frame, needs `Features::TIMESTAMP_QUERY`
+///
+/// Reading the timestamps back is asynchronous, so the result lags a frame or two behind
+pub struct GpuTimer {
+    query_set: QuerySet,
+    /// Where the timestamps get resolved to, so they can be read back
+    readback_buffer: Buffer,
+    /// Nanoseconds per timestamp tick
+    period: f32,
+    /// Set while `readback_buffer` is being mapped, frames in the meantime aren't measured
+    pending_map: Option<Receiver<Result<(), BufferAsyncError>>>,
+    /// Whether the current encoder has had `begin()` called on it
+    recording: bool,
+    last_frame_time: Option<Duration>,
+}
+
+impl GpuTimer {
+    pub fn new(device: &Device, queue: &Queue) -> Self {
+        let query_set = device.create_query_set(&QuerySetDescriptor {
+            label: Some("Frame Timestamps"),
+            ty: QueryType::Timestamp,
+            count: QUERY_COUNT,
+        });
+        let readback_buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("Timestamp Readback Buffer"),
+            size: BUFFER_SIZE,
+            usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        Self {
+            query_set,
+            readback_buffer,
+            period: queue.get_timestamp_period(),
+            pending_map: None,
+            recording: false,
+            last_frame_time: None,
+        }
+    }
+
+    /// Pick up the result of the last measurement if it's been read back, without blocking
+    pub fn poll(&mut self, device: &Device) {
+        let receiver = match &self.pending_map {
+            Some(receiver) => receiver,
+            None => return,
+        };
+        // Runs any map callbacks that are ready
+        device.poll(Maintain::Poll);
+        match receiver.try_recv() {
+            Err(TryRecvError::Empty) => return,
+            Ok(Ok(())) => {
+                let timestamps: [u64; QUERY_COUNT as usize] = {
+                    let data = self.readback_buffer.slice(..).get_mapped_range();
+                    bytemuck::pod_read_unaligned(&data)
+                };
+                self.readback_buffer.unmap();
+                let ticks = timestamps[1].saturating_sub(timestamps[0]);
+                self.last_frame_time = Some(Duration::from_nanos(
+                    (ticks as f64 * self.period as f64) as u64,
+                ));
+            }
+            Ok(Err(err)) => log::error!("Failed to read back the GPU timestamps: {err}"),
+            Err(TryRecvError::Disconnected) => {
+                log::error!("The GPU timestamps were dropped before being read back")
+            }
+        }
+        self.pending_map = None;
+    }
+
+    /// Record the start timestamp, unless the last measurement is still being read back
+    pub fn begin(&mut self, encoder: &mut CommandEncoder) {
+        if self.pending_map.is_none() {
+            encoder.write_timestamp(&self.query_set, 0);
+            self.recording = true;
+        }
+    }
+
+    /// Record the end timestamp, and resolve both somewhere we can read them from
+    pub fn end(&mut self, encoder: &mut CommandEncoder) {
+        if !self.recording {
+            return;
+        }
+        encoder.write_timestamp(&self.query_set, 1);
+        encoder.resolve_query_set(&self.query_set, 0..QUERY_COUNT, &self.readback_buffer, 0);
+    }
+
+    /// Start reading the timestamps back, once the encoder from `begin()` and `end()` has been submitted
+    pub fn submitted(&mut self) {
+        if !self.recording {
+            return;
+        }
+        self.recording = false;
+        let (sender, receiver) = mpsc::channel();
+        self.readback_buffer
+            .slice(..)
+            .map_async(MapMode::Read, move |result| {
+                let _ = sender.send(result);
+            });
+        self.pending_map = Some(receiver);
+    }
+
+    /// How long the GPU took over the last frame that's been read back
+    pub fn last_frame_time(&self) -> Option<Duration> {
+        self.last_frame_time
+    }
+}
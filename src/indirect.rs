@@ -0,0 +1,56 @@
+use bytemuck::{Pod, Zeroable};
+use wgpu::{
+    util::{BufferInitDescriptor, DeviceExt},
+    Buffer, BufferUsages, Device, Queue, RenderPass,
+};
+
+/// The arguments of one indexed draw, laid out the way the GPU reads them from an indirect buffer
+///
+/// The same as `draw_indexed(first_index..first_index + index_count, base_vertex, first_instance..first_instance + instance_count)`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Pod, Zeroable)]
+pub struct DrawIndexedIndirectArgs {
+    pub index_count: u32,
+    pub instance_count: u32,
+    pub first_index: u32,
+    pub base_vertex: i32,
+    /// Anything but 0 needs `Features::INDIRECT_FIRST_INSTANCE`
+    pub first_instance: u32,
+}
+
+/// A buffer holding `DrawIndexedIndirectArgs`, which a compute shader can write to decide what gets drawn without the CPU knowing
+///
+/// In WGSL it's an `array<u32, 5>` (or a struct of 5 `u32`s) in that order, with `base_vertex` being an `i32`
+pub struct DrawIndirect {
+    buffer: Buffer,
+}
+
+impl DrawIndirect {
+    pub fn new(device: &Device, args: DrawIndexedIndirectArgs) -> Self {
+        let buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Draw Indirect Buffer"),
+            contents: bytemuck::bytes_of(&args),
+            // `STORAGE` so compute shaders can fill it in, `COPY_SRC` so it can be read back to check what they wrote
+            usage: BufferUsages::INDIRECT
+                | BufferUsages::STORAGE
+                | BufferUsages::COPY_DST
+                | BufferUsages::COPY_SRC,
+        });
+        Self { buffer }
+    }
+
+    /// Overwrite the arguments from the CPU, takes effect when the queue is next submitted
+    pub fn write(&self, queue: &Queue, args: DrawIndexedIndirectArgs) {
+        queue.write_buffer(&self.buffer, 0, bytemuck::bytes_of(&args));
+    }
+
+    /// The buffer itself, for binding as `var<storage, read_write>` in a compute shader
+    pub fn buffer(&self) -> &Buffer {
+        &self.buffer
+    }
+
+    /// Draw with whatever arguments are in the buffer when the GPU gets to it, with the pipeline and buffers that are already set
+    pub fn draw<'a>(&'a self, render_pass: &mut RenderPass<'a>) {
+        render_pass.draw_indexed_indirect(&self.buffer, 0);
+    }
+}
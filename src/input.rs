@@ -0,0 +1,136 @@
+use std::collections::HashSet;
+
+use winit::event::{
+    ElementState, KeyboardInput, MouseButton, MouseScrollDelta, VirtualKeyCode, WindowEvent,
+};
+
+/// Roughly how many pixels a line of scrolling is, so touchpads (which scroll in pixels) and mouse wheels (which scroll in lines) match
+const PIXELS_PER_LINE: f64 = 20.0;
+
+/// Which keys and mouse buttons are currently held down, and what the mouse is up to
+///
+/// Lets `update()` poll for held keys instead of relying on the OS's key repeat
+#[derive(Debug)]
+pub struct InputState {
+    keys: HashSet<VirtualKeyCode>,
+    mouse_buttons: HashSet<MouseButton>,
+    /// In physical pixels from the top-left of the window
+    mouse_position: (f64, f64),
+    /// In lines, accumulated until `end_frame()`
+    scroll_delta: (f32, f32),
+    /// Physical pixels per logical pixel, from `ScaleFactorChanged`
+    scale_factor: f64,
+}
+
+impl Default for InputState {
+    fn default() -> Self {
+        Self {
+            keys: HashSet::new(),
+            mouse_buttons: HashSet::new(),
+            mouse_position: (0.0, 0.0),
+            scroll_delta: (0.0, 0.0),
+            scale_factor: 1.0,
+        }
+    }
+}
+
+impl InputState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Keep track of any keys or mouse buttons that `event` presses or releases
+    pub fn process_event(&mut self, event: &WindowEvent) {
+        match event {
+            WindowEvent::KeyboardInput {
+                input:
+                    KeyboardInput {
+                        state,
+                        virtual_keycode: Some(keycode),
+                        ..
+                    },
+                ..
+            } => match state {
+                ElementState::Pressed => {
+                    self.keys.insert(*keycode);
+                }
+                ElementState::Released => {
+                    self.keys.remove(keycode);
+                }
+            },
+            WindowEvent::MouseInput { state, button, .. } => match state {
+                ElementState::Pressed => {
+                    self.mouse_buttons.insert(*button);
+                }
+                ElementState::Released => {
+                    self.mouse_buttons.remove(button);
+                }
+            },
+            WindowEvent::CursorMoved { position, .. } => {
+                self.mouse_position = (position.x, position.y);
+            }
+            WindowEvent::MouseWheel { delta, .. } => {
+                let (x, y) = match delta {
+                    MouseScrollDelta::LineDelta(x, y) => (*x, *y),
+                    MouseScrollDelta::PixelDelta(position) => (
+                        (position.x / PIXELS_PER_LINE) as f32,
+                        (position.y / PIXELS_PER_LINE) as f32,
+                    ),
+                };
+                self.scroll_delta.0 += x;
+                self.scroll_delta.1 += y;
+            }
+            // We won't hear about anything being released while we're not focused, so forget everything
+            WindowEvent::Focused(false) => self.reset(),
+            _ => {}
+        }
+    }
+
+    /// Whether `key` is held down
+    pub fn is_key_down(&self, key: VirtualKeyCode) -> bool {
+        self.keys.contains(&key)
+    }
+
+    /// Whether `button` is held down
+    pub fn is_mouse_button_down(&self, button: MouseButton) -> bool {
+        self.mouse_buttons.contains(&button)
+    }
+
+    /// Where the cursor last was, in physical pixels from the top-left of the window
+    pub fn mouse_position(&self) -> (f64, f64) {
+        self.mouse_position
+    }
+
+    /// The same as `mouse_position()` but in logical pixels, which is what the UI and window sizes are given in
+    pub fn logical_mouse_position(&self) -> (f64, f64) {
+        let (x, y) = self.mouse_position;
+        (x / self.scale_factor, y / self.scale_factor)
+    }
+
+    /// Physical pixels per logical pixel, 1 unless the window's on a HiDPI display
+    pub fn scale_factor(&self) -> f64 {
+        self.scale_factor
+    }
+
+    /// Change how logical positions are worked out, the physical mouse position stays where it is
+    pub fn set_scale_factor(&mut self, scale_factor: f64) {
+        self.scale_factor = scale_factor;
+    }
+
+    /// How far the mouse wheel has scrolled (horizontally, vertically) this frame, in lines
+    pub fn scroll_delta(&self) -> (f32, f32) {
+        self.scroll_delta
+    }
+
+    /// Clear everything that only applies to a single frame, called by `State::end_update()`
+    pub fn end_frame(&mut self) {
+        self.scroll_delta = (0.0, 0.0);
+    }
+
+    /// Treat everything as released
+    pub fn reset(&mut self) {
+        self.keys.clear();
+        self.mouse_buttons.clear();
+        self.scroll_delta = (0.0, 0.0);
+    }
+}
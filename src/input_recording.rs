@@ -0,0 +1,245 @@
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter},
+    path::Path,
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+use winit::{
+    dpi::PhysicalPosition,
+    event::{
+        DeviceId, ElementState, KeyboardInput, ModifiersState, MouseButton, MouseScrollDelta,
+        TouchPhase, VirtualKeyCode, WindowEvent,
+    },
+};
+
+use crate::state::State;
+
+/// One of the events `State` cares about, stripped of anything tied to the machine it came from (like the device ID)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum RecordedEvent {
+    Key {
+        scancode: u32,
+        state: ElementState,
+        keycode: Option<VirtualKeyCode>,
+    },
+    MouseButton {
+        state: ElementState,
+        button: MouseButton,
+    },
+    /// In physical pixels from the top-left of the window
+    CursorMoved {
+        x: f64,
+        y: f64,
+    },
+    MouseWheel {
+        delta: MouseScrollDelta,
+        phase: TouchPhase,
+    },
+    /// Raw mouse movement, see `State::mouse_motion()`
+    MouseMotion {
+        x: f64,
+        y: f64,
+    },
+    ModifiersChanged(ModifiersState),
+    /// Typed text, for the UI
+    ReceivedCharacter(char),
+    Focused(bool),
+}
+
+impl RecordedEvent {
+    /// The parts of `event` we can replay, `None` for anything else (e.g. resizes, which depend on the window)
+    pub fn from_window_event(event: &WindowEvent) -> Option<Self> {
+        let event = match event {
+            WindowEvent::KeyboardInput { input, .. } => RecordedEvent::Key {
+                scancode: input.scancode,
+                state: input.state,
+                keycode: input.virtual_keycode,
+            },
+            WindowEvent::MouseInput { state, button, .. } => RecordedEvent::MouseButton {
+                state: *state,
+                button: *button,
+            },
+            WindowEvent::CursorMoved { position, .. } => RecordedEvent::CursorMoved {
+                x: position.x,
+                y: position.y,
+            },
+            WindowEvent::MouseWheel { delta, phase, .. } => RecordedEvent::MouseWheel {
+                delta: *delta,
+                phase: *phase,
+            },
+            WindowEvent::ModifiersChanged(modifiers) => RecordedEvent::ModifiersChanged(*modifiers),
+            WindowEvent::ReceivedCharacter(character) => {
+                RecordedEvent::ReceivedCharacter(*character)
+            }
+            WindowEvent::Focused(focused) => RecordedEvent::Focused(*focused),
+            _ => return None,
+        };
+        Some(event)
+    }
+
+    /// Turn it back into the `WindowEvent` it came from, `None` for `MouseMotion` which was never one
+    #[allow(deprecated)] // `modifiers` has to be filled in, even though `ModifiersChanged` replaces it
+    pub fn to_window_event(&self) -> Option<WindowEvent<'static>> {
+        // Only ever compared against itself by egui, never handed back to winit
+        let device_id = unsafe { DeviceId::dummy() };
+        let modifiers = ModifiersState::empty();
+        let event = match *self {
+            RecordedEvent::Key {
+                scancode,
+                state,
+                keycode,
+            } => WindowEvent::KeyboardInput {
+                device_id,
+                input: KeyboardInput {
+                    scancode,
+                    state,
+                    virtual_keycode: keycode,
+                    modifiers,
+                },
+                is_synthetic: false,
+            },
+            RecordedEvent::MouseButton { state, button } => WindowEvent::MouseInput {
+                device_id,
+                state,
+                button,
+                modifiers,
+            },
+            RecordedEvent::CursorMoved { x, y } => WindowEvent::CursorMoved {
+                device_id,
+                position: PhysicalPosition::new(x, y),
+                modifiers,
+            },
+            RecordedEvent::MouseWheel { delta, phase } => WindowEvent::MouseWheel {
+                device_id,
+                delta,
+                phase,
+                modifiers,
+            },
+            RecordedEvent::ModifiersChanged(modifiers) => WindowEvent::ModifiersChanged(modifiers),
+            RecordedEvent::ReceivedCharacter(character) => {
+                WindowEvent::ReceivedCharacter(character)
+            }
+            RecordedEvent::Focused(focused) => WindowEvent::Focused(focused),
+            RecordedEvent::MouseMotion { .. } => return None,
+        };
+        Some(event)
+    }
+}
+
+/// Collects the input `State` gets, a list of events per `update()`, see `State::start_recording_input()`
+///
+/// Saved as JSON, so a recording can be checked in next to a test and played back with an `InputReplayer`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InputRecorder {
+    /// What came in before each `update()`, with the last one still being added to
+    frames: Vec<Vec<RecordedEvent>>,
+}
+
+impl Default for InputRecorder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl InputRecorder {
+    pub fn new() -> Self {
+        Self {
+            frames: vec![Vec::new()],
+        }
+    }
+
+    /// Add `event` to the current frame
+    pub fn record(&mut self, event: RecordedEvent) {
+        match self.frames.last_mut() {
+            Some(frame) => frame.push(event),
+            None => self.frames.push(vec![event]),
+        }
+    }
+
+    /// Start the next frame, called at the end of every `State::step()`
+    pub fn end_frame(&mut self) {
+        self.frames.push(Vec::new());
+    }
+
+    /// How many `step()`s (one per `update()` without a fixed timestep) have been recorded so far
+    pub fn frame_count(&self) -> usize {
+        // The last one hasn't finished yet
+        self.frames.len().saturating_sub(1)
+    }
+
+    /// Write everything recorded so far to `path`, overwriting it
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let file = BufWriter::new(File::create(path)?);
+        serde_json::to_writer(file, self)?;
+        Ok(())
+    }
+
+    /// Play it back, see `InputReplayer::new()`
+    pub fn into_replayer(self, timestep: Duration) -> InputReplayer {
+        InputReplayer::new(self, timestep)
+    }
+}
+
+/// Feeds a recording back into `State`, one `update()` at a time with the same timestep every time
+///
+/// The camera ends up in exactly the same place however fast the machine is, which makes it good for regression tests
+/// The UI only sees the events when it has a window to take its input from, see `State::begin_ui_frame()`
+pub struct InputReplayer {
+    frames: Vec<Vec<RecordedEvent>>,
+    /// Which frame `step()` plays next
+    next_frame: usize,
+    timestep: Duration,
+}
+
+impl InputReplayer {
+    /// Play back `recording`, passing `timestep` to every `State::update()`
+    pub fn new(recording: InputRecorder, timestep: Duration) -> Self {
+        let mut frames = recording.frames;
+        // The last frame never got its `update()`, so it didn't affect anything
+        frames.pop();
+        Self {
+            frames,
+            next_frame: 0,
+            timestep,
+        }
+    }
+
+    /// Load a recording from `InputRecorder::save()`
+    pub fn load(path: impl AsRef<Path>, timestep: Duration) -> io::Result<Self> {
+        let file = BufReader::new(File::open(path)?);
+        let recording: InputRecorder = serde_json::from_reader(file)?;
+        Ok(Self::new(recording, timestep))
+    }
+
+    /// Send the next frame's events to `state` as if they came from the main window, then `update()` it
+    ///
+    /// Returns `false` without doing anything once every frame has been played
+    pub fn step(&mut self, state: &mut State) -> bool {
+        let frame = match self.frames.get(self.next_frame) {
+            Some(frame) => frame,
+            None => return false,
+        };
+        for event in frame {
+            if let RecordedEvent::MouseMotion { x, y } = *event {
+                state.mouse_motion((x, y));
+            } else if let Some(window_event) = event.to_window_event() {
+                state.primary_input(&window_event);
+            }
+        }
+        state.update(self.timestep);
+        self.next_frame += 1;
+        true
+    }
+
+    /// Play every frame that's left
+    pub fn run(&mut self, state: &mut State) {
+        while self.step(state) {}
+    }
+
+    /// Whether every frame has been played
+    pub fn is_finished(&self) -> bool {
+        self.next_frame >= self.frames.len()
+    }
+}
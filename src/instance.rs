@@ -0,0 +1,63 @@
+use std::mem;
+
+use glam::{Mat4, Quat, Vec3};
+use wgpu::{vertex_attr_array, BufferAddress, VertexAttribute, VertexBufferLayout, VertexStepMode};
+
+use crate::frustum::BoundingSphere;
+
+/// One copy of the mesh, placed somewhere in the world
+///
+/// There's no texture layer to pick here, meshes all sample the one `t_diffuse`, only `SpriteBatch` draws from texture arrays
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Instance {
+    pub position: Vec3,
+    pub rotation: Quat,
+    /// Can differ per axis, the shader keeps the normals right either way
+    pub scale: Vec3,
+}
+
+impl Default for Instance {
+    fn default() -> Self {
+        Self {
+            position: Vec3::ZERO,
+            rotation: Quat::IDENTITY,
+            scale: Vec3::ONE,
+        }
+    }
+}
+
+impl Instance {
+    // A `mat4x4` takes up 4 vertex slots, one per column
+    // We start at 5 to leave some room for more per-vertex attributes
+    const ATTRIBUTES: [VertexAttribute; 4] = vertex_attr_array![
+        5 => Float32x4,
+        6 => Float32x4,
+        7 => Float32x4,
+        8 => Float32x4,
+    ];
+
+    /// Takes the mesh from its own space into the world
+    pub fn model_matrix(&self) -> Mat4 {
+        Mat4::from_scale_rotation_translation(self.scale, self.rotation, self.position)
+    }
+
+    /// The model matrix for this instance, laid out the way the shader expects it
+    pub fn to_raw(&self) -> [[f32; 4]; 4] {
+        self.model_matrix().to_cols_array_2d()
+    }
+
+    /// Where the mesh bounded by `mesh_bounds` ends up with this instance's position, rotation and scale
+    pub fn bounding_sphere(&self, mesh_bounds: BoundingSphere) -> BoundingSphere {
+        mesh_bounds.transformed(self.model_matrix())
+    }
+
+    /// Describes to the pipeline how the output of `to_raw()` is laid out in the instance buffer
+    pub fn desc<'a>() -> VertexBufferLayout<'a> {
+        VertexBufferLayout {
+            array_stride: mem::size_of::<[[f32; 4]; 4]>() as BufferAddress,
+            // Only move on to the next element once per instance, rather than once per vertex
+            step_mode: VertexStepMode::Instance,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+}
@@ -0,0 +1,153 @@
+use wgpu::{
+    util::{BufferInitDescriptor, DeviceExt},
+    BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayoutDescriptor,
+    BindGroupLayoutEntry, BindingType, Buffer, BufferBindingType, BufferUsages, Color,
+    ColorTargetState, ColorWrites, CompareFunction, DepthBiasState, DepthStencilState, Device,
+    FragmentState, MultisampleState, PipelineLayout, PipelineLayoutDescriptor, PrimitiveState,
+    Queue, RenderPass, RenderPipeline, RenderPipelineDescriptor, ShaderModule,
+    ShaderModuleDescriptor, ShaderSource, ShaderStages, StencilState, TextureFormat, VertexState,
+};
+use winit::dpi::PhysicalSize;
+
+use crate::texture::DEPTH_FORMAT;
+
+/// The part of a target the scene gets drawn into, in physical pixels
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Viewport {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Viewport {
+    /// The biggest rectangle with `aspect` that fits in `size`, centred, leaving bars on either the sides or the top and bottom
+    pub fn letterboxed(size: PhysicalSize<u32>, aspect: f32) -> Self {
+        let (target_width, target_height) = (size.width as f32, size.height as f32);
+        // Rounded to whole pixels so the scene's edges stay sharp
+        let width = (target_height * aspect).min(target_width).round().max(1.0);
+        let height = (target_width / aspect).min(target_height).round().max(1.0);
+        Self {
+            x: ((target_width - width) / 2.0).floor(),
+            y: ((target_height - height) / 2.0).floor(),
+            width,
+            height,
+        }
+    }
+
+    /// Width divided by height
+    pub fn aspect(&self) -> f32 {
+        self.width / self.height
+    }
+}
+
+/// Fills the viewport with the clear colour, since clearing always covers the whole target and the bars need a different colour
+pub struct LetterboxFill {
+    shader: ShaderModule,
+    layout: PipelineLayout,
+    /// The clear colour, rewritten every frame
+    color_buffer: Buffer,
+    bind_group: BindGroup,
+}
+
+impl LetterboxFill {
+    pub fn new(device: &Device) -> Self {
+        let shader = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("Letterbox Shader"),
+            source: ShaderSource::Wgsl(include_str!("letterbox.wgsl").into()),
+        });
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Letterbox Bind Group Layout"),
+            entries: &[BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::FRAGMENT,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Letterbox Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let color_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Letterbox Colour Buffer"),
+            contents: bytemuck::bytes_of(&[0f32; 4]),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Letterbox Bind Group"),
+            layout: &bind_group_layout,
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: color_buffer.as_entire_binding(),
+            }],
+        });
+        Self {
+            shader,
+            layout,
+            color_buffer,
+            bind_group,
+        }
+    }
+
+    /// Build the pipeline for a target with `format` and `sample_count`, which has to be used in the same pass as the scene
+    pub fn create_pipeline(
+        &self,
+        device: &Device,
+        format: TextureFormat,
+        sample_count: u32,
+    ) -> RenderPipeline {
+        device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("Letterbox Pipeline"),
+            layout: Some(&self.layout),
+            vertex: VertexState {
+                module: &self.shader,
+                entry_point: "vs_main",
+                // The triangle is made up in the vertex shader
+                buffers: &[],
+            },
+            fragment: Some(FragmentState {
+                module: &self.shader,
+                entry_point: "fs_main",
+                targets: &[Some(ColorTargetState {
+                    format,
+                    // Replaces what's there, like clearing would
+                    blend: None,
+                    write_mask: ColorWrites::ALL,
+                })],
+            }),
+            primitive: PrimitiveState::default(),
+            // Has to match the scene's pass, but leaves the depth alone
+            depth_stencil: Some(DepthStencilState {
+                format: DEPTH_FORMAT,
+                depth_write_enabled: false,
+                depth_compare: CompareFunction::Always,
+                stencil: StencilState::default(),
+                bias: DepthBiasState::default(),
+            }),
+            multisample: MultisampleState {
+                count: sample_count,
+                ..MultisampleState::default()
+            },
+            multiview: None,
+        })
+    }
+
+    /// Set the colour `fill()` draws, takes effect when the queue is next submitted
+    pub fn set_color(&self, queue: &Queue, color: Color) {
+        let color = [color.r, color.g, color.b, color.a].map(|channel| channel as f32);
+        queue.write_buffer(&self.color_buffer, 0, bytemuck::bytes_of(&color));
+    }
+
+    /// Fill the render pass's current viewport using `pipeline` from `create_pipeline()`
+    pub fn fill<'a>(&'a self, render_pass: &mut RenderPass<'a>, pipeline: &'a RenderPipeline) {
+        render_pass.set_pipeline(pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
@@ -0,0 +1,20 @@
+// Fills the viewport with a single colour, the background of a letterboxed scene
+
+struct Fill {
+    color: vec4<f32>,
+};
+@group(0) @binding(0)
+var<uniform> fill: Fill;
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+    // One triangle big enough to cover the whole viewport, with corners at (-1, -1), (3, -1) and (-1, 3)
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    // As far away as possible, though the depth isn't tested or written anyway
+    return vec4<f32>(uv * 2.0 - 1.0, 1.0, 1.0);
+}
+
+@fragment
+fn fs_main() -> @location(0) vec4<f32> {
+    return fill.color;
+}
@@ -0,0 +1,44 @@
+pub mod anti_aliasing;
+pub mod assets;
+pub mod blend_mode;
+pub mod bloom;
+pub mod camera;
+pub mod color;
+pub mod compressed_texture;
+pub mod compute;
+pub mod cursor;
+pub mod debug_lines;
+pub mod debug_view;
+pub mod deferred;
+pub mod frustum;
+pub mod gbuffer;
+pub mod globals;
+pub mod gpu_timer;
+pub mod indirect;
+pub mod input;
+pub mod input_recording;
+pub mod instance;
+pub mod letterbox;
+pub mod light;
+pub mod limits_profile;
+pub mod material;
+pub mod mesh;
+pub mod mipmap;
+pub mod object_transforms;
+pub mod particles;
+pub mod picking;
+pub mod post_process;
+pub mod render_graph;
+pub mod render_target;
+pub mod run;
+pub mod shader_watcher;
+pub mod shadow;
+pub mod skybox;
+pub mod sprite;
+pub mod state;
+pub mod stencil;
+pub mod text;
+pub mod texture;
+pub mod timer;
+pub mod ui;
+pub mod vertex;
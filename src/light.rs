@@ -0,0 +1,32 @@
+use bytemuck::{Pod, Zeroable};
+
+/// A point light, read by the shader from `@group(0) @binding(1)`
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+pub struct Light {
+    /// Where the light is in world space
+    pub position: [f32; 3],
+    // A `vec3` takes up 16 bytes in a uniform buffer, so each one needs padding after it
+    _padding: u32,
+    /// Linear RGB, can go above 1 for a brighter light
+    pub color: [f32; 3],
+    _padding2: u32,
+}
+
+impl Light {
+    pub fn new(position: [f32; 3], color: [f32; 3]) -> Self {
+        Self {
+            position,
+            _padding: 0,
+            color,
+            _padding2: 0,
+        }
+    }
+}
+
+impl Default for Light {
+    /// A white light above and to the right of the camera's starting position
+    fn default() -> Self {
+        Self::new([2.0, 2.0, 2.0], [1.0, 1.0, 1.0])
+    }
+}
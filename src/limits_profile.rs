@@ -0,0 +1,47 @@
+use wgpu::{Adapter, Limits};
+
+/// Which limits to ask the device for, see `StateBuilder::limits_profile()`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LimitsProfile {
+    /// Low enough for pretty much any GPU, or WebGL 2's even lower limits in the browser
+    #[default]
+    Downlevel,
+    /// What WebGPU guarantees, e.g. 8192 pixel textures and 256 MB buffers
+    Default,
+    /// Everything the adapter can do, e.g. for huge textures on desktop GPUs
+    Adapter,
+}
+
+impl LimitsProfile {
+    /// The limits this profile asks for on `adapter`
+    ///
+    /// `Default` falls back to `Downlevel` if the adapter can't reach them, logging which limits it falls short on
+    pub fn limits(self, adapter: &Adapter) -> Limits {
+        let supported = adapter.limits();
+        let limits = match self {
+            LimitsProfile::Downlevel => return downlevel_limits(),
+            LimitsProfile::Default => Limits::default(),
+            LimitsProfile::Adapter => return supported,
+        };
+        let mut within = true;
+        limits.check_limits_with_fail_fn(&supported, false, |name, wanted, allowed| {
+            log::warn!("The adapter only supports {allowed} for {name}, not {wanted}");
+            within = false;
+        });
+        if within {
+            limits
+        } else {
+            log::warn!("Using the downlevel limits instead of {self:?}");
+            downlevel_limits()
+        }
+    }
+}
+
+/// WebGL 2 needs even lower limits than other downlevel backends (e.g. no storage buffers)
+fn downlevel_limits() -> Limits {
+    if cfg!(target_arch = "wasm32") {
+        Limits::downlevel_webgl2_defaults()
+    } else {
+        Limits::downlevel_defaults()
+    }
+}
@@ -1,8 +1,9 @@
-use run::run;
-
-mod run;
-mod state;
+use wgpu_thing::run::run;
 
 fn main() {
-    pollster::block_on(run());
+    #[cfg(not(target_arch = "wasm32"))]
+    pollster::block_on(run(None, None));
+    // The browser can't be blocked on, so the future gets driven by its event loop instead
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_futures::spawn_local(run(None, None));
 }
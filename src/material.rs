@@ -0,0 +1,167 @@
+use std::{error::Error, fmt};
+
+use image::{DynamicImage, ImageError};
+use wgpu::{
+    BindGroup, BindGroupLayout, Device, Face, Queue, RenderPipeline, ShaderModule,
+    ShaderModuleDescriptor, ShaderSource, StencilState,
+};
+
+use crate::blend_mode::BlendMode;
+use crate::state::error_description;
+use crate::texture::{Texture, TextureOptions};
+
+/// What a `Material` should look like, see `State::add_material()`
+#[derive(Debug, Clone)]
+pub struct MaterialDescriptor {
+    /// Shows up in the labels of its texture and shader
+    pub name: String,
+    pub blend_mode: BlendMode,
+    /// Which triangles get culled, one of `render_target::CULL_MODES`
+    pub cull_mode: Option<Face>,
+    /// The stencil test (and what gets written to the stencil) for its meshes, `None` to use the scene's from `State::set_stencil()`
+    ///
+    /// e.g. a material writing the stencil for a portal, and another only drawn where it was written
+    pub stencil: Option<StencilState>,
+    /// What its meshes' stencil is compared against (and written with), `None` to use `State::set_stencil_reference()`
+    pub stencil_reference: Option<u32>,
+    /// WGSL to draw with in place of the scene's shader, with the same bindings and `vs_main`/`fs_main` entry points
+    ///
+    /// `None` uses whatever the scene's shader is at the time, reloads included
+    pub shader_source: Option<String>,
+    /// Bound at `@group(2)` in place of the scene's texture, `None` to use the scene's
+    pub texture: Option<DynamicImage>,
+}
+
+impl Default for MaterialDescriptor {
+    fn default() -> Self {
+        Self {
+            name: "Material".to_owned(),
+            blend_mode: BlendMode::default(),
+            cull_mode: Some(Face::Back),
+            stencil: None,
+            stencil_reference: None,
+            shader_source: None,
+            texture: None,
+        }
+    }
+}
+
+/// A material added with `State::add_material()`, for setting as a mesh's `Mesh::material`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct MaterialId(pub(crate) usize);
+
+/// Everything that can go wrong in `State::add_material()`
+#[derive(Debug)]
+pub enum MaterialError {
+    /// The texture couldn't be uploaded, e.g. it's too big for the device
+    Texture(ImageError),
+    /// The shader or the pipelines built from it failed validation
+    Pipeline(wgpu::Error),
+}
+
+impl fmt::Display for MaterialError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MaterialError::Texture(err) => write!(f, "failed to upload the texture: {err}"),
+            MaterialError::Pipeline(err) => {
+                write!(
+                    f,
+                    "failed to build the material's pipelines: {}",
+                    error_description(err)
+                )
+            }
+        }
+    }
+}
+
+impl Error for MaterialError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            MaterialError::Texture(err) => Some(err),
+            MaterialError::Pipeline(err) => Some(err),
+        }
+    }
+}
+
+/// Pipeline state, a shader and a texture for drawing some meshes differently to the rest
+///
+/// The pipelines themselves are built for each target, see `RenderTarget::material_pipelines`
+pub struct Material {
+    descriptor: MaterialDescriptor,
+    /// Compiled from `descriptor.shader_source` with the transform prelude in front, `None` to use the scene's
+    shader: Option<ShaderModule>,
+    /// Kept alongside its bind group, `None` to use the scene's
+    texture: Option<(Texture, BindGroup)>,
+}
+
+impl Material {
+    /// Upload the texture and compile the shader, the shader isn't validated until the pipelines are built
+    ///
+    /// The texture is uploaded with `texture_options`, `State` passes its own so it matches the scene's
+    pub fn new(
+        device: &Device,
+        queue: &Queue,
+        texture_layout: &BindGroupLayout,
+        prelude: &str,
+        texture_options: TextureOptions,
+        descriptor: MaterialDescriptor,
+    ) -> Result<Self, ImageError> {
+        let texture = match &descriptor.texture {
+            Some(image) => {
+                let texture = Texture::from_image(
+                    device,
+                    queue,
+                    image,
+                    Some(&descriptor.name),
+                    texture_options,
+                )?;
+                let bind_group = texture.bind_group(device, texture_layout);
+                Some((texture, bind_group))
+            }
+            None => None,
+        };
+        let shader = descriptor.shader_source.as_ref().map(|source| {
+            device.create_shader_module(ShaderModuleDescriptor {
+                label: Some(&descriptor.name),
+                source: ShaderSource::Wgsl(format!("{prelude}{source}").into()),
+            })
+        });
+        Ok(Self {
+            descriptor,
+            shader,
+            texture,
+        })
+    }
+
+    /// What it was created with, e.g. for creating it again on a new device
+    pub fn descriptor(&self) -> &MaterialDescriptor {
+        &self.descriptor
+    }
+
+    /// Its own shader, `None` if it's drawn with the scene's
+    pub fn shader(&self) -> Option<&ShaderModule> {
+        self.shader.as_ref()
+    }
+
+    /// Binds its texture, `None` if it's drawn with the scene's
+    pub fn bind_group(&self) -> Option<&BindGroup> {
+        self.texture.as_ref().map(|(_, bind_group)| bind_group)
+    }
+}
+
+/// One material's pipelines for one target
+pub struct MaterialPipelines {
+    pub fill: RenderPipeline,
+    /// The same as `fill` but only drawing what's at the depth the depth prepass stored
+    pub after_prepass: RenderPipeline,
+}
+
+impl MaterialPipelines {
+    pub fn get(&self, after_prepass: bool) -> &RenderPipeline {
+        if after_prepass {
+            &self.after_prepass
+        } else {
+            &self.fill
+        }
+    }
+}
@@ -0,0 +1,169 @@
+use std::{
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+use glam::{Mat4, Vec3};
+use wgpu::{
+    util::{BufferInitDescriptor, DeviceExt},
+    Buffer, BufferUsages, Device,
+};
+
+use crate::frustum::BoundingSphere;
+use crate::material::MaterialId;
+use crate::vertex::Vertex;
+
+/// Part of a `Mesh` that uses a single material
+#[derive(Debug, Clone)]
+pub struct Submesh {
+    /// The object or group name from the OBJ
+    pub name: String,
+    /// Which of the mesh's indices belong to this submesh
+    pub indices: Range<u32>,
+    /// Added to every index, since the submeshes' vertices are all in the one buffer
+    pub base_vertex: i32,
+    /// The name of the material in the MTL file, if it has one
+    pub material: Option<String>,
+}
+
+/// Geometry loaded from a model file, ready to draw
+pub struct Mesh {
+    /// Where it was loaded from, so it can be loaded again (e.g. onto a new device)
+    pub path: PathBuf,
+    pub vertex_buffer: Buffer,
+    /// Always `u32` indices, models easily have more than 65536 vertices
+    pub index_buffer: Buffer,
+    /// How many indices are in `index_buffer`
+    pub num_indices: u32,
+    pub submeshes: Vec<Submesh>,
+    /// Around every vertex, `None` if there aren't any
+    pub bounding_sphere: Option<BoundingSphere>,
+    /// Moves every instance of this mesh, applied after each instance's own transform and before `State::set_transform()`'s
+    pub transform: Mat4,
+    /// What it's drawn with instead of the scene's pipeline and texture, see `State::add_material()`
+    pub material: Option<MaterialId>,
+}
+
+/// A `Mesh` that's been read but not uploaded yet, so the reading can happen on another thread
+pub struct MeshData {
+    pub path: PathBuf,
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u32>,
+    pub submeshes: Vec<Submesh>,
+}
+
+impl MeshData {
+    /// Create the buffers for the mesh, on the thread that owns `device`
+    pub fn upload(self, device: &Device) -> Mesh {
+        let positions: Vec<_> = self
+            .vertices
+            .iter()
+            .map(|vertex| Vec3::from(vertex.position))
+            .collect();
+        let vertex_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some(&format!("{} Vertex Buffer", self.path.display())),
+            contents: bytemuck::cast_slice(&self.vertices),
+            usage: BufferUsages::VERTEX,
+        });
+        let index_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some(&format!("{} Index Buffer", self.path.display())),
+            contents: bytemuck::cast_slice(&self.indices),
+            usage: BufferUsages::INDEX,
+        });
+        Mesh {
+            path: self.path,
+            vertex_buffer,
+            index_buffer,
+            num_indices: self.indices.len() as u32,
+            submeshes: self.submeshes,
+            bounding_sphere: BoundingSphere::from_points(&positions),
+            transform: Mat4::IDENTITY,
+            material: None,
+        }
+    }
+}
+
+/// Load a Wavefront OBJ file into a `Mesh`, with one submesh per object and material
+///
+/// Vertices are coloured with their material's diffuse colour (or white), the textures in the MTL file are ignored
+/// Normals are generated if the file doesn't have any
+pub fn load_obj(device: &Device, path: impl AsRef<Path>) -> Result<Mesh, tobj::LoadError> {
+    Ok(read_obj(path)?.upload(device))
+}
+
+/// The same as `load_obj()` without uploading anything, which is the slow part
+pub fn read_obj(path: impl AsRef<Path>) -> Result<MeshData, tobj::LoadError> {
+    let path = path.as_ref();
+    // Triangulated, with a single index per vertex, since that's all we can draw
+    let (models, materials) = tobj::load_obj(path, &tobj::GPU_LOAD_OPTIONS)?;
+    // The geometry is still usable without its materials
+    let materials = materials.unwrap_or_else(|err| {
+        log::warn!("Failed to load the materials for {}: {err}", path.display());
+        Vec::new()
+    });
+
+    let mut vertices = Vec::new();
+    let mut indices = Vec::new();
+    let mut submeshes = Vec::new();
+    for model in models {
+        let mesh = model.mesh;
+        let material = mesh.material_id.and_then(|id| materials.get(id));
+        let color = material
+            .and_then(|material| material.diffuse)
+            .unwrap_or([1.0, 1.0, 1.0]);
+        let normals = if mesh.normals.is_empty() {
+            generate_normals(&mesh.positions, &mesh.indices)
+        } else {
+            mesh.normals
+        };
+
+        let base_vertex = vertices.len() as i32;
+        let start = indices.len() as u32;
+        vertices.extend((0..mesh.positions.len() / 3).map(|i| Vertex {
+            position: [
+                mesh.positions[i * 3],
+                mesh.positions[i * 3 + 1],
+                mesh.positions[i * 3 + 2],
+            ],
+            color,
+            // OBJ texture coordinates start at the bottom left
+            tex_coords: match mesh.texcoords.get(i * 2..i * 2 + 2) {
+                Some(&[u, v]) => [u, 1.0 - v],
+                _ => [0.0, 0.0],
+            },
+            normal: [normals[i * 3], normals[i * 3 + 1], normals[i * 3 + 2]],
+        }));
+        indices.extend_from_slice(&mesh.indices);
+        submeshes.push(Submesh {
+            name: model.name,
+            indices: start..indices.len() as u32,
+            base_vertex,
+            material: material.map(|material| material.name.clone()),
+        });
+    }
+
+    Ok(MeshData {
+        path: path.to_owned(),
+        vertices,
+        indices,
+        submeshes,
+    })
+}
+
+/// Smooth normals for a triangle list, each vertex gets the average of the faces around it (weighted by their area)
+fn generate_normals(positions: &[f32], indices: &[u32]) -> Vec<f32> {
+    let position = |index: u32| Vec3::from_slice(&positions[index as usize * 3..]);
+    let mut normals = vec![Vec3::ZERO; positions.len() / 3];
+    for triangle in indices.chunks_exact(3) {
+        let [a, b, c] = [triangle[0], triangle[1], triangle[2]];
+        // Not normalised, so bigger faces count for more
+        let face_normal = (position(b) - position(a)).cross(position(c) - position(a));
+        for index in triangle {
+            normals[*index as usize] += face_normal;
+        }
+    }
+    normals
+        .into_iter()
+        .flat_map(|normal| normal.normalize_or_zero().to_array())
+        .collect()
+}
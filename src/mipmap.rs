@@ -0,0 +1,157 @@
+use std::num::NonZeroU32;
+
+use wgpu::{
+    BindGroupDescriptor, BindGroupEntry, BindGroupLayoutDescriptor, BindGroupLayoutEntry,
+    BindingResource, BindingType, Color, ColorTargetState, ColorWrites, CommandEncoderDescriptor,
+    Device, Extent3d, FragmentState, ImageCopyTexture, LoadOp, MultisampleState, Operations,
+    Origin3d, PipelineLayoutDescriptor, PrimitiveState, Queue, RenderPassColorAttachment,
+    RenderPassDescriptor, RenderPipelineDescriptor, ShaderModuleDescriptor, ShaderSource,
+    ShaderStages, Texture, TextureAspect, TextureDescriptor, TextureDimension, TextureFormat,
+    TextureSampleType, TextureUsages, TextureViewDescriptor, TextureViewDimension, VertexState,
+};
+
+/// How many mip levels a `width`x`height` texture has, halving (rounding down) until both sides are 1
+pub fn mip_level_count(width: u32, height: u32) -> u32 {
+    u32::BITS - width.max(height).max(1).leading_zeros()
+}
+
+/// Fill in every mip level of `texture` below the first, each one averaged down from the level above
+///
+/// wgpu has no blit to do this for us, so it's a render pass per level
+/// `texture` needs `TextureUsages::COPY_DST` and `TextureUsages::TEXTURE_BINDING`, and `format` has to be renderable
+pub fn generate_mipmaps(
+    device: &Device,
+    queue: &Queue,
+    texture: &Texture,
+    size: Extent3d,
+    format: TextureFormat,
+    mip_level_count: u32,
+) {
+    if mip_level_count <= 1 {
+        return;
+    }
+    // Only made when a texture gets loaded, so it's not worth keeping around
+    let shader = device.create_shader_module(ShaderModuleDescriptor {
+        label: Some("Mipmap Shader"),
+        source: ShaderSource::Wgsl(include_str!("mipmap.wgsl").into()),
+    });
+    let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+        label: Some("Mipmap Bind Group Layout"),
+        entries: &[BindGroupLayoutEntry {
+            binding: 0,
+            visibility: ShaderStages::FRAGMENT,
+            ty: BindingType::Texture {
+                // Read with `textureLoad()`, so it's never filtered
+                sample_type: TextureSampleType::Float { filterable: false },
+                view_dimension: TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        }],
+    });
+    let layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+        label: Some("Mipmap Pipeline Layout"),
+        bind_group_layouts: &[&bind_group_layout],
+        push_constant_ranges: &[],
+    });
+    let pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+        label: Some("Mipmap Pipeline"),
+        layout: Some(&layout),
+        vertex: VertexState {
+            module: &shader,
+            entry_point: "vs_main",
+            // The triangle is made up in the vertex shader
+            buffers: &[],
+        },
+        fragment: Some(FragmentState {
+            module: &shader,
+            entry_point: "downsample",
+            targets: &[Some(ColorTargetState {
+                format,
+                blend: None,
+                write_mask: ColorWrites::ALL,
+            })],
+        }),
+        primitive: PrimitiveState::default(),
+        depth_stencil: None,
+        multisample: MultisampleState::default(),
+        multiview: None,
+    });
+
+    let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor {
+        label: Some("Mipmap Encoder"),
+    });
+    // The GL backend can only sample the first level of a texture on its own, so each level is drawn into a texture
+    // of its own and copied across, then read from for the next level
+    // The first level of `texture` is the only one that gets read directly
+    // Sampling an sRGB view gives linear colours, and drawing into one converts back, so the averaging happens in linear
+    let mut source = texture.create_view(&TextureViewDescriptor {
+        label: Some("Mip Level"),
+        mip_level_count: NonZeroU32::new(1),
+        ..TextureViewDescriptor::default()
+    });
+    for level in 1..mip_level_count {
+        let level_size = Extent3d {
+            width: (size.width >> level).max(1),
+            height: (size.height >> level).max(1),
+            depth_or_array_layers: 1,
+        };
+        let scratch = device.create_texture(&TextureDescriptor {
+            label: Some("Mip Level Texture"),
+            size: level_size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format,
+            usage: TextureUsages::RENDER_ATTACHMENT
+                | TextureUsages::TEXTURE_BINDING
+                | TextureUsages::COPY_SRC,
+        });
+        let view = scratch.create_view(&TextureViewDescriptor::default());
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Mipmap Bind Group"),
+            layout: &bind_group_layout,
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: BindingResource::TextureView(&source),
+            }],
+        });
+        {
+            let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+                label: Some("Mipmap Pass"),
+                color_attachments: &[Some(RenderPassColorAttachment {
+                    view: &view,
+                    resolve_target: None,
+                    ops: Operations {
+                        // Every pixel gets drawn over
+                        load: LoadOp::Clear(Color::TRANSPARENT),
+                        store: true,
+                    },
+                })],
+                depth_stencil_attachment: None,
+            });
+            render_pass.set_pipeline(&pipeline);
+            render_pass.set_bind_group(0, &bind_group, &[]);
+            render_pass.draw(0..3, 0..1);
+        }
+        encoder.copy_texture_to_texture(
+            ImageCopyTexture {
+                texture: &scratch,
+                mip_level: 0,
+                origin: Origin3d::ZERO,
+                aspect: TextureAspect::All,
+            },
+            ImageCopyTexture {
+                texture,
+                mip_level: level,
+                origin: Origin3d::ZERO,
+                aspect: TextureAspect::All,
+            },
+            level_size,
+        );
+        // Dropping the texture is fine, wgpu keeps it alive until the commands using it have run
+        source = view;
+    }
+    // After the `write_texture()` that filled in the first level, which happens at the start of this submission
+    queue.submit(std::iter::once(encoder.finish()));
+}
@@ -0,0 +1,31 @@
+// Builds each mip level from the one above it, drawing a fullscreen triangle into it
+
+// The level above the one being drawn
+@group(0) @binding(0)
+var source: texture_2d<f32>;
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+    // One triangle big enough to cover the whole target, with corners at (-1, -1), (3, -1) and (-1, 3)
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    return vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+}
+
+// Average every source texel this one covers, which is 2x2 normally but 3 wide (or tall) when the source is an odd size
+// A bilinear sample would only see 2 of those 3 and skip the rest, so non-power-of-two textures would shimmer
+@fragment
+fn downsample(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let source_size = vec2<u32>(textureDimensions(source));
+    let size = max(source_size / 2u, vec2<u32>(1u, 1u));
+    let texel = vec2<u32>(position.xy);
+    let start = texel * source_size / size;
+    let end = min(((texel + 1u) * source_size + size - 1u) / size, source_size);
+    var sum = vec4<f32>(0.0);
+    for (var y = start.y; y < end.y; y = y + 1u) {
+        for (var x = start.x; x < end.x; x = x + 1u) {
+            sum = sum + textureLoad(source, vec2<i32>(i32(x), i32(y)), 0);
+        }
+    }
+    let count = (end - start).x * (end - start).y;
+    return sum / f32(count);
+}
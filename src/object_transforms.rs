@@ -0,0 +1,118 @@
+use glam::Mat4;
+use wgpu::{
+    BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor,
+    BindGroupLayoutEntry, BindingResource, BindingType, Buffer, BufferBinding, BufferBindingType,
+    BufferDescriptor, BufferSize, BufferUsages, Device, Queue, ShaderStages,
+};
+
+/// How big one transform is in the shader
+const TRANSFORM_SIZE: u64 = std::mem::size_of::<Mat4>() as u64;
+
+/// One big uniform buffer holding a transform per object, each drawn by binding the same bind group at a different offset
+///
+/// Saves having a buffer and bind group per object, since only the offset passed to `set_bind_group()` changes between draws
+pub struct ObjectTransforms {
+    layout: BindGroupLayout,
+    buffer: Buffer,
+    bind_group: BindGroup,
+    /// How far apart the transforms are, `TRANSFORM_SIZE` rounded up to `min_uniform_buffer_offset_alignment`
+    stride: u64,
+    /// How many transforms fit in `buffer`
+    capacity: usize,
+}
+
+impl ObjectTransforms {
+    pub fn new(device: &Device) -> Self {
+        let layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Transform Bind Group Layout"),
+            entries: &[BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::VERTEX,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Uniform,
+                    // Which transform is picked when the bind group is set
+                    has_dynamic_offset: true,
+                    min_binding_size: BufferSize::new(TRANSFORM_SIZE),
+                },
+                count: None,
+            }],
+        });
+        // Offsets have to be a multiple of this, 256 on most GPUs, so most of each slot is padding
+        let alignment = device.limits().min_uniform_buffer_offset_alignment as u64;
+        let stride = TRANSFORM_SIZE.div_ceil(alignment) * alignment;
+        let capacity = 1;
+        let (buffer, bind_group) = create_buffer(device, &layout, stride, capacity);
+        Self {
+            layout,
+            buffer,
+            bind_group,
+            stride,
+            capacity,
+        }
+    }
+
+    /// Goes at `@group(3)` in place of a bind group with a plain uniform buffer
+    pub fn layout(&self) -> &BindGroupLayout {
+        &self.layout
+    }
+
+    pub fn bind_group(&self) -> &BindGroup {
+        &self.bind_group
+    }
+
+    /// What to pass to `set_bind_group()` to draw with the transform at `index`
+    pub fn offset(&self, index: usize) -> u32 {
+        (index as u64 * self.stride) as u32
+    }
+
+    /// Replace the transforms, growing the buffer if they don't fit, takes effect when the queue is next submitted
+    ///
+    /// Growing the buffer replaces the bind group, so this can't happen while one is being drawn with
+    pub fn write(&mut self, device: &Device, queue: &Queue, transforms: &[Mat4]) {
+        if transforms.len() > self.capacity {
+            // Doubled so adding objects one at a time doesn't make a new buffer every frame
+            self.capacity = transforms.len().next_power_of_two();
+            (self.buffer, self.bind_group) =
+                create_buffer(device, &self.layout, self.stride, self.capacity);
+        }
+        // Each one goes at the start of its slot, the rest of the slot is left alone
+        let mut contents = vec![0; transforms.len() * self.stride as usize];
+        for (slot, transform) in contents
+            .chunks_exact_mut(self.stride as usize)
+            .zip(transforms)
+        {
+            slot[..TRANSFORM_SIZE as usize].copy_from_slice(bytemuck::bytes_of(transform));
+        }
+        if !contents.is_empty() {
+            queue.write_buffer(&self.buffer, 0, &contents);
+        }
+    }
+}
+
+fn create_buffer(
+    device: &Device,
+    layout: &BindGroupLayout,
+    stride: u64,
+    capacity: usize,
+) -> (Buffer, BindGroup) {
+    let buffer = device.create_buffer(&BufferDescriptor {
+        label: Some("Transform Buffer"),
+        size: stride * capacity as u64,
+        usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+    let bind_group = device.create_bind_group(&BindGroupDescriptor {
+        label: Some("Transform Bind Group"),
+        layout,
+        entries: &[BindGroupEntry {
+            binding: 0,
+            // Only one transform's worth is visible at a time, starting at the dynamic offset
+            resource: BindingResource::Buffer(BufferBinding {
+                buffer: &buffer,
+                offset: 0,
+                size: BufferSize::new(TRANSFORM_SIZE),
+            }),
+        }],
+    });
+    (buffer, bind_group)
+}
@@ -0,0 +1,64 @@
+// Moves the particles along, respawning them at the emitter once they're too old
+
+struct Particle {
+    position: vec3<f32>,
+    // Seconds since it was spawned, negative while it's waiting to be spawned for the first time
+    age: f32,
+    velocity: vec3<f32>,
+    _padding: f32,
+};
+
+struct Params {
+    emitter: vec3<f32>,
+    dt: f32,
+    gravity: vec3<f32>,
+    lifetime: f32,
+    speed: f32,
+    size: f32,
+    time: f32,
+    _padding: f32,
+};
+
+@group(0) @binding(0)
+var<uniform> params: Params;
+@group(0) @binding(1)
+var<storage, read_write> particles: array<Particle>;
+
+// PCG hash, good enough randomness for where particles go
+fn hash(value: u32) -> u32 {
+    let state = value * 747796405u + 2891336453u;
+    let word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+    return (word >> 22u) ^ word;
+}
+
+// 0..1, different for every particle and every time it respawns
+fn random(seed: u32) -> f32 {
+    return f32(hash(seed)) / 4294967295.0;
+}
+
+// Has to match `PARTICLE_WORKGROUP_SIZE`
+@compute @workgroup_size(64)
+fn cs_main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let index = id.x;
+    // The last workgroup can hang off the end of the array
+    if index >= arrayLength(&particles) {
+        return;
+    }
+    var particle = particles[index];
+    let waiting = particle.age < 0.0;
+    particle.age = particle.age + params.dt;
+    if (waiting && particle.age >= 0.0) || particle.age >= params.lifetime {
+        let seed = hash(index) ^ bitcast<u32>(params.time);
+        // Somewhere in a cone pointing up
+        let angle = random(seed) * 6.2831853;
+        let spread = random(seed + 1u) * 0.5;
+        let direction = normalize(vec3<f32>(cos(angle) * spread, 1.0, sin(angle) * spread));
+        particle.position = params.emitter;
+        particle.velocity = direction * params.speed * (0.75 + random(seed + 2u) * 0.5);
+        particle.age = 0.0;
+    } else if !waiting {
+        particle.velocity = particle.velocity + params.gravity * params.dt;
+        particle.position = particle.position + particle.velocity * params.dt;
+    }
+    particles[index] = particle;
+}
@@ -0,0 +1,356 @@
+use std::mem;
+
+use bytemuck::{Pod, Zeroable};
+use glam::Vec3;
+use wgpu::{
+    util::{BufferInitDescriptor, DeviceExt},
+    vertex_attr_array, BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout,
+    BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingType, BlendComponent, BlendFactor,
+    BlendOperation, BlendState, Buffer, BufferAddress, BufferBindingType, BufferUsages,
+    ColorTargetState, ColorWrites, CommandEncoder, CompareFunction, ComputePassDescriptor,
+    ComputePipeline, ComputePipelineDescriptor, DepthBiasState, DepthStencilState, Device,
+    FragmentState, MultisampleState, PipelineLayout, PipelineLayoutDescriptor, PrimitiveState,
+    Queue, RenderPass, RenderPipeline, RenderPipelineDescriptor, ShaderModule,
+    ShaderModuleDescriptor, ShaderSource, ShaderStages, StencilState, TextureFormat,
+    VertexAttribute, VertexBufferLayout, VertexState, VertexStepMode,
+};
+
+use crate::texture::DEPTH_FORMAT;
+
+/// How many particles each workgroup simulates, has to match `@workgroup_size` in particle_sim.wgsl
+pub const PARTICLE_WORKGROUP_SIZE: u32 = 64;
+
+/// How many seconds particles last unless `Particles::lifetime` is changed
+const DEFAULT_LIFETIME: f32 = 2.0;
+
+/// The most particles `device` can simulate at once
+///
+/// Limited by how many workgroups can be dispatched, and how big a storage buffer can be bound for the simulation
+pub fn max_count(device: &Device) -> u32 {
+    let limits = device.limits();
+    // Each workgroup covers `PARTICLE_WORKGROUP_SIZE` particles, and they're only dispatched along x
+    let workgroups = limits.max_compute_workgroups_per_dimension * PARTICLE_WORKGROUP_SIZE;
+    // The whole buffer is bound at once
+    let binding = limits.max_storage_buffer_binding_size / mem::size_of::<Particle>() as u32;
+    workgroups.min(binding)
+}
+
+/// One particle, matches `Particle` in particle_sim.wgsl
+///
+/// Doubles as the instance data when drawing, which only reads `position` and `age`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+struct Particle {
+    position: [f32; 3],
+    /// Seconds since it was spawned, negative while it's waiting to be spawned for the first time
+    age: f32,
+    velocity: [f32; 3],
+    _padding: f32,
+}
+
+impl Particle {
+    const ATTRIBUTES: [VertexAttribute; 2] = vertex_attr_array![0 => Float32x3, 1 => Float32];
+
+    fn desc<'a>() -> VertexBufferLayout<'a> {
+        VertexBufferLayout {
+            array_stride: mem::size_of::<Self>() as BufferAddress,
+            // One quad per particle
+            step_mode: VertexStepMode::Instance,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+}
+
+/// What both shaders get told, matches `Params` in particle_sim.wgsl and particles.wgsl
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+struct Params {
+    emitter: [f32; 3],
+    dt: f32,
+    gravity: [f32; 3],
+    lifetime: f32,
+    speed: f32,
+    size: f32,
+    time: f32,
+    _padding: f32,
+}
+
+/// Particles that are simulated by a compute shader and drawn as camera-facing quads, never leaving the GPU
+///
+/// Each one respawns at `emitter` after `lifetime` seconds, shooting upwards at around `speed`
+pub struct Particles {
+    /// Where new particles start
+    pub emitter: Vec3,
+    /// Added to every particle's velocity each second
+    pub gravity: Vec3,
+    /// Seconds before a particle respawns
+    pub lifetime: f32,
+    /// Roughly how fast particles start out, in units per second
+    pub speed: f32,
+    /// How wide each particle is, in world units
+    pub size: f32,
+    /// How long the simulation has been running, to vary where particles respawn to
+    time: f32,
+    params_buffer: Buffer,
+    sim_pipeline: ComputePipeline,
+    sim_bind_group_layout: BindGroupLayout,
+    /// The particles and the bind group the compute shader sees them through, `None` until `spawn()`
+    particles: Option<(Buffer, BindGroup)>,
+    /// How many particles are in the buffer
+    count: u32,
+    shader: ShaderModule,
+    layout: PipelineLayout,
+    /// `params_buffer` for the render pipeline, which only needs the lifetime and size
+    render_bind_group: BindGroup,
+}
+
+impl Particles {
+    /// `camera_bind_group_layout` is the same layout the scene uses
+    ///
+    /// The device has to support compute shaders
+    pub fn new(device: &Device, camera_bind_group_layout: &BindGroupLayout) -> Self {
+        // Overwritten by every `simulate()`, but the lifetime is needed for drawing before then
+        let params = Params {
+            lifetime: DEFAULT_LIFETIME,
+            ..Params::zeroed()
+        };
+        let params_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Particle Params Buffer"),
+            contents: bytemuck::bytes_of(&params),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+
+        let sim_shader = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("Particle Simulation Shader"),
+            source: ShaderSource::Wgsl(include_str!("particle_sim.wgsl").into()),
+        });
+        let sim_bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Particle Simulation Bind Group Layout"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Storage { read_only: false },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let sim_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Particle Simulation Pipeline Layout"),
+            bind_group_layouts: &[&sim_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let sim_pipeline = device.create_compute_pipeline(&ComputePipelineDescriptor {
+            label: Some("Particle Simulation Pipeline"),
+            layout: Some(&sim_layout),
+            module: &sim_shader,
+            entry_point: "cs_main",
+        });
+
+        let shader = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("Particle Shader"),
+            source: ShaderSource::Wgsl(include_str!("particles.wgsl").into()),
+        });
+        let render_bind_group_layout =
+            device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+                label: Some("Particle Bind Group Layout"),
+                entries: &[BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::VERTEX,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }],
+            });
+        let render_bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Particle Bind Group"),
+            layout: &render_bind_group_layout,
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: params_buffer.as_entire_binding(),
+            }],
+        });
+        let layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Particle Pipeline Layout"),
+            bind_group_layouts: &[camera_bind_group_layout, &render_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        Self {
+            emitter: Vec3::ZERO,
+            gravity: Vec3::new(0.0, -1.0, 0.0),
+            lifetime: DEFAULT_LIFETIME,
+            speed: 1.5,
+            size: 0.05,
+            time: 0.0,
+            params_buffer,
+            sim_pipeline,
+            sim_bind_group_layout,
+            particles: None,
+            count: 0,
+            shader,
+            layout,
+            render_bind_group,
+        }
+    }
+
+    /// Replace the particles with `count` new ones, which are spawned one after another over the first `lifetime`
+    pub fn spawn(&mut self, device: &Device, count: u32) {
+        self.count = count;
+        if count == 0 {
+            self.particles = None;
+            return;
+        }
+        // Staggered so they come out in a steady stream rather than all at once
+        let particles: Vec<Particle> = (0..count)
+            .map(|i| Particle {
+                age: -((i + 1) as f32 / count as f32) * self.lifetime,
+                ..Particle::zeroed()
+            })
+            .collect();
+        let buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Particle Buffer"),
+            contents: bytemuck::cast_slice(&particles),
+            // Written by the simulation, then read as instances when drawing
+            usage: BufferUsages::STORAGE | BufferUsages::VERTEX,
+        });
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Particle Simulation Bind Group"),
+            layout: &self.sim_bind_group_layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: self.params_buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: buffer.as_entire_binding(),
+                },
+            ],
+        });
+        self.particles = Some((buffer, bind_group));
+    }
+
+    /// How many particles there are, alive or waiting to spawn
+    pub fn count(&self) -> u32 {
+        self.count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// Record a compute pass moving every particle on by `dt` seconds
+    ///
+    /// The settings are written to the queue, so each step has to be submitted before the next one is recorded
+    pub fn simulate(&mut self, queue: &Queue, encoder: &mut CommandEncoder, dt: f32) {
+        let bind_group = match &self.particles {
+            Some((_, bind_group)) => bind_group,
+            None => return,
+        };
+        self.time += dt;
+        let params = Params {
+            emitter: self.emitter.to_array(),
+            dt,
+            gravity: self.gravity.to_array(),
+            lifetime: self.lifetime,
+            speed: self.speed,
+            size: self.size,
+            time: self.time,
+            _padding: 0.0,
+        };
+        queue.write_buffer(&self.params_buffer, 0, bytemuck::bytes_of(&params));
+        let mut compute_pass = encoder.begin_compute_pass(&ComputePassDescriptor {
+            label: Some("Particle Simulation Pass"),
+        });
+        compute_pass.set_pipeline(&self.sim_pipeline);
+        compute_pass.set_bind_group(0, bind_group, &[]);
+        compute_pass.dispatch_workgroups(self.count.div_ceil(PARTICLE_WORKGROUP_SIZE), 1, 1);
+    }
+
+    /// Build the pipeline for a target with `format` and `sample_count`
+    pub fn create_pipeline(
+        &self,
+        device: &Device,
+        format: TextureFormat,
+        sample_count: u32,
+    ) -> RenderPipeline {
+        // Adding rather than blending means the particles don't have to be sorted back to front
+        let additive = BlendComponent {
+            src_factor: BlendFactor::SrcAlpha,
+            dst_factor: BlendFactor::One,
+            operation: BlendOperation::Add,
+        };
+        device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("Particle Pipeline"),
+            layout: Some(&self.layout),
+            vertex: VertexState {
+                module: &self.shader,
+                entry_point: "vs_main",
+                // The quad's corners are made up in the vertex shader
+                buffers: &[Particle::desc()],
+            },
+            fragment: Some(FragmentState {
+                module: &self.shader,
+                entry_point: "fs_main",
+                targets: &[Some(ColorTargetState {
+                    format,
+                    blend: Some(BlendState {
+                        color: additive,
+                        alpha: BlendComponent::OVER,
+                    }),
+                    write_mask: ColorWrites::ALL,
+                })],
+            }),
+            primitive: PrimitiveState::default(),
+            // Hidden behind the scene, but not hiding each other
+            depth_stencil: Some(DepthStencilState {
+                format: DEPTH_FORMAT,
+                depth_write_enabled: false,
+                depth_compare: CompareFunction::LessEqual,
+                stencil: StencilState::default(),
+                bias: DepthBiasState::default(),
+            }),
+            multisample: MultisampleState {
+                count: sample_count,
+                ..MultisampleState::default()
+            },
+            multiview: None,
+        })
+    }
+
+    /// Draw every particle with `pipeline` from `create_pipeline()`
+    pub fn draw<'a>(
+        &'a self,
+        render_pass: &mut RenderPass<'a>,
+        pipeline: &'a RenderPipeline,
+        camera_bind_group: &'a BindGroup,
+    ) {
+        let buffer = match &self.particles {
+            Some((buffer, _)) => buffer,
+            None => return,
+        };
+        render_pass.set_pipeline(pipeline);
+        render_pass.set_bind_group(0, camera_bind_group, &[]);
+        render_pass.set_bind_group(1, &self.render_bind_group, &[]);
+        render_pass.set_vertex_buffer(0, buffer.slice(..));
+        render_pass.draw(0..6, 0..self.count);
+    }
+}
@@ -0,0 +1,80 @@
+// Draws each particle as a soft round quad facing the camera, one instance per particle
+
+struct Camera {
+    view_proj: mat4x4<f32>,
+    view_position: vec4<f32>,
+};
+@group(0) @binding(0)
+var<uniform> camera: Camera;
+
+// The same as in particle_sim.wgsl
+struct Params {
+    emitter: vec3<f32>,
+    dt: f32,
+    gravity: vec3<f32>,
+    lifetime: f32,
+    speed: f32,
+    size: f32,
+    time: f32,
+    _padding: f32,
+};
+@group(1) @binding(0)
+var<uniform> params: Params;
+
+struct InstanceInput {
+    @location(0) position: vec3<f32>,
+    @location(1) age: f32,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    // -1..1 across the quad
+    @location(0) offset: vec2<f32>,
+    // 0 when it's just spawned, 1 when it's about to respawn
+    @location(1) progress: f32,
+};
+
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32, instance: InstanceInput) -> VertexOutput {
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(1.0, -1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(-1.0, 1.0),
+    );
+    let corner = corners[vertex_index];
+    var out: VertexOutput;
+    out.offset = corner;
+    out.progress = instance.age / params.lifetime;
+    // Not spawned yet, so it's put outside the clip volume
+    if instance.age < 0.0 {
+        out.clip_position = vec4<f32>(2.0, 2.0, 2.0, 1.0);
+        return out;
+    }
+    // Turned to face the camera
+    let forward = normalize(camera.view_position.xyz - instance.position);
+    // Straight above or below there's no telling which way is right from world up, so Z stands in for it
+    var world_up = vec3<f32>(0.0, 1.0, 0.0);
+    if abs(forward.y) > 0.999 {
+        world_up = vec3<f32>(0.0, 0.0, 1.0);
+    }
+    let right = normalize(cross(world_up, forward));
+    let up = cross(forward, right);
+    let position = instance.position + (right * corner.x + up * corner.y) * params.size * 0.5;
+    out.clip_position = camera.view_proj * vec4<f32>(position, 1.0);
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let distance = length(in.offset);
+    if distance > 1.0 {
+        discard;
+    }
+    // Yellow to red as it ages, fading out towards the edge and the end of its life
+    let color = mix(vec3<f32>(1.0, 0.8, 0.2), vec3<f32>(0.8, 0.1, 0.0), in.progress);
+    let alpha = (1.0 - distance) * (1.0 - in.progress);
+    return vec4<f32>(color, alpha);
+}
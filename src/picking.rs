@@ -0,0 +1,174 @@
+use std::num::NonZeroU32;
+
+use wgpu::{
+    Buffer, BufferDescriptor, BufferUsages, Color, ColorTargetState, ColorWrites, CommandEncoder,
+    CompareFunction, DepthBiasState, DepthStencilState, Device, Extent3d, Face, FragmentState,
+    ImageCopyBuffer, ImageCopyTexture, ImageDataLayout, LoadOp, MultisampleState, Operations,
+    Origin3d, PipelineLayout, PrimitiveState, RenderPass, RenderPassColorAttachment,
+    RenderPassDepthStencilAttachment, RenderPassDescriptor, RenderPipeline,
+    RenderPipelineDescriptor, ShaderModuleDescriptor, ShaderSource, StencilState, Texture,
+    TextureAspect, TextureDescriptor, TextureDimension, TextureFormat, TextureUsages, TextureView,
+    TextureViewDescriptor, VertexState, COPY_BYTES_PER_ROW_ALIGNMENT,
+};
+use winit::dpi::PhysicalSize;
+
+use crate::instance::Instance;
+use crate::texture::{self, DEPTH_FORMAT};
+use crate::vertex::Vertex;
+
+/// One ID per pixel, 0 where nothing was drawn
+pub const ID_FORMAT: TextureFormat = TextureFormat::R32Uint;
+
+/// Build the pipeline that draws the ID of each instance into an `ID_FORMAT` target, with `picking.wgsl`
+///
+/// `prelude` declares `transform` the same way as for the scene's shader, so it fits the same layout
+pub fn create_pipeline(
+    device: &Device,
+    layout: &PipelineLayout,
+    prelude: &str,
+    cull_mode: Option<Face>,
+    depth_compare: CompareFunction,
+) -> RenderPipeline {
+    let shader = device.create_shader_module(ShaderModuleDescriptor {
+        label: Some("Picking Shader"),
+        source: ShaderSource::Wgsl(format!("{prelude}{}", include_str!("picking.wgsl")).into()),
+    });
+    device.create_render_pipeline(&RenderPipelineDescriptor {
+        label: Some("Picking Pipeline"),
+        layout: Some(layout),
+        vertex: VertexState {
+            module: &shader,
+            entry_point: "vs_main",
+            buffers: &[Vertex::desc(), Instance::desc()],
+        },
+        fragment: Some(FragmentState {
+            module: &shader,
+            entry_point: "fs_main",
+            // Integer formats can't be blended
+            targets: &[Some(ColorTargetState {
+                format: ID_FORMAT,
+                blend: None,
+                write_mask: ColorWrites::ALL,
+            })],
+        }),
+        // Culled (and depth tested) the same as the scene, so what's picked is what's on screen
+        primitive: PrimitiveState {
+            cull_mode,
+            ..PrimitiveState::default()
+        },
+        depth_stencil: Some(DepthStencilState {
+            format: DEPTH_FORMAT,
+            depth_write_enabled: true,
+            depth_compare,
+            stencil: StencilState::default(),
+            bias: DepthBiasState::default(),
+        }),
+        // Averaging IDs would make up ones that aren't there
+        multisample: MultisampleState::default(),
+        multiview: None,
+    })
+}
+
+/// Where the IDs get drawn and copied back from, kept around between picks as long as the target stays the same size
+pub struct PickTextures {
+    size: PhysicalSize<u32>,
+    ids: Texture,
+    ids_view: TextureView,
+    /// Never multisampled like the target's own depth texture might be, so it can't be shared with the scene
+    depth_texture: texture::Texture,
+    /// Holds the one pixel that gets picked, padded out to a whole row since copies can't have rows any shorter
+    readback_buffer: Buffer,
+}
+
+impl PickTextures {
+    pub fn new(device: &Device, size: PhysicalSize<u32>) -> Self {
+        let ids = device.create_texture(&TextureDescriptor {
+            label: Some("Picking Texture"),
+            size: Extent3d {
+                width: size.width,
+                height: size.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: ID_FORMAT,
+            usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::COPY_SRC,
+        });
+        let ids_view = ids.create_view(&TextureViewDescriptor::default());
+        let depth_texture = texture::create_depth_texture(device, size, 1);
+        let readback_buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("Picking Readback Buffer"),
+            size: COPY_BYTES_PER_ROW_ALIGNMENT as u64,
+            usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        Self {
+            size,
+            ids,
+            ids_view,
+            depth_texture,
+            readback_buffer,
+        }
+    }
+
+    /// What they were created for, they have to be recreated to pick in a target of any other size
+    pub fn size(&self) -> PhysicalSize<u32> {
+        self.size
+    }
+
+    /// Start a pass drawing into the IDs, cleared to 0 (nothing) and as far away as possible
+    pub fn begin_pass<'a>(&'a self, encoder: &'a mut CommandEncoder) -> RenderPass<'a> {
+        encoder.begin_render_pass(&RenderPassDescriptor {
+            label: Some("Picking Pass"),
+            color_attachments: &[Some(RenderPassColorAttachment {
+                view: &self.ids_view,
+                resolve_target: None,
+                ops: Operations {
+                    load: LoadOp::Clear(Color::TRANSPARENT),
+                    store: true,
+                },
+            })],
+            depth_stencil_attachment: Some(RenderPassDepthStencilAttachment {
+                view: &self.depth_texture.view,
+                depth_ops: Some(Operations {
+                    load: LoadOp::Clear(1.0),
+                    store: false,
+                }),
+                stencil_ops: None,
+            }),
+        })
+    }
+
+    /// Copy the ID at (`x`, `y`) into `readback_buffer()`, which has to be inside `size()`
+    pub fn copy_pixel(&self, encoder: &mut CommandEncoder, x: u32, y: u32) {
+        encoder.copy_texture_to_buffer(
+            ImageCopyTexture {
+                texture: &self.ids,
+                mip_level: 0,
+                // Textures start at the top left like window coordinates do, so there's nothing to flip
+                origin: Origin3d { x, y, z: 0 },
+                aspect: TextureAspect::All,
+            },
+            ImageCopyBuffer {
+                buffer: &self.readback_buffer,
+                layout: ImageDataLayout {
+                    offset: 0,
+                    // Even a single pixel counts as a whole row
+                    bytes_per_row: NonZeroU32::new(COPY_BYTES_PER_ROW_ALIGNMENT),
+                    rows_per_image: None,
+                },
+            },
+            Extent3d {
+                width: 1,
+                height: 1,
+                depth_or_array_layers: 1,
+            },
+        );
+    }
+
+    /// Holds the ID `copy_pixel()` copied in its first 4 bytes, once the copy has been submitted and the buffer mapped
+    pub fn readback_buffer(&self) -> &Buffer {
+        &self.readback_buffer
+    }
+}
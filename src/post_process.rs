@@ -0,0 +1,241 @@
+use wgpu::{
+    util::{BufferInitDescriptor, DeviceExt},
+    BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor,
+    BindGroupLayoutEntry, BindingResource, BindingType, Buffer, BufferBindingType, BufferUsages,
+    Color, ColorTargetState, ColorWrites, CommandEncoder, Device, FragmentState, LoadOp,
+    MultisampleState, Operations, PipelineLayout, PipelineLayoutDescriptor, PrimitiveState, Queue,
+    RenderPassColorAttachment, RenderPassDescriptor, RenderPipeline, RenderPipelineDescriptor,
+    SamplerBindingType, ShaderModule, ShaderModuleDescriptor, ShaderSource, ShaderStages,
+    TextureFormat, TextureSampleType, TextureView, TextureViewDimension, VertexState,
+};
+
+use crate::texture::Texture;
+
+/// What gets done to the scene on its way to the screen
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PostEffect {
+    /// Draw the scene straight into the target, skipping post-processing entirely
+    #[default]
+    None,
+    Grayscale,
+    /// Flip every colour
+    Invert,
+}
+
+impl PostEffect {
+    /// Every effect, e.g. for listing them in a UI
+    pub const ALL: [PostEffect; 3] = [PostEffect::None, PostEffect::Grayscale, PostEffect::Invert];
+
+    /// What the shader knows this effect as, has to match the `switch` in post_process.wgsl
+    fn id(self) -> u32 {
+        self as u32
+    }
+}
+
+/// How HDR colours get squeezed into what the screen can show
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Tonemap {
+    /// Anything brighter than 1 is just clipped
+    #[default]
+    None,
+    /// `c / (1 + c)`, simple but a bit washed out
+    Reinhard,
+    /// A fit of the ACES filmic curve, more contrast and saturation than Reinhard
+    Aces,
+}
+
+impl Tonemap {
+    /// Every operator, e.g. for listing them in a UI
+    pub const ALL: [Tonemap; 3] = [Tonemap::None, Tonemap::Reinhard, Tonemap::Aces];
+
+    /// What the shader knows this operator as, has to match the `switch` in post_process.wgsl
+    fn id(self) -> u32 {
+        self as u32
+    }
+}
+
+/// What the post-processing shader gets told, matches `Settings` in post_process.wgsl
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct Settings {
+    effect: u32,
+    bloom_intensity: f32,
+    tonemap: u32,
+    exposure: f32,
+}
+
+impl Settings {
+    pub fn new(effect: PostEffect, bloom_intensity: f32, tonemap: Tonemap, exposure: f32) -> Self {
+        Self {
+            effect: effect.id(),
+            bloom_intensity,
+            tonemap: tonemap.id(),
+            exposure,
+        }
+    }
+}
+
+/// Draws the scene, rendered into a texture beforehand, onto the target with bloom, tonemapping and a `PostEffect` applied
+pub struct PostProcess {
+    shader: ShaderModule,
+    layout: PipelineLayout,
+    bind_group_layout: BindGroupLayout,
+    /// Which effect the shader applies, how much bloom it adds and how it tonemaps
+    settings_buffer: Buffer,
+}
+
+impl PostProcess {
+    pub fn new(device: &Device) -> Self {
+        let shader = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("Post Process Shader"),
+            source: ShaderSource::Wgsl(include_str!("post_process.wgsl").into()),
+        });
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Post Process Bind Group Layout"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: true },
+                        view_dimension: TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                // The bloom, sampled with the scene's sampler
+                BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: true },
+                        view_dimension: TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Post Process Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let settings_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Post Process Settings Buffer"),
+            contents: bytemuck::bytes_of(&Settings::new(PostEffect::None, 0.0, Tonemap::None, 1.0)),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+        Self {
+            shader,
+            layout,
+            bind_group_layout,
+            settings_buffer,
+        }
+    }
+
+    /// Build the pipeline for a target with `format`, the post-processing pass is never multisampled
+    pub fn create_pipeline(&self, device: &Device, format: TextureFormat) -> RenderPipeline {
+        device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("Post Process Pipeline"),
+            layout: Some(&self.layout),
+            vertex: VertexState {
+                module: &self.shader,
+                entry_point: "vs_main",
+                // The triangle is made up in the vertex shader
+                buffers: &[],
+            },
+            fragment: Some(FragmentState {
+                module: &self.shader,
+                entry_point: "post_effect",
+                targets: &[Some(ColorTargetState {
+                    format,
+                    blend: None,
+                    write_mask: ColorWrites::ALL,
+                })],
+            }),
+            primitive: PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: MultisampleState::default(),
+            multiview: None,
+        })
+    }
+
+    /// Bind `scene` and `bloom` for sampling, this has to be redone whenever they're recreated
+    pub fn create_bind_group(
+        &self,
+        device: &Device,
+        scene: &Texture,
+        bloom: &TextureView,
+    ) -> BindGroup {
+        device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Post Process Bind Group"),
+            layout: &self.bind_group_layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::TextureView(&scene.view),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::Sampler(&scene.sampler),
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: self.settings_buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 3,
+                    resource: BindingResource::TextureView(bloom),
+                },
+            ],
+        })
+    }
+
+    /// Change what the shader does, takes effect when the queue is next submitted
+    pub fn set_settings(&self, queue: &Queue, settings: Settings) {
+        queue.write_buffer(&self.settings_buffer, 0, bytemuck::bytes_of(&settings));
+    }
+
+    /// Record drawing the scene from `bind_group` into `view` with `pipeline` from `create_pipeline()`
+    pub fn apply(
+        &self,
+        encoder: &mut CommandEncoder,
+        view: &TextureView,
+        pipeline: &RenderPipeline,
+        bind_group: &BindGroup,
+    ) {
+        let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+            label: Some("Post Process Pass"),
+            color_attachments: &[Some(RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: Operations {
+                    // Every pixel gets drawn over, but clearing is cheaper than loading on some GPUs
+                    load: LoadOp::Clear(Color::BLACK),
+                    store: true,
+                },
+            })],
+            depth_stencil_attachment: None,
+        });
+        render_pass.set_pipeline(pipeline);
+        render_pass.set_bind_group(0, bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
@@ -0,0 +1,81 @@
+// Draws the (HDR) scene texture onto the target, tonemapped and with an effect applied on the way
+
+struct Settings {
+    // Which effect to apply, the same numbers as `PostEffect` (0 is none)
+    effect: u32,
+    // How much of the bloom to add, 0 turns it off
+    bloom_intensity: f32,
+    // Which tonemapping operator to use, the same numbers as `Tonemap` (0 is none)
+    tonemap: u32,
+    // Everything gets multiplied by this before tonemapping
+    exposure: f32,
+};
+@group(0) @binding(0)
+var scene: texture_2d<f32>;
+@group(0) @binding(1)
+var scene_sampler: sampler;
+@group(0) @binding(2)
+var<uniform> settings: Settings;
+@group(0) @binding(3)
+var bloom: texture_2d<f32>;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+};
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    // One triangle big enough to cover the whole target, with corners at (-1, -1), (3, -1) and (-1, 3)
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+    // Texture coordinates start at the top left, clip space at the bottom left
+    out.tex_coords = vec2<f32>(uv.x, 1.0 - uv.y);
+    return out;
+}
+
+// Krzysztof Narkowicz's fit of the ACES filmic curve
+fn aces(color: vec3<f32>) -> vec3<f32> {
+    let numerator = color * (2.51 * color + 0.03);
+    let denominator = color * (2.43 * color + 0.59) + 0.14;
+    return clamp(numerator / denominator, vec3<f32>(0.0), vec3<f32>(1.0));
+}
+
+fn tonemap(color: vec3<f32>) -> vec3<f32> {
+    let exposed = color * settings.exposure;
+    switch settings.tonemap {
+        case 1u: {
+            return exposed / (1.0 + exposed);
+        }
+        case 2u: {
+            return aces(exposed);
+        }
+        default: {
+            return clamp(exposed, vec3<f32>(0.0), vec3<f32>(1.0));
+        }
+    }
+}
+
+@fragment
+fn post_effect(in: VertexOutput) -> @location(0) vec4<f32> {
+    let scene_color = textureSample(scene, scene_sampler, in.tex_coords);
+    // Sampled either way, since sampling has to happen in uniform control flow
+    let glow = textureSample(bloom, scene_sampler, in.tex_coords).rgb;
+    // The sRGB target takes care of the gamma
+    let color = vec4<f32>(tonemap(scene_color.rgb + glow * settings.bloom_intensity), scene_color.a);
+    switch settings.effect {
+        // Grayscale, weighting each channel by how bright it looks
+        case 1u: {
+            let luminance = dot(color.rgb, vec3<f32>(0.2126, 0.7152, 0.0722));
+            return vec4<f32>(vec3<f32>(luminance), color.a);
+        }
+        // Invert
+        case 2u: {
+            return vec4<f32>(1.0 - color.rgb, color.a);
+        }
+        default: {
+            return color;
+        }
+    }
+}
@@ -0,0 +1,58 @@
+use wgpu::{CommandEncoder, TextureView};
+
+use crate::render_target::RenderTarget;
+use crate::state::State;
+
+/// Everything a `RenderPass` gets to draw with
+pub struct RenderContext<'a> {
+    /// For the device, the camera's bind group and so on
+    pub state: &'a State,
+    /// The target being drawn, each one gets every pass in turn
+    pub target: &'a RenderTarget,
+    /// What ends up on screen (or in the capture), already the size and format of `target`
+    pub view: &'a TextureView,
+}
+
+/// One step of drawing a frame, see `State::render_passes_mut()`
+///
+/// Every target records every pass into the same encoder, in the order they're in, and the debug overlay and UI go on top after
+pub trait RenderPass {
+    /// What the debug group around everything this pass records is called, in GPU debuggers like RenderDoc
+    fn label(&self) -> &str {
+        "Render Pass"
+    }
+
+    /// Record the commands for this step into `encoder`
+    fn record(&self, encoder: &mut CommandEncoder, context: &RenderContext);
+}
+
+/// The scene itself: clears the target and draws everything in `State`, post-processing included
+///
+/// The only pass there is to start with, anything drawn before it gets cleared away
+pub struct ScenePass;
+
+impl RenderPass for ScenePass {
+    fn label(&self) -> &str {
+        "Scene"
+    }
+
+    fn record(&self, encoder: &mut CommandEncoder, context: &RenderContext) {
+        context
+            .state
+            .encode_frame(encoder, context.target, context.view);
+    }
+}
+
+/// Record whatever `record` does inside a debug group called `label`, so it shows up as one named scope in GPU debuggers
+///
+/// Scopes can go inside each other, every pass already gets one named after `RenderPass::label()`
+pub fn debug_scope<R>(
+    encoder: &mut CommandEncoder,
+    label: &str,
+    record: impl FnOnce(&mut CommandEncoder) -> R,
+) -> R {
+    encoder.push_debug_group(label);
+    let result = record(encoder);
+    encoder.pop_debug_group();
+    result
+}
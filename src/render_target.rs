@@ -0,0 +1,394 @@
+use wgpu::{
+    Adapter, BindGroup, CompareFunction, CompositeAlphaMode, Device, Face, PresentMode,
+    RenderPipeline, Surface, SurfaceConfiguration, TextureFormat,
+};
+use winit::{dpi::PhysicalSize, window::WindowId};
+
+use crate::blend_mode::BlendMode;
+use crate::bloom::BloomTextures;
+use crate::debug_view::DebugView;
+use crate::gbuffer::GBufferTextures;
+use crate::letterbox::Viewport;
+use crate::material::MaterialPipelines;
+use crate::texture::{self, Texture};
+
+/// Which triangles can be culled, in the order of the sets in `Pipelines`
+///
+/// Back faces by default, nothing for double-sided geometry, and front faces for meshes with their winding the wrong way round
+pub const CULL_MODES: [Option<Face>; 3] = [Some(Face::Back), None, Some(Face::Front)];
+
+/// Where `cull_mode` is in `CULL_MODES`, and so which of a set of pipelines built for each of them culls it
+pub fn cull_mode_index(cull_mode: Option<Face>) -> usize {
+    CULL_MODES
+        .iter()
+        .position(|&mode| mode == cull_mode)
+        .expect("every cull mode is in `CULL_MODES`")
+}
+
+/// Every variation of the render pipeline we can switch between
+pub struct Pipelines {
+    /// One set for each of `CULL_MODES`
+    pub by_cull_mode: [PipelineSet; CULL_MODES.len()],
+}
+
+impl Pipelines {
+    /// The pipelines that cull `cull_mode`
+    pub fn for_cull_mode(&self, cull_mode: Option<Face>) -> &PipelineSet {
+        &self.by_cull_mode[cull_mode_index(cull_mode)]
+    }
+}
+
+/// The pipelines for one cull mode
+pub struct PipelineSet {
+    /// One for each `BlendMode`, in the order of `BlendMode::ALL`
+    pub fill: [RenderPipeline; BlendMode::ALL.len()],
+    /// The same as `fill` but only drawing edges, `None` when the device doesn't support it
+    pub wireframe: Option<[RenderPipeline; BlendMode::ALL.len()]>,
+    /// Only writes depth, for filling in the depth buffer before anything else gets drawn
+    pub depth_prepass: RenderPipeline,
+    /// The same as `fill` but only drawing what's at the depth `depth_prepass` stored
+    pub after_prepass: [RenderPipeline; BlendMode::ALL.len()],
+    /// One for each `DebugView` other than `Lit`, in the order of `DebugView::ALL`
+    ///
+    /// `None` where the shader doesn't have the view's entry point, in which case it's drawn lit
+    pub debug_views: Vec<Option<RenderPipeline>>,
+}
+
+impl PipelineSet {
+    /// The pipeline to draw with, falling back to `fill` if there aren't any wireframe pipelines
+    ///
+    /// Any `debug_view` but `Lit` takes priority over everything else
+    pub fn get(
+        &self,
+        blend_mode: BlendMode,
+        wireframe: bool,
+        after_prepass: bool,
+        debug_view: DebugView,
+    ) -> &RenderPipeline {
+        // `Lit` is drawn with the pipelines above, so it isn't in `debug_views`
+        if let Some(Some(pipeline)) = debug_view
+            .index()
+            .checked_sub(1)
+            .and_then(|index| self.debug_views.get(index))
+        {
+            return pipeline;
+        }
+        let pipelines = match &self.wireframe {
+            Some(wireframe_pipelines) if wireframe => wireframe_pipelines,
+            _ if after_prepass => &self.after_prepass,
+            _ => &self.fill,
+        };
+        &pipelines[blend_mode.index()]
+    }
+}
+
+/// `mode` if it's in `supported`, otherwise whichever supported mode behaves the most like it
+///
+/// `surface.configure()` panics with an unsupported mode, and `Fifo` is supported everywhere so it's the last resort
+pub fn closest_present_mode(supported: &[PresentMode], mode: PresentMode) -> PresentMode {
+    let preferences: &[PresentMode] = match mode {
+        // wgpu works out what these mean for the surface itself
+        PresentMode::AutoVsync | PresentMode::AutoNoVsync => return mode,
+        // Keeping the low latency matters more than avoiding tearing
+        PresentMode::Mailbox => &[PresentMode::Mailbox, PresentMode::Immediate],
+        PresentMode::Immediate => &[PresentMode::Immediate, PresentMode::Mailbox],
+        PresentMode::FifoRelaxed => &[PresentMode::FifoRelaxed],
+        PresentMode::Fifo => &[],
+    };
+    preferences
+        .iter()
+        .copied()
+        .find(|mode| supported.contains(mode))
+        .unwrap_or(PresentMode::Fifo)
+}
+
+/// `mode` if it's in `supported`, otherwise `Auto`, which wgpu turns into `Opaque` or `Inherit` depending on which the surface supports
+pub fn closest_alpha_mode(
+    supported: &[CompositeAlphaMode],
+    mode: CompositeAlphaMode,
+) -> CompositeAlphaMode {
+    if mode == CompositeAlphaMode::Auto || supported.contains(&mode) {
+        mode
+    } else {
+        CompositeAlphaMode::Auto
+    }
+}
+
+/// What a surface supports with our adapter, so there's something to pick from that `surface.configure()` won't panic on
+///
+/// All empty when rendering headlessly, since there's no surface to ask
+#[derive(Debug, Clone, Default)]
+pub struct SurfaceInfo {
+    /// The first one is the one the surface prefers
+    pub formats: Vec<TextureFormat>,
+    pub present_modes: Vec<PresentMode>,
+    /// Always has at least `Opaque` or `Inherit`, `Auto` isn't listed but is always allowed
+    pub alpha_modes: Vec<CompositeAlphaMode>,
+}
+
+impl SurfaceInfo {
+    /// Everything would be empty if the adapter can't draw to `surface` at all
+    pub fn new(surface: &Surface, adapter: &Adapter) -> Self {
+        Self {
+            formats: surface.get_supported_formats(adapter),
+            present_modes: surface.get_supported_present_modes(adapter),
+            alpha_modes: surface.get_supported_alpha_modes(adapter),
+        }
+    }
+}
+
+/// `texture::clamp_size()`, warning if `size` had to be scaled down to anything other than `current`
+///
+/// Dragging a window's edge resizes it every frame, so the same warning isn't repeated while the clamped size stays put
+fn clamp_size(
+    device: &Device,
+    size: PhysicalSize<u32>,
+    current: Option<PhysicalSize<u32>>,
+) -> PhysicalSize<u32> {
+    let clamped = texture::clamp_size(device, size);
+    if clamped != size && Some(clamped) != current {
+        log::warn!(
+            "{}x{} is too big for this device (the most it supports is {}), rendering at {}x{} instead",
+            size.width,
+            size.height,
+            device.limits().max_texture_dimension_2d,
+            clamped.width,
+            clamped.height
+        );
+    }
+    clamped
+}
+
+/// Something we draw into: a window's surface, or an offscreen texture when running headlessly
+///
+/// Everything in here depends on the size or format of what we're drawing into, everything else lives in `State`
+pub struct RenderTarget {
+    /// The window this target belongs to, `None` when rendering headlessly
+    pub window_id: Option<WindowId>,
+    /// `None` when rendering headlessly
+    pub surface: Option<Surface>,
+    /// What `surface` was last configured with (its format, size and present mode)
+    ///
+    /// Changing this doesn't do anything by itself, use `State::resize()` or `State::set_present_mode()`
+    pub config: SurfaceConfiguration,
+    /// The size of the surface in physical pixels, the same as `config`'s width and height
+    ///
+    /// Can be smaller than the window, if the window is bigger than the device's `max_texture_dimension_2d`
+    pub size: PhysicalSize<u32>,
+    /// What the scene gets drawn in, the surface's format unless it's going through post-processing first
+    ///
+    /// `pipelines`, `msaa_texture` and the letterbox, sprite and line pipelines all have to match it
+    pub scene_format: TextureFormat,
+    /// Every target gets its own pipelines, since different surfaces can have different formats
+    pub pipelines: Pipelines,
+    /// `pipelines` as they were built for the other depth compares that have been used, see `State::set_depth_compare()`
+    ///
+    /// Kept so switching back doesn't rebuild them, and cleared whenever `pipelines` gets rebuilt for any other reason
+    pub depth_compare_pipelines: Vec<(CompareFunction, Pipelines)>,
+    /// The formats, present modes and alpha modes the surface supports with our adapter
+    pub surface_info: SurfaceInfo,
+    /// Needs to be recreated whenever the surface changes size
+    pub depth_texture: Texture,
+    /// What we actually render into when multisampling, gets resolved to the surface at the end of the pass
+    pub msaa_texture: Option<Texture>,
+    /// What we render into instead of the surface when running headlessly
+    pub offscreen_target: Option<Texture>,
+    /// What the G-buffer pass draws into, `None` unless `State::set_gbuffer()` turned it on
+    ///
+    /// Always the same size as the target, for sampling in a later pass
+    pub gbuffer_textures: Option<GBufferTextures>,
+    /// Where the scene gets drawn when letterboxing, `None` for the whole target
+    pub viewport: Option<Viewport>,
+    /// Fills `viewport` with the clear colour, only created once letterboxing is turned on
+    pub letterbox_pipeline: Option<RenderPipeline>,
+    /// Lights the G-buffer in place of drawing the geometry, only created once `State::enable_deferred_lighting()` is used
+    pub deferred_pipeline: Option<RenderPipeline>,
+    /// Draws the sky from `State::load_skybox()`, only created once there is one
+    pub skybox_pipeline: Option<RenderPipeline>,
+    /// Draws the quads from `State::draw_quad()`, only created once there are some
+    pub sprite_pipeline: Option<RenderPipeline>,
+    /// Draws the particles from `State::spawn_particles()`, only created once there are some
+    pub particle_pipeline: Option<RenderPipeline>,
+    /// Draws the lines from `State::draw_line()`, only created once there are some
+    pub line_pipeline: Option<RenderPipeline>,
+    /// One for each of `State`'s materials, in the same order, built by `State`'s `prepare_materials()`
+    pub material_pipelines: Vec<MaterialPipelines>,
+    /// What the scene gets drawn into when there's a `PostEffect`, `None` otherwise
+    pub scene_texture: Option<Texture>,
+    /// Where the bloom is built up from `scene_texture`, `None` when there's no bloom
+    pub bloom_textures: Option<BloomTextures>,
+    /// Binds `scene_texture` (and the bloom) for the post-processing pass
+    pub post_bind_group: Option<BindGroup>,
+    /// Only created once post-processing is turned on
+    pub post_pipeline: Option<RenderPipeline>,
+    /// What everything before FXAA gets drawn into when it's on, `None` otherwise
+    pub fxaa_texture: Option<Texture>,
+    /// Binds `fxaa_texture` for the FXAA pass
+    pub fxaa_bind_group: Option<BindGroup>,
+    /// Only created once FXAA is turned on
+    pub fxaa_pipeline: Option<RenderPipeline>,
+    /// Set when the window shrinks to 0x0, there's nothing to render to until it's restored
+    minimized: bool,
+    /// The latest size we've been asked to resize to, applied once per frame by `State::render()`
+    pending_size: Option<PhysicalSize<u32>>,
+}
+
+impl RenderTarget {
+    /// Configures `surface` (if there is one) and creates the textures we need to draw into it
+    pub fn new(
+        device: &Device,
+        window_id: Option<WindowId>,
+        surface: Option<Surface>,
+        mut config: SurfaceConfiguration,
+        surface_info: SurfaceInfo,
+        pipelines: Pipelines,
+        sample_count: u32,
+    ) -> Self {
+        let size = clamp_size(device, PhysicalSize::new(config.width, config.height), None);
+        config.width = size.width;
+        config.height = size.height;
+        if let Some(surface) = &surface {
+            surface.configure(device, &config);
+        }
+        // Without a surface we need something else to draw into
+        let offscreen_target = surface
+            .is_none()
+            .then(|| texture::create_render_target(device, &config));
+        Self {
+            window_id,
+            surface,
+            size,
+            depth_texture: texture::create_depth_texture(device, size, sample_count),
+            msaa_texture: texture::create_msaa_texture(
+                device,
+                &config,
+                config.format,
+                sample_count,
+            ),
+            scene_format: config.format,
+            config,
+            pipelines,
+            depth_compare_pipelines: Vec::new(),
+            surface_info,
+            offscreen_target,
+            gbuffer_textures: None,
+            viewport: None,
+            letterbox_pipeline: None,
+            deferred_pipeline: None,
+            skybox_pipeline: None,
+            sprite_pipeline: None,
+            particle_pipeline: None,
+            line_pipeline: None,
+            material_pipelines: Vec::new(),
+            scene_texture: None,
+            bloom_textures: None,
+            post_bind_group: None,
+            post_pipeline: None,
+            fxaa_texture: None,
+            fxaa_bind_group: None,
+            fxaa_pipeline: None,
+            minimized: false,
+            pending_size: None,
+        }
+    }
+
+    /// Remember `new_size` to resize to later, replacing any size that's already pending
+    ///
+    /// Clamped to the device's limits up front, so a too-big window doesn't look like a new size every frame
+    pub fn request_resize(&mut self, device: &Device, new_size: PhysicalSize<u32>) {
+        let current = self.pending_size.unwrap_or(self.size);
+        self.pending_size = Some(clamp_size(device, new_size, Some(current)));
+    }
+
+    /// The pending size from `request_resize()`, if resizing to it would actually change anything
+    pub fn take_pending_resize(&mut self) -> Option<PhysicalSize<u32>> {
+        self.pending_size
+            .take()
+            .filter(|&new_size| new_size != self.size || self.minimized)
+    }
+
+    /// Resize the surface with `new_size` right away, returns `true` if this restored the target from being minimized
+    pub fn resize(
+        &mut self,
+        device: &Device,
+        new_size: PhysicalSize<u32>,
+        sample_count: u32,
+    ) -> bool {
+        if new_size.width == 0 || new_size.height == 0 {
+            // The surface can't be 0x0, so we leave it as it is and stop rendering for now
+            self.minimized = true;
+            return false;
+        }
+        let restored = self.minimized;
+        self.minimized = false;
+        // The surface and its textures can't be any bigger than this
+        let new_size = clamp_size(device, new_size, Some(self.size));
+        self.size = new_size;
+        self.config.width = new_size.width;
+        self.config.height = new_size.height;
+        // Have to reconfigure the surface with the new width and height
+        if let Some(surface) = &self.surface {
+            surface.configure(device, &self.config);
+        }
+        self.recreate_textures(device, sample_count);
+        restored
+    }
+
+    /// Recreate everything that has to match the size or sample count of the target
+    pub fn recreate_textures(&mut self, device: &Device, sample_count: u32) {
+        if self.offscreen_target.is_some() {
+            self.offscreen_target = Some(texture::create_render_target(device, &self.config));
+        }
+        self.depth_texture = texture::create_depth_texture(device, self.size, sample_count);
+        self.msaa_texture =
+            texture::create_msaa_texture(device, &self.config, self.scene_format, sample_count);
+    }
+
+    /// Switch to a different present mode, or the closest one the surface supports, and return which one that was
+    ///
+    /// Only the present mode changes, the textures are left alone
+    pub fn set_present_mode(&mut self, device: &Device, mode: PresentMode) -> PresentMode {
+        let mode = closest_present_mode(&self.surface_info.present_modes, mode);
+        if mode != self.config.present_mode {
+            self.config.present_mode = mode;
+            if let Some(surface) = &self.surface {
+                surface.configure(device, &self.config);
+            }
+        }
+        mode
+    }
+
+    /// Switch to a different alpha mode, or `Auto` if the surface doesn't support it, and return which one that was
+    pub fn set_alpha_mode(
+        &mut self,
+        device: &Device,
+        mode: CompositeAlphaMode,
+    ) -> CompositeAlphaMode {
+        let mode = closest_alpha_mode(&self.surface_info.alpha_modes, mode);
+        if mode != self.config.alpha_mode {
+            self.config.alpha_mode = mode;
+            if let Some(surface) = &self.surface {
+                surface.configure(device, &self.config);
+            }
+        }
+        mode
+    }
+
+    /// Whether the window is minimized, in which case there's nothing to render to
+    ///
+    /// Goes by the pending size if there is one, otherwise a restored window would never get drawn and so never get resized
+    pub fn is_minimized(&self) -> bool {
+        match self.pending_size {
+            Some(size) => size.width == 0 || size.height == 0,
+            None => self.minimized,
+        }
+    }
+
+    /// Width divided by height of what the scene gets drawn into, which is `viewport` when letterboxing
+    pub fn aspect(&self) -> f32 {
+        match &self.viewport {
+            Some(viewport) => viewport.aspect(),
+            None => self.config.width as f32 / self.config.height as f32,
+        }
+    }
+}
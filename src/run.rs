@@ -1,63 +1,418 @@
-use wgpu::SurfaceError;
+use std::time::Duration;
+
+// `std::time::Instant` panics in the browser
+use instant::Instant;
+use wgpu::{util::backend_bits_from_env, Backends, PresentMode, SurfaceError};
 use winit::{
-    event::{ElementState, Event, KeyboardInput, VirtualKeyCode, WindowEvent},
+    dpi::LogicalSize,
+    event::{
+        DeviceEvent, ElementState, Event, KeyboardInput, MouseButton, VirtualKeyCode, WindowEvent,
+    },
     event_loop::{ControlFlow, EventLoop},
-    window::WindowBuilder,
+    window::{Fullscreen, Icon, Window, WindowBuilder},
+};
+
+use crate::{
+    anti_aliasing::AntiAliasing, cursor::CursorGrab, render_target::CULL_MODES, state::State,
 };
 
-use crate::state::State;
+/// How often to draw while paused, see `State::set_pause_when_unfocused()`
+const PAUSED_FRAME_INTERVAL: Duration = Duration::from_millis(250);
+/// The most fixed timesteps we'll run in one frame, see `State::set_fixed_timestep()`
+///
+/// If updating takes longer than the timestep we'd otherwise fall further behind every frame, so the rest are dropped
+const MAX_FIXED_STEPS: u32 = 5;
 
-pub async fn run() {
-    env_logger::init();
+/// How the window `run()` opens should look
+#[derive(Debug, Clone)]
+pub struct WindowConfig {
+    /// The FPS gets added on the end, once a second
+    pub title: String,
+    /// A PNG to use as the window's icon, e.g. from `include_bytes!()`
+    ///
+    /// If it can't be decoded the window just doesn't get an icon
+    pub icon_bytes: Option<Vec<u8>>,
+    /// The size of the inside of the window, in logical pixels
+    pub width: u32,
+    pub height: u32,
+    pub resizable: bool,
+}
+
+impl Default for WindowConfig {
+    fn default() -> Self {
+        Self {
+            title: "WGPU Thing".to_owned(),
+            icon_bytes: None,
+            width: 800,
+            height: 600,
+            resizable: true,
+        }
+    }
+}
+
+/// Open a window and render into it until it's closed
+///
+/// `max_fps` caps how often we redraw, `None` caps it at the monitor's refresh rate (if we can find out what that is)
+/// and `Some(0)` draws as fast as the present mode allows
+/// `window_config` sets the window's title, icon and size, `None` uses `WindowConfig::default()`
+pub async fn run(max_fps: Option<u32>, window_config: Option<WindowConfig>) {
+    run_with(max_fps, window_config, |_, _| {}).await
+}
+
+/// The same as `run()`, but calls `update` every frame, before `State::update()` and rendering
+///
+/// `update` gets the time since the last frame, and can change anything in `State` (e.g. move the camera or add instances)
+/// With `State::set_fixed_timestep()` it's called every timestep instead, which could be several times a frame or none at all
+pub async fn run_with(
+    max_fps: Option<u32>,
+    window_config: Option<WindowConfig>,
+    mut update: impl FnMut(&mut State, Duration) + 'static,
+) {
+    init_logger();
+    let window_config = window_config.unwrap_or_default();
     let event_loop = EventLoop::new();
     let window = WindowBuilder::new()
-        .with_title("WGPU Thing")
+        .with_title(&window_config.title)
+        .with_window_icon(window_config.icon_bytes.as_deref().and_then(load_icon))
+        .with_inner_size(LogicalSize::new(window_config.width, window_config.height))
+        .with_resizable(window_config.resizable)
         .build(&event_loop)
         .unwrap();
+    let title = window_config.title;
+    // The surface is found through the page, so the canvas has to be on it before `State::new()`
+    #[cfg(target_arch = "wasm32")]
+    add_canvas_to_page(&window);
 
-    let mut state = State::new(&window).await;
+    // Follows wgpu's convention, e.g. `WGPU_BACKEND=vulkan` or `WGPU_BACKEND=dx12,gl`
+    let backends = backend_bits_from_env().unwrap_or_else(Backends::all);
+    let mut state = match State::new(&window, backends).await {
+        Ok(state) => state,
+        Err(err) => {
+            log::error!("Failed to set up the renderer: {err}");
+            return;
+        }
+    };
+
+    // Not something you'd want to ship with
+    state.set_debug_overlay(cfg!(debug_assertions));
+
+    // Makes iterating on the shader a lot faster, but there's no source tree to watch in release builds (or in the browser)
+    if cfg!(all(debug_assertions, not(target_arch = "wasm32"))) {
+        if let Err(err) =
+            state.watch_shader(concat!(env!("CARGO_MANIFEST_DIR"), "/src/shader.wgsl"))
+        {
+            log::warn!("Not hot-reloading the shader: {err}");
+        }
+    }
+
+    // When the FPS in the title was last refreshed
+    let mut last_title_update = Instant::now();
+    // When the last frame started, so we know how much time has passed
+    let mut last_update = Instant::now();
+    // Time that's passed but hasn't been simulated yet, when using a fixed timestep
+    let mut accumulator = Duration::ZERO;
+    // How long to wait between frames when capped, so `Immediate` doesn't burn through the CPU on a high refresh rate monitor
+    let mut frame_interval = pick_frame_interval(max_fps, &window);
+    // When we last asked for a redraw
+    let mut last_frame = Instant::now();
+    // Whether the last frame failed with `SurfaceError::Lost`
+    let mut surface_lost = false;
+    // Grabbed for mouse-look by right-clicking
+    let mut cursor_grab = CursorGrab::default();
 
     event_loop.run(move |event, _, control_flow| match event {
         Event::WindowEvent {
             ref event,
             window_id,
-        } if window_id == window.id() => {
-            if !state.input(event) {
-                match event {
-                    WindowEvent::CloseRequested
-                    | WindowEvent::KeyboardInput {
-                        input:
-                            KeyboardInput {
-                                state: ElementState::Pressed,
-                                virtual_keycode: Some(VirtualKeyCode::Escape),
-                                ..
-                            },
+        } if window_id == window.id() && !state.input(window_id, event) => match event {
+            WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
+            WindowEvent::KeyboardInput {
+                input:
+                    KeyboardInput {
+                        state: ElementState::Pressed,
+                        virtual_keycode: Some(keycode),
                         ..
-                    } => *control_flow = ControlFlow::Exit,
-                    WindowEvent::Resized(physical_size) => state.resize(*physical_size),
-                    WindowEvent::ScaleFactorChanged { new_inner_size, .. } => {
-                        state.resize(**new_inner_size);
-                    }
-                    _ => {}
+                    },
+                ..
+            } => match keycode {
+                // Escape unless the app has picked something else (or nothing)
+                keycode if Some(*keycode) == state.exit_key() => *control_flow = ControlFlow::Exit,
+                // Toggle VSync, handy for checking latency
+                VirtualKeyCode::V => {
+                    state.set_present_mode(match state.primary_target().config.present_mode {
+                        PresentMode::Fifo => PresentMode::Immediate,
+                        _ => PresentMode::Fifo,
+                    })
+                }
+                #[cfg(not(target_arch = "wasm32"))]
+                VirtualKeyCode::F12 | VirtualKeyCode::Snapshot => save_screenshot(&mut state),
+                VirtualKeyCode::Tab => state.set_wireframe(!state.is_wireframe()),
+                VirtualKeyCode::F1 => state.set_ui_visible(!state.is_ui_visible()),
+                // Cycle through the shader's debug views, e.g. to check the normals
+                VirtualKeyCode::F2 => state.set_debug_view(state.debug_view().next()),
+                // Cycle through culling back faces, nothing and front faces, for meshes that go missing
+                VirtualKeyCode::F3 => {
+                    let index = CULL_MODES
+                        .iter()
+                        .position(|&mode| mode == state.cull_mode())
+                        .unwrap_or(0);
+                    state.set_cull_mode(CULL_MODES[(index + 1) % CULL_MODES.len()]);
                 }
+                // Show which way the world's axes point, e.g. for checking a model's orientation
+                VirtualKeyCode::G => state.set_axes_gizmo(match state.axes_gizmo() {
+                    Some(_) => None,
+                    None => Some(1.0),
+                }),
+                // Switch the anti-aliasing, for comparing them side by side
+                // 2 and 8 samples aren't supported by this version of wgpu, so those keys just warn (see `set_sample_count()`)
+                VirtualKeyCode::Key0 => state.set_anti_aliasing(AntiAliasing::Fxaa),
+                VirtualKeyCode::Key1 => state.set_anti_aliasing(AntiAliasing::None),
+                VirtualKeyCode::Key2 => state.set_anti_aliasing(AntiAliasing::Msaa(2)),
+                VirtualKeyCode::Key4 => state.set_anti_aliasing(AntiAliasing::Msaa(4)),
+                VirtualKeyCode::Key8 => state.set_anti_aliasing(AntiAliasing::Msaa(8)),
+                // The size change comes through as a `Resized` event, so the surface gets reconfigured there
+                VirtualKeyCode::F11 => window.set_fullscreen(match window.fullscreen() {
+                    Some(_) => None,
+                    None => Some(Fullscreen::Borderless(None)),
+                }),
+                _ => {}
+            },
+            // Toggle FPS-style mouse-look, the camera turns with the mouse without having to drag
+            WindowEvent::MouseInput {
+                state: ElementState::Pressed,
+                button: MouseButton::Right,
+                ..
+            } => {
+                if cursor_grab.is_grabbed() {
+                    cursor_grab.release(&window);
+                } else {
+                    cursor_grab.grab(&window);
+                }
+                state
+                    .camera_controller
+                    .set_mouse_look(cursor_grab.is_grabbed());
+            }
+            // Don't hold on to the cursor while the user's doing something else
+            WindowEvent::Focused(false) if cursor_grab.is_grabbed() => {
+                cursor_grab.release(&window);
+                state.camera_controller.set_mouse_look(false);
+            }
+            WindowEvent::Resized(physical_size) => state.resize(window_id, *physical_size),
+            // It might be on a monitor with a different refresh rate now
+            WindowEvent::Moved(_) if max_fps.is_none() => {
+                frame_interval = pick_frame_interval(max_fps, &window);
             }
+            // Both at once, so the UI's scale and the surface's size never disagree
+            WindowEvent::ScaleFactorChanged {
+                scale_factor,
+                new_inner_size,
+            } => {
+                state.set_scale_factor(*scale_factor);
+                state.resize(window_id, **new_inner_size);
+            }
+            _ => {}
+        },
+        // Raw movement rather than `CursorMoved`, which stops when the cursor can't go any further
+        // The controller ignores it unless it's dragging or mouse-looking
+        Event::DeviceEvent {
+            event: DeviceEvent::MouseMotion { delta },
+            ..
+        } => {
+            state.mouse_motion(delta);
+            cursor_grab.recenter(&window);
         }
-        Event::RedrawRequested(window_id) if window_id == window.id() => {
-            state.update();
-            match state.render() {
-                Ok(_) => (),
-                // Reconfigure the surface if lost
-                Err(SurfaceError::Lost) => state.resize(state.size),
+        Event::RedrawRequested(window_id) if window_id == window.id() && !state.is_minimized() => {
+            let now = Instant::now();
+            let dt = now - last_update;
+            state.begin_ui_frame(&window);
+            let alpha = match state.fixed_timestep() {
+                Some(timestep) => {
+                    // The UI and the frame's input are only handled once, however many steps there are
+                    state.begin_update();
+                    accumulator += dt;
+                    let mut steps = 0;
+                    while accumulator >= timestep && steps < MAX_FIXED_STEPS {
+                        update(&mut state, timestep);
+                        state.step(timestep);
+                        accumulator -= timestep;
+                        steps += 1;
+                    }
+                    state.end_update();
+                    // Can't keep up, so give up on the backlog rather than spiralling
+                    if accumulator >= timestep {
+                        log::debug!("Dropping {accumulator:?} of updates to catch up");
+                        accumulator = Duration::from_secs_f64(
+                            accumulator.as_secs_f64() % timestep.as_secs_f64(),
+                        );
+                    }
+                    accumulator.as_secs_f32() / timestep.as_secs_f32()
+                }
+                None => {
+                    // Before `state.update()`, which clears the frame's scroll delta
+                    update(&mut state, dt);
+                    state.update(dt);
+                    accumulator = Duration::ZERO;
+                    1.0
+                }
+            };
+            last_update = now;
+            if state.exit_requested() {
+                *control_flow = ControlFlow::Exit;
+                return;
+            }
+            // Drawn at the new size straight away, the `Resized` event that follows has the size we actually got
+            if let Some(size) = state.take_requested_size() {
+                window.set_inner_size(size);
+                state.resize(window.id(), size.to_physical(window.scale_factor()));
+            }
+            match state.render(alpha) {
+                Ok(_) => surface_lost = false,
+                // Reconfiguring usually sorts out a lost surface, if it's still lost the device itself has probably gone
+                Err(SurfaceError::Lost) if surface_lost => {
+                    surface_lost = false;
+                    if let Err(err) = state.recreate_device() {
+                        log::error!("Failed to recreate the device: {err}");
+                        *control_flow = ControlFlow::Exit;
+                    }
+                }
+                // Reconfigure the surface if it's lost or out of date (e.g. mid fullscreen toggle)
+                // We ask the window for its size since the `Resized` event might not have arrived yet
+                Err(err @ (SurfaceError::Lost | SurfaceError::Outdated)) => {
+                    surface_lost = err == SurfaceError::Lost;
+                    state.reconfigure(window.id(), window.inner_size())
+                }
                 // The system is OOM, should probably quit :p
                 Err(SurfaceError::OutOfMemory) => *control_flow = ControlFlow::Exit,
-                // All other errors, e.g. `Outdated` and `Timeout` should be resolved by the next frame
+                // All other errors, e.g. `Timeout` should be resolved by the next frame
                 Err(e) => log::error!("{:?}", e),
             }
+            if last_title_update.elapsed() >= Duration::from_secs(1) {
+                window.set_title(&format!("{title} ({:.0} FPS)", state.fps()));
+                last_title_update = Instant::now();
+            }
         }
         Event::MainEventsCleared => {
-            // `Event::RedrawRequested` will only trigger once, unless we manually request it
-            window.request_redraw();
+            // Throttled while in the background if the app asked for it, unless the cap is already slower than that
+            let frame_interval = if state.is_paused() {
+                frame_interval.max(Some(PAUSED_FRAME_INTERVAL))
+            } else {
+                frame_interval
+            };
+            if state.is_minimized() {
+                // Nothing to draw, so sleep until something happens (like the window being restored)
+                *control_flow = ControlFlow::Wait;
+            } else if let Some(frame_interval) = frame_interval {
+                // Only draw once the interval has passed, and sleep until then otherwise
+                let next_frame = last_frame + frame_interval;
+                let now = Instant::now();
+                if now >= next_frame {
+                    last_frame = now;
+                    window.request_redraw();
+                    *control_flow = ControlFlow::WaitUntil(now + frame_interval);
+                } else {
+                    *control_flow = ControlFlow::WaitUntil(next_frame);
+                }
+            } else {
+                *control_flow = ControlFlow::Poll;
+                // `Event::RedrawRequested` will only trigger once, unless we manually request it
+                window.request_redraw();
+            }
         }
+        // The last event before everything (including `state`) gets dropped
+        Event::LoopDestroyed => state.shutdown(),
         _ => {}
     });
 }
+
+fn init_logger() {
+    #[cfg(not(target_arch = "wasm32"))]
+    env_logger::init();
+    // No terminal in the browser, so log (and report panics) to the console
+    #[cfg(target_arch = "wasm32")]
+    {
+        std::panic::set_hook(Box::new(console_error_panic_hook::hook));
+        console_log::init_with_level(log::Level::Warn)
+            .expect("the logger should only be set up once");
+    }
+}
+
+/// How long to wait between frames for `max_fps`, see `run()`
+fn pick_frame_interval(max_fps: Option<u32>, window: &Window) -> Option<Duration> {
+    let fps = match max_fps {
+        Some(0) => return None,
+        Some(fps) => fps as f64,
+        None => match monitor_refresh_rate(window) {
+            Some(refresh_rate) => {
+                log::debug!("Capping the frame rate at the monitor's {refresh_rate} Hz");
+                refresh_rate
+            }
+            None => {
+                log::debug!("Couldn't find the monitor's refresh rate, not capping the frame rate");
+                return None;
+            }
+        },
+    };
+    Some(Duration::from_secs_f64(1.0 / fps))
+}
+
+/// The refresh rate of the monitor `window` is on in Hz, `None` if winit can't tell (e.g. in the browser)
+fn monitor_refresh_rate(window: &Window) -> Option<f64> {
+    let monitor = window.current_monitor()?;
+    let millihertz = monitor.refresh_rate_millihertz().or_else(|| {
+        // Not every platform reports it directly, but the video mode that matches the monitor's size should know
+        monitor
+            .video_modes()
+            .filter(|mode| mode.size() == monitor.size())
+            .map(|mode| mode.refresh_rate_millihertz())
+            .max()
+    })?;
+    (millihertz > 0).then(|| millihertz as f64 / 1000.0)
+}
+
+/// Decode a PNG into a window icon, logging why if it can't be
+fn load_icon(bytes: &[u8]) -> Option<Icon> {
+    let image = match image::load_from_memory(bytes) {
+        Ok(image) => image.to_rgba8(),
+        Err(err) => {
+            log::error!("Failed to decode the window icon: {err}");
+            return None;
+        }
+    };
+    let (width, height) = image.dimensions();
+    match Icon::from_rgba(image.into_raw(), width, height) {
+        Ok(icon) => Some(icon),
+        Err(err) => {
+            log::error!("Failed to create the window icon: {err}");
+            None
+        }
+    }
+}
+
+/// Put the window's canvas at the end of the page, it's already been sized by `WindowBuilder`
+#[cfg(target_arch = "wasm32")]
+fn add_canvas_to_page(window: &winit::window::Window) {
+    use winit::platform::web::WindowExtWebSys;
+
+    let body = web_sys::window()
+        .and_then(|window| window.document())
+        .and_then(|document| document.body())
+        .expect("the page should have a body");
+    body.append_child(&window.canvas())
+        .expect("failed to add the canvas to the page");
+}
+
+/// Capture the current frame and write it to `screenshot-<unix time in ms>.png` in the working directory
+#[cfg(not(target_arch = "wasm32"))]
+fn save_screenshot(state: &mut State) {
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+    let path = format!("screenshot-{timestamp}.png");
+    match state.capture_frame().save(&path) {
+        Ok(()) => log::info!("Saved screenshot to {path}"),
+        Err(err) => log::error!("Failed to save screenshot to {path}: {err}"),
+    }
+}
@@ -1,21 +1,192 @@
+// `transform` gets declared before this, as either a push constant or a uniform depending on what the device supports
+
+struct Globals {
+    time: f32,
+    // The size of the target in pixels, for working out where a fragment is on screen from its `clip_position`
+    resolution: vec2<f32>,
+    inverse_resolution: vec2<f32>,
+};
+@group(0) @binding(0)
+var<uniform> globals: Globals;
+
+struct Light {
+    position: vec3<f32>,
+    color: vec3<f32>,
+};
+@group(0) @binding(1)
+var<uniform> light: Light;
+
+struct Shadow {
+    // Takes a point in world space to the light's clip space
+    view_proj: mat4x4<f32>,
+    // 0 when shadows are off, in which case nothing's been drawn into the shadow map
+    enabled: u32,
+};
+@group(0) @binding(2)
+var<uniform> shadow: Shadow;
+// The depth of the closest thing to the light, drawn by the shadow pass
+@group(0) @binding(3)
+var t_shadow: texture_depth_2d;
+@group(0) @binding(4)
+var s_shadow: sampler_comparison;
+
+// How much of the light reaches `world_position`, from 0 in full shadow to 1 fully lit
+fn shadow_factor(world_position: vec3<f32>) -> f32 {
+    if (shadow.enabled == 0u) {
+        return 1.0;
+    }
+    let light_clip = shadow.view_proj * vec4<f32>(world_position, 1.0);
+    let ndc = light_clip.xyz / light_clip.w;
+    // Clip space has y going up but textures have it going down
+    let uv = ndc.xy * vec2<f32>(0.5, -0.5) + 0.5;
+    // Outside of what the shadow map covers, so nothing can be in the way
+    if (any(uv < vec2<f32>(0.0)) || any(uv > vec2<f32>(1.0)) || ndc.z > 1.0) {
+        return 1.0;
+    }
+    // Percentage-closer filtering: averaging the comparisons over a 3x3 block softens the shadow's jagged edges
+    let texel = 1.0 / vec2<f32>(textureDimensions(t_shadow));
+    var lit = 0.0;
+    for (var x = -1; x <= 1; x = x + 1) {
+        for (var y = -1; y <= 1; y = y + 1) {
+            let offset = vec2<f32>(f32(x), f32(y)) * texel;
+            lit = lit + textureSampleCompareLevel(t_shadow, s_shadow, uv + offset, ndc.z);
+        }
+    }
+    return lit / 9.0;
+}
+
+struct Camera {
+    view_proj: mat4x4<f32>,
+    view_position: vec4<f32>,
+};
+@group(1) @binding(0)
+var<uniform> camera: Camera;
+
+// A single texture rather than an array, picking layers per quad is only done by sprite.wgsl
+@group(2) @binding(0)
+var t_diffuse: texture_2d<f32>;
+@group(2) @binding(1)
+var s_diffuse: sampler;
+
 // Vertex shader
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) color: vec3<f32>,
+    @location(2) tex_coords: vec2<f32>,
+    @location(3) normal: vec3<f32>,
+};
+
+struct InstanceInput {
+    @location(5) model_matrix_0: vec4<f32>,
+    @location(6) model_matrix_1: vec4<f32>,
+    @location(7) model_matrix_2: vec4<f32>,
+    @location(8) model_matrix_3: vec4<f32>,
+};
+
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
+    // Switched to `@interpolate(flat)` by `State::set_color_interpolation()`, so keep it written exactly like this
+    @location(0) @interpolate(perspective) color: vec3<f32>,
+    @location(1) tex_coords: vec2<f32>,
+    @location(2) world_normal: vec3<f32>,
+    @location(3) world_position: vec3<f32>,
 };
 
+// The inverse transpose of `m`'s upper 3x3, which keeps normals perpendicular to the surface under non-uniform scaling
+// Scaled by the determinant, but the normal gets normalised anyway (and flipped back if the determinant is negative)
+fn normal_matrix(m: mat4x4<f32>) -> mat3x3<f32> {
+    let c0 = m[0].xyz;
+    let c1 = m[1].xyz;
+    let c2 = m[2].xyz;
+    let adjugate = mat3x3<f32>(cross(c1, c2), cross(c2, c0), cross(c0, c1));
+    return adjugate * sign(dot(c0, cross(c1, c2)));
+}
+
 @vertex
 fn vs_main(
-    @builtin(vertex_index) in_vertex_index: u32,
+    model: VertexInput,
+    instance: InstanceInput,
 ) -> VertexOutput {
+    let model_matrix = mat4x4<f32>(
+        instance.model_matrix_0,
+        instance.model_matrix_1,
+        instance.model_matrix_2,
+        instance.model_matrix_3,
+    );
+    let world_matrix = transform.value * model_matrix;
+    let world_position = world_matrix * vec4<f32>(model.position, 1.0);
     var out: VertexOutput;
-    let x = f32(1 - i32(in_vertex_index)) * 0.5;
-    let y = f32(i32(in_vertex_index & 1u) * 2 - 1) * 0.5;
-    out.clip_position = vec4<f32>(x, y, 0.0, 1.0);
+    out.color = model.color;
+    out.tex_coords = model.tex_coords;
+    out.world_normal = normal_matrix(world_matrix) * model.normal;
+    out.world_position = world_position.xyz;
+    out.clip_position = camera.view_proj * world_position;
     return out;
 }
 
 @fragment
                                 // stores in 0th colour target
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    return vec4<f32>(0.3, 0.2, 0.1, 1.0);
-}
\ No newline at end of file
+    // Pulse between 50% and 100% brightness
+    let pulse = 0.75 + 0.25 * sin(globals.time);
+    let texel = textureSample(t_diffuse, s_diffuse, in.tex_coords);
+    let object_color = in.color * texel.rgb * pulse;
+
+    // Blinn-Phong: a bit of light everywhere, plus diffuse and specular from the light
+    let ambient = 0.1;
+    let normal = normalize(in.world_normal);
+    let light_dir = normalize(light.position - in.world_position);
+    let view_dir = normalize(camera.view_position.xyz - in.world_position);
+    // Halfway between the light and the camera, cheaper and nicer than reflecting the light direction
+    let half_dir = normalize(view_dir + light_dir);
+    let diffuse = max(dot(normal, light_dir), 0.0);
+    let specular = pow(max(dot(normal, half_dir), 0.0), 32.0);
+
+    // The ambient still reaches into shadows, so they aren't pitch black
+    let lit = shadow_factor(in.world_position);
+
+    // Highlights are the colour of the light, not the object
+    let color = ((ambient + diffuse * lit) * object_color + specular * lit) * light.color;
+    return vec4<f32>(color, texel.a);
+}
+
+// The debug views, see `DebugView`
+
+// Just the vertex colour and the texture, as if everything were fully lit
+@fragment
+fn fs_unlit(in: VertexOutput) -> @location(0) vec4<f32> {
+    let texel = textureSample(t_diffuse, s_diffuse, in.tex_coords);
+    return vec4<f32>(in.color * texel.rgb, texel.a);
+}
+
+// Each axis of the normal mapped from -1..1 to 0..1, so +X is red, +Y is green and +Z is blue
+@fragment
+fn fs_normals(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(normalize(in.world_normal) * 0.5 + 0.5, 1.0);
+}
+
+// Red for `u` and green for `v`, wrapped to 0..1 the same way a repeating texture is
+@fragment
+fn fs_tex_coords(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(fract(in.tex_coords), 0.0, 1.0);
+}
+
+// What the G-buffer pass writes, one output for each of its textures (see `gbuffer::DEFAULT_ATTACHMENTS`)
+struct GBufferOutput {
+    @location(0) albedo: vec4<f32>,
+    @location(1) normal: vec4<f32>,
+    // `w` is 1 wherever something got drawn, the textures are cleared to 0
+    @location(2) position: vec4<f32>,
+};
+
+// Only stores what the lighting needs, so it can happen later in one go
+@fragment
+fn fs_gbuffer(in: VertexOutput) -> GBufferOutput {
+    let pulse = 0.75 + 0.25 * sin(globals.time);
+    let texel = textureSample(t_diffuse, s_diffuse, in.tex_coords);
+    var out: GBufferOutput;
+    out.albedo = vec4<f32>(in.color * texel.rgb * pulse, texel.a);
+    out.normal = vec4<f32>(normalize(in.world_normal), 0.0);
+    out.position = vec4<f32>(in.world_position, 1.0);
+    return out;
+}
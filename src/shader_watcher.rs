@@ -0,0 +1,69 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Receiver},
+};
+
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+
+/// Watches a shader file on disk so it can be reloaded when it changes
+pub struct ShaderWatcher {
+    path: PathBuf,
+    // Has to be kept alive, otherwise it stops watching
+    _watcher: RecommendedWatcher,
+    changes: Receiver<()>,
+}
+
+impl ShaderWatcher {
+    pub fn new(path: impl AsRef<Path>) -> notify::Result<Self> {
+        let path = path.as_ref().canonicalize()?;
+        let parent = path
+            .parent()
+            .ok_or_else(|| notify::Error::io(io::ErrorKind::NotFound.into()))?
+            .to_path_buf();
+
+        let (sender, changes) = mpsc::channel();
+        let watched = path.clone();
+        let mut watcher = notify::recommended_watcher(move |event: notify::Result<Event>| {
+            match event {
+                Ok(event)
+                    if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_))
+                        && event.paths.contains(&watched) =>
+                {
+                    // If the receiver is gone then nobody cares anymore
+                    let _ = sender.send(());
+                }
+                Ok(_) => {}
+                Err(err) => log::error!("Error while watching shader: {err}"),
+            }
+        })?;
+        // Lots of editors save by replacing the file, which would lose a watch on the file itself, so we watch its directory instead
+        watcher.watch(&parent, RecursiveMode::NonRecursive)?;
+
+        Ok(Self {
+            path,
+            _watcher: watcher,
+            changes,
+        })
+    }
+
+    /// The path of the shader being watched
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the new source of the shader if it has changed since the last call
+    pub fn poll(&self) -> Option<String> {
+        // A single save can fire several events, we only need to reload once
+        if self.changes.try_iter().count() == 0 {
+            return None;
+        }
+        match fs::read_to_string(&self.path) {
+            Ok(source) => Some(source),
+            Err(err) => {
+                log::error!("Failed to read {}: {err}", self.path.display());
+                None
+            }
+        }
+    }
+}
@@ -0,0 +1,229 @@
+use bytemuck::{Pod, Zeroable};
+use glam::{Mat4, Vec3};
+use wgpu::{
+    util::{BufferInitDescriptor, DeviceExt},
+    BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout, Buffer, BufferUsages,
+    CommandEncoder, CompareFunction, DepthBiasState, DepthStencilState, Device, Extent3d, LoadOp,
+    MultisampleState, Operations, PipelineLayout, PrimitiveState, Queue, RenderPass,
+    RenderPassDepthStencilAttachment, RenderPassDescriptor, RenderPipeline,
+    RenderPipelineDescriptor, ShaderModule, StencilState, TextureDescriptor, TextureDimension,
+    TextureFormat, TextureUsages, TextureViewDescriptor, VertexState,
+};
+
+use crate::camera::CameraUniform;
+use crate::instance::Instance;
+use crate::light::Light;
+use crate::texture::{self, Texture};
+use crate::vertex::Vertex;
+
+/// Only depth gets stored, and it has to be sampleable with a comparison sampler
+pub const SHADOW_FORMAT: TextureFormat = TextureFormat::Depth32Float;
+/// How many texels there are along each side of the shadow map, more gives sharper shadows
+pub const SHADOW_MAP_SIZE: u32 = 2048;
+/// Pushes what the light sees a little further away, so surfaces don't shadow themselves (shadow acne)
+///
+/// `constant` is in the depth buffer's smallest steps, `slope_scale` grows it on surfaces at a steep angle to the light
+pub const DEFAULT_DEPTH_BIAS: DepthBiasState = DepthBiasState {
+    constant: 2,
+    slope_scale: 2.0,
+    clamp: 0.0,
+};
+
+/// What the shader gets at `@group(0) @binding(2)`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+struct ShadowUniform {
+    /// Takes a point in world space to the light's clip space
+    view_proj: [[f32; 4]; 4],
+    /// 0 when shadows are off, since the shadow map isn't drawn into then
+    enabled: u32,
+    // Uniform structs are padded out to 16 bytes
+    _padding: [u32; 3],
+}
+
+/// A depth texture drawn from the light's point of view, so the scene can tell what the light can't reach
+///
+/// The light shines from `Light::position` towards `center` as if it were infinitely far away, like the sun,
+/// and only what's within `radius` of `center` casts or receives shadows
+pub struct ShadowMap {
+    pub center: Vec3,
+    pub radius: f32,
+    texture: Texture,
+    /// Bound in place of `texture` while it's being drawn into, since it can't be drawn into and bound at the same time
+    placeholder: Texture,
+    uniform_buffer: Buffer,
+    /// The light's view-projection laid out like a `CameraUniform`, bound in place of the camera for the shadow pass
+    light_camera_buffer: Buffer,
+    light_camera_bind_group: BindGroup,
+}
+
+impl ShadowMap {
+    pub fn new(device: &Device, camera_layout: &BindGroupLayout) -> Self {
+        let texture = create_texture(device, "Shadow Map", SHADOW_MAP_SIZE);
+        let placeholder = create_texture(device, "Shadow Map Placeholder", 1);
+        let uniform_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Shadow Buffer"),
+            contents: bytemuck::bytes_of(&ShadowUniform::zeroed()),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+        let light_camera_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Light Camera Buffer"),
+            contents: bytemuck::bytes_of(&CameraUniform::zeroed()),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+        let light_camera_bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Light Camera Bind Group"),
+            layout: camera_layout,
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: light_camera_buffer.as_entire_binding(),
+            }],
+        });
+        Self {
+            center: Vec3::ZERO,
+            radius: 10.0,
+            texture,
+            placeholder,
+            uniform_buffer,
+            light_camera_buffer,
+            light_camera_bind_group,
+        }
+    }
+
+    /// What gets bound for the scene to sample, along with its comparison sampler
+    pub fn texture(&self) -> &Texture {
+        &self.texture
+    }
+
+    /// What gets bound instead while drawing the shadow map
+    pub fn placeholder(&self) -> &Texture {
+        &self.placeholder
+    }
+
+    /// Holds the light's view-projection, and whether shadows are on at all
+    pub fn uniform_buffer(&self) -> &Buffer {
+        &self.uniform_buffer
+    }
+
+    /// Bound in place of the camera when drawing the shadow map, so the scene's vertex shader draws it from the light
+    pub fn light_camera_bind_group(&self) -> &BindGroup {
+        &self.light_camera_bind_group
+    }
+
+    /// Takes a point in world space to the light's clip space, looking from `light` towards `center`
+    pub fn view_projection(&self, light: &Light) -> Mat4 {
+        let direction = (self.center - Vec3::from(light.position)).normalize_or_zero();
+        // Straight down if the light is right on top of `center`
+        let direction = if direction == Vec3::ZERO {
+            Vec3::NEG_Y
+        } else {
+            direction
+        };
+        // Anything will do as long as it isn't parallel to the direction
+        let up = if direction.cross(Vec3::Y).length_squared() < 1e-6 {
+            Vec3::Z
+        } else {
+            Vec3::Y
+        };
+        // Backed off far enough that everything within `radius` is in front of the near plane
+        let eye = self.center - direction * self.radius;
+        let view = Mat4::look_at_rh(eye, self.center, up);
+        // Already maps depth to 0..1 like wgpu wants
+        let proj = Mat4::orthographic_rh(
+            -self.radius,
+            self.radius,
+            -self.radius,
+            self.radius,
+            0.0,
+            2.0 * self.radius,
+        );
+        proj * view
+    }
+
+    /// Point the shadow map at where `light` is now, takes effect when the queue is next submitted
+    pub fn update(&self, queue: &Queue, light: &Light, enabled: bool) {
+        let view_proj = self.view_projection(light);
+        let uniform = ShadowUniform {
+            view_proj: view_proj.to_cols_array_2d(),
+            enabled: enabled as u32,
+            _padding: [0; 3],
+        };
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::bytes_of(&uniform));
+        if enabled {
+            let mut camera = CameraUniform::zeroed();
+            camera.view_proj = uniform.view_proj;
+            queue.write_buffer(&self.light_camera_buffer, 0, bytemuck::bytes_of(&camera));
+        }
+    }
+
+    /// Start a depth-only pass into the shadow map, cleared to as far away as possible
+    pub fn begin_pass<'a>(&'a self, encoder: &'a mut CommandEncoder) -> RenderPass<'a> {
+        encoder.begin_render_pass(&RenderPassDescriptor {
+            label: Some("Shadow Pass"),
+            color_attachments: &[],
+            depth_stencil_attachment: Some(RenderPassDepthStencilAttachment {
+                view: &self.texture.view,
+                depth_ops: Some(Operations {
+                    load: LoadOp::Clear(1.0),
+                    store: true,
+                }),
+                stencil_ops: None,
+            }),
+        })
+    }
+}
+
+/// Build the pipeline that draws our vertices into the shadow map with the `vs_main` entry point of `shader`
+///
+/// There's no fragment shader, only depth gets written
+pub fn create_pipeline(
+    device: &Device,
+    layout: &PipelineLayout,
+    shader: &ShaderModule,
+    bias: DepthBiasState,
+) -> RenderPipeline {
+    device.create_render_pipeline(&RenderPipelineDescriptor {
+        label: Some("Shadow Pipeline"),
+        layout: Some(layout),
+        vertex: VertexState {
+            module: shader,
+            entry_point: "vs_main",
+            buffers: &[Vertex::desc(), Instance::desc()],
+        },
+        fragment: None,
+        // Both sides cast shadows, otherwise a quad facing away from the light would let it straight through
+        primitive: PrimitiveState::default(),
+        depth_stencil: Some(DepthStencilState {
+            format: SHADOW_FORMAT,
+            depth_write_enabled: true,
+            depth_compare: CompareFunction::Less,
+            stencil: StencilState::default(),
+            bias,
+        }),
+        multisample: MultisampleState::default(),
+        multiview: None,
+    })
+}
+
+fn create_texture(device: &Device, label: &str, size: u32) -> Texture {
+    let texture = device.create_texture(&TextureDescriptor {
+        label: Some(label),
+        size: Extent3d {
+            width: size,
+            height: size,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: TextureDimension::D2,
+        format: SHADOW_FORMAT,
+        usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+    });
+    let view = texture.create_view(&TextureViewDescriptor::default());
+    Texture {
+        texture,
+        view,
+        // Compares against the depth it's given, 1 where it's lit and 0 where it's in shadow
+        sampler: texture::create_clamped_sampler(device, Some(CompareFunction::LessEqual)),
+    }
+}
@@ -0,0 +1,304 @@
+use std::{f32::consts::PI, num::NonZeroU32};
+
+use glam::{Mat4, Vec3};
+use image::{
+    error::{LimitError, LimitErrorKind, ParameterError, ParameterErrorKind},
+    imageops, DynamicImage, ImageError, ImageResult, Rgba, RgbaImage,
+};
+use wgpu::{
+    util::{BufferInitDescriptor, DeviceExt},
+    AddressMode, BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayoutDescriptor,
+    BindGroupLayoutEntry, BindingResource, BindingType, Buffer, BufferBindingType, BufferUsages,
+    ColorTargetState, ColorWrites, CompareFunction, DepthBiasState, DepthStencilState, Device,
+    Extent3d, FilterMode, FragmentState, ImageCopyTexture, ImageDataLayout, MultisampleState,
+    Origin3d, PipelineLayout, PipelineLayoutDescriptor, PrimitiveState, Queue, RenderPass,
+    RenderPipeline, RenderPipelineDescriptor, SamplerBindingType, SamplerDescriptor, ShaderModule,
+    ShaderModuleDescriptor, ShaderSource, ShaderStages, StencilState, TextureAspect,
+    TextureDescriptor, TextureDimension, TextureFormat, TextureSampleType, TextureUsages,
+    TextureViewDescriptor, TextureViewDimension, VertexState,
+};
+
+use crate::{
+    camera::Camera,
+    texture::{Texture, DEPTH_FORMAT},
+};
+
+/// A cubemap drawn behind the whole scene, which stays put as the camera moves and only changes as it turns
+pub struct Skybox {
+    shader: ShaderModule,
+    layout: PipelineLayout,
+    /// Where `update()` writes the camera's inverse view-projection, without its position
+    uniform_buffer: Buffer,
+    bind_group: BindGroup,
+    /// Only held so the view in `bind_group` stays valid
+    _texture: Texture,
+    /// What was uploaded, so it can be uploaded again to a new device
+    faces: [RgbaImage; 6],
+}
+
+impl Skybox {
+    /// Upload six square faces of the same size, in the order +X, -X, +Y, -Y, +Z, -Z
+    ///
+    /// Each face is what you'd see looking along that axis, the same layout OpenGL and most skybox images use
+    /// Fails if the faces aren't square and the same size, or if they're bigger than the device supports
+    pub fn new(device: &Device, queue: &Queue, faces: [RgbaImage; 6]) -> ImageResult<Self> {
+        let size = faces[0].width();
+        if faces
+            .iter()
+            .any(|face| face.width() != size || face.height() != size)
+        {
+            log::error!("The skybox's faces have to be square and all the same size");
+            return Err(ImageError::Parameter(ParameterError::from_kind(
+                ParameterErrorKind::DimensionMismatch,
+            )));
+        }
+        let max_dimension = device.limits().max_texture_dimension_2d;
+        if size == 0 || size > max_dimension {
+            log::error!("The skybox's faces are {size}x{size}, but this device only supports textures up to {max_dimension}x{max_dimension}");
+            return Err(ImageError::Limits(LimitError::from_kind(
+                LimitErrorKind::DimensionError,
+            )));
+        }
+
+        let extent = Extent3d {
+            width: size,
+            height: size,
+            depth_or_array_layers: 6,
+        };
+        let texture = device.create_texture(&TextureDescriptor {
+            label: Some("Skybox Texture"),
+            size: extent,
+            // The sky is never far enough away to shrink, it's always the same distance
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: TextureFormat::Rgba8UnormSrgb,
+            usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+        });
+        // Each face is a layer of the texture
+        queue.write_texture(
+            ImageCopyTexture {
+                texture: &texture,
+                mip_level: 0,
+                origin: Origin3d::ZERO,
+                aspect: TextureAspect::All,
+            },
+            &faces
+                .iter()
+                .flat_map(|face| face.as_raw())
+                .copied()
+                .collect::<Vec<_>>(),
+            ImageDataLayout {
+                offset: 0,
+                bytes_per_row: NonZeroU32::new(4 * size),
+                rows_per_image: NonZeroU32::new(size),
+            },
+            extent,
+        );
+        let view = texture.create_view(&TextureViewDescriptor {
+            label: Some("Skybox Texture View"),
+            dimension: Some(TextureViewDimension::Cube),
+            ..TextureViewDescriptor::default()
+        });
+        let sampler = device.create_sampler(&SamplerDescriptor {
+            label: Some("Skybox Sampler"),
+            // Otherwise the seams between faces show
+            address_mode_u: AddressMode::ClampToEdge,
+            address_mode_v: AddressMode::ClampToEdge,
+            address_mode_w: AddressMode::ClampToEdge,
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            mipmap_filter: FilterMode::Nearest,
+            ..Default::default()
+        });
+        let texture = Texture {
+            texture,
+            view,
+            sampler,
+        };
+
+        let shader = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("Skybox Shader"),
+            source: ShaderSource::Wgsl(include_str!("skybox.wgsl").into()),
+        });
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Skybox Bind Group Layout"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: true },
+                        view_dimension: TextureViewDimension::Cube,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+        let uniform_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Skybox Buffer"),
+            contents: bytemuck::bytes_of(&Mat4::IDENTITY.to_cols_array_2d()),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Skybox Bind Group"),
+            layout: &bind_group_layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::TextureView(&texture.view),
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: BindingResource::Sampler(&texture.sampler),
+                },
+            ],
+        });
+        let layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Skybox Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        Ok(Self {
+            shader,
+            layout,
+            uniform_buffer,
+            bind_group,
+            _texture: texture,
+            faces,
+        })
+    }
+
+    /// Convert a single equirectangular (latitude/longitude) image into a cubemap and upload that
+    ///
+    /// The middle of the image ends up straight ahead of the default camera, along -Z
+    pub fn from_equirectangular(
+        device: &Device,
+        queue: &Queue,
+        image: &DynamicImage,
+    ) -> ImageResult<Self> {
+        Self::new(device, queue, faces_from_equirectangular(image))
+    }
+
+    /// The faces that were uploaded, in the order `new()` takes them
+    pub fn faces(&self) -> &[RgbaImage; 6] {
+        &self.faces
+    }
+
+    /// Point the sky the same way as `camera`, takes effect when the queue is next submitted
+    pub fn update(&self, queue: &Queue, camera: &Camera) {
+        let matrix = camera.build_inverse_direction_matrix();
+        queue.write_buffer(
+            &self.uniform_buffer,
+            0,
+            bytemuck::bytes_of(&matrix.to_cols_array_2d()),
+        );
+    }
+
+    /// Build the pipeline for a target with `format` and `sample_count`
+    pub fn create_pipeline(
+        &self,
+        device: &Device,
+        format: TextureFormat,
+        sample_count: u32,
+    ) -> RenderPipeline {
+        device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("Skybox Pipeline"),
+            layout: Some(&self.layout),
+            vertex: VertexState {
+                module: &self.shader,
+                entry_point: "vs_main",
+                // The triangle is made up in the vertex shader
+                buffers: &[],
+            },
+            fragment: Some(FragmentState {
+                module: &self.shader,
+                entry_point: "fs_main",
+                targets: &[Some(ColorTargetState {
+                    format,
+                    blend: None,
+                    write_mask: ColorWrites::ALL,
+                })],
+            }),
+            primitive: PrimitiveState::default(),
+            // Drawn on the far plane, which the depth buffer is cleared to, so `LessEqual` only skips what's already been covered
+            // It doesn't write depth, so the scene drawn after it isn't hidden
+            depth_stencil: Some(DepthStencilState {
+                format: DEPTH_FORMAT,
+                depth_write_enabled: false,
+                depth_compare: CompareFunction::LessEqual,
+                stencil: StencilState::default(),
+                bias: DepthBiasState::default(),
+            }),
+            multisample: MultisampleState {
+                count: sample_count,
+                ..MultisampleState::default()
+            },
+            multiview: None,
+        })
+    }
+
+    /// Draw the sky with `pipeline` from `create_pipeline()`
+    pub fn draw<'a>(&'a self, render_pass: &mut RenderPass<'a>, pipeline: &'a RenderPipeline) {
+        render_pass.set_pipeline(pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
+
+/// Resample an equirectangular image into six cubemap faces, in the order `Skybox::new()` takes them
+///
+/// Each face is a quarter of the image's width, which keeps about the same detail around the horizon
+pub fn faces_from_equirectangular(image: &DynamicImage) -> [RgbaImage; 6] {
+    let image = image.to_rgba8();
+    let size = (image.width() / 4).max(1);
+    [0, 1, 2, 3, 4, 5].map(|face| {
+        RgbaImage::from_fn(size, size, |x, y| {
+            // -1..1 across the face, through the middle of each pixel
+            let u = (x as f32 + 0.5) / size as f32 * 2.0 - 1.0;
+            let v = (y as f32 + 0.5) / size as f32 * 2.0 - 1.0;
+            let direction = face_direction(face, u, v).normalize();
+            // Longitude around from -Z, and latitude up from the horizon
+            let longitude = direction.x.atan2(-direction.z);
+            let latitude = direction.y.asin();
+            imageops::sample_bilinear(&image, 0.5 + longitude / (2.0 * PI), 0.5 - latitude / PI)
+                .unwrap_or(Rgba([0, 0, 0, 255]))
+        })
+    })
+}
+
+/// Which way the point at (`u`, `v`) on `face` looks, with `u` going right and `v` going down the face
+///
+/// These are the directions the GPU samples a cubemap's faces in
+fn face_direction(face: usize, u: f32, v: f32) -> Vec3 {
+    match face {
+        0 => Vec3::new(1.0, -v, -u),
+        1 => Vec3::new(-1.0, -v, u),
+        2 => Vec3::new(u, 1.0, v),
+        3 => Vec3::new(u, -1.0, -v),
+        4 => Vec3::new(u, -v, 1.0),
+        _ => Vec3::new(-u, -v, -1.0),
+    }
+}
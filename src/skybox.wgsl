@@ -0,0 +1,34 @@
+// Draws the sky behind everything, by working out which way each pixel looks and sampling the cubemap in that direction
+
+struct Sky {
+    // Takes clip space back to a direction in world space, ignoring where the camera is so the sky is infinitely far away
+    inverse_view_proj: mat4x4<f32>,
+};
+@group(0) @binding(0)
+var<uniform> sky: Sky;
+@group(0) @binding(1)
+var t_sky: texture_cube<f32>;
+@group(0) @binding(2)
+var s_sky: sampler;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) clip: vec2<f32>,
+};
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    // One triangle big enough to cover the whole target, with corners at (-1, -1), (3, -1) and (-1, 3)
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    var out: VertexOutput;
+    out.clip = uv * 2.0 - 1.0;
+    // On the far plane, so anything in the scene is in front of it
+    out.clip_position = vec4<f32>(out.clip, 1.0, 1.0);
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let world = sky.inverse_view_proj * vec4<f32>(in.clip, 1.0, 1.0);
+    return textureSample(t_sky, s_sky, world.xyz / world.w);
+}
@@ -0,0 +1,349 @@
+use std::{mem, ops::Range};
+
+use bytemuck::{Pod, Zeroable};
+use glam::{Mat4, Quat, Vec3};
+use image::{DynamicImage, ImageResult, Rgba, RgbaImage};
+use wgpu::{
+    util::{BufferInitDescriptor, DeviceExt},
+    vertex_attr_array, BindGroup, BindGroupLayout, BlendState, Buffer, BufferAddress,
+    BufferDescriptor, BufferUsages, Color, ColorTargetState, ColorWrites, CompareFunction,
+    DepthBiasState, DepthStencilState, Device, FragmentState, IndexFormat, MultisampleState,
+    PipelineLayout, PipelineLayoutDescriptor, PrimitiveState, Queue, RenderPass, RenderPipeline,
+    RenderPipelineDescriptor, ShaderModule, ShaderModuleDescriptor, ShaderSource, StencilState,
+    TextureFormat, VertexAttribute, VertexBufferLayout, VertexState, VertexStepMode,
+};
+
+use crate::texture::{Texture, DEPTH_FORMAT};
+
+/// The corners of the quad every sprite is drawn with, from (0, 0) to (1, 1)
+const CORNERS: [[f32; 2]; 4] = [[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]];
+/// Two counter-clockwise triangles
+const INDICES: [u16; 6] = [0, 1, 2, 0, 2, 3];
+
+/// A rectangle on the XY plane, in world units, `(x, y)` being the bottom left corner
+///
+/// With `Projection::Orthographic` and the camera looking down the Z axis, world units are as good as screen units
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rect {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Rect {
+    /// From (0, 0) to (1, 1), in texture coordinates the whole texture
+    pub const UNIT: Rect = Rect {
+        x: 0.0,
+        y: 0.0,
+        width: 1.0,
+        height: 1.0,
+    };
+
+    pub fn new(x: f32, y: f32, width: f32, height: f32) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+}
+
+/// A texture added with `State::add_sprite_texture()`, for drawing quads with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SpriteTexture(usize);
+
+/// What the shader gets for each quad, matches `InstanceInput` in sprite.wgsl
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+struct QuadInstance {
+    model: [[f32; 4]; 4],
+    /// The top left corner and size of the part of the texture to show
+    uv_rect: [f32; 4],
+    color: [f32; 4],
+    /// Which layer of the texture array to show
+    layer: u32,
+}
+
+impl QuadInstance {
+    // Location 0 is the quad's corner
+    const ATTRIBUTES: [VertexAttribute; 7] = vertex_attr_array![
+        1 => Float32x4,
+        2 => Float32x4,
+        3 => Float32x4,
+        4 => Float32x4,
+        5 => Float32x4,
+        6 => Float32x4,
+        7 => Uint32,
+    ];
+
+    fn desc<'a>() -> VertexBufferLayout<'a> {
+        VertexBufferLayout {
+            array_stride: mem::size_of::<Self>() as BufferAddress,
+            step_mode: VertexStepMode::Instance,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+}
+
+/// A run of quads with the same texture (but not necessarily the same layer), drawn with a single call
+struct Batch {
+    /// `None` for untextured quads
+    texture: Option<SpriteTexture>,
+    instances: Range<u32>,
+}
+
+/// Collects quads over a frame and draws them with as few draw calls as it can, one per texture
+///
+/// Every texture is an array, so quads showing different layers of the same one still share a draw call
+///
+/// Quads are drawn grouped by texture rather than in the order they were added, so overlapping translucent quads with different textures can come out in the wrong order
+pub struct SpriteBatch {
+    shader: ShaderModule,
+    layout: PipelineLayout,
+    /// Every texture is bound as an array, even the ones with a single image
+    texture_bind_group_layout: BindGroupLayout,
+    /// The corners of the unit quad, shared by every sprite
+    vertex_buffer: Buffer,
+    index_buffer: Buffer,
+    /// `None` until something's drawn, grows to fit the busiest frame
+    instance_buffer: Option<Buffer>,
+    /// Stands in for the texture of untextured quads, so they can use the same pipeline
+    _white_texture: Texture,
+    white_bind_group: BindGroup,
+    /// The textures from `add_texture()` and `add_texture_array()`, indexed by `SpriteTexture`
+    textures: Vec<(Texture, BindGroup)>,
+    /// The layers each of `textures` was made from, so they can be uploaded again to a new device
+    images: Vec<Vec<DynamicImage>>,
+    /// What's been queued with `push()` since the last `prepare()`
+    quads: Vec<(Option<SpriteTexture>, QuadInstance)>,
+    /// What `prepare()` last uploaded, and what `draw()` draws
+    batches: Vec<Batch>,
+}
+
+impl SpriteBatch {
+    /// `camera_bind_group_layout` is the same layout the scene uses
+    pub fn new(device: &Device, queue: &Queue, camera_bind_group_layout: &BindGroupLayout) -> Self {
+        let texture_bind_group_layout = Texture::array_bind_group_layout(device);
+        let shader = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("Sprite Shader"),
+            source: ShaderSource::Wgsl(include_str!("sprite.wgsl").into()),
+        });
+        let layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Sprite Pipeline Layout"),
+            bind_group_layouts: &[camera_bind_group_layout, &texture_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let vertex_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Sprite Vertex Buffer"),
+            contents: bytemuck::cast_slice(&CORNERS),
+            usage: BufferUsages::VERTEX,
+        });
+        let index_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Sprite Index Buffer"),
+            contents: bytemuck::cast_slice(&INDICES),
+            usage: BufferUsages::INDEX,
+        });
+        let white = DynamicImage::ImageRgba8(RgbaImage::from_pixel(1, 1, Rgba([255; 4])));
+        let white_texture =
+            Texture::array_from_images(device, queue, &[white], Some("White Texture"), true)
+                .expect("a 1x1 texture should fit on any device");
+        let white_bind_group = white_texture.bind_group(device, &texture_bind_group_layout);
+        Self {
+            shader,
+            layout,
+            texture_bind_group_layout,
+            vertex_buffer,
+            index_buffer,
+            instance_buffer: None,
+            _white_texture: white_texture,
+            white_bind_group,
+            textures: Vec::new(),
+            images: Vec::new(),
+            quads: Vec::new(),
+            batches: Vec::new(),
+        }
+    }
+
+    /// Upload `image` for drawing quads with
+    ///
+    /// Fails if it's bigger than the device supports, like `Texture::from_image()`
+    pub fn add_texture(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        image: DynamicImage,
+    ) -> ImageResult<SpriteTexture> {
+        self.add_texture_array(device, queue, vec![image])
+    }
+
+    /// Upload `images` as the layers of one texture, picked between with the `layer` passed to `push()`
+    ///
+    /// Fails if they aren't all the same size or there are too many, like `Texture::array_from_images()`
+    pub fn add_texture_array(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        images: Vec<DynamicImage>,
+    ) -> ImageResult<SpriteTexture> {
+        // Sprites are usually drawn at about their own size, and mipmaps would blur pixel art
+        let texture =
+            Texture::array_from_images(device, queue, &images, Some("Sprite Texture"), true)?;
+        let bind_group = texture.bind_group(device, &self.texture_bind_group_layout);
+        self.textures.push((texture, bind_group));
+        self.images.push(images);
+        Ok(SpriteTexture(self.textures.len() - 1))
+    }
+
+    /// The layers of every texture, in the order they were added
+    pub fn images(&self) -> &[Vec<DynamicImage>] {
+        &self.images
+    }
+
+    /// Queue a quad covering `rect`, showing the `uv_rect` part of `texture`'s `layer` (or plain white) multiplied by `color`
+    ///
+    /// `uv_rect` is in texture coordinates, `Rect::UNIT` being the whole texture
+    /// A `layer` past the end of the texture shows its last layer
+    pub fn push(
+        &mut self,
+        rect: Rect,
+        uv_rect: Rect,
+        color: Color,
+        texture: Option<SpriteTexture>,
+        layer: u32,
+    ) {
+        let model = Mat4::from_scale_rotation_translation(
+            Vec3::new(rect.width, rect.height, 1.0),
+            Quat::IDENTITY,
+            Vec3::new(rect.x, rect.y, 0.0),
+        );
+        let instance = QuadInstance {
+            model: model.to_cols_array_2d(),
+            uv_rect: [uv_rect.x, uv_rect.y, uv_rect.width, uv_rect.height],
+            color: [color.r, color.g, color.b, color.a].map(|channel| channel as f32),
+            // Clamped here rather than by the GPU, since the texture can have an extra layer at the end (see `Texture::array_from_images()`)
+            layer: match texture {
+                Some(SpriteTexture(index)) => layer.min(self.images[index].len() as u32 - 1),
+                None => 0,
+            },
+        };
+        self.quads.push((texture, instance));
+    }
+
+    /// Upload everything queued since the last call, replacing what `draw()` draws, and start a new frame
+    pub fn prepare(&mut self, device: &Device, queue: &Queue) {
+        self.batches.clear();
+        if self.quads.is_empty() {
+            return;
+        }
+        // Stable, so quads with the same texture stay in the order they were added
+        self.quads.sort_by_key(|(texture, _)| *texture);
+        let mut instances = Vec::with_capacity(self.quads.len());
+        for (index, (texture, instance)) in self.quads.drain(..).enumerate() {
+            let index = index as u32;
+            match self.batches.last_mut() {
+                Some(batch) if batch.texture == texture => batch.instances.end = index + 1,
+                _ => self.batches.push(Batch {
+                    texture,
+                    instances: index..index + 1,
+                }),
+            }
+            instances.push(instance);
+        }
+        let size = mem::size_of_val(instances.as_slice()) as BufferAddress;
+        let instance_buffer = match &self.instance_buffer {
+            Some(buffer) if buffer.size() >= size => buffer,
+            // Doubling, so a slowly growing number of quads doesn't mean a new buffer every frame
+            _ => self
+                .instance_buffer
+                .insert(device.create_buffer(&BufferDescriptor {
+                    label: Some("Sprite Instance Buffer"),
+                    size: size.next_power_of_two(),
+                    usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                })),
+        };
+        queue.write_buffer(instance_buffer, 0, bytemuck::cast_slice(&instances));
+    }
+
+    /// Whether `draw()` has anything to draw
+    pub fn is_empty(&self) -> bool {
+        self.batches.is_empty()
+    }
+
+    /// Build the pipeline for a target with `format` and `sample_count`, which has to be used in the same pass as the scene
+    pub fn create_pipeline(
+        &self,
+        device: &Device,
+        format: TextureFormat,
+        sample_count: u32,
+    ) -> RenderPipeline {
+        device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("Sprite Pipeline"),
+            layout: Some(&self.layout),
+            vertex: VertexState {
+                module: &self.shader,
+                entry_point: "vs_main",
+                buffers: &[
+                    VertexBufferLayout {
+                        array_stride: mem::size_of::<[f32; 2]>() as BufferAddress,
+                        step_mode: VertexStepMode::Vertex,
+                        attributes: &vertex_attr_array![0 => Float32x2],
+                    },
+                    QuadInstance::desc(),
+                ],
+            },
+            fragment: Some(FragmentState {
+                module: &self.shader,
+                entry_point: "fs_main",
+                targets: &[Some(ColorTargetState {
+                    format,
+                    blend: Some(BlendState::ALPHA_BLENDING),
+                    write_mask: ColorWrites::ALL,
+                })],
+            }),
+            // Sprites can be flipped with a negative width or height, so both sides get drawn
+            primitive: PrimitiveState::default(),
+            // Hidden behind the scene, but translucent sprites don't hide each other
+            depth_stencil: Some(DepthStencilState {
+                format: DEPTH_FORMAT,
+                depth_write_enabled: false,
+                depth_compare: CompareFunction::LessEqual,
+                stencil: StencilState::default(),
+                bias: DepthBiasState::default(),
+            }),
+            multisample: MultisampleState {
+                count: sample_count,
+                ..MultisampleState::default()
+            },
+            multiview: None,
+        })
+    }
+
+    /// Draw what was last `prepare()`d with `pipeline` from `create_pipeline()`
+    pub fn draw<'a>(
+        &'a self,
+        render_pass: &mut RenderPass<'a>,
+        pipeline: &'a RenderPipeline,
+        camera_bind_group: &'a BindGroup,
+    ) {
+        let instance_buffer = match &self.instance_buffer {
+            Some(instance_buffer) if !self.batches.is_empty() => instance_buffer,
+            _ => return,
+        };
+        render_pass.set_pipeline(pipeline);
+        render_pass.set_bind_group(0, camera_bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        render_pass.set_vertex_buffer(1, instance_buffer.slice(..));
+        render_pass.set_index_buffer(self.index_buffer.slice(..), IndexFormat::Uint16);
+        for batch in &self.batches {
+            let bind_group = match batch.texture {
+                Some(SpriteTexture(index)) => &self.textures[index].1,
+                None => &self.white_bind_group,
+            };
+            render_pass.set_bind_group(1, bind_group, &[]);
+            render_pass.draw_indexed(0..INDICES.len() as u32, 0, batch.instances.clone());
+        }
+    }
+}
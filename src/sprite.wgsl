@@ -0,0 +1,55 @@
+// Draws batches of textured, tinted quads, one instance per quad
+
+struct Camera {
+    view_proj: mat4x4<f32>,
+    view_position: vec4<f32>,
+};
+@group(0) @binding(0)
+var<uniform> camera: Camera;
+
+// Every texture is an array, with one layer if it's just a single image
+@group(1) @binding(0)
+var t_sprite: texture_2d_array<f32>;
+@group(1) @binding(1)
+var s_sprite: sampler;
+
+struct InstanceInput {
+    @location(1) model_matrix_0: vec4<f32>,
+    @location(2) model_matrix_1: vec4<f32>,
+    @location(3) model_matrix_2: vec4<f32>,
+    @location(4) model_matrix_3: vec4<f32>,
+    // The part of the texture to show, `xy` is the top left corner and `zw` the size
+    @location(5) uv_rect: vec4<f32>,
+    @location(6) color: vec4<f32>,
+    @location(7) layer: u32,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+    @location(1) color: vec4<f32>,
+    // Integers can't be interpolated, every corner has the same one anyway
+    @location(2) @interpolate(flat) layer: u32,
+};
+
+@vertex
+fn vs_main(@location(0) corner: vec2<f32>, instance: InstanceInput) -> VertexOutput {
+    let model_matrix = mat4x4<f32>(
+        instance.model_matrix_0,
+        instance.model_matrix_1,
+        instance.model_matrix_2,
+        instance.model_matrix_3,
+    );
+    var out: VertexOutput;
+    out.clip_position = camera.view_proj * model_matrix * vec4<f32>(corner, 0.0, 1.0);
+    // The quad goes up from the bottom, texture coordinates go down from the top
+    out.tex_coords = instance.uv_rect.xy + vec2<f32>(corner.x, 1.0 - corner.y) * instance.uv_rect.zw;
+    out.color = instance.color;
+    out.layer = instance.layer;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return textureSample(t_sprite, s_sprite, in.tex_coords, i32(in.layer)) * in.color;
+}
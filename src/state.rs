@@ -1,217 +1,4809 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    error::Error,
+    fmt, fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
 use wgpu::{
-    Backends, BlendState, Color, ColorTargetState, ColorWrites, CommandEncoderDescriptor,
-    CompositeAlphaMode, Device, DeviceDescriptor, Face, Features, FragmentState, FrontFace,
-    Instance, Limits, LoadOp, MultisampleState, Operations, PipelineLayoutDescriptor, PolygonMode,
-    PowerPreference, PresentMode, PrimitiveState, PrimitiveTopology, Queue,
-    RenderPassColorAttachment, RenderPassDescriptor, RenderPipeline, RenderPipelineDescriptor,
-    RequestAdapterOptions, ShaderModuleDescriptor, ShaderSource, Surface, SurfaceConfiguration,
-    SurfaceError, TextureUsages, TextureViewDescriptor, VertexState,
+    util::{BufferInitDescriptor, DeviceExt, StagingBelt},
+    Adapter, AdapterInfo, Backends, BindGroup, BindGroupDescriptor, BindGroupEntry,
+    BindGroupLayout, BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingResource, BindingType,
+    Buffer, BufferBindingType, BufferDescriptor, BufferSize, BufferUsages, Color, ColorTargetState,
+    ColorWrites, CommandEncoder, CommandEncoderDescriptor, CompareFunction, CompositeAlphaMode,
+    DepthBiasState, DepthStencilState, Device, DeviceDescriptor, DownlevelFlags, ErrorFilter, Face,
+    Features, FragmentState, FrontFace, IndexFormat, Limits, LoadOp, Maintain, MultisampleState,
+    Operations, PipelineLayout, PipelineLayoutDescriptor, PolygonMode, PowerPreference,
+    PresentMode, PrimitiveState, PrimitiveTopology, PushConstantRange, Queue, RenderPass,
+    RenderPassColorAttachment, RenderPassDepthStencilAttachment, RenderPassDescriptor,
+    RenderPipeline, RenderPipelineDescriptor, RequestAdapterOptions, RequestDeviceError,
+    SamplerBindingType, ShaderModule, ShaderModuleDescriptor, ShaderSource, ShaderStages,
+    StencilState, SubmissionIndex, Surface, SurfaceConfiguration, SurfaceError, TextureFormat,
+    TextureFormatFeatureFlags, TextureSampleType, TextureUsages, TextureView,
+    TextureViewDescriptor, TextureViewDimension, VertexState,
+};
+use winit::{
+    dpi::{LogicalSize, PhysicalSize},
+    event::{VirtualKeyCode, WindowEvent},
+    window::{Window, WindowId},
+};
+
+use glam::{Mat4, Vec3};
+use image::{DynamicImage, ImageResult};
+
+use crate::anti_aliasing::{AntiAliasing, Fxaa};
+use crate::assets::{AssetHandle, AssetState, Decoded, PendingAsset};
+use crate::blend_mode::BlendMode;
+use crate::bloom::Bloom;
+use crate::camera::{Camera, CameraController, CameraUniform, Projection};
+use crate::color;
+use crate::compressed_texture::CompressedTextureError;
+use crate::compute::Compute;
+use crate::debug_lines::DebugLines;
+use crate::debug_view::DebugView;
+use crate::deferred::{DeferredLighting, PointLight};
+use crate::frustum::{BoundingSphere, CullingStats, Frustum};
+use crate::gbuffer::{self, GBuffer, GBufferAttachment};
+use crate::globals::Globals;
+use crate::gpu_timer::GpuTimer;
+use crate::indirect::{DrawIndexedIndirectArgs, DrawIndirect};
+use crate::input::InputState;
+use crate::input_recording::{InputRecorder, RecordedEvent};
+use crate::instance::Instance;
+use crate::letterbox::{LetterboxFill, Viewport};
+use crate::light::Light;
+use crate::limits_profile::LimitsProfile;
+use crate::material::{Material, MaterialDescriptor, MaterialError, MaterialId, MaterialPipelines};
+use crate::mesh::{self, Mesh};
+use crate::object_transforms::ObjectTransforms;
+use crate::particles::{self, Particles};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::picking::{self, PickTextures};
+use crate::post_process::{PostEffect, PostProcess, Settings as PostSettings, Tonemap};
+use crate::render_graph::{self, RenderContext, ScenePass};
+use crate::render_target::{
+    closest_alpha_mode, closest_present_mode, cull_mode_index, PipelineSet, Pipelines,
+    RenderTarget, SurfaceInfo, CULL_MODES,
+};
+use crate::shader_watcher::ShaderWatcher;
+use crate::shadow::{self, ShadowMap};
+use crate::skybox::Skybox;
+use crate::sprite::{Rect, SpriteBatch, SpriteTexture};
+use crate::stencil::{self, MaskRect};
+use crate::text::TextBrush;
+use crate::texture::{self, Texture, TextureOptions, DEPTH_FORMAT};
+use crate::timer::FrameTimer;
+use crate::ui::{self, Ui, UiCallback};
+use crate::vertex::{
+    ColorInterpolation, ColorInterpolationError, Vertex, QUAD_INDICES, QUAD_VERTICES,
+};
+
+mod headless;
+
+/// How many samples per pixel we ask for when multisampling, 4 is guaranteed to be supported by most formats
+const DEFAULT_SAMPLE_COUNT: u32 = 4;
+/// How big each of the staging belt's buffers is, enough for a few targets' worth of uniforms so a frame only needs one
+const STAGING_BELT_CHUNK_SIZE: u64 = 1024;
+/// The longest step `update()` will take in one go
+pub const MAX_UPDATE_DT: Duration = Duration::from_millis(100);
+
+/// Features we turn on whenever the adapter supports them, everything using them has a fallback
+const OPTIONAL_FEATURES: Features = Features::PUSH_CONSTANTS
+    .union(Features::POLYGON_MODE_LINE)
+    .union(Features::TIMESTAMP_QUERY)
+    .union(Features::INDIRECT_FIRST_INSTANCE)
+    // Without them compressed textures get decoded on the CPU, see `load_compressed_texture()`
+    .union(Features::TEXTURE_COMPRESSION_BC)
+    .union(Features::TEXTURE_COMPRESSION_ASTC_LDR);
+/// Enough for the 4x4 transform matrix
+const PUSH_CONSTANT_SIZE: u32 = std::mem::size_of::<Mat4>() as u32;
+
+/// Prepended to the shader to declare `transform` when we have push constants
+const PUSH_CONSTANT_PRELUDE: &str = "struct Transform {
+    value: mat4x4<f32>,
 };
-use winit::{dpi::PhysicalSize, event::WindowEvent, window::Window};
+var<push_constant> transform: Transform;
+";
+/// Prepended to the shader to declare `transform` when we have to use a uniform buffer instead
+const UNIFORM_PRELUDE: &str = "struct Transform {
+    value: mat4x4<f32>,
+};
+@group(3) @binding(0)
+var<uniform> transform: Transform;
+";
+
+/// How `transform` gets to the shader
+enum TransformBinding {
+    /// Set straight from the render pass, needs `Features::PUSH_CONSTANTS`
+    PushConstants,
+    /// The fallback, a uniform buffer bound to `@group(3)` with a transform per object, picked with a dynamic offset
+    ///
+    /// The built-in geometry uses the first one, and each of the meshes the one after
+    Uniform(ObjectTransforms),
+}
+
+impl TransformBinding {
+    /// The WGSL declaring `transform`, which has to go before the rest of the shader
+    fn prelude(&self) -> &'static str {
+        match self {
+            TransformBinding::PushConstants => PUSH_CONSTANT_PRELUDE,
+            TransformBinding::Uniform(_) => UNIFORM_PRELUDE,
+        }
+    }
+}
+
+/// Where `diffuse_texture` came from, so `recreate_device()` can upload it again
+#[derive(Clone)]
+enum DiffuseSource {
+    /// The built-in checkerboard
+    Checker,
+    /// Decoded from a file, kept around since the file might not be there (or be the same) by the time it's needed
+    Image {
+        image: DynamicImage,
+        label: Option<String>,
+    },
+    /// From `load_compressed_texture()`, read from the file again
+    Compressed(PathBuf),
+}
+
+impl DiffuseSource {
+    /// Upload the texture onto `device`, with `options` from `State::texture_options`
+    fn create_texture(
+        &self,
+        device: &Device,
+        queue: &Queue,
+        options: TextureOptions,
+    ) -> Result<Texture, String> {
+        match self {
+            DiffuseSource::Checker => Ok(create_checker_texture(device, queue, options)),
+            DiffuseSource::Image { image, label } => {
+                Texture::from_image(device, queue, image, label.as_deref(), options)
+                    .map_err(|err| err.to_string())
+            }
+            DiffuseSource::Compressed(path) => {
+                Texture::load_compressed(device, queue, path, options)
+                    .map_err(|err| err.to_string())
+            }
+        }
+    }
+}
+
+/// Everything that can go wrong while setting up a `State`
+#[derive(Debug)]
+pub enum StateInitError {
+    /// No adapter could be found that is compatible with the surface
+    NoAdapter,
+    /// The adapter refused to give us a `Device`
+    DeviceRequest(RequestDeviceError),
+    /// The surface doesn't support any texture formats with this adapter
+    NoSupportedFormat,
+    /// The shader or render pipeline failed validation
+    Pipeline(wgpu::Error),
+    /// A window's surface can't be drawn to with the adapter we already picked
+    UnsupportedSurface,
+    /// `StateBuilder::adapter_index()` was past the end of `State::list_adapters()`
+    NoAdapterAtIndex(usize),
+}
+
+impl fmt::Display for StateInitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StateInitError::NoAdapter => write!(f, "no compatible graphics adapter found"),
+            StateInitError::DeviceRequest(err) => write!(f, "failed to request device: {err}"),
+            StateInitError::NoSupportedFormat => {
+                write!(f, "the surface has no supported texture formats")
+            }
+            StateInitError::UnsupportedSurface => {
+                write!(f, "the window's surface isn't supported by the adapter")
+            }
+            StateInitError::NoAdapterAtIndex(index) => {
+                write!(
+                    f,
+                    "there's no adapter {index}, see `State::list_adapters()`"
+                )
+            }
+            StateInitError::Pipeline(err) => {
+                write!(
+                    f,
+                    "failed to build the render pipeline: {}",
+                    error_description(err)
+                )
+            }
+        }
+    }
+}
+
+impl Error for StateInitError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            StateInitError::DeviceRequest(err) => Some(err),
+            StateInitError::Pipeline(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<RequestDeviceError> for StateInitError {
+    fn from(err: RequestDeviceError) -> Self {
+        StateInitError::DeviceRequest(err)
+    }
+}
 
 pub struct State {
-    pub surface: Surface,
+    /// Kept around so we can create surfaces for more windows (or a new device) later
+    pub instance: Arc<wgpu::Instance>,
+    pub adapter: Adapter,
     pub device: Device,
     pub queue: Queue,
-    pub config: SurfaceConfiguration,
-    pub size: PhysicalSize<u32>,
-    pub render_pipeline: RenderPipeline,
+    /// Everything we draw into, the first one is the main window (or the offscreen texture when headless)
+    pub targets: Vec<RenderTarget>,
+    pub render_pipeline_layout: PipelineLayout,
+    /// The WGSL every target's pipeline is built from, so targets added later match the rest
+    shader_source: String,
+    pub vertex_buffer: Buffer,
+    /// How many vertices are in `vertex_buffer`
+    pub num_vertices: u32,
+    /// Around every vertex in `vertex_buffer`, for frustum culling, so it needs updating if the vertices change
+    pub geometry_bounds: Option<BoundingSphere>,
+    /// `None` if there are no indices, in which case we just draw the vertices in order
+    pub index_buffer: Option<Buffer>,
+    /// How many indices are in `index_buffer`
+    pub num_indices: u32,
+    /// Where the draw of `index_buffer` gets its arguments from instead, see `enable_draw_indirect()`
+    draw_indirect: Option<DrawIndirect>,
+    /// The index and instance counts `draw_indirect` was last reset for, see `prepare_draw_indirect()`
+    draw_indirect_counts: (u32, u32),
+    /// Models drawn along with the built-in quad, e.g. from `mesh::load_obj()`
+    pub meshes: Vec<Mesh>,
+    /// What meshes can be drawn with in place of the scene's pipeline, see `add_material()`
+    materials: Vec<Material>,
+    /// Every copy of the mesh we draw, use `set_instances()` to change these
+    pub instances: Vec<Instance>,
+    /// The model matrices of `instances`, only the ones the camera can see when frustum culling
+    pub instance_buffer: Buffer,
+    /// Whether `update()` leaves out the instances the camera can't see, see `set_frustum_culling()`
+    frustum_culling: bool,
+    /// How many of `instances` are in `instance_buffer`, and how many were left out
+    culling_stats: CullingStats,
+    /// Which of `instances` is in each slot of `instance_buffer`, so `pick()` can tell which one it hit
+    #[cfg_attr(target_arch = "wasm32", allow(dead_code))]
+    drawn_instances: Vec<u32>,
+    /// The colour the screen gets cleared to at the start of every frame
+    pub clear_color: Color,
+    /// CPU-side copy of what's in `globals_buffer`
+    pub globals: Globals,
+    pub globals_buffer: Buffer,
+    /// The one light the scene is lit by, uploaded to `light_buffer` every `update()`
+    pub light: Light,
+    pub light_buffer: Buffer,
+    pub globals_bind_group_layout: BindGroupLayout,
+    pub globals_bind_group: BindGroup,
+    /// What the light can't reach, see `enable_shadows()`
+    shadow_map: ShadowMap,
+    /// The same as `globals_bind_group`, but with a placeholder in place of the shadow map while it's drawn into
+    shadow_pass_globals_bind_group: BindGroup,
+    /// Whether the shadow map gets drawn every frame
+    shadows: bool,
+    /// How far what the light sees is pushed back, see `set_shadow_bias()`
+    shadow_bias: DepthBiasState,
+    /// Draws the geometry into the shadow map, built from the same shader as the targets' pipelines once shadows are on
+    shadow_pipeline: Option<RenderPipeline>,
+    /// Draws instance IDs for `pick()`, built the first time it's needed and again if the cull mode it was built for changes
+    #[cfg(not(target_arch = "wasm32"))]
+    pick_pipeline: Option<(Option<Face>, CompareFunction, RenderPipeline)>,
+    /// What `pick()` draws into, kept for the next pick while the main window stays the same size
+    #[cfg(not(target_arch = "wasm32"))]
+    pick_textures: Option<PickTextures>,
+    pub camera: Camera,
+    /// `camera` as of the end of the last two `update()`s, for interpolating between them in `render()`
+    previous_camera: Camera,
+    stepped_camera: Camera,
+    /// CPU-side copy of what's in `camera_buffer`
+    pub camera_uniform: CameraUniform,
+    pub camera_buffer: Buffer,
+    pub camera_bind_group_layout: BindGroupLayout,
+    pub camera_bind_group: BindGroup,
+    pub camera_controller: CameraController,
+    /// Which keys and mouse buttons are held down, for polling in `update()`
+    pub input_state: InputState,
+    /// Applied to everything before the camera, small and cheap to change every frame
+    transform: Mat4,
+    transform_binding: TransformBinding,
+    /// Whether we're drawing with the targets' wireframe pipelines
+    wireframe: bool,
+    /// Which fragment shader the geometry is drawn with, see `set_debug_view()`
+    debug_view: DebugView,
+    /// Which of the targets' sets of pipelines we draw with, see `set_cull_mode()`
+    cull_mode: Option<Face>,
+    /// Which of the targets' pipelines we draw with
+    blend_mode: BlendMode,
+    /// Whether to fill in the depth buffer before drawing any colour, see `enable_depth_prepass()`
+    depth_prepass: bool,
+    /// How a fragment's depth is compared against what's already there, see `set_depth_compare()`
+    depth_compare: CompareFunction,
+    /// How the vertex colours are blended across triangles, applied to every shader the scene is drawn with
+    color_interpolation: ColorInterpolation,
+    /// The stencil test everything in the scene is drawn with, see `set_stencil()`
+    stencil: StencilState,
+    /// What the stencil is compared against, and what the mask writes
+    stencil_reference: u32,
+    /// Written into the stencil before the scene is drawn, `None` leaves the stencil at 0
+    stencil_mask: Option<MaskRect>,
+    stencil_mask_pipeline: RenderPipeline,
+    /// Whether to draw the scene at `letterbox_aspect`, with bars around it, instead of stretching it over the whole window
+    letterbox: bool,
+    letterbox_aspect: f32,
+    /// The colour of the bars
+    letterbox_color: Color,
+    letterbox_fill: LetterboxFill,
+    /// Which textures the G-buffer pass draws into, `None` when there isn't one, see `set_gbuffer()`
+    gbuffer: Option<GBuffer>,
+    /// Draws the geometry into each target's G-buffer, built from the same shader as the targets' pipelines
+    ///
+    /// One for each of `CULL_MODES`, with the same depth compare and stencil as the targets' pipelines
+    gbuffer_pipelines: Option<[RenderPipeline; CULL_MODES.len()]>,
+    /// Lights the G-buffer instead of the scene's geometry being drawn directly, see `enable_deferred_lighting()`
+    deferred: Option<DeferredLighting>,
+    /// Only used by the deferred lighting, uploaded every frame
+    pub point_lights: Vec<PointLight>,
+    /// Drawn behind everything, see `load_skybox()`
+    skybox: Option<Skybox>,
+    /// The quads from `draw_quad()`, drawn on top of the scene's geometry
+    sprite_batch: SpriteBatch,
+    /// The lines from `draw_line()`, drawn in their own pass after the scene
+    debug_lines: DebugLines,
+    /// Whether the lines are hidden behind the scene, see `set_debug_lines_depth_test()`
+    debug_lines_depth_test: bool,
+    /// How long the X, Y and Z lines drawn at the origin are, if they're shown, see `set_axes_gizmo()`
+    axes_gizmo: Option<f32>,
+    /// Applies `post_effect` on the way from the scene texture to the target
+    post_process: PostProcess,
+    post_effect: PostEffect,
+    /// Builds each target's bloom when `bloom_intensity` is above 0
+    bloom: Bloom,
+    bloom_intensity: f32,
+    /// How the scene is squeezed into the target's range after bloom, see `set_tonemap()`
+    tonemap: Tonemap,
+    /// What the scene gets multiplied by before tonemapping, see `set_exposure()`
+    exposure: f32,
+    /// Smooths each target's edges after post-processing when `fxaa_enabled`, see `set_anti_aliasing()`
+    fxaa: Fxaa,
+    fxaa_enabled: bool,
+    /// What the scene gets drawn in when post-processing, see `set_hdr_format()`
+    hdr_format: TextureFormat,
+    /// Whether the adapter can draw the scene in `hdr_format`, otherwise post-processing uses the target's format and clips at 1
+    hdr_supported: bool,
+    /// Whether to pick sRGB formats for the surfaces of new windows, unless `StateBuilder::force_linear()` was used
+    prefer_srgb: bool,
+    /// Which kind of adapter to try first when the device has to be recreated, see `StateBuilder::power_preference()`
+    power_preference: PowerPreference,
+    /// The adapter to use when the device has to be recreated instead, see `StateBuilder::adapter_index()`
+    adapter_index: Option<usize>,
+    /// The exact limits to ask the new device for when it has to be recreated, see `StateBuilder::limits()`
+    limits: Option<Limits>,
+    /// Otherwise the limits get worked out from this again, for whichever adapter is picked then
+    limits_profile: LimitsProfile,
+    /// The texture that gets drawn onto our geometry
+    ///
+    /// Replacing it directly works, but `recreate_device()` will go back to whatever was loaded through `State` last
+    pub diffuse_texture: Texture,
+    pub texture_bind_group_layout: BindGroupLayout,
+    pub diffuse_bind_group: BindGroup,
+    /// Where `diffuse_texture` came from, see `set_diffuse_texture()`
+    diffuse_source: DiffuseSource,
+    /// What every texture loaded through `State` is set up with, including materials' and the built-in one
+    texture_options: TextureOptions,
+    /// The last `load_texture_async()`, any older one that finishes after it's been asked for is dropped
+    latest_texture: Option<AssetHandle>,
+    /// Assets being read and decoded off the main thread, uploaded by `update()` when they're done
+    pending_assets: Vec<PendingAsset>,
+    /// How every asset from a `load_*_async()` is getting on, see `asset_state()`
+    asset_states: HashMap<AssetHandle, AssetState>,
+    /// The handle the next `load_*_async()` gives out
+    next_asset_handle: u64,
+    frame_timer: FrameTimer,
+    /// `None` if the device doesn't support `Features::TIMESTAMP_QUERY`
+    gpu_timer: Option<GpuTimer>,
+    adapter_info: AdapterInfo,
+    /// How many samples per pixel we render with, 1 means no multisampling
+    ///
+    /// Changed with `set_sample_count()`, which rebuilds everything that depends on it
+    pub sample_count: u32,
+    /// `None` if the device can't run compute shaders, like with WebGL
+    pub compute: Option<Compute>,
+    /// Simulated by a compute shader in `update()` and drawn after the sprites, `None` if compute shaders aren't supported
+    ///
+    /// Empty until `spawn_particles()`, its fields can be changed to move the emitter and so on
+    pub particles: Option<Particles>,
+    /// Draws the debug overlay, only into the main target since it's made for that format
+    ///
+    /// Only created once the overlay is turned on
+    text_brush: Option<TextBrush>,
+    /// Whether to show FPS, frame time and the backend in the top-left corner
+    debug_overlay: bool,
+    /// The egui debug UI, only `None` while it's taken out in `update()` and `render()`
+    egui_state: Option<Ui>,
+    /// Uploads the camera, light and globals as part of each frame's commands, only `None` while it's taken out in `render()`
+    staging_belt: Option<StagingBelt>,
+    /// Whether the egui UI is shown (and gets input)
+    ui_visible: bool,
+    /// Extra widgets from the caller, see `set_ui_callback()`
+    ui_callback: Option<UiCallback>,
+    /// What the command encoders and the scene's main pass are called in GPU debuggers, see `set_encoder_label()`
+    encoder_label: String,
+    render_pass_label: String,
+    /// What gets recorded for each target every frame, in order, see `render_passes_mut()`
+    render_passes: Vec<Box<dyn render_graph::RenderPass>>,
+    /// The key that makes `run()` quit, `None` to leave every key to the app
+    exit_key: Option<VirtualKeyCode>,
+    /// Set by `request_exit()`, for `run()` to pick up
+    exit_requested: bool,
+    /// Set by `set_size()`, for `run()` to apply to the window
+    requested_size: Option<LogicalSize<u32>>,
+    /// Whether `run()` should slow down to a few frames a second while none of our windows have focus
+    pause_when_unfocused: bool,
+    /// Where the input goes while it's being recorded, see `start_recording_input()`
+    input_recorder: Option<InputRecorder>,
+    /// How often `run()` calls `update()` when simulating at a fixed rate, see `set_fixed_timestep()`
+    fixed_timestep: Option<Duration>,
+    /// How many frames the CPU can get ahead of the GPU, `None` leaves it to the driver, see `set_max_frame_latency()`
+    max_frame_latency: Option<u32>,
+    /// The last submission of every frame the GPU might not have finished yet, oldest first
+    frames_in_flight: VecDeque<SubmissionIndex>,
+    /// Whether one of our windows has focus, going by the last `Focused` event
+    focused: bool,
+    /// The workgroups to dispatch at the start of the next frame, set by `dispatch_compute()`
+    pending_dispatch: Option<[u32; 3]>,
+    /// Set by `watch_shader()` to reload the shader whenever it changes on disk
+    shader_watcher: Option<ShaderWatcher>,
 }
 
-impl State {
+/// Configures how a `State` gets set up, the defaults are the same as `State::new()` with `Backends::all()`
+#[derive(Debug, Clone)]
+pub struct StateBuilder {
+    backends: Backends,
+    power_preference: PowerPreference,
+    features: Features,
+    /// Overrides `limits_profile` when it's set
+    limits: Option<Limits>,
+    limits_profile: LimitsProfile,
+    present_mode: PresentMode,
+    alpha_mode: CompositeAlphaMode,
+    title: Option<String>,
+    force_linear: bool,
+    adapter_index: Option<usize>,
+}
+
+impl Default for StateBuilder {
+    fn default() -> Self {
+        Self {
+            backends: Backends::all(),
+            // Falls back to `LowPower` if there's no discrete GPU (or it can't draw to the window)
+            power_preference: PowerPreference::HighPerformance,
+            features: Features::empty(),
+            limits: None,
+            // Works pretty much everywhere
+            limits_profile: LimitsProfile::Downlevel,
+            // How to sync the surface with the display, `PresentMode::Fifo` will cap the display rate at the display's framerate, essentially VSync, which is guaranteed to be supported on all platforms
+            present_mode: PresentMode::Fifo,
+            // How the alpha channel of the textures should be handled during compositing (combining textures), `CompositeAlphaMode::Auto` picks depending on what the surface can support
+            alpha_mode: CompositeAlphaMode::Auto,
+            title: None,
+            // sRGB gets the gamma right without any extra work in the shader
+            force_linear: false,
+            adapter_index: None,
+        }
+    }
+}
+
+impl StateBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Restrict which graphics APIs wgpu is allowed to use, e.g. `Backends::VULKAN` to force Vulkan
+    pub fn backends(mut self, backends: Backends) -> Self {
+        self.backends = backends;
+        self
+    }
+
+    /// Whether to try an integrated (`LowPower`) or discrete (`HighPerformance`) GPU first, `HighPerformance` by default
+    ///
+    /// If there isn't one we try the other kind, then a software adapter, before giving up
+    pub fn power_preference(mut self, power_preference: PowerPreference) -> Self {
+        self.power_preference = power_preference;
+        self
+    }
+
+    /// Extra features to request from the device, the adapter has to support all of them
+    pub fn features(mut self, features: Features) -> Self {
+        self.features = features;
+        self
+    }
+
+    /// The exact limits to request from the device, the adapter has to support them
+    ///
+    /// Takes priority over `limits_profile()`
+    pub fn limits(mut self, limits: Limits) -> Self {
+        self.limits = Some(limits);
+        self
+    }
+
+    /// Ask for one of a few sets of limits instead of exact ones, `LimitsProfile::Downlevel` by default
+    ///
+    /// `LimitsProfile::Adapter` allows for much bigger textures and buffers on desktop GPUs
+    pub fn limits_profile(mut self, profile: LimitsProfile) -> Self {
+        self.limits_profile = profile;
+        self
+    }
+
+    /// Falls back to `PresentMode::Fifo` if the surface doesn't support it
+    pub fn present_mode(mut self, present_mode: PresentMode) -> Self {
+        self.present_mode = present_mode;
+        self
+    }
+
+    /// Falls back to `CompositeAlphaMode::Auto` if the surface doesn't support it
+    pub fn alpha_mode(mut self, alpha_mode: CompositeAlphaMode) -> Self {
+        self.alpha_mode = alpha_mode;
+        self
+    }
+
+    /// Set the window's title once the `State` is built
+    pub fn title(mut self, title: impl Into<String>) -> Self {
+        self.title = Some(title.into());
+        self
+    }
+
+    /// Use a linear (non-sRGB) surface format even if an sRGB one is available
+    ///
+    /// The shader then has to do the gamma correction itself, see `State::is_srgb()`
+    pub fn force_linear(mut self, force_linear: bool) -> Self {
+        self.force_linear = force_linear;
+        self
+    }
+
+    /// Use the adapter at `index` in `State::list_adapters()` (with the same backends), instead of picking one by `power_preference()`
+    ///
+    /// For when the wrong GPU gets picked, building fails if there's no such adapter or it can't draw to the window
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn adapter_index(mut self, index: usize) -> Self {
+        self.adapter_index = Some(index);
+        self
+    }
+
     /// Too much stuff in here
-    pub async fn new(window: &Window) -> Self {
+    pub async fn build(self, window: &Window) -> Result<State, StateInitError> {
+        if let Some(title) = &self.title {
+            window.set_title(title);
+        }
         let size = window.inner_size();
 
         // The `instance` is the handle to our GPU, used to create `Adapter`s and `Surface`s
-        let instance = Instance::new(Backends::all());
+        let instance = Arc::new(wgpu::Instance::new(self.backends));
         let surface = unsafe { instance.create_surface(window) };
         // The `adapter` is a handle to our actual graphics card, and can be used to fetch info about it, and we can use this to create the `Device` and `Queue`
-        let adapter = instance
-            .request_adapter(&RequestAdapterOptions {
-                power_preference: PowerPreference::default(),
-                compatible_surface: Some(&surface),
-                force_fallback_adapter: false,
-            })
-            .await
-            .unwrap();
-        dbg!(adapter.get_info());
-        let (device, queue) = adapter
-            .request_device(
-                &DeviceDescriptor {
-                    features: Features::empty(),
-                    limits: Limits::downlevel_defaults(),
-                    label: None,
-                },
-                None,
-            )
-            .await
-            .unwrap();
+        let adapter = match self.adapter_index {
+            #[cfg(not(target_arch = "wasm32"))]
+            Some(index) => adapter_by_index(&instance, index, Some(&surface))?,
+            _ => request_adapter(&instance, self.power_preference, Some(&surface))
+                .await
+                .ok_or(StateInitError::NoAdapter)?,
+        };
+        let limits = match &self.limits {
+            Some(limits) => limits.clone(),
+            None => self.limits_profile.limits(&adapter),
+        };
+        let (device, queue) = request_device(&adapter, self.features, limits).await?;
 
+        // Anything the surface doesn't support gets swapped for something it does, since `surface.configure()` would panic
+        let surface_info = SurfaceInfo::new(&surface, &adapter);
+        // Different displays support different formats, so we pick from what the surface supports with this adapter
+        let format = pick_surface_format(&surface_info.formats, !self.force_linear)
+            .ok_or(StateInitError::NoSupportedFormat)?;
+        if format.describe().srgb == self.force_linear {
+            log::warn!(
+                "No {} format is supported by this surface, using {format:?}",
+                if self.force_linear { "linear" } else { "sRGB" }
+            );
+        }
+        let present_mode = closest_present_mode(&surface_info.present_modes, self.present_mode);
+        if present_mode != self.present_mode {
+            log::warn!(
+                "Present mode {:?} is not supported by this surface, using {present_mode:?}",
+                self.present_mode
+            );
+        }
+        let alpha_mode = closest_alpha_mode(&surface_info.alpha_modes, self.alpha_mode);
+        if alpha_mode != self.alpha_mode {
+            log::warn!(
+                "Alpha mode {:?} is not supported by this surface, using {alpha_mode:?}",
+                self.alpha_mode
+            );
+        }
         let config = SurfaceConfiguration {
             // How `SurfaceTexture`s will be used, `RENDER_ATTACHMENT` means the textures will be used to write to the screen
             usage: TextureUsages::RENDER_ATTACHMENT,
-            // How `SurfaceTexture`s will be stored on the GPU, different displays prefer different formats, so we use `surface.get_preferred_format()` to figure out the best format based on the display being used
-            format: surface.get_supported_formats(&adapter)[0],
+            // How `SurfaceTexture`s will be stored on the GPU
+            format,
             // The size in pixels of a `SurfaceTexture` (should usually be the size of the window)
             width: size.width,
             height: size.height,
-            // How to sync the surface with the display, `PresentMode::Fifo` will cap the display rate at the display's framerate, essentially VSync, which is guaranteed to be supported on all platforms
-            present_mode: PresentMode::Fifo,
-            // How the alpha channel of the textures should be handled during compositing (combining textures), `CompositeAlphaMode::Auto` picks depending on what the surface can support
-            alpha_mode: CompositeAlphaMode::Auto,
+            present_mode,
+            alpha_mode,
         };
-        surface.configure(&device, &config);
 
-        let shader = device.create_shader_module(ShaderModuleDescriptor {
-            label: Some("Shader"),
-            source: ShaderSource::Wgsl(include_str!("shader.wgsl").into()),
+        let mut state = State::with_device(
+            instance,
+            adapter,
+            device,
+            queue,
+            Some(window.id()),
+            Some(surface),
+            config,
+        )?;
+        state.prefer_srgb = !self.force_linear;
+        state.power_preference = self.power_preference;
+        state.adapter_index = self.adapter_index;
+        state.limits = self.limits;
+        state.limits_profile = self.limits_profile;
+        // Kept up to date by `ScaleFactorChanged` after this
+        state.set_scale_factor(window.scale_factor());
+        Ok(state)
+    }
+}
+
+impl State {
+    /// Set up a `State` with the default settings, use `State::builder()` for more control
+    ///
+    /// `backends` restricts which graphics APIs wgpu is allowed to use, e.g. `Backends::VULKAN` to force Vulkan
+    pub async fn new(window: &Window, backends: Backends) -> Result<Self, StateInitError> {
+        StateBuilder::new().backends(backends).build(window).await
+    }
+
+    /// The same as `State::new()`, but using the adapter at `adapter_index` in `State::list_adapters()` rather than letting wgpu pick
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn new_with_adapter(
+        window: &Window,
+        backends: Backends,
+        adapter_index: usize,
+    ) -> Result<Self, StateInitError> {
+        StateBuilder::new()
+            .backends(backends)
+            .adapter_index(adapter_index)
+            .build(window)
+            .await
+    }
+
+    /// Every adapter wgpu can find with `backends`, e.g. for finding the index to pass to `new_with_adapter()`
+    ///
+    /// Includes adapters that can't draw to any particular window, and isn't available in the browser
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn list_adapters(backends: Backends) -> Vec<AdapterInfo> {
+        wgpu::Instance::new(backends)
+            .enumerate_adapters(backends)
+            .map(|adapter| adapter.get_info())
+            .collect()
+    }
+
+    pub fn builder() -> StateBuilder {
+        StateBuilder::new()
+    }
+
+    /// Everything that doesn't care whether we're drawing to a window or not
+    ///
+    /// The first target gets made from `window_id`, `surface` and `config`
+    fn with_device(
+        instance: Arc<wgpu::Instance>,
+        adapter: Adapter,
+        device: Device,
+        queue: Queue,
+        window_id: Option<WindowId>,
+        surface: Option<Surface>,
+        config: SurfaceConfiguration,
+    ) -> Result<Self, StateInitError> {
+        let adapter_info = adapter.get_info();
+        log::info!(
+            "Using {} ({:?}, driver: {} {})",
+            adapter_info.name,
+            adapter_info.backend,
+            adapter_info.driver,
+            adapter_info.driver_info
+        );
+        let limits = device.limits();
+        log::info!(
+            "Limits: {} pixel textures, {} byte buffers, {} storage buffers per shader stage",
+            limits.max_texture_dimension_2d,
+            limits.max_buffer_size,
+            limits.max_storage_buffers_per_shader_stage
+        );
+        log::debug!("All limits: {limits:?}");
+        let surface_info = surface
+            .as_ref()
+            .map(|surface| SurfaceInfo::new(surface, &adapter))
+            .unwrap_or_default();
+
+        let sample_count = pick_sample_count(&adapter, config.format, DEFAULT_SAMPLE_COUNT);
+        let hdr_supported = supports_hdr(&adapter, texture::HDR_FORMAT, sample_count);
+        if !hdr_supported {
+            log::warn!(
+                "{:?} isn't supported for drawing the scene into, post-processing won't have HDR",
+                texture::HDR_FORMAT
+            );
+        }
+
+        let globals = Globals::default();
+        let globals_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Globals Buffer"),
+            contents: bytemuck::bytes_of(&globals),
+            // `COPY_DST` so we can update it with `queue.write_buffer()`
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
         });
+        let light = Light::default();
+        let light_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Light Buffer"),
+            contents: bytemuck::bytes_of(&light),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+
+        let camera = Camera::new(config.width as f32 / config.height as f32);
+        let camera_uniform = CameraUniform::new(&camera);
+        let camera_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Camera Buffer"),
+            contents: bytemuck::bytes_of(&camera_uniform),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+        // The fragment shader needs the camera's position for specular lighting
+        let (camera_bind_group_layout, camera_bind_group) = create_uniform_bind_group(
+            &device,
+            "Camera",
+            &[&camera_buffer],
+            ShaderStages::VERTEX | ShaderStages::FRAGMENT,
+        );
+
+        // The light and its shadow map share a bind group with the globals, since we can only count on having 4 bind groups
+        let shadow_map = ShadowMap::new(&device, &camera_bind_group_layout);
+        let globals_bind_group_layout = create_globals_bind_group_layout(&device);
+        let globals_bind_group = create_globals_bind_group(
+            &device,
+            &globals_bind_group_layout,
+            [&globals_buffer, &light_buffer, shadow_map.uniform_buffer()],
+            shadow_map.texture(),
+        );
+        let shadow_pass_globals_bind_group = create_globals_bind_group(
+            &device,
+            &globals_bind_group_layout,
+            [&globals_buffer, &light_buffer, shadow_map.uniform_buffer()],
+            shadow_map.placeholder(),
+        );
+
+        // Mipmaps so the checks don't turn to noise in the distance, and anisotropy so they don't blur into grey when looked at side on
+        let texture_options =
+            TextureOptions::default().anisotropy(texture::MAX_ANISOTROPY, &adapter);
+        let diffuse_texture = create_checker_texture(&device, &queue, texture_options);
+        let texture_bind_group_layout = Texture::bind_group_layout(&device);
+        let diffuse_bind_group = diffuse_texture.bind_group(&device, &texture_bind_group_layout);
+        let sprite_batch = SpriteBatch::new(&device, &queue, &camera_bind_group_layout);
+        let debug_lines = DebugLines::new(&device, &camera_bind_group_layout);
+
+        let transform = Mat4::IDENTITY;
+        let (transform_binding, push_constant_ranges) = if device
+            .features()
+            .contains(Features::PUSH_CONSTANTS)
+        {
+            let range = PushConstantRange {
+                stages: ShaderStages::VERTEX,
+                range: 0..PUSH_CONSTANT_SIZE,
+            };
+            (TransformBinding::PushConstants, vec![range])
+        } else {
+            log::info!("Push constants aren't supported, using a uniform buffer for the transform");
+            (
+                TransformBinding::Uniform(ObjectTransforms::new(&device)),
+                vec![],
+            )
+        };
+        // The index of each layout corresponds to `@group(n)` in the shader
+        let mut bind_group_layouts = vec![
+            &globals_bind_group_layout,
+            &camera_bind_group_layout,
+            &texture_bind_group_layout,
+        ];
+        if let TransformBinding::Uniform(object_transforms) = &transform_binding {
+            bind_group_layouts.push(object_transforms.layout());
+        }
 
         let render_pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
             label: Some("Render Pipeline Layout"),
-            bind_group_layouts: &[],
-            push_constant_ranges: &[],
+            bind_group_layouts: &bind_group_layouts,
+            push_constant_ranges: &push_constant_ranges,
         });
-        let render_pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
-            label: Some("Render Pipeline"),
-            layout: Some(&render_pipeline_layout),
-            vertex: VertexState {
-                module: &shader,
-                // The function we marked with `@vertex`
-                entry_point: "vs_main",
-                // Tells `wgpu` what type of vertices we want to pass to the vertex shader
-                // We specify the vertices in the vertex shader itself so we'll leave it empty
-                buffers: &[],
-            },
-            // Technically optional
-            fragment: Some(FragmentState {
-                module: &shader,
-                // The function we marked with `@fragment`
-                entry_point: "fs_main",
-                // Tells `wgpu` what colour outputs it should set up
-                // We only need one for the `surface`
-                targets: &[Some(ColorTargetState {
-                    // We copy `surface`'s format so that copying to it is easy
-                    format: config.format,
-                    // Replace old pixel data with new data
-                    blend: Some(BlendState::REPLACE),
-                    // Write to all colours
-                    write_mask: ColorWrites::ALL,
-                })],
-            }),
-            primitive: PrimitiveState {
-                // Every 3 vertices will correspond to 1 trongle
-                topology: PrimitiveTopology::TriangleList,
-                strip_index_format: None,
-                // How to determine whether a triangle is facing forwards (if its counter-clockwise)
-                front_face: FrontFace::Ccw,
-                // Cull any triangles facing backwards
-                cull_mode: Some(Face::Back),
-                // Setting this to anything other than `PolygonMode::Fill` requires `Features::NON_FILL_POLYGON_MODE`
-                polygon_mode: PolygonMode::Fill,
-                // Requires `Features::DEPTH_CLIP_CONTROL`
-                unclipped_depth: false,
-                // Requires `Features::CONSERVATIVE_RASTERIZATION`
-                conservative: false,
-            },
-            // We're not using a depth/stencil buffer currently
-            depth_stencil: None,
-            multisample: MultisampleState {
-                // How many samples the pipeline will use
-                count: 1,
-                // Which samples should be active
-                mask: !0,
-                // To do with anti-aliasing
-                alpha_to_coverage_enabled: false,
-            },
-            // How many array layers the render attachments can have, we won't be rendering to array textures
-            multiview: None,
+        let shader_source = include_str!("shader.wgsl").to_owned();
+        let pipelines = build_pipelines(
+            &device,
+            &render_pipeline_layout,
+            transform_binding.prelude(),
+            &shader_source,
+            config.format,
+            sample_count,
+            &depth_stencil_state(CompareFunction::Less, &StencilState::default()),
+        )
+        .map_err(StateInitError::Pipeline)?;
+        // Gets the window's scale factor once there is one
+        let egui_state = Ui::new(&device, config.format, 1.0);
+        let target = RenderTarget::new(
+            &device,
+            window_id,
+            surface,
+            config,
+            surface_info,
+            pipelines,
+            sample_count,
+        );
+
+        let vertex_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Vertex Buffer"),
+            contents: bytemuck::cast_slice(QUAD_VERTICES),
+            usage: BufferUsages::VERTEX,
         });
+        let num_vertices = QUAD_VERTICES.len() as u32;
+        let positions: Vec<_> = QUAD_VERTICES
+            .iter()
+            .map(|vertex| Vec3::from(vertex.position))
+            .collect();
+        let geometry_bounds = BoundingSphere::from_points(&positions);
+        let index_buffer = create_index_buffer(&device, QUAD_INDICES);
+        let num_indices = QUAD_INDICES.len() as u32;
+        let instances = vec![Instance::default()];
+        let instance_buffer = create_instance_buffer(&device, &instances);
+        let gpu_timer = if device.features().contains(Features::TIMESTAMP_QUERY) {
+            Some(GpuTimer::new(&device, &queue))
+        } else {
+            log::info!("Timestamp queries aren't supported, the GPU frame time won't be measured");
+            None
+        };
+        let letterbox_fill = LetterboxFill::new(&device);
+        let post_process = PostProcess::new(&device);
+        let fxaa = Fxaa::new(&device);
+        let bloom = Bloom::new(&device);
+        let stencil_mask_pipeline = stencil::create_mask_pipeline(&device, sample_count);
+        // Something to play with, `set_compute_data()` can replace it
+        let (compute, particles) = if adapter
+            .get_downlevel_capabilities()
+            .flags
+            .contains(DownlevelFlags::COMPUTE_SHADERS)
+            && device.limits().max_storage_buffers_per_shader_stage > 0
+        {
+            (
+                Some(Compute::new(&device, &[1.0, 2.0, 3.0, 4.0])),
+                Some(Particles::new(&device, &camera_bind_group_layout)),
+            )
+        } else {
+            log::info!("Compute shaders aren't supported, `dispatch_compute()` and particles won't do anything");
+            (None, None)
+        };
 
         // et voilà
-        Self {
-            surface,
+        Ok(Self {
+            instance,
+            adapter,
             device,
             queue,
+            targets: vec![target],
+            render_pipeline_layout,
+            shader_source,
+            vertex_buffer,
+            num_vertices,
+            geometry_bounds,
+            index_buffer,
+            num_indices,
+            draw_indirect: None,
+            draw_indirect_counts: (0, 0),
+            meshes: Vec::new(),
+            materials: Vec::new(),
+            frustum_culling: true,
+            culling_stats: CullingStats {
+                drawn: instances.len() as u32,
+                culled: 0,
+            },
+            drawn_instances: (0..instances.len() as u32).collect(),
+            instances,
+            instance_buffer,
+            // A nice blueish colour
+            clear_color: color::from_srgb(89, 124, 149, 255),
+            globals,
+            globals_buffer,
+            light,
+            light_buffer,
+            globals_bind_group_layout,
+            globals_bind_group,
+            shadow_map,
+            shadow_pass_globals_bind_group,
+            shadows: false,
+            shadow_bias: shadow::DEFAULT_DEPTH_BIAS,
+            shadow_pipeline: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            pick_pipeline: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            pick_textures: None,
+            previous_camera: camera,
+            stepped_camera: camera,
+            camera,
+            camera_uniform,
+            camera_buffer,
+            camera_bind_group_layout,
+            camera_bind_group,
+            camera_controller: CameraController::new(2.0, 0.005),
+            input_state: InputState::new(),
+            transform,
+            transform_binding,
+            wireframe: false,
+            debug_view: DebugView::Lit,
+            cull_mode: Some(Face::Back),
+            blend_mode: BlendMode::default(),
+            depth_prepass: false,
+            depth_compare: CompareFunction::Less,
+            color_interpolation: ColorInterpolation::Smooth,
+            stencil: StencilState::default(),
+            stencil_reference: 0,
+            stencil_mask: None,
+            stencil_mask_pipeline,
+            letterbox: false,
+            letterbox_aspect: 16.0 / 9.0,
+            letterbox_color: Color::BLACK,
+            letterbox_fill,
+            gbuffer: None,
+            gbuffer_pipelines: None,
+            deferred: None,
+            point_lights: Vec::new(),
+            skybox: None,
+            sprite_batch,
+            debug_lines,
+            debug_lines_depth_test: true,
+            axes_gizmo: None,
+            post_process,
+            post_effect: PostEffect::None,
+            bloom,
+            bloom_intensity: 0.0,
+            tonemap: Tonemap::None,
+            exposure: 1.0,
+            fxaa,
+            fxaa_enabled: false,
+            hdr_format: texture::HDR_FORMAT,
+            hdr_supported,
+            prefer_srgb: true,
+            power_preference: PowerPreference::HighPerformance,
+            adapter_index: None,
+            limits: None,
+            limits_profile: LimitsProfile::Downlevel,
+            diffuse_texture,
+            texture_bind_group_layout,
+            diffuse_bind_group,
+            diffuse_source: DiffuseSource::Checker,
+            texture_options,
+            latest_texture: None,
+            pending_assets: Vec::new(),
+            asset_states: HashMap::new(),
+            next_asset_handle: 0,
+            frame_timer: FrameTimer::default(),
+            gpu_timer,
+            adapter_info,
+            sample_count,
+            compute,
+            particles,
+            pending_dispatch: None,
+            text_brush: None,
+            debug_overlay: false,
+            egui_state: Some(egui_state),
+            staging_belt: Some(StagingBelt::new(STAGING_BELT_CHUNK_SIZE)),
+            ui_visible: false,
+            ui_callback: None,
+            encoder_label: "Render Encoder".to_owned(),
+            render_pass_label: "Render Pass".to_owned(),
+            render_passes: vec![Box::new(ScenePass)],
+            exit_key: Some(VirtualKeyCode::Escape),
+            exit_requested: false,
+            requested_size: None,
+            pause_when_unfocused: false,
+            input_recorder: None,
+            fixed_timestep: None,
+            max_frame_latency: None,
+            frames_in_flight: VecDeque::new(),
+            focused: true,
+            shader_watcher: None,
+        })
+    }
+
+    /// Start drawing into another window as well, sharing the same device and queue
+    pub fn add_window(&mut self, window: &Window) -> Result<(), StateInitError> {
+        let surface = unsafe { self.instance.create_surface(window) };
+        self.add_surface(window.id(), surface, window.inner_size())
+    }
+
+    fn add_surface(
+        &mut self,
+        window_id: WindowId,
+        surface: Surface,
+        size: PhysicalSize<u32>,
+    ) -> Result<(), StateInitError> {
+        if !self.adapter.is_surface_supported(&surface) {
+            return Err(StateInitError::UnsupportedSurface);
+        }
+        let surface_info = SurfaceInfo::new(&surface, &self.adapter);
+        let format = pick_surface_format(&surface_info.formats, self.prefer_srgb)
+            .ok_or(StateInitError::NoSupportedFormat)?;
+        // Match the main window as closely as we can
+        let primary = &self.primary_target().config;
+        let present_mode = closest_present_mode(&surface_info.present_modes, primary.present_mode);
+        let alpha_mode = closest_alpha_mode(&surface_info.alpha_modes, primary.alpha_mode);
+        let config = SurfaceConfiguration {
+            usage: TextureUsages::RENDER_ATTACHMENT,
+            format,
+            // The surface can't be 0x0, it'll get the proper size when the window is resized
+            width: size.width.max(1),
+            height: size.height.max(1),
+            present_mode,
+            alpha_mode,
+        };
+        let pipelines = build_pipelines(
+            &self.device,
+            &self.render_pipeline_layout,
+            self.transform_binding.prelude(),
+            &self.shader_source,
+            format,
+            self.sample_count,
+            &depth_stencil_state(self.depth_compare, &self.stencil),
+        )
+        .map_err(StateInitError::Pipeline)?;
+        self.targets.push(RenderTarget::new(
+            &self.device,
+            Some(window_id),
+            Some(surface),
             config,
-            size,
-            render_pipeline,
+            surface_info,
+            pipelines,
+            self.sample_count,
+        ));
+        self.update_viewport(self.targets.len() - 1);
+        self.update_post_process(self.targets.len() - 1);
+        self.update_fxaa(self.targets.len() - 1);
+        self.update_gbuffer(self.targets.len() - 1);
+        Ok(())
+    }
+
+    /// Stop drawing into a window, returns `false` if we weren't drawing into it
+    ///
+    /// The last target can't be removed, since there'd be nothing left to render to
+    pub fn remove_window(&mut self, window_id: WindowId) -> bool {
+        if self.targets.len() == 1 {
+            log::warn!("Not removing the last render target");
+            return false;
+        }
+        let len = self.targets.len();
+        self.targets
+            .retain(|target| target.window_id != Some(window_id));
+        self.targets.len() != len
+    }
+
+    /// Throw away the device and everything made with it, and start again with a fresh one
+    ///
+    /// For when the device is lost (e.g. the driver was reset or the GPU was removed)
+    /// The windows, camera, instances, shader and other settings all carry over
+    ///
+    /// wgpu doesn't tell us when the device is lost, so it's up to the caller to notice (e.g. the surface staying lost after being reconfigured)
+    /// If this fails partway the surfaces are gone as well, so the `State` should be dropped
+    pub fn recreate_device(&mut self) -> Result<(), StateInitError> {
+        log::warn!("Recreating the device");
+        let adapter = match self.adapter_index {
+            #[cfg(not(target_arch = "wasm32"))]
+            Some(index) => adapter_by_index(
+                &self.instance,
+                index,
+                self.primary_target().surface.as_ref(),
+            )?,
+            _ => pollster::block_on(request_adapter(
+                &self.instance,
+                self.power_preference,
+                self.primary_target().surface.as_ref(),
+            ))
+            .ok_or(StateInitError::NoAdapter)?,
+        };
+        // The optional features (and the push constant limit) get added back if the new adapter supports them
+        let features = self.device.features() - OPTIONAL_FEATURES;
+        // Worked out again rather than copied from the old device, the new adapter might not be able to give as much
+        let limits = Limits {
+            max_push_constant_size: 0,
+            ..match &self.limits {
+                Some(limits) => limits.clone(),
+                None => self.limits_profile.limits(&adapter),
+            }
+        };
+        let (device, queue) = pollster::block_on(request_device(&adapter, features, limits))?;
+
+        let mut targets = std::mem::take(&mut self.targets).into_iter();
+        let mut primary = targets.next().expect("there's always at least one target");
+        let present_mode = primary.config.present_mode;
+        let alpha_mode = primary.config.alpha_mode;
+        // The new adapter might not support them, `set_present_mode()` and `set_alpha_mode()` check below
+        primary.config.present_mode = PresentMode::Fifo;
+        primary.config.alpha_mode = CompositeAlphaMode::Auto;
+        let mut state = State::with_device(
+            self.instance.clone(),
+            adapter,
+            device,
+            queue,
+            primary.window_id,
+            primary.surface.take(),
+            primary.config.clone(),
+        )?;
+        if primary.is_minimized() {
+            state.resize_target(0, PhysicalSize::new(0, 0));
+        }
+        state.prefer_srgb = self.prefer_srgb;
+        state.power_preference = self.power_preference;
+        // Otherwise the next recreation would go back to picking by `power_preference`
+        state.adapter_index = self.adapter_index;
+        state.limits = self.limits.take();
+        state.limits_profile = self.limits_profile;
+        for mut target in targets {
+            if let (Some(window_id), Some(surface)) = (target.window_id, target.surface.take()) {
+                state.add_surface(window_id, surface, target.size)?;
+            }
+        }
+        state.set_present_mode(present_mode);
+        state.set_alpha_mode(alpha_mode);
+
+        state.clear_color = self.clear_color;
+        state.globals = self.globals;
+        state.light = self.light;
+        state.camera = self.camera;
+        std::mem::swap(&mut state.camera_controller, &mut self.camera_controller);
+        std::mem::swap(&mut state.input_state, &mut self.input_state);
+        std::mem::swap(&mut state.frame_timer, &mut self.frame_timer);
+        state.shader_watcher = self.shader_watcher.take();
+        state.set_instances(std::mem::take(&mut self.instances));
+        // The buffers belong to the old device, so they're loaded again
+        state.meshes = self
+            .meshes
+            .iter()
+            .filter_map(|mesh| match mesh::load_obj(&state.device, &mesh.path) {
+                Ok(new_mesh) => Some(Mesh {
+                    transform: mesh.transform,
+                    material: mesh.material,
+                    ..new_mesh
+                }),
+                Err(err) => {
+                    log::error!("Failed to reload {}: {err}", mesh.path.display());
+                    None
+                }
+            })
+            .collect();
+        // Likewise the materials, added again in the same order so every `MaterialId` still refers to the same one
+        for material in &self.materials {
+            if let Err(err) = state.add_material(material.descriptor().clone()) {
+                log::error!("Failed to recreate a material on the new device: {err}");
+            }
+        }
+        // Likewise the sprite textures, uploaded again in the same order so every `SpriteTexture` still refers to the same one
+        for images in self.sprite_batch.images() {
+            if let Err(err) = state.add_sprite_texture_array(images.clone()) {
+                log::error!("Failed to upload a sprite texture to the new device: {err}");
+            }
+        }
+        // The new device starts out with the checkerboard
+        if !matches!(self.diffuse_source, DiffuseSource::Checker) {
+            match self.diffuse_source.create_texture(
+                &state.device,
+                &state.queue,
+                state.texture_options,
+            ) {
+                Ok(texture) => state.set_diffuse_texture(texture, self.diffuse_source.clone()),
+                Err(err) => log::error!("Failed to upload the texture to the new device: {err}"),
+            }
+        }
+        if let Some(skybox) = &self.skybox {
+            if let Err(err) =
+                state.load_skybox(skybox.faces().clone().map(DynamicImage::ImageRgba8))
+            {
+                log::error!("Failed to upload the skybox to the new device: {err}");
+            }
+        }
+        state.set_transform(self.transform);
+        state.set_frustum_culling(self.frustum_culling);
+        // Before the sample count, so it's checked against the format that'll actually be multisampled
+        state.set_hdr_format(self.hdr_format);
+        state.set_sample_count(self.sample_count);
+        state.set_fxaa(self.fxaa_enabled);
+        // Back to drawing everything, whatever was in the old buffer is gone with the old device
+        state.enable_draw_indirect(self.draw_indirect.is_some());
+        state.set_wireframe(self.wireframe);
+        state.set_debug_view(self.debug_view);
+        state.set_cull_mode(self.cull_mode);
+        state.set_blend_mode(self.blend_mode);
+        state.enable_depth_prepass(self.depth_prepass);
+        state.stencil = self.stencil.clone();
+        state.depth_compare = self.depth_compare;
+        // Before the shader's reloaded, otherwise it'd be switched back to the default
+        state.color_interpolation = self.color_interpolation;
+        state.stencil_reference = self.stencil_reference;
+        state.stencil_mask = self.stencil_mask;
+        state.set_letterbox_aspect(self.letterbox_aspect);
+        state.set_letterbox_color(self.letterbox_color);
+        state.set_letterbox(self.letterbox);
+        state.set_debug_lines_depth_test(self.debug_lines_depth_test);
+        state.axes_gizmo = self.axes_gizmo;
+        // The particles themselves are lost with the old device, so they start over
+        if let (Some(old), Some(new)) = (&self.particles, &mut state.particles) {
+            new.emitter = old.emitter;
+            new.gravity = old.gravity;
+            new.lifetime = old.lifetime;
+            new.speed = old.speed;
+            new.size = old.size;
+            let count = old.count();
+            state.spawn_particles(count);
         }
+        state.set_post_effect(self.post_effect);
+        state.set_bloom(self.bloom_intensity);
+        state.set_tonemap(self.tonemap);
+        state.set_exposure(self.exposure);
+        state.set_debug_overlay(self.debug_overlay);
+        state.ui_visible = self.ui_visible;
+        state.ui_callback = self.ui_callback.take();
+        state.render_passes = std::mem::take(&mut self.render_passes);
+        state.encoder_label = std::mem::take(&mut self.encoder_label);
+        state.render_pass_label = std::mem::take(&mut self.render_pass_label);
+        state.input_recorder = self.input_recorder.take();
+        // Nothing's on the GPU until they're done, so they can carry on as they are
+        state.pending_assets = std::mem::take(&mut self.pending_assets);
+        state.asset_states = std::mem::take(&mut self.asset_states);
+        state.next_asset_handle = self.next_asset_handle;
+        state.latest_texture = self.latest_texture;
+        // Runs on the new device's compute data, since the old data went with the old device
+        state.pending_dispatch = self.pending_dispatch;
+        state.exit_key = self.exit_key;
+        state.exit_requested = self.exit_requested;
+        state.requested_size = self.requested_size;
+        state.pause_when_unfocused = self.pause_when_unfocused;
+        state.fixed_timestep = self.fixed_timestep;
+        state.max_frame_latency = self.max_frame_latency;
+        state.focused = self.focused;
+        let pixels_per_point = self.ui_mut().pixels_per_point();
+        state.ui_mut().set_pixels_per_point(pixels_per_point);
+        if let Err(err) = state.reload_shader(&self.shader_source) {
+            log::error!(
+                "Failed to rebuild the current shader, using the built-in one: {}",
+                error_description(&err)
+            );
+        }
+        // After the shader, since that's what draws into it
+        if let Some(gbuffer) = &self.gbuffer {
+            if let Err(err) = state.set_gbuffer(Some(gbuffer.attachments())) {
+                log::error!(
+                    "Failed to recreate the G-buffer: {}",
+                    error_description(&err)
+                );
+            }
+        }
+        state.point_lights = std::mem::take(&mut self.point_lights);
+        state.enable_deferred_lighting(self.deferred.is_some());
+        state.set_shadow_bounds(self.shadow_map.center, self.shadow_map.radius);
+        state.set_shadow_bias(self.shadow_bias.constant, self.shadow_bias.slope_scale);
+        state.enable_shadows(self.shadows);
+
+        *self = state;
+        Ok(())
     }
 
-    /// Resize the surface with `new_size`
-    pub fn resize(&mut self, new_size: PhysicalSize<u32>) {
-        if new_size.width > 0 && new_size.height > 0 {
-            self.size = new_size;
-            self.config.width = new_size.width;
-            self.config.height = new_size.height;
-            // Have to reconfigure the surface with the new width and height
-            self.surface.configure(&self.device, &self.config);
+    /// The main window's target, or the offscreen one when running headlessly
+    pub fn primary_target(&self) -> &RenderTarget {
+        &self.targets[0]
+    }
+
+    /// Resize the surface belonging to `window_id` with `new_size`
+    ///
+    /// Dragging a window's edge sends a flood of `Resized` events, so the surface is only actually reconfigured at the start of the next `render()`
+    pub fn resize(&mut self, window_id: WindowId, new_size: PhysicalSize<u32>) {
+        if let Some(index) = self.target_index(window_id) {
+            self.targets[index].request_resize(&self.device, new_size);
+            // Cheap enough to do straight away, and means the next `update()` already has the right aspect ratio
+            if index == 0 && new_size.width > 0 && new_size.height > 0 && !self.letterbox {
+                self.camera.aspect = new_size.width as f32 / new_size.height as f32;
+            }
         }
     }
 
-    /// Indicates whether an event has been fully processed
-    pub fn input(&mut self, _event: &WindowEvent) -> bool {
-        false
+    /// Reconfigure the surface belonging to `window_id` right now, even if its size hasn't changed
+    ///
+    /// For when the surface is lost or outdated, in which case it won't draw again until it's reconfigured
+    pub fn reconfigure(&mut self, window_id: WindowId, size: PhysicalSize<u32>) {
+        if let Some(index) = self.target_index(window_id) {
+            self.resize_target(index, size);
+        }
     }
 
-    pub fn update(&mut self) {}
+    fn target_index(&self, window_id: WindowId) -> Option<usize> {
+        self.targets
+            .iter()
+            .position(|target| target.window_id == Some(window_id))
+    }
 
-    /// Where the magic happens
-    pub fn render(&mut self) -> Result<(), SurfaceError> {
-        let output =
-            // Will wait for `self.surface` to provide a new `SurfaceTexture` to be rendered to
-            self.surface.get_current_texture()?;
-        // Creates a `TextureView` with the default settings
-        // We need to do this because we want to control how the render code interacts with the texture
-        let view = output
-            .texture
-            .create_view(&TextureViewDescriptor::default());
-        // Most modern graphics libs expect commands to be stored in a command buffer before being sent to the GPU
-        // The `encoder` builds a command buffer that we can then send to the GPU
-        let mut encoder = self
-            .device
-            .create_command_encoder(&CommandEncoderDescriptor {
-                label: Some("Render Encoder"),
-            });
-        // `begin_render_pass()` performs a mutable borrow of `encoder`
-        // We can't call `encoder.finish()` until we release the borrow
-        // This is the purpose of the block: to drop the mutable borrow of `encoder`
+    fn resize_target(&mut self, index: usize, new_size: PhysicalSize<u32>) {
+        if self.targets[index].resize(&self.device, new_size, self.sample_count) {
+            // There weren't any frames while we were minimized, so the old timings are meaningless
+            self.frame_timer.reset();
+        }
+        self.update_viewport(index);
+        if !self.targets[index].is_minimized() {
+            self.update_post_process(index);
+            self.update_fxaa(index);
+            self.update_gbuffer(index);
+        }
+    }
+
+    /// (Re)create `index`'s G-buffer textures after a resize, or free them if there's no G-buffer
+    fn update_gbuffer(&mut self, index: usize) {
+        let target = &mut self.targets[index];
+        target.gbuffer_textures = self
+            .gbuffer
+            .as_ref()
+            .map(|gbuffer| gbuffer.create_textures(&self.device, target.size));
+    }
+
+    /// Work out where the scene goes in a target after it's resized or the letterboxing changes
+    fn update_viewport(&mut self, index: usize) {
+        let target = &mut self.targets[index];
+        target.viewport = self
+            .letterbox
+            .then(|| Viewport::letterboxed(target.size, self.letterbox_aspect));
+        if self.letterbox && target.letterbox_pipeline.is_none() {
+            target.letterbox_pipeline = Some(self.letterbox_fill.create_pipeline(
+                &self.device,
+                target.scene_format,
+                self.sample_count,
+            ));
+        }
+        // The camera follows the shape of the main window (or its viewport)
+        if index == 0 && !target.is_minimized() {
+            self.camera.aspect = target.aspect();
+        }
+    }
+
+    /// (Re)create the textures the scene and bloom are drawn into for post-processing, after a resize or post-processing being turned on
+    ///
+    /// Frees them when there's nothing to post-process
+    fn update_post_process(&mut self, index: usize) {
+        let post_processing = self.is_post_processing();
+        self.set_scene_format(index, self.scene_format_for(index));
+        let target = &mut self.targets[index];
+        if !post_processing {
+            target.scene_texture = None;
+            target.bloom_textures = None;
+            target.post_bind_group = None;
+            return;
+        }
+        let scene_texture =
+            texture::create_scene_texture(&self.device, &target.config, target.scene_format);
+        target.bloom_textures = (self.bloom_intensity > 0.0).then(|| {
+            self.bloom
+                .create_textures(&self.device, &scene_texture.view, target.size)
+        });
+        // Without bloom the scene stands in for it, since something has to be bound and it adds nothing at 0 intensity
+        let bloom_view = match &target.bloom_textures {
+            Some(bloom_textures) => bloom_textures.output(),
+            None => &scene_texture.view,
+        };
+        target.post_bind_group = Some(self.post_process.create_bind_group(
+            &self.device,
+            &scene_texture,
+            bloom_view,
+        ));
+        target.scene_texture = Some(scene_texture);
+        if target.post_pipeline.is_none() {
+            target.post_pipeline = Some(
+                self.post_process
+                    .create_pipeline(&self.device, target.config.format),
+            );
+        }
+    }
+
+    /// (Re)create the texture `index`'s scene gets drawn into before FXAA, after a resize or FXAA being turned on
+    ///
+    /// Frees it when FXAA is off
+    fn update_fxaa(&mut self, index: usize) {
+        let target = &mut self.targets[index];
+        if !self.fxaa_enabled {
+            target.fxaa_texture = None;
+            target.fxaa_bind_group = None;
+            return;
+        }
+        // In the target's own format, since it's what post-processing (or the scene without it) would have drawn into the target
+        let fxaa_texture =
+            texture::create_scene_texture(&self.device, &target.config, target.config.format);
+        target.fxaa_bind_group = Some(self.fxaa.create_bind_group(&self.device, &fxaa_texture));
+        target.fxaa_texture = Some(fxaa_texture);
+        if target.fxaa_pipeline.is_none() {
+            target.fxaa_pipeline = Some(
+                self.fxaa
+                    .create_pipeline(&self.device, target.config.format),
+            );
+        }
+    }
+
+    /// The format `index`'s scene should be drawn in, given the current post-processing settings
+    fn scene_format_for(&self, index: usize) -> TextureFormat {
+        // Anything brighter than 1 only makes it to the tonemapping if the scene is drawn in HDR
+        if self.is_post_processing() && self.hdr_supported {
+            self.hdr_format
+        } else {
+            self.targets[index].config.format
+        }
+    }
+
+    /// Switch the format `index`'s scene is drawn in, rebuilding the pipelines and textures that have to match it
+    ///
+    /// If the pipelines can't be built the target keeps its old format
+    fn set_scene_format(&mut self, index: usize, format: TextureFormat) {
+        if self.targets[index].scene_format == format {
+            return;
+        }
+        let pipelines = match build_pipelines(
+            &self.device,
+            &self.render_pipeline_layout,
+            self.transform_binding.prelude(),
+            &self.shader_source,
+            format,
+            self.sample_count,
+            &depth_stencil_state(self.depth_compare, &self.stencil),
+        ) {
+            Ok(pipelines) => pipelines,
+            Err(err) => {
+                log::error!(
+                    "Failed to build the pipelines for drawing into {format:?}: {}",
+                    error_description(&err)
+                );
+                return;
+            }
+        };
+        self.replace_pipelines(index, pipelines, format);
+    }
+
+    /// Give `index` pipelines built for `format` and the current sample count, along with textures and lazily built pipelines to match
+    fn replace_pipelines(&mut self, index: usize, pipelines: Pipelines, format: TextureFormat) {
+        let target = &mut self.targets[index];
+        target.pipelines = pipelines;
+        // Built for the old format or sample count
+        target.depth_compare_pipelines.clear();
+        target.scene_format = format;
+        target.recreate_textures(&self.device, self.sample_count);
+        // Rebuilt by `update_viewport()` if we're letterboxing, and by the `prepare_*()`s if there's anything for them to draw
+        target.letterbox_pipeline = None;
+        target.deferred_pipeline = None;
+        target.skybox_pipeline = None;
+        target.sprite_pipeline = None;
+        target.particle_pipeline = None;
+        target.line_pipeline = None;
+        target.material_pipelines.clear();
+        self.update_viewport(index);
+    }
+
+    /// Whether every window is minimized, in which case `render()` has nothing to do
+    pub fn is_minimized(&self) -> bool {
+        self.targets.iter().all(RenderTarget::is_minimized)
+    }
+
+    /// Switch the camera between perspective and orthographic, takes effect on the next `update()`
+    ///
+    /// The aspect ratio is kept up to date with the main window either way
+    pub fn set_projection(&mut self, projection: Projection) {
+        self.camera.projection = projection;
+    }
+
+    pub fn projection(&self) -> Projection {
+        self.camera.projection
+    }
+
+    /// Change the colour the screen is cleared to, takes effect on the next frame
+    ///
+    /// `color` is linear, use `color::from_srgb()` to convert one from a colour picker
+    pub fn set_clear_color(&mut self, color: Color) {
+        self.clear_color = color;
+    }
+
+    pub fn clear_color(&self) -> Color {
+        self.clear_color
+    }
+
+    /// Like `set_clear_color()` but with an 8-bit sRGB colour, like you'd get from a colour picker
+    ///
+    /// Looks the same whether or not the surface is sRGB, the alpha is left as it was
+    pub fn set_clear_color_srgb(&mut self, [r, g, b]: [u8; 3]) {
+        let alpha = self.clear_color.a;
+        self.clear_color = Color {
+            a: alpha,
+            ..color::from_srgb(r, g, b, 255)
+        };
+    }
+
+    /// The clear colour as 8-bit sRGB, rounded to the nearest value, for handing back to a colour picker
+    pub fn clear_color_srgb(&self) -> [u8; 3] {
+        let [r, g, b, _] = color::to_srgb(self.clear_color);
+        [r, g, b]
+    }
+
+    /// Switch between smooth and flat vertex colours, rebuilding the pipelines with the shader's colour declared the new way
+    ///
+    /// Flat colours come from each triangle's provoking vertex, which in wgpu is the first one, so it depends on the order of the indices
+    /// The shader has to declare its colour like shader.wgsl does (see `ColorInterpolation::declaration()`),
+    /// otherwise nothing changes and `ColorInterpolationError::NotDeclared` is returned
+    /// Materials with their own shader are left as they are
+    /// If the pipelines can't be rebuilt the old interpolation is kept and the error returned
+    pub fn set_color_interpolation(
+        &mut self,
+        interpolation: ColorInterpolation,
+    ) -> Result<(), ColorInterpolationError> {
+        if interpolation == self.color_interpolation {
+            return Ok(());
+        }
+        if !self
+            .shader_source
+            .contains(self.color_interpolation.declaration())
         {
-            let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
-                label: Some("Render Pass"),
-                // Where we are going to draw our colour to, we use `view` to ensure we render to the screen
-                color_attachments: &[Some(RenderPassColorAttachment {
-                    // Which texture to save the colours to
-                    view: &view,
-                    // The texture that will recieve the resolved output, which will be the same as `view` unless mutli-sampling is enabled
-                    // Since we don't need to specify this (because we're not using mutli-sampling), we leave it as `None`
-                    resolve_target: None,
-                    // Tells wgpu what to do with the colours on the screen
-                    ops: Operations {
-                        // How to handle colors stored from the previous frame, currently we are clearing the screen with a blueish colour
-                        load: LoadOp::Clear(Color {
-                            r: 0.1,
-                            g: 0.2,
-                            b: 0.3,
-                            a: 1.0,
-                        }),
-                        // Whether we want to store the rendered results to the `Texture` behind `view`
-                        store: true,
-                    },
-                })],
-                depth_stencil_attachment: None,
-            });
+            return Err(ColorInterpolationError::NotDeclared(
+                self.color_interpolation,
+            ));
+        }
+        let old_interpolation = std::mem::replace(&mut self.color_interpolation, interpolation);
+        let source = self.shader_source.clone();
+        self.reload_shader(&source).map_err(|err| {
+            self.color_interpolation = old_interpolation;
+            ColorInterpolationError::Pipeline(err)
+        })
+    }
+
+    pub fn color_interpolation(&self) -> ColorInterpolation {
+        self.color_interpolation
+    }
 
-            render_pass.set_pipeline(&self.render_pipeline);
-            // Draw something with 3 vertices, and 1 instance
-            render_pass.draw(0..3, 0..1);
+    /// Change the stencil test (and what gets written to the stencil) for everything in the scene
+    ///
+    /// The pipelines have to be rebuilt, if that fails the old stencil state is kept and the error returned
+    /// `stencil::inside_mask()` and `stencil::outside_mask()` go with `set_stencil_mask()`
+    /// Meshes drawn with a material can have their own instead, see `MaterialDescriptor::stencil`
+    pub fn set_stencil(&mut self, stencil: StencilState) -> Result<(), wgpu::Error> {
+        let old_stencil = std::mem::replace(&mut self.stencil, stencil);
+        let source = self.shader_source.clone();
+        let result = self.reload_shader(&source);
+        if result.is_err() {
+            self.stencil = old_stencil;
         }
+        result
+    }
 
-        // submit will accept any `IntoIter`
-        self.queue.submit(std::iter::once(encoder.finish()));
-        output.present();
-        Ok(())
+    /// The value the stencil is compared against (and the mask writes), 0 by default
+    ///
+    /// Materials can have their own, see `MaterialDescriptor::stencil_reference`
+    pub fn set_stencil_reference(&mut self, reference: u32) {
+        self.stencil_reference = reference;
+    }
+
+    /// Write the stencil reference value into `rect` of every target before drawing the scene
+    ///
+    /// Everywhere else the stencil is 0, `None` leaves all of it at 0
+    pub fn set_stencil_mask(&mut self, rect: Option<MaskRect>) {
+        self.stencil_mask = rect;
+    }
+
+    /// Draw the scene into a texture first, then onto the targets with `effect` applied
+    ///
+    /// `PostEffect::None` (the default) skips all that and draws straight into the targets
+    /// The debug overlay and UI are drawn afterwards, so they're left alone
+    pub fn set_post_effect(&mut self, effect: PostEffect) {
+        let textures_needed = self.post_textures_needed();
+        self.post_effect = effect;
+        self.post_settings_changed(textures_needed);
+    }
+
+    pub fn post_effect(&self) -> PostEffect {
+        self.post_effect
+    }
+
+    /// Make the bright parts of the scene glow, `intensity` is how much of the glow gets added on top (0 turns it off)
+    ///
+    /// Goes through post-processing like `set_post_effect()`, with the bloom added before the effect
+    pub fn set_bloom(&mut self, intensity: f32) {
+        let textures_needed = self.post_textures_needed();
+        self.bloom_intensity = intensity.max(0.0);
+        self.post_settings_changed(textures_needed);
+    }
+
+    pub fn bloom(&self) -> f32 {
+        self.bloom_intensity
+    }
+
+    /// Squeeze the scene's colours into the range the target can show, instead of clipping everything above 1
+    ///
+    /// Turns on post-processing (unless it's `Tonemap::None` and the exposure is 1), which draws the scene in HDR where the adapter supports it
+    pub fn set_tonemap(&mut self, tonemap: Tonemap) {
+        let textures_needed = self.post_textures_needed();
+        self.tonemap = tonemap;
+        self.post_settings_changed(textures_needed);
+    }
+
+    pub fn tonemap(&self) -> Tonemap {
+        self.tonemap
+    }
+
+    /// Multiply the scene's colours by `exposure` before tonemapping, 1 by default
+    ///
+    /// Goes through post-processing like `set_tonemap()`
+    pub fn set_exposure(&mut self, exposure: f32) {
+        let textures_needed = self.post_textures_needed();
+        self.exposure = exposure.max(0.0);
+        self.post_settings_changed(textures_needed);
+    }
+
+    pub fn exposure(&self) -> f32 {
+        self.exposure
+    }
+
+    /// Whether the scene has to go through a texture on its way to the targets
+    fn is_post_processing(&self) -> bool {
+        self.post_effect != PostEffect::None
+            || self.bloom_intensity > 0.0
+            || self.tonemap != Tonemap::None
+            || self.exposure != 1.0
     }
+
+    /// Which of the scene and bloom textures the targets need right now
+    fn post_textures_needed(&self) -> (bool, bool) {
+        (self.is_post_processing(), self.bloom_intensity > 0.0)
+    }
+
+    /// Pass the post-processing settings on to the shader, and create or free the targets' textures if what they need has changed from `textures_needed`
+    fn post_settings_changed(&mut self, textures_needed: (bool, bool)) {
+        self.post_process.set_settings(
+            &self.queue,
+            PostSettings::new(
+                self.post_effect,
+                self.bloom_intensity,
+                self.tonemap,
+                self.exposure,
+            ),
+        );
+        if self.post_textures_needed() != textures_needed {
+            for index in 0..self.targets.len() {
+                self.update_post_process(index);
+            }
+        }
+    }
+
+    /// Keep the scene at the letterbox aspect ratio, with bars around it, instead of stretching it to fit the window
+    pub fn set_letterbox(&mut self, on: bool) {
+        self.letterbox = on;
+        for index in 0..self.targets.len() {
+            self.update_viewport(index);
+        }
+    }
+
+    pub fn is_letterboxed(&self) -> bool {
+        self.letterbox
+    }
+
+    /// Width divided by height of the scene when letterboxing, 16:9 by default
+    pub fn set_letterbox_aspect(&mut self, aspect: f32) {
+        self.letterbox_aspect = aspect;
+        self.set_letterbox(self.letterbox);
+    }
+
+    /// Change the colour of the bars around the letterboxed scene, black by default
+    ///
+    /// `color` is linear, like `set_clear_color()`
+    pub fn set_letterbox_color(&mut self, color: Color) {
+        self.letterbox_color = color;
+    }
+
+    /// Draw the scene's geometry into a texture for each of `attachments` before drawing the scene itself, or stop with `None`
+    ///
+    /// The shader's `fs_gbuffer` fills them in, with an output at `@location(n)` for the `n`th attachment
+    /// Each target gets its own textures, the same size as it and never multisampled, see `RenderTarget::gbuffer_textures`
+    /// If the shader doesn't match or a format can't be drawn into, the error is returned and the old G-buffer (if any) is kept
+    pub fn set_gbuffer(
+        &mut self,
+        attachments: Option<&[GBufferAttachment]>,
+    ) -> Result<(), wgpu::Error> {
+        let attachments = match attachments {
+            Some(attachments) => attachments,
+            None => {
+                self.gbuffer = None;
+                self.gbuffer_pipelines = None;
+                for target in &mut self.targets {
+                    target.gbuffer_textures = None;
+                }
+                return Ok(());
+            }
+        };
+        // Unsupported formats only show up once the textures are created, so that's checked too
+        self.device.push_error_scope(ErrorFilter::Validation);
+        let gbuffer = GBuffer::new(&self.device, attachments);
+        let pipelines = build_gbuffer_pipelines(
+            &self.device,
+            &self.render_pipeline_layout,
+            self.transform_binding.prelude(),
+            &self.shader_source,
+            &gbuffer,
+            &depth_stencil_state(self.depth_compare, &self.stencil),
+        );
+        let textures: Vec<_> = self
+            .targets
+            .iter()
+            .map(|target| gbuffer.create_textures(&self.device, target.size))
+            .collect();
+        let pipelines = match (pollster::block_on(self.device.pop_error_scope()), pipelines) {
+            (Some(err), _) | (None, Err(err)) => return Err(err),
+            (None, Ok(pipelines)) => pipelines,
+        };
+        for (target, textures) in self.targets.iter_mut().zip(textures) {
+            target.gbuffer_textures = Some(textures);
+            // The lighting has to be rebuilt for the new layout
+            target.deferred_pipeline = None;
+        }
+        self.gbuffer_pipelines = Some(pipelines);
+        self.gbuffer = Some(gbuffer);
+        Ok(())
+    }
+
+    /// The attachments and bind group layout of the G-buffer, for building a pass that samples it
+    pub fn gbuffer(&self) -> Option<&GBuffer> {
+        self.gbuffer.as_ref()
+    }
+
+    /// Light the scene with a pass over the G-buffer instead of while drawing the geometry, so it can have hundreds of lights
+    ///
+    /// On top of the scene's `light`, every one of `point_lights` is added, which drawing directly ignores
+    /// Turns the G-buffer on with `gbuffer::DEFAULT_ATTACHMENTS` if it isn't already, and leaves it on after
+    /// Wireframe and the depth prepass don't apply while it's on
+    /// Needs storage buffers in fragment shaders, which WebGL doesn't have
+    pub fn enable_deferred_lighting(&mut self, on: bool) {
+        if on == self.deferred.is_some() {
+            return;
+        }
+        if !on {
+            self.deferred = None;
+            for target in &mut self.targets {
+                target.deferred_pipeline = None;
+            }
+            return;
+        }
+        if self.device.limits().max_storage_buffers_per_shader_stage == 0 {
+            log::warn!(
+                "Deferred lighting isn't supported, the device doesn't have storage buffers"
+            );
+            return;
+        }
+        if self.gbuffer.is_none() {
+            if let Err(err) = self.set_gbuffer(Some(&gbuffer::DEFAULT_ATTACHMENTS)) {
+                log::error!(
+                    "Failed to create the G-buffer for deferred lighting: {}",
+                    error_description(&err)
+                );
+                return;
+            }
+        }
+        self.deferred = Some(DeferredLighting::new(&self.device));
+    }
+
+    pub fn is_deferred_lighting(&self) -> bool {
+        self.deferred.is_some()
+    }
+
+    /// Add a light for the deferred lighting that reaches `radius` away from `position`, returning its index in `point_lights`
+    pub fn add_light(&mut self, position: Vec3, color: Color, radius: f32) -> usize {
+        self.point_lights
+            .push(PointLight::new(position, color, radius));
+        self.point_lights.len() - 1
+    }
+
+    pub fn clear_lights(&mut self) {
+        self.point_lights.clear();
+    }
+
+    /// Upload `point_lights`, creating the targets' lighting pipelines if they don't have them yet
+    ///
+    /// A G-buffer that doesn't match what the lighting reads turns it back off
+    fn prepare_deferred(&mut self) {
+        let (deferred, gbuffer) = match (&mut self.deferred, &self.gbuffer) {
+            (Some(deferred), Some(gbuffer)) => (deferred, gbuffer),
+            _ => return,
+        };
+        deferred.prepare(&self.device, &self.queue, &self.point_lights);
+        self.device.push_error_scope(ErrorFilter::Validation);
+        for target in &mut self.targets {
+            if target.deferred_pipeline.is_none() {
+                target.deferred_pipeline = Some(deferred.create_pipeline(
+                    &self.device,
+                    &self.globals_bind_group_layout,
+                    &self.camera_bind_group_layout,
+                    gbuffer.bind_group_layout(),
+                    target.scene_format,
+                    self.sample_count,
+                ));
+            }
+        }
+        if let Some(err) = pollster::block_on(self.device.pop_error_scope()) {
+            log::error!(
+                "The G-buffer doesn't fit the deferred lighting, turning it off: {}",
+                error_description(&err)
+            );
+            self.enable_deferred_lighting(false);
+        }
+    }
+
+    /// Have the light cast shadows, by drawing the scene from its point of view into a shadow map every frame
+    ///
+    /// The shadows are cast as if the light were infinitely far away in the direction of `light.position`, see `set_shadow_bounds()`
+    /// Off by default, since it draws everything an extra time
+    pub fn enable_shadows(&mut self, on: bool) {
+        if on && self.shadow_pipeline.is_none() {
+            match build_shadow_pipeline(
+                &self.device,
+                &self.render_pipeline_layout,
+                self.transform_binding.prelude(),
+                &self.shader_source,
+                self.shadow_bias,
+            ) {
+                Ok(pipeline) => self.shadow_pipeline = Some(pipeline),
+                Err(err) => {
+                    log::error!(
+                        "Failed to build the shadow pipeline: {}",
+                        error_description(&err)
+                    );
+                    return;
+                }
+            }
+        }
+        self.shadows = on;
+    }
+
+    pub fn shadows_enabled(&self) -> bool {
+        self.shadows
+    }
+
+    /// Push what the light sees further away, more gets rid of surfaces shadowing themselves but detaches shadows from what casts them
+    ///
+    /// `constant` is in the shadow map's smallest depth steps, and `slope_scale` adds more on surfaces at a steep angle to the light
+    /// See `shadow::DEFAULT_DEPTH_BIAS` for the default
+    pub fn set_shadow_bias(&mut self, constant: i32, slope_scale: f32) {
+        let bias = DepthBiasState {
+            constant,
+            slope_scale,
+            clamp: 0.0,
+        };
+        if bias == self.shadow_bias {
+            return;
+        }
+        // The bias is part of the pipeline, so it has to be rebuilt if there is one
+        if self.shadow_pipeline.is_some() {
+            match build_shadow_pipeline(
+                &self.device,
+                &self.render_pipeline_layout,
+                self.transform_binding.prelude(),
+                &self.shader_source,
+                bias,
+            ) {
+                Ok(pipeline) => self.shadow_pipeline = Some(pipeline),
+                Err(err) => {
+                    log::error!(
+                        "Failed to rebuild the shadow pipeline: {}",
+                        error_description(&err)
+                    );
+                    return;
+                }
+            }
+        }
+        self.shadow_bias = bias;
+    }
+
+    pub fn shadow_bias(&self) -> DepthBiasState {
+        self.shadow_bias
+    }
+
+    /// Only cast and receive shadows within `radius` of `center`, the whole shadow map is spread over that area
+    ///
+    /// Smaller gives sharper shadows, by default it's 10 units around the origin
+    pub fn set_shadow_bounds(&mut self, center: Vec3, radius: f32) {
+        self.shadow_map.center = center;
+        self.shadow_map.radius = radius;
+    }
+
+    /// Point the shadow map at the light, and draw the scene into it if shadows are on
+    ///
+    /// Shared by every target, so it gets its own command buffer, submitted before any of theirs
+    fn prepare_shadows(&mut self) {
+        self.shadow_map
+            .update(&self.queue, &self.light, self.shadows);
+        let pipeline = match (self.shadows, &self.shadow_pipeline) {
+            (true, Some(pipeline)) => pipeline,
+            _ => return,
+        };
+        let mut encoder = self
+            .device
+            .create_command_encoder(&CommandEncoderDescriptor {
+                label: Some("Shadow Encoder"),
+            });
+        {
+            let mut render_pass = self.shadow_map.begin_pass(&mut encoder);
+            render_pass.set_pipeline(pipeline);
+            // The light takes the camera's place
+            self.draw_geometry_with(
+                &mut render_pass,
+                &self.shadow_pass_globals_bind_group,
+                self.shadow_map.light_camera_bind_group(),
+            );
+        }
+        self.queue.submit(std::iter::once(encoder.finish()));
+    }
+
+    /// Draw a cubemap behind the scene, made from six square faces of the same size in the order +X, -X, +Y, -Y, +Z, -Z
+    ///
+    /// Replaces the clear colour (inside the viewport when letterboxing), and replaces any skybox that was already loaded
+    /// Fails if the faces don't match or are bigger than the device supports, leaving the old skybox alone
+    pub fn load_skybox(&mut self, faces: [DynamicImage; 6]) -> ImageResult<()> {
+        self.set_skybox(Skybox::new(
+            &self.device,
+            &self.queue,
+            faces.map(|face| face.to_rgba8()),
+        )?);
+        Ok(())
+    }
+
+    /// The same as `load_skybox()`, but from one equirectangular (latitude/longitude) image
+    pub fn load_skybox_equirectangular(&mut self, image: &DynamicImage) -> ImageResult<()> {
+        self.set_skybox(Skybox::from_equirectangular(
+            &self.device,
+            &self.queue,
+            image,
+        )?);
+        Ok(())
+    }
+
+    fn set_skybox(&mut self, skybox: Skybox) {
+        self.skybox = Some(skybox);
+        // Each skybox has its own layout, so the pipelines are rebuilt by `prepare_skybox()`
+        for target in &mut self.targets {
+            target.skybox_pipeline = None;
+        }
+    }
+
+    /// Go back to clearing to the clear colour
+    pub fn clear_skybox(&mut self) {
+        self.skybox = None;
+        for target in &mut self.targets {
+            target.skybox_pipeline = None;
+        }
+    }
+
+    pub fn has_skybox(&self) -> bool {
+        self.skybox.is_some()
+    }
+
+    /// Point the sky the same way as `camera`, creating the targets' skybox pipelines if they don't have them yet
+    fn prepare_skybox(&mut self, camera: &Camera) {
+        let skybox = match &self.skybox {
+            Some(skybox) => skybox,
+            None => return,
+        };
+        skybox.update(&self.queue, camera);
+        for target in &mut self.targets {
+            if target.skybox_pipeline.is_none() {
+                target.skybox_pipeline = Some(skybox.create_pipeline(
+                    &self.device,
+                    target.scene_format,
+                    self.sample_count,
+                ));
+            }
+        }
+    }
+
+    /// Upload `image` for drawing quads with `draw_quad()`
+    ///
+    /// Fails with `ImageError::Limits` if it's bigger than the device supports
+    pub fn add_sprite_texture(&mut self, image: DynamicImage) -> ImageResult<SpriteTexture> {
+        self.sprite_batch
+            .add_texture(&self.device, &self.queue, image)
+    }
+
+    /// Upload `images` as the layers of one texture, for drawing quads with `draw_quad_layer()`
+    ///
+    /// Quads showing any of its layers are drawn together, without switching bind groups
+    /// Fails if they aren't all the same size, or there are more layers or they're bigger than the device supports
+    pub fn add_sprite_texture_array(
+        &mut self,
+        images: Vec<DynamicImage>,
+    ) -> ImageResult<SpriteTexture> {
+        self.sprite_batch
+            .add_texture_array(&self.device, &self.queue, images)
+    }
+
+    /// Draw a quad covering `rect` on the XY plane this frame, filled with `texture` (or plain white) multiplied by `color`
+    ///
+    /// Quads are collected until the next `render()` and drawn after the scene's geometry, with one draw call per texture
+    /// `color` is linear, like `set_clear_color()`, and its alpha blends the quad with what's behind it
+    pub fn draw_quad(&mut self, rect: Rect, color: Color, texture: Option<SpriteTexture>) {
+        self.draw_quad_region(rect, Rect::UNIT, color, texture);
+    }
+
+    /// The same as `draw_quad()`, but only showing the `uv_rect` part of the texture (in texture coordinates), e.g. one frame of a sprite sheet
+    pub fn draw_quad_region(
+        &mut self,
+        rect: Rect,
+        uv_rect: Rect,
+        color: Color,
+        texture: Option<SpriteTexture>,
+    ) {
+        self.draw_quad_layer(rect, uv_rect, color, texture, 0);
+    }
+
+    /// The same as `draw_quad_region()`, but showing `layer` of a texture from `add_sprite_texture_array()`
+    ///
+    /// Past the last layer shows the last one, and textures from `add_sprite_texture()` only have layer 0
+    pub fn draw_quad_layer(
+        &mut self,
+        rect: Rect,
+        uv_rect: Rect,
+        color: Color,
+        texture: Option<SpriteTexture>,
+        layer: u32,
+    ) {
+        self.sprite_batch.push(rect, uv_rect, color, texture, layer);
+    }
+
+    /// Upload the quads drawn since the last frame, creating the targets' sprite pipelines if they don't have them yet
+    fn prepare_sprites(&mut self) {
+        self.sprite_batch.prepare(&self.device, &self.queue);
+        if self.sprite_batch.is_empty() {
+            return;
+        }
+        for target in &mut self.targets {
+            if target.sprite_pipeline.is_none() {
+                target.sprite_pipeline = Some(self.sprite_batch.create_pipeline(
+                    &self.device,
+                    target.scene_format,
+                    self.sample_count,
+                ));
+            }
+        }
+    }
+
+    /// Add a material that meshes can be drawn with, by setting their `Mesh::material` to what this returns
+    ///
+    /// Its pipelines are built for every target straight away, if they fail validation (e.g. the shader doesn't compile) nothing's added
+    /// Only the forward pass uses materials, the depth prepass, shadows and deferred lighting draw every mesh like the rest of the scene
+    pub fn add_material(
+        &mut self,
+        descriptor: MaterialDescriptor,
+    ) -> Result<MaterialId, MaterialError> {
+        // So the new material's pipelines go on the end of every target's, lined up with `materials`
+        self.prepare_materials();
+        // Its shader is compiled along with the texture, so that needs catching too
+        self.device.push_error_scope(ErrorFilter::Validation);
+        let material = match Material::new(
+            &self.device,
+            &self.queue,
+            &self.texture_bind_group_layout,
+            self.transform_binding.prelude(),
+            self.texture_options,
+            descriptor,
+        ) {
+            Ok(material) => material,
+            Err(err) => {
+                // Still has to be popped, and anything in it is down to the texture anyway
+                pollster::block_on(self.device.pop_error_scope());
+                return Err(MaterialError::Texture(err));
+            }
+        };
+        let scene_shader;
+        let shader = match material.shader() {
+            Some(shader) => shader,
+            None => {
+                scene_shader = create_shader(
+                    &self.device,
+                    self.transform_binding.prelude(),
+                    &self.shader_source,
+                );
+                &scene_shader
+            }
+        };
+        let pipelines: Vec<_> = self
+            .targets
+            .iter()
+            .map(|target| {
+                build_material_pipelines(
+                    &self.device,
+                    &self.render_pipeline_layout,
+                    shader,
+                    material.descriptor(),
+                    target.scene_format,
+                    self.sample_count,
+                    &depth_stencil_state(self.depth_compare, &self.stencil),
+                )
+            })
+            .collect();
+        // Resolves immediately on native
+        if let Some(err) = pollster::block_on(self.device.pop_error_scope()) {
+            return Err(MaterialError::Pipeline(err));
+        }
+        for (target, pipelines) in self.targets.iter_mut().zip(pipelines) {
+            target.material_pipelines.push(pipelines);
+        }
+        self.materials.push(material);
+        Ok(MaterialId(self.materials.len() - 1))
+    }
+
+    pub fn material(&self, id: MaterialId) -> Option<&Material> {
+        self.materials.get(id.0)
+    }
+
+    /// Build any material pipelines the targets are missing, e.g. after the shader's been reloaded
+    fn prepare_materials(&mut self) {
+        // Only compiled if a material needs it
+        let mut scene_shader = None;
+        for target in &mut self.targets {
+            for material in &self.materials[target.material_pipelines.len()..] {
+                let shader = match material.shader() {
+                    Some(shader) => shader,
+                    None => scene_shader.get_or_insert_with(|| {
+                        create_shader(
+                            &self.device,
+                            self.transform_binding.prelude(),
+                            &self.shader_source,
+                        )
+                    }),
+                };
+                target.material_pipelines.push(build_material_pipelines(
+                    &self.device,
+                    &self.render_pipeline_layout,
+                    shader,
+                    material.descriptor(),
+                    target.scene_format,
+                    self.sample_count,
+                    &depth_stencil_state(self.depth_compare, &self.stencil),
+                ));
+            }
+        }
+    }
+
+    /// Replace the particles with `count` new ones, simulated on the GPU every `update()` and drawn every `render()`
+    ///
+    /// They stream out of `particles.emitter` over the first `lifetime` seconds, respawning once they're that old
+    /// Pass 0 to get rid of them, does nothing if compute shaders aren't supported
+    /// Anything over `particles::max_count()` is capped to it with a warning
+    pub fn spawn_particles(&mut self, count: u32) {
+        let particles = match &mut self.particles {
+            Some(particles) => particles,
+            None => {
+                log::warn!(
+                    "Compute shaders aren't supported by this device, so there can't be particles"
+                );
+                return;
+            }
+        };
+        let max_count = particles::max_count(&self.device);
+        if count > max_count {
+            log::warn!("Can't have {count} particles, only spawning {max_count}");
+        }
+        particles.spawn(&self.device, count.min(max_count));
+    }
+
+    /// Create the targets' particle pipelines if there are particles and they don't have them yet
+    fn prepare_particles(&mut self) {
+        let particles = match &self.particles {
+            Some(particles) if !particles.is_empty() => particles,
+            _ => return,
+        };
+        for target in &mut self.targets {
+            if target.particle_pipeline.is_none() {
+                target.particle_pipeline = Some(particles.create_pipeline(
+                    &self.device,
+                    target.scene_format,
+                    self.sample_count,
+                ));
+            }
+        }
+    }
+
+    /// Draw a line from `a` to `b` in world space this frame, e.g. for showing axes, bounding boxes or normals
+    ///
+    /// Lines are collected until the next `render()` and drawn in one go after the scene
+    /// `color` is linear, like `set_clear_color()`
+    pub fn draw_line(&mut self, a: Vec3, b: Vec3, color: Color) {
+        self.debug_lines.push(a, b, color);
+    }
+
+    /// Whether lines from `draw_line()` are hidden behind the scene (the default), or drawn over everything
+    pub fn set_debug_lines_depth_test(&mut self, depth_test: bool) {
+        if depth_test != self.debug_lines_depth_test {
+            self.debug_lines_depth_test = depth_test;
+            // Rebuilt by `prepare_debug_lines()`
+            for target in &mut self.targets {
+                target.line_pipeline = None;
+            }
+        }
+    }
+
+    pub fn debug_lines_depth_test(&self) -> bool {
+        self.debug_lines_depth_test
+    }
+
+    /// Show the world's axes as lines `length` long from the origin, X in red, Y in green and Z in blue, or `None` to hide them
+    ///
+    /// They're drawn with the rest of the debug lines, so `set_debug_lines_depth_test(false)` keeps them visible through the scene
+    pub fn set_axes_gizmo(&mut self, length: Option<f32>) {
+        self.axes_gizmo = length;
+    }
+
+    pub fn axes_gizmo(&self) -> Option<f32> {
+        self.axes_gizmo
+    }
+
+    /// Upload the lines drawn since the last frame, creating the targets' line pipelines if they don't have them yet
+    fn prepare_debug_lines(&mut self) {
+        if let Some(length) = self.axes_gizmo {
+            let axes = [
+                (Vec3::X, Color::RED),
+                (Vec3::Y, Color::GREEN),
+                (Vec3::Z, Color::BLUE),
+            ];
+            for (axis, color) in axes {
+                self.debug_lines.push(Vec3::ZERO, axis * length, color);
+            }
+        }
+        self.debug_lines.prepare(&self.device, &self.queue);
+        if self.debug_lines.is_empty() {
+            return;
+        }
+        for target in &mut self.targets {
+            if target.line_pipeline.is_none() {
+                target.line_pipeline = Some(self.debug_lines.create_pipeline(
+                    &self.device,
+                    target.scene_format,
+                    self.sample_count,
+                    self.debug_lines_depth_test,
+                ));
+            }
+        }
+    }
+
+    /// Switch every surface to a different present mode, e.g. `PresentMode::Mailbox` for low latency without tearing
+    ///
+    /// Surfaces that don't support it get the closest mode they do support instead, see `supported_present_modes()`
+    pub fn set_present_mode(&mut self, mode: PresentMode) {
+        for target in &mut self.targets {
+            let chosen = target.set_present_mode(&self.device, mode);
+            // Headless targets don't have any modes to choose from, and don't need one
+            if chosen != mode && target.surface.is_some() {
+                log::warn!(
+                    "Present mode {mode:?} is not supported by this surface, using {chosen:?}"
+                );
+            }
+        }
+    }
+
+    /// The present modes the main window's surface supports, empty when running headlessly
+    pub fn supported_present_modes(&self) -> &[PresentMode] {
+        &self.primary_target().surface_info.present_modes
+    }
+
+    /// Switch every surface to a different alpha mode, e.g. `CompositeAlphaMode::PreMultiplied` for a see-through window
+    ///
+    /// Surfaces that don't support it get `CompositeAlphaMode::Auto` instead, see `surface_info()`
+    pub fn set_alpha_mode(&mut self, mode: CompositeAlphaMode) {
+        for target in &mut self.targets {
+            let chosen = target.set_alpha_mode(&self.device, mode);
+            if chosen != mode && target.surface.is_some() {
+                log::warn!(
+                    "Alpha mode {mode:?} is not supported by this surface, using {chosen:?}"
+                );
+            }
+        }
+    }
+
+    /// Everything the main window's surface supports, for offering choices that won't need replacing
+    ///
+    /// Empty when running headlessly
+    pub fn surface_info(&self) -> &SurfaceInfo {
+        &self.primary_target().surface_info
+    }
+
+    /// Replace the instances being drawn
+    ///
+    /// The instance buffer is only recreated if the number of instances changed, otherwise it's just overwritten
+    pub fn set_instances(&mut self, instances: Vec<Instance>) {
+        if instances.len() == self.instances.len() {
+            self.instances = instances;
+            self.write_all_instances();
+        } else {
+            self.instance_buffer = create_instance_buffer(&self.device, &instances);
+            self.culling_stats = CullingStats {
+                drawn: instances.len() as u32,
+                culled: 0,
+            };
+            self.drawn_instances = (0..instances.len() as u32).collect();
+            self.instances = instances;
+        }
+    }
+
+    /// Whether `update()` leaves out the instances that are outside the camera's view, on by default
+    ///
+    /// Each instance is tested with a sphere around the built-in geometry and every mesh, since they're all drawn for each one
+    /// Doesn't do anything while `enable_draw_indirect()` is on, since the GPU decides which instances get drawn
+    pub fn set_frustum_culling(&mut self, on: bool) {
+        self.frustum_culling = on;
+        if !on {
+            self.write_all_instances();
+        }
+    }
+
+    pub fn frustum_culling(&self) -> bool {
+        self.frustum_culling
+    }
+
+    /// How many instances were drawn and culled, as of the last `update()`
+    pub fn culling_stats(&self) -> CullingStats {
+        self.culling_stats
+    }
+
+    /// Put every instance back in the instance buffer, in order, after culling has left some out
+    fn write_all_instances(&mut self) {
+        let raw: Vec<_> = self.instances.iter().map(Instance::to_raw).collect();
+        self.queue
+            .write_buffer(&self.instance_buffer, 0, bytemuck::cast_slice(&raw));
+        self.culling_stats = CullingStats {
+            drawn: self.instances.len() as u32,
+            culled: 0,
+        };
+        self.drawn_instances = (0..self.instances.len() as u32).collect();
+    }
+
+    /// Only upload the instances the camera can see, packed at the start of the instance buffer
+    fn cull_instances(&mut self) {
+        if !self.frustum_culling || self.draw_indirect.is_some() {
+            return;
+        }
+        // Every instance draws the built-in geometry and all of the meshes, each with its own transform after the instance's
+        let objects: Vec<_> = std::iter::once(self.geometry_bounds)
+            .chain(self.meshes.iter().map(|mesh| mesh.bounding_sphere))
+            .zip(self.object_transforms())
+            .filter_map(|(bounds, transform)| Some((bounds?, transform)))
+            .collect();
+        if objects.is_empty() {
+            return;
+        }
+        // `render()` can draw anywhere between the last two cameras, so anything either of them can see is kept
+        let mut frustums = vec![self.camera.frustum(), self.previous_camera.frustum()];
+        // Things just off screen can still cast shadows onto what's on it
+        if self.shadows {
+            frustums.push(Frustum::from_view_projection(
+                self.shadow_map.view_projection(&self.light),
+            ));
+        }
+        let (drawn_instances, raw): (Vec<_>, Vec<_>) = self
+            .instances
+            .iter()
+            .enumerate()
+            .filter(|(_, instance)| {
+                objects.iter().any(|&(bounds, transform)| {
+                    let sphere = instance.bounding_sphere(bounds).transformed(transform);
+                    frustums
+                        .iter()
+                        .any(|frustum| frustum.intersects_sphere(&sphere))
+                })
+            })
+            .map(|(index, instance)| (index as u32, instance.to_raw()))
+            .unzip();
+        self.drawn_instances = drawn_instances;
+        if !raw.is_empty() {
+            self.queue
+                .write_buffer(&self.instance_buffer, 0, bytemuck::cast_slice(&raw));
+        }
+        self.culling_stats = CullingStats {
+            drawn: raw.len() as u32,
+            culled: (self.instances.len() - raw.len()) as u32,
+        };
+    }
+
+    /// Rebuild the render pipeline with a new shader
+    ///
+    /// If `source` doesn't compile (or doesn't fit the pipeline) the error is returned and we keep the old pipeline
+    pub fn reload_shader(&mut self, source: &str) -> Result<(), wgpu::Error> {
+        // With the vertex colours interpolated however they're set to be, which is also how it's kept
+        let source = self.color_interpolation.apply(source);
+        let source = source.as_ref();
+        // Build them all first so we don't end up with only some of the targets using the new shader
+        let pipelines = self
+            .targets
+            .iter()
+            .map(|target| {
+                build_pipelines(
+                    &self.device,
+                    &self.render_pipeline_layout,
+                    self.transform_binding.prelude(),
+                    source,
+                    target.scene_format,
+                    self.sample_count,
+                    &depth_stencil_state(self.depth_compare, &self.stencil),
+                )
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let gbuffer_pipelines = match &self.gbuffer {
+            Some(gbuffer) => Some(build_gbuffer_pipelines(
+                &self.device,
+                &self.render_pipeline_layout,
+                self.transform_binding.prelude(),
+                source,
+                gbuffer,
+                &depth_stencil_state(self.depth_compare, &self.stencil),
+            )?),
+            None => None,
+        };
+        let shadow_pipeline = match self.shadow_pipeline {
+            Some(_) => Some(build_shadow_pipeline(
+                &self.device,
+                &self.render_pipeline_layout,
+                self.transform_binding.prelude(),
+                source,
+                self.shadow_bias,
+            )?),
+            None => None,
+        };
+        for (target, pipelines) in self.targets.iter_mut().zip(pipelines) {
+            target.pipelines = pipelines;
+            // Built with the old shader (or stencil)
+            target.depth_compare_pipelines.clear();
+            // Some of them use the scene's shader, they're all rebuilt by `prepare_materials()`
+            target.material_pipelines.clear();
+        }
+        self.gbuffer_pipelines = gbuffer_pipelines;
+        self.shadow_pipeline = shadow_pipeline;
+        self.shader_source = source.to_owned();
+        Ok(())
+    }
+
+    /// Load an OBJ file like `mesh::load_obj()` without waiting for it, it's added to `meshes` in the first `update()` after it's been read
+    ///
+    /// The file is read on a thread of its own, only creating the buffers happens on this one
+    /// On the web there aren't any threads, so it's read straight away
+    pub fn load_mesh_async(&mut self, path: impl AsRef<Path>) -> AssetHandle {
+        let handle = self.next_asset_handle();
+        self.pending_assets
+            .push(PendingAsset::mesh(handle, path.as_ref()));
+        handle
+    }
+
+    /// Load the image at `path` like `load_mesh_async()`, replacing `diffuse_texture` once it's been decoded
+    ///
+    /// The texture already there (the built-in checkerboard, unless it's been replaced) is drawn until then
+    /// If it's called again before this one's done, only the later texture is used, whichever finishes first
+    pub fn load_texture_async(&mut self, path: impl AsRef<Path>) -> AssetHandle {
+        let handle = self.next_asset_handle();
+        self.pending_assets
+            .push(PendingAsset::texture(handle, path.as_ref()));
+        self.latest_texture = Some(handle);
+        handle
+    }
+
+    /// Replace `diffuse_texture` with a DDS or KTX2 file of BC or ASTC blocks, kept compressed on the GPU if the device supports it
+    ///
+    /// See `Texture::from_compressed()` for when it gets decoded instead
+    pub fn load_compressed_texture(
+        &mut self,
+        path: impl AsRef<Path>,
+    ) -> Result<(), CompressedTextureError> {
+        let path = path.as_ref();
+        let texture =
+            Texture::load_compressed(&self.device, &self.queue, path, self.texture_options)?;
+        self.set_diffuse_texture(texture, DiffuseSource::Compressed(path.to_owned()));
+        // Otherwise a texture still loading from before this would replace it when it's done
+        self.latest_texture = None;
+        Ok(())
+    }
+
+    /// Draw `texture` from now on, remembering `source` so it can be uploaded again by `recreate_device()`
+    fn set_diffuse_texture(&mut self, texture: Texture, source: DiffuseSource) {
+        self.diffuse_bind_group = texture.bind_group(&self.device, &self.texture_bind_group_layout);
+        self.diffuse_texture = texture;
+        self.diffuse_source = source;
+    }
+
+    /// How the asset from a `load_*_async()` is getting on, `None` if `handle` came from a different `State`
+    pub fn asset_state(&self, handle: AssetHandle) -> Option<&AssetState> {
+        self.asset_states.get(&handle)
+    }
+
+    fn next_asset_handle(&mut self) -> AssetHandle {
+        let handle = AssetHandle(self.next_asset_handle);
+        self.next_asset_handle += 1;
+        self.asset_states.insert(handle, AssetState::Loading);
+        handle
+    }
+
+    /// Upload whichever of `pending_assets` have finished loading, here on the thread that owns the device
+    fn poll_assets(&mut self) {
+        let mut index = 0;
+        while index < self.pending_assets.len() {
+            let result = match self.pending_assets[index].poll() {
+                Some(result) => result,
+                None => {
+                    index += 1;
+                    continue;
+                }
+            };
+            let pending = self.pending_assets.remove(index);
+            let state = match result {
+                // They can finish in any order, and the one asked for last should win
+                Ok(Decoded::Texture(_)) if self.latest_texture != Some(pending.handle) => {
+                    log::debug!(
+                        "Dropping {}, a later texture replaced it",
+                        pending.path.display()
+                    );
+                    AssetState::Superseded
+                }
+                result => match result.and_then(|decoded| self.upload_asset(&pending, decoded)) {
+                    Ok(()) => AssetState::Ready,
+                    Err(err) => {
+                        log::error!("Failed to load {}: {err}", pending.path.display());
+                        AssetState::Failed(err)
+                    }
+                },
+            };
+            self.asset_states.insert(pending.handle, state);
+        }
+    }
+
+    fn upload_asset(&mut self, pending: &PendingAsset, decoded: Decoded) -> Result<(), String> {
+        match decoded {
+            Decoded::Mesh(data) => self.meshes.push(data.upload(&self.device)),
+            Decoded::Texture(image) => {
+                let source = DiffuseSource::Image {
+                    image,
+                    label: pending.path.to_str().map(str::to_owned),
+                };
+                let texture =
+                    source.create_texture(&self.device, &self.queue, self.texture_options)?;
+                self.set_diffuse_texture(texture, source);
+            }
+        }
+        Ok(())
+    }
+
+    /// Load the shader from `path` instead of the one baked into the binary, and reload it whenever the file changes
+    pub fn watch_shader(&mut self, path: impl AsRef<Path>) -> notify::Result<()> {
+        let watcher = ShaderWatcher::new(path)?;
+        match fs::read_to_string(watcher.path()) {
+            Ok(source) => self.reload_and_log(&source),
+            Err(err) => log::error!("Failed to read {}: {err}", watcher.path().display()),
+        }
+        self.shader_watcher = Some(watcher);
+        Ok(())
+    }
+
+    fn reload_and_log(&mut self, source: &str) {
+        match self.reload_shader(source) {
+            Ok(()) => log::info!("Reloaded shader"),
+            Err(err) => log::error!(
+                "Failed to reload shader, keeping the old one: {}",
+                error_description(&err)
+            ),
+        }
+    }
+
+    /// Indicates whether an event from `window_id` has been fully processed
+    pub fn input(&mut self, window_id: WindowId, event: &WindowEvent) -> bool {
+        match self.target_index(window_id) {
+            Some(index) => self.target_input(index, event),
+            None => false,
+        }
+    }
+
+    /// The same as `input()` for an event from the main window, even if it's headless (e.g. when replaying input)
+    pub fn primary_input(&mut self, event: &WindowEvent) -> bool {
+        self.target_input(0, event)
+    }
+
+    fn target_input(&mut self, index: usize, event: &WindowEvent) -> bool {
+        // Replays all go to the main window, so anything from the others would end up there too
+        if index == 0 {
+            if let Some(recorder) = &mut self.input_recorder {
+                if let Some(event) = RecordedEvent::from_window_event(event) {
+                    recorder.record(event);
+                }
+            }
+        }
+        // Tracked even if something else uses the event, so held keys are always accurate
+        self.input_state.process_event(event);
+        // Moving between our own windows unfocuses one just before focusing the other, so this ends up true
+        if let WindowEvent::Focused(focused) = event {
+            self.focused = *focused;
+        }
+        // The UI is only on the main window, and always sees its events so it keeps up with `ScaleFactorChanged` while hidden
+        if index == 0 {
+            let consumed = self.ui_mut().on_event(event);
+            if consumed && self.ui_visible {
+                return true;
+            }
+        }
+        self.camera_controller.process_event(event)
+    }
+
+    /// Turn the camera by raw mouse movement, from `DeviceEvent::MouseMotion`
+    ///
+    /// Only does anything while dragging or with mouse-look on, see `CameraController::process_mouse_motion()`
+    pub fn mouse_motion(&mut self, delta: (f64, f64)) {
+        if let Some(recorder) = &mut self.input_recorder {
+            recorder.record(RecordedEvent::MouseMotion {
+                x: delta.0,
+                y: delta.1,
+            });
+        }
+        self.camera_controller.process_mouse_motion(delta);
+    }
+
+    /// Start recording the input from the main window, replacing any recording that's already going
+    ///
+    /// Every event `input()` gets from the main window (and everything `mouse_motion()` gets) is kept, split up by `update()`,
+    /// until `stop_recording_input()`
+    pub fn start_recording_input(&mut self) {
+        self.input_recorder = Some(InputRecorder::new());
+    }
+
+    /// Stop recording and hand over what was recorded, e.g. to `InputRecorder::save()`, `None` if nothing was being recorded
+    pub fn stop_recording_input(&mut self) -> Option<InputRecorder> {
+        self.input_recorder.take()
+    }
+
+    pub fn is_recording_input(&self) -> bool {
+        self.input_recorder.is_some()
+    }
+
+    /// Change which key makes `run()` quit, Escape by default
+    ///
+    /// `None` means no key quits, e.g. so Escape can open a pause menu instead (which can use `request_exit()`)
+    pub fn set_exit_key(&mut self, key: Option<VirtualKeyCode>) {
+        self.exit_key = key;
+    }
+
+    pub fn exit_key(&self) -> Option<VirtualKeyCode> {
+        self.exit_key
+    }
+
+    /// Ask `run()` to quit once the current frame is done
+    pub fn request_exit(&mut self) {
+        self.exit_requested = true;
+    }
+
+    pub fn exit_requested(&self) -> bool {
+        self.exit_requested
+    }
+
+    /// Ask `run()` to resize the main window to `width` by `height` logical pixels, e.g. to snap it to a preset resolution
+    ///
+    /// The surface follows straight away, but the OS might give the window a slightly different size,
+    /// in which case the `Resized` event that comes after sorts the surface out
+    pub fn set_size(&mut self, width: u32, height: u32) {
+        self.requested_size = Some(LogicalSize::new(width, height));
+    }
+
+    /// The size from the last `set_size()`, if it hasn't been applied yet, for whatever owns the window (`run()` does this for you)
+    pub fn take_requested_size(&mut self) -> Option<LogicalSize<u32>> {
+        self.requested_size.take()
+    }
+
+    /// Have `run()` only draw a few frames a second while none of our windows have focus, off by default
+    ///
+    /// Saves battery when the app is in the background, full speed comes back as soon as it's focused again
+    pub fn set_pause_when_unfocused(&mut self, pause: bool) {
+        self.pause_when_unfocused = pause;
+    }
+
+    pub fn pauses_when_unfocused(&self) -> bool {
+        self.pause_when_unfocused
+    }
+
+    /// Whether `run()` should be throttling right now, see `set_pause_when_unfocused()`
+    pub fn is_paused(&self) -> bool {
+        self.pause_when_unfocused && !self.focused
+    }
+
+    /// Have `run()` call `step()` every `timestep` (e.g. 1/60th of a second) however fast it's drawing, `None` (the default) updates once per frame
+    ///
+    /// Makes the simulation deterministic, `render()` then blends the camera between the last two updates so motion stays smooth
+    /// A timestep of 0 is treated as `None`
+    pub fn set_fixed_timestep(&mut self, timestep: Option<Duration>) {
+        self.fixed_timestep = timestep.filter(|timestep| !timestep.is_zero());
+    }
+
+    pub fn fixed_timestep(&self) -> Option<Duration> {
+        self.fixed_timestep
+    }
+
+    /// Limit how many frames `render()` can submit before the GPU has finished them, `None` (the default) leaves it to the driver
+    ///
+    /// Fewer frames in flight means what's on screen is closer to the latest input, at the cost of throughput,
+    /// since the CPU sits waiting instead of getting on with the next frame while the GPU is busy
+    /// `Some(1)` lets the CPU work on one frame while the GPU draws the last, `Some(0)` waits for every frame to finish
+    /// Matters most with `Immediate` and `Mailbox`, which can otherwise queue up several frames, and does nothing on the web
+    pub fn set_max_frame_latency(&mut self, frames: Option<u32>) {
+        self.max_frame_latency = frames;
+    }
+
+    pub fn max_frame_latency(&self) -> Option<u32> {
+        self.max_frame_latency
+    }
+
+    /// Block until no more than `max_frame_latency` frames are in flight, `submission` being this frame's last
+    fn limit_frame_latency(&mut self, submission: Option<SubmissionIndex>) {
+        let max_frames = match self.max_frame_latency {
+            Some(max_frames) => max_frames as usize,
+            None => {
+                self.frames_in_flight.clear();
+                return;
+            }
+        };
+        self.frames_in_flight.extend(submission);
+        while self.frames_in_flight.len() > max_frames {
+            if let Some(oldest) = self.frames_in_flight.pop_front() {
+                self.device.poll(Maintain::WaitForSubmissionIndex(oldest));
+            }
+        }
+    }
+
+    /// Show or hide the egui debug UI on the main window
+    pub fn set_ui_visible(&mut self, visible: bool) {
+        self.ui_visible = visible;
+    }
+
+    pub fn is_ui_visible(&self) -> bool {
+        self.ui_visible
+    }
+
+    /// Add widgets of your own to the debug UI, `callback` runs in every `update()` while it's visible
+    ///
+    /// `callback` gets the `State` too, e.g. to hook sliders up to it
+    pub fn set_ui_callback(&mut self, callback: impl FnMut(&egui::Context, &mut State) + 'static) {
+        self.ui_callback = Some(Box::new(callback));
+    }
+
+    /// The steps every frame is drawn in, starting out as just a `ScenePass`
+    pub fn render_passes(&self) -> &[Box<dyn render_graph::RenderPass>] {
+        &self.render_passes
+    }
+
+    /// Add, remove or reorder the steps every frame is drawn in, e.g. to draw something of your own after the scene
+    ///
+    /// Passes are kept by `recreate_device()`, so any that hold on to their own GPU resources have to make them again after it
+    pub fn render_passes_mut(&mut self) -> &mut Vec<Box<dyn render_graph::RenderPass>> {
+        &mut self.render_passes
+    }
+
+    /// Add `pass` after all the others
+    pub fn add_render_pass(&mut self, pass: impl render_graph::RenderPass + 'static) {
+        self.render_passes.push(Box::new(pass));
+    }
+
+    /// Name the command encoders `render()` records each frame with, "Render Encoder" by default
+    ///
+    /// Only shows up in GPU debuggers like RenderDoc and in validation errors, handy for telling apart captures from different builds
+    pub fn set_encoder_label(&mut self, label: impl Into<String>) {
+        self.encoder_label = label.into();
+    }
+
+    pub fn encoder_label(&self) -> &str {
+        &self.encoder_label
+    }
+
+    /// Name the render pass the scene's geometry is drawn in, "Render Pass" by default
+    pub fn set_render_pass_label(&mut self, label: impl Into<String>) {
+        self.render_pass_label = label.into();
+    }
+
+    pub fn render_pass_label(&self) -> &str {
+        &self.render_pass_label
+    }
+
+    /// Collect the UI's input for the next `update()`, call once a frame before it
+    ///
+    /// Needs the main window, to know its size and to change things like the cursor for the UI
+    pub fn begin_ui_frame(&mut self, window: &Window) {
+        self.ui_mut().begin_frame(window);
+    }
+
+    fn ui_mut(&mut self) -> &mut Ui {
+        self.egui_state
+            .as_mut()
+            .expect("the UI is only taken out during update() and render()")
+    }
+
+    /// Draw just the edges of triangles, a no-op (besides a warning) if the device doesn't support it
+    pub fn set_wireframe(&mut self, on: bool) {
+        if on && !self.device.features().contains(Features::POLYGON_MODE_LINE) {
+            log::warn!("Wireframe rendering isn't supported by this device");
+            return;
+        }
+        self.wireframe = on;
+    }
+
+    /// Draw the geometry with one of the shader's debug views instead of lighting it, or go back with `DebugView::Lit`
+    ///
+    /// Takes priority over wireframe, the blend mode and deferred lighting while it's on
+    /// Views the shader doesn't have an entry point for are drawn lit
+    pub fn set_debug_view(&mut self, debug_view: DebugView) {
+        self.debug_view = debug_view;
+    }
+
+    pub fn debug_view(&self) -> DebugView {
+        self.debug_view
+    }
+
+    /// Which triangles the scene's geometry leaves out, `Some(Face::Back)` by default
+    ///
+    /// `None` draws both sides, for double-sided geometry or finding triangles wound the wrong way round
+    /// Switches between pipelines that are already built, but doesn't apply to the G-buffer or shadow map
+    pub fn set_cull_mode(&mut self, cull_mode: Option<Face>) {
+        self.cull_mode = cull_mode;
+    }
+
+    pub fn cull_mode(&self) -> Option<Face> {
+        self.cull_mode
+    }
+
+    /// Switch between MSAA, FXAA and no anti-aliasing at all, rebuilding whatever the new one needs
+    ///
+    /// Not something to do every frame, but fine for comparing them while running
+    /// If the sample count isn't supported (see `set_sample_count()`) nothing changes
+    pub fn set_anti_aliasing(&mut self, anti_aliasing: AntiAliasing) {
+        let sample_count = anti_aliasing.sample_count();
+        self.set_sample_count(sample_count);
+        if self.sample_count != sample_count {
+            return;
+        }
+        self.set_fxaa(anti_aliasing == AntiAliasing::Fxaa);
+    }
+
+    /// FXAA takes priority if it's somehow on along with multisampling
+    pub fn anti_aliasing(&self) -> AntiAliasing {
+        if self.fxaa_enabled {
+            AntiAliasing::Fxaa
+        } else if self.sample_count > 1 {
+            AntiAliasing::Msaa(self.sample_count)
+        } else {
+            AntiAliasing::None
+        }
+    }
+
+    /// Turn the FXAA pass on or off for every target, creating or freeing the textures it draws from
+    fn set_fxaa(&mut self, on: bool) {
+        if on == self.fxaa_enabled {
+            return;
+        }
+        self.fxaa_enabled = on;
+        for index in 0..self.targets.len() {
+            self.update_fxaa(index);
+        }
+    }
+
+    /// Switch to drawing with `count` samples per pixel, 1 turns multisampling off
+    ///
+    /// Everything that has to match is rebuilt, so this isn't something to do every frame
+    /// Render passes in this version of wgpu only take 1 or 4 samples, and every target's format has to be multisampleable
+    /// Anything else is ignored with a warning
+    pub fn set_sample_count(&mut self, count: u32) {
+        if count == self.sample_count {
+            return;
+        }
+        if !matches!(count, 1 | 4) {
+            log::warn!("Can't draw with {count} samples, it has to be 1 or 4");
+            return;
+        }
+        if count > 1 {
+            let formats = self.targets.iter().map(|target| target.config.format);
+            if let Some(format) = find_unmultisampleable(&self.adapter, formats) {
+                log::warn!("Can't draw with {count} samples, {format:?} can't be multisampled on this adapter");
+                return;
+            }
+        }
+        let old_count = std::mem::replace(&mut self.sample_count, count);
+        let old_hdr_supported = std::mem::replace(
+            &mut self.hdr_supported,
+            supports_hdr(&self.adapter, self.hdr_format, count),
+        );
+        // Built up front so a failure leaves every target as it was
+        let pipelines = (0..self.targets.len())
+            .map(|index| {
+                let format = self.scene_format_for(index);
+                build_pipelines(
+                    &self.device,
+                    &self.render_pipeline_layout,
+                    self.transform_binding.prelude(),
+                    &self.shader_source,
+                    format,
+                    count,
+                    &depth_stencil_state(self.depth_compare, &self.stencil),
+                )
+                .map(|pipelines| (pipelines, format))
+            })
+            .collect::<Result<Vec<_>, _>>();
+        let pipelines = match pipelines {
+            Ok(pipelines) => pipelines,
+            Err(err) => {
+                log::error!(
+                    "Failed to build the pipelines for {count} samples: {}",
+                    error_description(&err)
+                );
+                self.sample_count = old_count;
+                self.hdr_supported = old_hdr_supported;
+                return;
+            }
+        };
+        self.stencil_mask_pipeline = stencil::create_mask_pipeline(&self.device, count);
+        for (index, (pipelines, format)) in pipelines.into_iter().enumerate() {
+            self.replace_pipelines(index, pipelines, format);
+            // The scene texture might have changed format along with the pipelines
+            self.update_post_process(index);
+        }
+    }
+
+    /// Draw the scene in `format` when post-processing, e.g. `TextureFormat::Rgba32Float` for more precision than the default `texture::HDR_FORMAT`
+    ///
+    /// The multisampled texture and the one it's resolved into before tonemapping both switch to it
+    /// It has to be a floating point format that's renderable, filterable and (with MSAA) multisampleable on this adapter,
+    /// otherwise it's ignored with a warning
+    pub fn set_hdr_format(&mut self, format: TextureFormat) {
+        if format == self.hdr_format {
+            return;
+        }
+        if !is_float_format(format) {
+            log::warn!("{format:?} isn't a floating point format, the scene would be clipped at 1 before it's tonemapped");
+            return;
+        }
+        if !supports_hdr(&self.adapter, format, self.sample_count) {
+            log::warn!(
+                "Can't draw the scene in {format:?} with {} samples on this adapter",
+                self.sample_count
+            );
+            return;
+        }
+        self.hdr_format = format;
+        self.hdr_supported = true;
+        for index in 0..self.targets.len() {
+            // Switches the scene format, and the textures that have to match it, if we're post-processing
+            self.update_post_process(index);
+        }
+    }
+
+    pub fn hdr_format(&self) -> TextureFormat {
+        self.hdr_format
+    }
+
+    /// Draw the built-in geometry with arguments read from a buffer on the GPU, so a compute pass can decide how much gets drawn
+    ///
+    /// The buffer starts out drawing every index and instance, after that it's up to `set_draw_indirect_args()` or a compute shader
+    /// writing to `draw_indirect()`, which has to stay within `index_buffer` and `instances`
+    /// Needs an index buffer, and a device that supports `DownlevelFlags::INDIRECT_EXECUTION`
+    pub fn enable_draw_indirect(&mut self, on: bool) {
+        if !on {
+            self.draw_indirect = None;
+            return;
+        }
+        if !self
+            .adapter
+            .get_downlevel_capabilities()
+            .flags
+            .contains(DownlevelFlags::INDIRECT_EXECUTION)
+        {
+            log::warn!("Indirect draws aren't supported by this device");
+            return;
+        }
+        if self.index_buffer.is_none() {
+            log::warn!("Indirect draws need an index buffer");
+            return;
+        }
+        if self.draw_indirect.is_none() {
+            // The GPU gets to pick from every instance
+            self.write_all_instances();
+            self.draw_indirect_counts = (self.num_indices, self.instances.len() as u32);
+            self.draw_indirect = Some(DrawIndirect::new(
+                &self.device,
+                draw_everything(self.draw_indirect_counts),
+            ));
+        }
+    }
+
+    /// Go back to drawing everything if the number of indices or instances has changed since the indirect args were written
+    ///
+    /// Whatever's in the buffer could reach past the end of the new index or instance buffer otherwise
+    fn prepare_draw_indirect(&mut self) {
+        let draw_indirect = match &self.draw_indirect {
+            Some(draw_indirect) => draw_indirect,
+            None => return,
+        };
+        let counts = (self.num_indices, self.instances.len() as u32);
+        if counts != self.draw_indirect_counts {
+            log::debug!(
+                "The geometry or instances changed, resetting the indirect draw to draw everything"
+            );
+            draw_indirect.write(&self.queue, draw_everything(counts));
+            self.draw_indirect_counts = counts;
+        }
+    }
+
+    /// The buffer the built-in geometry's draw arguments come from, `None` unless `enable_draw_indirect()` is on
+    pub fn draw_indirect(&self) -> Option<&DrawIndirect> {
+        self.draw_indirect.as_ref()
+    }
+
+    /// Change what the indirect draw draws from the CPU, does nothing unless `enable_draw_indirect()` is on
+    ///
+    /// A `first_instance` other than 0 is ignored with a warning unless the device supports `Features::INDIRECT_FIRST_INSTANCE`
+    /// So are args that reach past the end of `index_buffer` or `instances`
+    /// They're reset to draw everything if either of those change size, see `prepare_draw_indirect()`
+    pub fn set_draw_indirect_args(&mut self, args: DrawIndexedIndirectArgs) {
+        let draw_indirect = match &self.draw_indirect {
+            Some(draw_indirect) => draw_indirect,
+            None => {
+                log::warn!("Indirect draws aren't enabled, see `enable_draw_indirect()`");
+                return;
+            }
+        };
+        if args.first_instance != 0
+            && !self
+                .device
+                .features()
+                .contains(Features::INDIRECT_FIRST_INSTANCE)
+        {
+            log::warn!("Starting an indirect draw from instance {} needs `Features::INDIRECT_FIRST_INSTANCE`", args.first_instance);
+            return;
+        }
+        let (num_indices, num_instances) = self.draw_indirect_counts;
+        // Added as `u64`s so huge values can't wrap around to something that looks in range
+        let index_end = args.first_index as u64 + args.index_count as u64;
+        let instance_end = args.first_instance as u64 + args.instance_count as u64;
+        if index_end > num_indices as u64 || instance_end > num_instances as u64 {
+            log::warn!("Can't draw indices up to {index_end} and instances up to {instance_end}, there are only {num_indices} and {num_instances}");
+            return;
+        }
+        draw_indirect.write(&self.queue, args);
+    }
+
+    /// Replace the data the compute shader works on
+    pub fn set_compute_data(&mut self, data: &[f32]) {
+        match &mut self.compute {
+            Some(compute) => compute.set_data(&self.device, &self.queue, data),
+            None => log::warn!("Compute shaders aren't supported by this device"),
+        }
+    }
+
+    /// Run the compute shader with (`x`, `y`, `z`) workgroups, which happens before the render pass in the next `render()`
+    ///
+    /// Each workgroup covers `COMPUTE_WORKGROUP_SIZE` elements of the data
+    pub fn dispatch_compute(&mut self, x: u32, y: u32, z: u32) {
+        self.pending_dispatch = Some([x, y, z]);
+    }
+
+    /// Copy the compute data back from the GPU, waiting for any work on it to finish
+    ///
+    /// Empty if the device can't run compute shaders
+    pub fn read_compute_data(&self) -> Vec<f32> {
+        let compute = match &self.compute {
+            Some(compute) => compute,
+            None => return Vec::new(),
+        };
+        let size = compute.storage_buffer.size();
+        let staging_buffer = self.device.create_buffer(&BufferDescriptor {
+            label: Some("Compute Readback Buffer"),
+            size,
+            usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        let mut encoder = self.create_encoder();
+        encoder.copy_buffer_to_buffer(&compute.storage_buffer, 0, &staging_buffer, 0, size);
+        self.queue.submit(std::iter::once(encoder.finish()));
+        bytemuck::cast_slice(&self.read_buffer(&staging_buffer)).to_vec()
+    }
+
+    /// Change how what we draw is blended with what's already there
+    ///
+    /// Just picks a different pipeline, they're all built up front (including whenever the shader is reloaded)
+    pub fn set_blend_mode(&mut self, blend_mode: BlendMode) {
+        self.blend_mode = blend_mode;
+    }
+
+    pub fn blend_mode(&self) -> BlendMode {
+        self.blend_mode
+    }
+
+    /// Show FPS, frame time and the backend in the top-left corner of the main window
+    pub fn set_debug_overlay(&mut self, on: bool) {
+        if on && self.text_brush.is_none() {
+            self.text_brush = Some(TextBrush::new(&self.device, self.surface_format()));
+        }
+        self.debug_overlay = on;
+    }
+
+    pub fn debug_overlay(&self) -> bool {
+        self.debug_overlay
+    }
+
+    /// Draw everything twice: once to fill in the depth buffer, then again only shading the closest fragments
+    ///
+    /// Worth it when lots of things overlap and the fragment shader is expensive, since each pixel only gets shaded once
+    /// Relies on the depth texture every target has, and is skipped while drawing in wireframe
+    pub fn enable_depth_prepass(&mut self, on: bool) {
+        self.depth_prepass = on;
+    }
+
+    /// Change which fragments pass the depth test, `CompareFunction::Less` (the closest one wins) by default
+    ///
+    /// E.g. `Always` draws everything on top of what came before it, and `LessEqual` lets decals sit exactly on a surface
+    /// The depth prepass stores whichever fragment wins this comparison, then the colour goes where the depth is equal to that
+    /// Each comparison's pipelines are built the first time it's used and kept, so switching back to one is free
+    /// (apart from the G-buffer's, if there is one, which are rebuilt every time)
+    /// If they can't be built the old comparison is kept and the error returned
+    pub fn set_depth_compare(&mut self, depth_compare: CompareFunction) -> Result<(), wgpu::Error> {
+        if depth_compare == self.depth_compare {
+            return Ok(());
+        }
+        let depth_stencil = depth_stencil_state(depth_compare, &self.stencil);
+        // Built first so a failure leaves every target as it was, `None` where they're already around
+        let built = self
+            .targets
+            .iter()
+            .map(|target| {
+                if target
+                    .depth_compare_pipelines
+                    .iter()
+                    .any(|(compare, _)| *compare == depth_compare)
+                {
+                    return Ok(None);
+                }
+                build_pipelines(
+                    &self.device,
+                    &self.render_pipeline_layout,
+                    self.transform_binding.prelude(),
+                    &self.shader_source,
+                    target.scene_format,
+                    self.sample_count,
+                    &depth_stencil,
+                )
+                .map(Some)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        // Only the one set, so they're rebuilt every time
+        let gbuffer_pipelines = match &self.gbuffer {
+            Some(gbuffer) => Some(build_gbuffer_pipelines(
+                &self.device,
+                &self.render_pipeline_layout,
+                self.transform_binding.prelude(),
+                &self.shader_source,
+                gbuffer,
+                &depth_stencil,
+            )?),
+            None => None,
+        };
+        self.gbuffer_pipelines = gbuffer_pipelines;
+        let old_depth_compare = std::mem::replace(&mut self.depth_compare, depth_compare);
+        for (target, built) in self.targets.iter_mut().zip(built) {
+            let pipelines = match built {
+                Some(pipelines) => pipelines,
+                None => {
+                    let index = target
+                        .depth_compare_pipelines
+                        .iter()
+                        .position(|(compare, _)| *compare == depth_compare)
+                        .expect("only left unbuilt if they were kept");
+                    target.depth_compare_pipelines.swap_remove(index).1
+                }
+            };
+            let old_pipelines = std::mem::replace(&mut target.pipelines, pipelines);
+            target
+                .depth_compare_pipelines
+                .push((old_depth_compare, old_pipelines));
+            // They use the depth compare as well, and are rebuilt by `prepare_materials()`
+            target.material_pipelines.clear();
+        }
+        Ok(())
+    }
+
+    pub fn depth_compare(&self) -> CompareFunction {
+        self.depth_compare
+    }
+
+    /// The format of the main surface, which any pipeline drawing into it has to match
+    pub fn surface_format(&self) -> TextureFormat {
+        self.primary_target().config.format
+    }
+
+    /// Width divided by height of the main surface
+    pub fn aspect_ratio(&self) -> f32 {
+        self.primary_target().aspect()
+    }
+
+    /// Whether the main surface is sRGB, i.e. the GPU converts the shader's linear output to sRGB for us
+    ///
+    /// When it isn't, colours have to be gamma corrected by hand to look right
+    pub fn is_srgb(&self) -> bool {
+        self.primary_target().config.format.describe().srgb
+    }
+
+    /// Whether we're drawing in wireframe
+    pub fn is_wireframe(&self) -> bool {
+        self.wireframe
+    }
+
+    /// Set the transform applied to everything before the camera, on top of each mesh's own `Mesh::transform`
+    ///
+    /// Goes through push constants when the device supports them, so it's cheap to change every frame
+    pub fn set_transform(&mut self, transform: Mat4) {
+        self.transform = transform;
+    }
+
+    /// What each object gets drawn with, `transform` for the built-in geometry followed by one per mesh
+    fn object_transforms(&self) -> impl Iterator<Item = Mat4> + '_ {
+        std::iter::once(self.transform).chain(
+            self.meshes
+                .iter()
+                .map(|mesh| self.transform * mesh.transform),
+        )
+    }
+
+    /// Write every object's transform to the uniform buffer, when there are no push constants to set them with while drawing
+    fn prepare_transforms(&mut self) {
+        let transforms: Vec<_> = self.object_transforms().collect();
+        if let TransformBinding::Uniform(object_transforms) = &mut self.transform_binding {
+            object_transforms.write(&self.device, &self.queue, &transforms);
+        }
+    }
+
+    /// Where the cursor last was in the window, in physical pixels
+    pub fn mouse_position(&self) -> (f64, f64) {
+        self.input_state.mouse_position()
+    }
+
+    /// Where the cursor last was in the window, in logical pixels like the UI, see `set_scale_factor()`
+    pub fn logical_mouse_position(&self) -> (f64, f64) {
+        self.input_state.logical_mouse_position()
+    }
+
+    /// Tell us the main window's new scale factor, from `ScaleFactorChanged`, so logical positions and the UI stay the right size
+    ///
+    /// The window's new physical size comes with the same event, and still has to go to `resize()`
+    pub fn set_scale_factor(&mut self, scale_factor: f64) {
+        self.input_state.set_scale_factor(scale_factor);
+        self.ui_mut().set_pixels_per_point(scale_factor as f32);
+    }
+
+    /// Physical pixels per logical pixel on the main window
+    pub fn scale_factor(&self) -> f64 {
+        self.input_state.scale_factor()
+    }
+
+    /// How far the mouse wheel has scrolled this frame, in lines, reset by `end_update()` (at the end of every `update()`)
+    pub fn scroll_delta(&self) -> (f32, f32) {
+        self.input_state.scroll_delta()
+    }
+
+    /// Which instance is in front at (`x`, `y`) in the main window, in physical pixels from the top left like `mouse_position()`
+    ///
+    /// Returns its index in `instances`, `None` if there's only background there or it's outside the window
+    /// The IDs get drawn into an integer texture and the one pixel is copied back, waiting for the GPU to finish it,
+    /// so it's best kept to when it's needed, e.g. on a click
+    /// That's a pass of its own rather than another attachment on the scene pass, since then every frame would pay to
+    /// draw IDs for every pixel to pick from one of them now and then, and every scene pipeline would need the extra target
+    /// Drawn from where the camera was for the last frame, so it picks what's on screen even if `update()` has moved it since
+    /// Not on the web, where the GPU can't be waited on
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn pick(&mut self, x: u32, y: u32) -> Option<u32> {
+        let size = self.primary_target().size;
+        if x >= size.width || y >= size.height {
+            return None;
+        }
+        self.prepare_transforms();
+        let pick_pipeline = match self.pick_pipeline.take() {
+            Some((cull_mode, depth_compare, pipeline))
+                if cull_mode == self.cull_mode && depth_compare == self.depth_compare =>
+            {
+                pipeline
+            }
+            _ => picking::create_pipeline(
+                &self.device,
+                &self.render_pipeline_layout,
+                self.transform_binding.prelude(),
+                self.cull_mode,
+                self.depth_compare,
+            ),
+        };
+        let pick_textures = match self.pick_textures.take() {
+            Some(pick_textures) if pick_textures.size() == size => pick_textures,
+            _ => PickTextures::new(&self.device, size),
+        };
+        let mut encoder = self.create_encoder();
+        {
+            let mut render_pass = pick_textures.begin_pass(&mut encoder);
+            // Letterboxed the same as the scene, so the pixel under the cursor has what's drawn under it
+            set_viewport(&mut render_pass, self.primary_target().viewport);
+            // Only the one pixel gets read, so there's no point filling in the rest
+            render_pass.set_scissor_rect(x, y, 1, 1);
+            render_pass.set_pipeline(&pick_pipeline);
+            self.draw_geometry(&mut render_pass);
+        }
+        pick_textures.copy_pixel(&mut encoder, x, y);
+        self.queue.submit(std::iter::once(encoder.finish()));
+        let data = self.read_buffer(pick_textures.readback_buffer());
+        self.pick_pipeline = Some((self.cull_mode, self.depth_compare, pick_pipeline));
+        self.pick_textures = Some(pick_textures);
+        // The shader adds 1 to the slot in the instance buffer, leaving 0 for the background
+        let id = u32::from_le_bytes(data[..4].try_into().ok()?);
+        let slot = id.checked_sub(1)?;
+        self.drawn_instances.get(slot as usize).copied()
+    }
+
+    /// `pick()` whatever's under the cursor in the main window
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn pick_at_cursor(&mut self) -> Option<u32> {
+        let (x, y) = self.mouse_position();
+        // Can be off the top or left while a button is held and the cursor is dragged out of the window
+        if x < 0.0 || y < 0.0 {
+            return None;
+        }
+        self.pick(x as u32, y as u32)
+    }
+
+    /// Advance everything by `dt`, the time since the last update
+    ///
+    /// The same as `begin_update()`, `step()` and `end_update()` one after the other, for when there's one update per frame
+    pub fn update(&mut self, dt: Duration) {
+        self.begin_update();
+        self.step(dt);
+        self.end_update();
+    }
+
+    /// The part of `update()` that happens once per frame before any `step()`s, however many there are (even none)
+    ///
+    /// Reloads the shader if it changed, picks up finished assets and runs the UI with the input from `begin_ui_frame()`
+    pub fn begin_update(&mut self) {
+        if let Some(source) = self.shader_watcher.as_ref().and_then(ShaderWatcher::poll) {
+            self.reload_and_log(&source);
+        }
+        self.poll_assets();
+
+        // Before the camera is updated, so changes from the UI show up this frame
+        if self.ui_visible {
+            self.run_ui();
+        }
+    }
+
+    /// The part of `update()` that moves the simulation on by `dt`, called every timestep when there's a fixed one
+    ///
+    /// `dt` is clamped to `MAX_UPDATE_DT` so a long stall (e.g. dragging the window) doesn't make things jump
+    pub fn step(&mut self, dt: Duration) {
+        let dt = dt.min(MAX_UPDATE_DT).as_secs_f32();
+        self.globals.time += dt;
+        // Each target gets its own resolution when it's drawn, this is just so `globals` has the main window's
+        self.globals = self.globals.with_resolution(self.primary_target().size);
+        // Like the light and the camera, it gets copied to the GPU by `render()`, see `write_uniforms()`
+
+        if let Some(particles) = self
+            .particles
+            .as_mut()
+            .filter(|particles| !particles.is_empty())
+        {
+            let mut encoder = self
+                .device
+                .create_command_encoder(&CommandEncoderDescriptor {
+                    label: Some("Particle Encoder"),
+                });
+            particles.simulate(&self.queue, &mut encoder, dt);
+            // Submitted straight away, since the next update would overwrite this one's settings
+            self.queue.submit(std::iter::once(encoder.finish()));
+        }
+
+        self.camera_controller.update_camera(&mut self.camera, dt);
+        self.previous_camera = std::mem::replace(&mut self.stepped_camera, self.camera);
+        self.camera_uniform = CameraUniform::new(&self.camera);
+        // After the camera's moved, so it's culled against where it'll be drawn from
+        self.cull_instances();
+
+        // Each recorded frame is one step, so replaying them a step at a time lines up
+        if let Some(recorder) = &mut self.input_recorder {
+            recorder.end_frame();
+        }
+    }
+
+    /// The part of `update()` that happens once per frame after all the `step()`s
+    pub fn end_update(&mut self) {
+        // So the scroll delta only covers the next frame
+        self.input_state.end_frame();
+    }
+
+    /// Lay out the built-in settings window and the caller's widgets
+    fn run_ui(&mut self) {
+        // Both are taken out of `self` so the widgets can change anything in it
+        let mut egui_state = match self.egui_state.take() {
+            Some(egui_state) => egui_state,
+            None => return,
+        };
+        let mut callback = self.ui_callback.take();
+        egui_state.run(|context| {
+            ui::settings_window(context, self);
+            if let Some(callback) = &mut callback {
+                callback(context, self);
+            }
+        });
+        self.egui_state = Some(egui_state);
+        // Unless the callback replaced itself
+        if self.ui_callback.is_none() {
+            self.ui_callback = callback;
+        }
+    }
+
+    /// Which GPU we're running on, what backend we're using, and driver details
+    pub fn adapter_info(&self) -> &AdapterInfo {
+        &self.adapter_info
+    }
+
+    /// The features the device was created with, including any optional ones we turned on
+    pub fn features(&self) -> Features {
+        self.device.features()
+    }
+
+    /// The limits the device was created with
+    pub fn limits(&self) -> Limits {
+        self.device.limits()
+    }
+
+    /// The widest (or tallest) a 2D texture can be, windows bigger than this get rendered at a lower resolution
+    pub fn max_texture_dimension(&self) -> u32 {
+        self.device.limits().max_texture_dimension_2d
+    }
+
+    /// Every feature the adapter supports, which might be more than we asked for
+    ///
+    /// Comparing this with `features()` helps explain why something (e.g. wireframe or push constants) is off
+    pub fn adapter_features(&self) -> Features {
+        self.adapter.features()
+    }
+
+    /// The best limits the adapter supports
+    pub fn adapter_limits(&self) -> Limits {
+        self.adapter.limits()
+    }
+
+    /// Frames-per-second, averaged over roughly the last second
+    pub fn fps(&self) -> f32 {
+        self.frame_timer.fps()
+    }
+
+    /// Run any finished `map_async()` callbacks (and free resources the GPU is done with), for doing your own readbacks
+    ///
+    /// `Maintain::Wait` blocks until everything submitted so far is done, returns whether the queue is empty
+    /// wgpu makes this a no-op with WebGPU, since the browser polls for us (WebGL still needs it though)
+    pub fn poll(&self, maintain: Maintain) -> bool {
+        self.device.poll(maintain)
+    }
+
+    /// Wait for the GPU to finish everything it's been given, so none of it is still in use when `State` is dropped
+    ///
+    /// Some drivers crash (or the validation layers complain) if resources are destroyed mid-frame
+    /// `run()` calls this on the way out, anything else driving `State` should call it before dropping it
+    pub fn shutdown(&self) {
+        self.poll(Maintain::Wait);
+    }
+
+    /// How long the GPU spent drawing the main window's last frame (or rather one from a frame or two ago)
+    ///
+    /// `None` if the device doesn't support `Features::TIMESTAMP_QUERY`, or nothing has been measured yet
+    pub fn gpu_frame_time(&self) -> Option<Duration> {
+        self.gpu_timer.as_ref().and_then(GpuTimer::last_frame_time)
+    }
+
+    /// Where the magic happens
+    ///
+    /// Draws into every target, if any of them fail the rest are still drawn and the first error is returned
+    /// `alpha` is how far we are from the last `update()` to the next one, in fixed timesteps (see `set_fixed_timestep()`)
+    /// The camera is drawn that far between where it was after the last two updates, pass 1 to draw it where it is
+    pub fn render(&mut self, alpha: f32) -> Result<(), SurfaceError> {
+        let camera = if alpha < 1.0 {
+            let camera = self.previous_camera.lerp(&self.camera, alpha.max(0.0));
+            self.camera_uniform = CameraUniform::new(&camera);
+            camera
+        } else {
+            self.camera
+        };
+        for index in 0..self.targets.len() {
+            if let Some(new_size) = self.targets[index].take_pending_resize() {
+                self.resize_target(index, new_size);
+            }
+        }
+        self.frame_timer.tick();
+        self.prepare_transforms();
+        self.prepare_shadows();
+        self.prepare_skybox(&camera);
+        self.prepare_deferred();
+        self.prepare_sprites();
+        self.prepare_materials();
+        self.prepare_particles();
+        self.prepare_debug_lines();
+        self.prepare_draw_indirect();
+        // Goes in whichever target's encoder comes first
+        let mut dispatch = self.pending_dispatch.take();
+        // Taken out of `self` while drawing, since it needs to be mutable
+        let mut text_brush = self.text_brush.take();
+        let overlay_text = self.debug_overlay.then(|| self.overlay_text());
+        let mut gpu_timer = self.gpu_timer.take();
+        if let Some(gpu_timer) = &mut gpu_timer {
+            gpu_timer.poll(&self.device);
+        }
+        let mut egui_state = if self.ui_visible {
+            self.egui_state.take()
+        } else {
+            None
+        };
+        let mut staging_belt = self
+            .staging_belt
+            .take()
+            .expect("the staging belt is only taken out during render()");
+        let mut result = Ok(());
+        let mut last_submission = None;
+        for (index, target) in self.targets.iter().enumerate() {
+            // The overlay only goes on the main window
+            let overlay = text_brush
+                .as_mut()
+                .zip(overlay_text.as_deref())
+                .filter(|_| index == 0);
+            let ui = egui_state.as_mut().filter(|_| index == 0);
+            // Only the main window is timed
+            let timer = gpu_timer.as_mut().filter(|_| index == 0);
+            match self.render_into(target, &mut staging_belt, &mut dispatch, overlay, ui, timer) {
+                Ok(submission) => last_submission = submission.or(last_submission),
+                Err(err) => {
+                    if result.is_ok() {
+                        result = Err(err);
+                    }
+                }
+            }
+        }
+        // Its buffers get mapped again once the GPU's done copying out of them, ready to be reused by a later frame
+        staging_belt.recall();
+        // Which only happens when the device is polled, otherwise the belt would keep making new buffers instead of waiting
+        self.device.poll(Maintain::Poll);
+        self.staging_belt = Some(staging_belt);
+        // Nothing got drawn, so try again next frame
+        self.pending_dispatch = dispatch;
+        if let Some(text_brush) = &mut text_brush {
+            text_brush.submitted();
+        }
+        self.text_brush = text_brush;
+        if let Some(egui_state) = egui_state {
+            self.egui_state = Some(egui_state);
+        }
+        self.gpu_timer = gpu_timer;
+        self.limit_frame_latency(last_submission);
+        result
+    }
+
+    /// What the debug overlay shows
+    fn overlay_text(&self) -> String {
+        let fps = self.fps();
+        let frame_time = if fps > 0.0 { 1000.0 / fps } else { 0.0 };
+        let mut text = format!(
+            "{fps:.0} FPS ({frame_time:.2} ms)\n{:?}",
+            self.adapter_info.backend
+        );
+        if let Some(gpu_frame_time) = self.gpu_frame_time() {
+            text += &format!("\nGPU: {:.2} ms", gpu_frame_time.as_secs_f64() * 1000.0);
+        }
+        let CullingStats { drawn, culled } = self.culling_stats;
+        text += &format!("\nInstances: {drawn} drawn, {culled} culled");
+        text
+    }
+
+    fn render_into(
+        &self,
+        target: &RenderTarget,
+        staging_belt: &mut StagingBelt,
+        dispatch: &mut Option<[u32; 3]>,
+        overlay: Option<(&mut TextBrush, &str)>,
+        ui: Option<&mut Ui>,
+        mut gpu_timer: Option<&mut GpuTimer>,
+    ) -> Result<Option<SubmissionIndex>, SurfaceError> {
+        if target.is_minimized() {
+            return Ok(None);
+        }
+        let output = match &target.surface {
+            // Will wait for `surface` to provide a new `SurfaceTexture` to be rendered to
+            Some(surface) => Some(surface.get_current_texture()?),
+            // Nothing to present to, so we just draw into the offscreen target (if there is one)
+            None => None,
+        };
+        let mut encoder = self.create_encoder();
+        self.write_uniforms(staging_belt, &mut encoder, target.size);
+        // Before the render pass, so it could use the results
+        if let (Some(workgroups), Some(compute)) = (dispatch.take(), &self.compute) {
+            compute.encode(&mut encoder, workgroups);
+        }
+        // Creates a `TextureView` with the default settings
+        // We need to do this because we want to control how the render code interacts with the texture
+        let view = output.as_ref().map(|output| {
+            output
+                .texture
+                .create_view(&TextureViewDescriptor::default())
+        });
+        if let Some(view) = view.as_ref().or_else(|| {
+            target
+                .offscreen_target
+                .as_ref()
+                .map(|offscreen| &offscreen.view)
+        }) {
+            if let Some(gpu_timer) = gpu_timer.as_deref_mut() {
+                gpu_timer.begin(&mut encoder);
+            }
+            self.record_passes(&mut encoder, target, view);
+            // A separate pass on top of the scene, after it's been resolved (and post-processed)
+            if let Some((text_brush, text)) = overlay {
+                render_graph::debug_scope(&mut encoder, "Debug Overlay", |encoder| {
+                    text_brush.draw(&self.device, encoder, view, target.size, text)
+                });
+            }
+            if let Some(ui) = ui {
+                render_graph::debug_scope(&mut encoder, "UI", |encoder| {
+                    ui.draw(&self.device, &self.queue, encoder, view, target.size)
+                });
+            }
+            if let Some(gpu_timer) = gpu_timer.as_deref_mut() {
+                gpu_timer.end(&mut encoder);
+            }
+        }
+
+        // Every write through the belt has to be finished before the commands copying them are submitted
+        staging_belt.finish();
+        // submit will accept any `IntoIter`
+        let submission = self.queue.submit(std::iter::once(encoder.finish()));
+        if let Some(gpu_timer) = gpu_timer {
+            gpu_timer.submitted();
+        }
+        if let Some(output) = output {
+            output.present();
+        }
+        Ok(Some(submission))
+    }
+
+    /// Record copying the camera, the light and the globals (with `size` as the resolution) into their buffers, before anything in `encoder` reads them
+    ///
+    /// Goes through `staging_belt` instead of `queue.write_buffer()`, which makes a new staging buffer every time
+    /// `staging_belt` has to be `finish()`ed before `encoder` is submitted, and `recall()`ed after
+    fn write_uniforms(
+        &self,
+        staging_belt: &mut StagingBelt,
+        encoder: &mut CommandEncoder,
+        size: PhysicalSize<u32>,
+    ) {
+        // Recorded along with each target's commands, so every target sees its own size even though they share the buffer
+        let globals = self.globals.with_resolution(size);
+        let uniforms: [(&Buffer, &[u8]); 3] = [
+            (&self.globals_buffer, bytemuck::bytes_of(&globals)),
+            (&self.light_buffer, bytemuck::bytes_of(&self.light)),
+            (
+                &self.camera_buffer,
+                bytemuck::bytes_of(&self.camera_uniform),
+            ),
+        ];
+        for (buffer, data) in uniforms {
+            let size = BufferSize::new(data.len() as u64).expect("uniforms are never empty");
+            staging_belt
+                .write_buffer(encoder, buffer, 0, size, &self.device)
+                .copy_from_slice(data);
+        }
+    }
+
+    /// Most modern graphics libs expect commands to be stored in a command buffer before being sent to the GPU
+    /// The `encoder` builds a command buffer that we can then send to the GPU
+    fn create_encoder(&self) -> CommandEncoder {
+        self.device
+            .create_command_encoder(&CommandEncoderDescriptor {
+                label: Some(&self.encoder_label),
+            })
+    }
+
+    /// Record every one of `render_passes` for `target`, in order
+    fn record_passes(
+        &self,
+        encoder: &mut CommandEncoder,
+        target: &RenderTarget,
+        view: &TextureView,
+    ) {
+        let context = RenderContext {
+            state: self,
+            target,
+            view,
+        };
+        for pass in &self.render_passes {
+            render_graph::debug_scope(encoder, pass.label(), |encoder| {
+                pass.record(encoder, &context)
+            });
+        }
+    }
+
+    /// Record the commands to draw the scene into `view`, going through the scene texture when there's a `PostEffect`
+    /// and the FXAA texture when FXAA is on
+    pub(crate) fn encode_frame(
+        &self,
+        encoder: &mut CommandEncoder,
+        target: &RenderTarget,
+        view: &TextureView,
+    ) {
+        match (
+            &target.fxaa_texture,
+            &target.fxaa_pipeline,
+            &target.fxaa_bind_group,
+        ) {
+            (Some(fxaa_texture), Some(pipeline), Some(bind_group)) => {
+                // Everything that would've gone into `view` goes into the FXAA texture instead, then gets smoothed on the way
+                self.encode_post_processed(encoder, target, &fxaa_texture.view);
+                self.fxaa.apply(encoder, view, pipeline, bind_group);
+            }
+            _ => self.encode_post_processed(encoder, target, view),
+        }
+    }
+
+    /// Record the commands to draw the scene into `view`, going through the scene texture when there's a `PostEffect`
+    fn encode_post_processed(
+        &self,
+        encoder: &mut CommandEncoder,
+        target: &RenderTarget,
+        view: &TextureView,
+    ) {
+        match (
+            &target.scene_texture,
+            &target.post_pipeline,
+            &target.post_bind_group,
+        ) {
+            (Some(scene_texture), Some(pipeline), Some(bind_group)) => {
+                self.encode_scene(encoder, target, &scene_texture.view);
+                if let Some(bloom_textures) = &target.bloom_textures {
+                    self.bloom.encode(encoder, bloom_textures);
+                }
+                self.post_process.apply(encoder, view, pipeline, bind_group);
+            }
+            _ => self.encode_scene(encoder, target, view),
+        }
+    }
+
+    /// Record the commands to draw the scene into `view`, which has to have the same format as `target.scene_format`
+    fn encode_scene(
+        &self,
+        encoder: &mut CommandEncoder,
+        target: &RenderTarget,
+        view: &TextureView,
+    ) {
+        // Clamped every time, since the target could have shrunk since the mask was set
+        let stencil_mask = self
+            .stencil_mask
+            .and_then(|mask| mask.clamp_to(target.size));
+
+        // Separate from the rest, since it has its own depth buffer
+        if let (Some(gbuffer), Some(gbuffer_textures), Some(pipelines)) = (
+            &self.gbuffer,
+            &target.gbuffer_textures,
+            &self.gbuffer_pipelines,
+        ) {
+            // Which means the mask has to be written into that one too
+            if let Some(mask) = stencil_mask {
+                gbuffer_textures.draw_stencil_mask(
+                    encoder,
+                    gbuffer.stencil_mask_pipeline(),
+                    mask,
+                    self.stencil_reference,
+                );
+            }
+            let mut render_pass = gbuffer_textures.begin_pass(encoder, stencil_mask.is_some());
+            set_viewport(&mut render_pass, target.viewport);
+            render_pass.set_pipeline(&pipelines[cull_mode_index(self.cull_mode)]);
+            self.draw_geometry(&mut render_pass);
+        }
+
+        // Whether the depth and stencil still need clearing, which the first pass does
+        let mut clear_depth_stencil = true;
+        if let Some(mask) = stencil_mask {
+            let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+                label: Some("Stencil Mask Pass"),
+                // Only writing to the stencil
+                color_attachments: &[],
+                depth_stencil_attachment: Some(depth_stencil_attachment(
+                    &target.depth_texture.view,
+                    clear_depth_stencil,
+                )),
+            });
+            clear_depth_stencil = false;
+            stencil::draw_mask(
+                &mut render_pass,
+                &self.stencil_mask_pipeline,
+                mask,
+                self.stencil_reference,
+            );
+        }
+
+        // The debug views draw the geometry directly, skipping the lighting
+        let deferred = match (
+            self.deferred
+                .as_ref()
+                .filter(|_| self.debug_view == DebugView::Lit),
+            &target.deferred_pipeline,
+            &target.gbuffer_textures,
+        ) {
+            (Some(deferred), Some(pipeline), Some(gbuffer_textures)) => {
+                Some((deferred, pipeline, gbuffer_textures))
+            }
+            _ => None,
+        };
+        // Lines don't cover the same fragments as the filled triangles, so there's no point
+        // The deferred lighting only draws each pixel once anyway, and the debug views are cheap enough not to bother
+        let depth_prepass = self.depth_prepass
+            && !self.wireframe
+            && deferred.is_none()
+            && self.debug_view == DebugView::Lit;
+        if depth_prepass {
+            let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+                label: Some("Depth Prepass"),
+                // Only filling in the depth buffer, so there's no colour
+                color_attachments: &[],
+                depth_stencil_attachment: Some(depth_stencil_attachment(
+                    &target.depth_texture.view,
+                    clear_depth_stencil,
+                )),
+            });
+            clear_depth_stencil = false;
+            set_viewport(&mut render_pass, target.viewport);
+            render_pass.set_pipeline(&target.pipelines.for_cull_mode(self.cull_mode).depth_prepass);
+            self.draw_geometry(&mut render_pass);
+        }
+
+        // `render_pass` mutably borrows `encoder` until the end of this function
+        let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+            label: Some(&self.render_pass_label),
+            // Where we are going to draw our colour to, we use `view` to ensure we render to the screen
+            color_attachments: &[Some(RenderPassColorAttachment {
+                // Which texture to save the colours to, the multisampled texture if we have one
+                view: target.msaa_texture.as_ref().map_or(view, |msaa| &msaa.view),
+                // The texture that will recieve the resolved output, which is the screen when we're mutli-sampling
+                // Otherwise we're already drawing straight to the screen so we leave it as `None`
+                resolve_target: target.msaa_texture.as_ref().map(|_| view),
+                // Tells wgpu what to do with the colours on the screen
+                ops: Operations {
+                    // How to handle colors stored from the previous frame, currently we are clearing the screen with `self.clear_color`
+                    // When letterboxing the whole target gets the bar colour, and the viewport is filled in below
+                    load: LoadOp::Clear(match target.viewport {
+                        Some(_) => self.letterbox_color,
+                        None => self.clear_color,
+                    }),
+                    // Whether we want to store the rendered results to the `Texture` behind `view`
+                    store: true,
+                },
+            })],
+            // Keeps what the mask and the prepass wrote, if they ran
+            depth_stencil_attachment: Some(depth_stencil_attachment(
+                &target.depth_texture.view,
+                clear_depth_stencil,
+            )),
+        });
+
+        set_viewport(&mut render_pass, target.viewport);
+        if let (Some(_), Some(pipeline)) = (target.viewport, &target.letterbox_pipeline) {
+            // `clear_color` can be changed at any point, so it's just written every time
+            self.letterbox_fill.set_color(&self.queue, self.clear_color);
+            self.letterbox_fill.fill(&mut render_pass, pipeline);
+        }
+        // First, so it's behind everything
+        if let (Some(skybox), Some(pipeline)) = (&self.skybox, &target.skybox_pipeline) {
+            skybox.draw(&mut render_pass, pipeline);
+        }
+        match deferred {
+            Some((deferred, pipeline, gbuffer_textures)) => deferred.draw(
+                &mut render_pass,
+                pipeline,
+                &self.globals_bind_group,
+                &self.camera_bind_group,
+                gbuffer_textures.bind_group(),
+            ),
+            None => {
+                render_pass.set_pipeline(target.pipelines.for_cull_mode(self.cull_mode).get(
+                    self.blend_mode,
+                    self.wireframe,
+                    depth_prepass,
+                    self.debug_view,
+                ));
+                self.draw_geometry_with_materials(&mut render_pass, target, depth_prepass);
+            }
+        }
+        if let Some(pipeline) = &target.sprite_pipeline {
+            self.sprite_batch
+                .draw(&mut render_pass, pipeline, &self.camera_bind_group);
+        }
+        if let (Some(particles), Some(pipeline)) = (&self.particles, &target.particle_pipeline) {
+            particles.draw(&mut render_pass, pipeline, &self.camera_bind_group);
+        }
+        drop(render_pass);
+
+        if let (false, Some(pipeline)) = (self.debug_lines.is_empty(), &target.line_pipeline) {
+            let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+                label: Some("Debug Line Pass"),
+                // On top of the scene, resolving to `view` again when multisampling
+                color_attachments: &[Some(RenderPassColorAttachment {
+                    view: target.msaa_texture.as_ref().map_or(view, |msaa| &msaa.view),
+                    resolve_target: target.msaa_texture.as_ref().map(|_| view),
+                    ops: Operations {
+                        load: LoadOp::Load,
+                        store: true,
+                    },
+                })],
+                // The scene's depth, for hiding lines behind it
+                depth_stencil_attachment: Some(depth_stencil_attachment(
+                    &target.depth_texture.view,
+                    false,
+                )),
+            });
+            set_viewport(&mut render_pass, target.viewport);
+            self.debug_lines
+                .draw(&mut render_pass, pipeline, &self.camera_bind_group);
+        }
+    }
+
+    /// Bind everything the shader needs and draw every instance, with whatever pipeline is already set
+    fn draw_geometry<'a>(&'a self, render_pass: &mut RenderPass<'a>) {
+        self.draw_geometry_with(
+            render_pass,
+            &self.globals_bind_group,
+            &self.camera_bind_group,
+        );
+    }
+
+    /// The same as `draw_geometry()`, but with other bind groups in place of the globals and the camera
+    fn draw_geometry_with<'a>(
+        &'a self,
+        render_pass: &mut RenderPass<'a>,
+        globals_bind_group: &'a BindGroup,
+        camera_bind_group: &'a BindGroup,
+    ) {
+        render_pass.set_bind_group(0, globals_bind_group, &[]);
+        render_pass.set_bind_group(1, camera_bind_group, &[]);
+        render_pass.set_bind_group(2, &self.diffuse_bind_group, &[]);
+        render_pass.set_stencil_reference(self.stencil_reference);
+        // An empty buffer can't be bound, and there'd be nothing to draw anyway
+        if self.culling_stats.drawn == 0 {
+            return;
+        }
+        self.draw_builtin_geometry(render_pass);
+        for index in 0..self.meshes.len() {
+            self.draw_mesh(render_pass, index);
+        }
+    }
+
+    /// The same as `draw_geometry()`, but meshes with a material are drawn with its pipeline and texture
+    ///
+    /// The meshes are grouped by material, so each material's pipeline only gets set once
+    /// Wireframe and the debug views are for looking at the geometry itself, so they still draw everything the same way
+    fn draw_geometry_with_materials<'a>(
+        &'a self,
+        render_pass: &mut RenderPass<'a>,
+        target: &'a RenderTarget,
+        after_prepass: bool,
+    ) {
+        if self.wireframe || self.debug_view != DebugView::Lit {
+            self.draw_geometry(render_pass);
+            return;
+        }
+        render_pass.set_bind_group(0, &self.globals_bind_group, &[]);
+        render_pass.set_bind_group(1, &self.camera_bind_group, &[]);
+        render_pass.set_bind_group(2, &self.diffuse_bind_group, &[]);
+        render_pass.set_stencil_reference(self.stencil_reference);
+        if self.culling_stats.drawn == 0 {
+            return;
+        }
+        // With the pipeline that's already set, along with any meshes whose material doesn't have pipelines yet
+        self.draw_builtin_geometry(render_pass);
+        let material_index = |mesh: &Mesh| {
+            mesh.material
+                .map(|material| material.0)
+                .filter(|&index| index < target.material_pipelines.len())
+        };
+        for (index, _) in self
+            .meshes
+            .iter()
+            .enumerate()
+            .filter(|(_, mesh)| material_index(mesh).is_none())
+        {
+            self.draw_mesh(render_pass, index);
+        }
+        for (material_index, (material, pipelines)) in self
+            .materials
+            .iter()
+            .zip(&target.material_pipelines)
+            .enumerate()
+        {
+            let mut meshes = self
+                .meshes
+                .iter()
+                .enumerate()
+                .filter(|(_, mesh)| mesh.material == Some(MaterialId(material_index)))
+                .peekable();
+            // Not worth switching pipelines for nothing
+            if meshes.peek().is_none() {
+                continue;
+            }
+            render_pass.set_pipeline(pipelines.get(after_prepass));
+            render_pass.set_bind_group(
+                2,
+                material.bind_group().unwrap_or(&self.diffuse_bind_group),
+                &[],
+            );
+            render_pass.set_stencil_reference(
+                material
+                    .descriptor()
+                    .stencil_reference
+                    .unwrap_or(self.stencil_reference),
+            );
+            for (index, _) in meshes {
+                self.draw_mesh(render_pass, index);
+            }
+        }
+    }
+
+    /// Draw the built-in geometry once per instance, with the bind groups and pipeline that are already set
+    ///
+    /// Leaves the instance buffer bound for the meshes
+    fn draw_builtin_geometry<'a>(&'a self, render_pass: &mut RenderPass<'a>) {
+        // Only the ones that weren't culled are in the instance buffer
+        let instances = 0..self.culling_stats.drawn;
+        // The built-in geometry has the first transform
+        self.set_object_transform(render_pass, 0, self.transform);
+        // Slot 0 corresponds to the first entry in the pipeline's `VertexState.buffers`
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        render_pass.set_vertex_buffer(1, self.instance_buffer.slice(..));
+        match &self.index_buffer {
+            Some(index_buffer) => {
+                // We can only have one index buffer bound at a time
+                render_pass.set_index_buffer(index_buffer.slice(..), IndexFormat::Uint16);
+                match &self.draw_indirect {
+                    // However many indices and instances the GPU says
+                    Some(draw_indirect) => draw_indirect.draw(render_pass),
+                    // Draw everything in the index buffer, once per instance
+                    None => render_pass.draw_indexed(0..self.num_indices, 0, instances),
+                }
+            }
+            // Draw everything in the vertex buffer, once per instance
+            None => render_pass.draw(0..self.num_vertices, instances),
+        }
+    }
+
+    /// Draw `meshes[index]` once per instance, after `draw_builtin_geometry()` has bound the instance buffer
+    fn draw_mesh<'a>(&'a self, render_pass: &mut RenderPass<'a>, index: usize) {
+        let mesh = &self.meshes[index];
+        if mesh.num_indices == 0 {
+            return;
+        }
+        // Each mesh's transform comes after the built-in geometry's
+        self.set_object_transform(render_pass, index + 1, self.transform * mesh.transform);
+        render_pass.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
+        render_pass.set_index_buffer(mesh.index_buffer.slice(..), IndexFormat::Uint32);
+        for submesh in &mesh.submeshes {
+            render_pass.draw_indexed(
+                submesh.indices.clone(),
+                submesh.base_vertex,
+                0..self.culling_stats.drawn,
+            );
+        }
+    }
+
+    /// Draw with the object at `index`'s transform, either pushing `transform` or pointing at where `prepare_transforms()` put it
+    fn set_object_transform<'a>(
+        &'a self,
+        render_pass: &mut RenderPass<'a>,
+        index: usize,
+        transform: Mat4,
+    ) {
+        match &self.transform_binding {
+            TransformBinding::PushConstants => render_pass.set_push_constants(
+                ShaderStages::VERTEX,
+                0,
+                bytemuck::bytes_of(&transform),
+            ),
+            TransformBinding::Uniform(object_transforms) => render_pass.set_bind_group(
+                3,
+                object_transforms.bind_group(),
+                &[object_transforms.offset(index)],
+            ),
+        }
+    }
+}
+
+/// How the scene's pipelines test depth and stencil, before the depth prepass changes anything
+///
+/// `stencil` doesn't do anything unless `State::set_stencil()` was used
+fn depth_stencil_state(
+    depth_compare: CompareFunction,
+    stencil: &StencilState,
+) -> DepthStencilState {
+    DepthStencilState {
+        format: DEPTH_FORMAT,
+        depth_write_enabled: true,
+        depth_compare,
+        stencil: stencil.clone(),
+        bias: DepthBiasState::default(),
+    }
+}
+
+/// Attach `view` as the depth and stencil buffer, either clearing both or keeping what an earlier pass wrote
+fn depth_stencil_attachment(
+    view: &TextureView,
+    clear: bool,
+) -> RenderPassDepthStencilAttachment<'_> {
+    RenderPassDepthStencilAttachment {
+        view,
+        depth_ops: Some(Operations {
+            // Everything starts out infinitely far away
+            load: if clear {
+                LoadOp::Clear(1.0)
+            } else {
+                LoadOp::Load
+            },
+            store: true,
+        }),
+        stencil_ops: Some(Operations {
+            // Nothing's masked to begin with
+            load: if clear {
+                LoadOp::Clear(0)
+            } else {
+                LoadOp::Load
+            },
+            store: true,
+        }),
+    }
+}
+
+/// Limit drawing to `viewport`, if there is one (the default is the whole target)
+fn set_viewport(render_pass: &mut RenderPass, viewport: Option<Viewport>) {
+    if let Some(viewport) = viewport {
+        render_pass.set_viewport(
+            viewport.x,
+            viewport.y,
+            viewport.width,
+            viewport.height,
+            0.0,
+            1.0,
+        );
+    }
+}
+
+/// Compile `source` and build the render pipelines with it, returning the error if either step fails validation
+///
+/// There's one for every `BlendMode` (for normal drawing, and drawing after the depth prepass), plus the depth prepass itself
+/// The wireframe ones are only built if the device supports `Features::POLYGON_MODE_LINE`
+///
+/// Without this wgpu's default error handler would just panic on a broken shader
+fn build_pipelines(
+    device: &Device,
+    layout: &PipelineLayout,
+    prelude: &str,
+    source: &str,
+    format: TextureFormat,
+    sample_count: u32,
+    depth_stencil: &DepthStencilState,
+) -> Result<Pipelines, wgpu::Error> {
+    // Catch validation errors instead of letting them reach the default handler
+    device.push_error_scope(ErrorFilter::Validation);
+    let shader = device.create_shader_module(ShaderModuleDescriptor {
+        label: Some("Shader"),
+        source: ShaderSource::Wgsl(format!("{prelude}{source}").into()),
+    });
+    let build_view = |cull_mode, polygon_mode, blend_mode, depth_pass, debug_view| {
+        let variant = PipelineVariant {
+            cull_mode,
+            polygon_mode,
+            blend_mode,
+            depth_pass,
+            debug_view,
+        };
+        create_render_pipeline(
+            device,
+            layout,
+            &shader,
+            format,
+            sample_count,
+            depth_stencil,
+            variant,
+        )
+    };
+    let build = |cull_mode, polygon_mode, blend_mode, depth_pass| {
+        build_view(
+            cull_mode,
+            polygon_mode,
+            blend_mode,
+            depth_pass,
+            DebugView::Lit,
+        )
+    };
+    // They're all built up front so switching between them is free
+    let build_all = |cull_mode, polygon_mode, depth_pass| {
+        BlendMode::ALL.map(|blend_mode| build(cull_mode, polygon_mode, blend_mode, depth_pass))
+    };
+    let build_set = |cull_mode| PipelineSet {
+        fill: build_all(cull_mode, PolygonMode::Fill, DepthPass::Normal),
+        wireframe: device
+            .features()
+            .contains(Features::POLYGON_MODE_LINE)
+            .then(|| build_all(cull_mode, PolygonMode::Line, DepthPass::Normal)),
+        // The blend mode doesn't matter since there's no colour
+        depth_prepass: build(
+            cull_mode,
+            PolygonMode::Fill,
+            BlendMode::Replace,
+            DepthPass::Prepass,
+        ),
+        after_prepass: build_all(cull_mode, PolygonMode::Fill, DepthPass::AfterPrepass),
+        // Each in its own error scope, so a shader without some of the entry points still works
+        debug_views: DebugView::ALL[1..]
+            .iter()
+            .map(|&debug_view| {
+                device.push_error_scope(ErrorFilter::Validation);
+                let pipeline = build_view(
+                    cull_mode,
+                    PolygonMode::Fill,
+                    BlendMode::Replace,
+                    DepthPass::Normal,
+                    debug_view,
+                );
+                match pollster::block_on(device.pop_error_scope()) {
+                    Some(err) => {
+                        log::debug!(
+                            "No {debug_view:?} debug view for this shader: {}",
+                            error_description(&err)
+                        );
+                        None
+                    }
+                    None => Some(pipeline),
+                }
+            })
+            .collect(),
+    };
+    let pipelines = Pipelines {
+        by_cull_mode: CULL_MODES.map(build_set),
+    };
+    // Resolves immediately on native
+    match pollster::block_on(device.pop_error_scope()) {
+        Some(err) => Err(err),
+        None => Ok(pipelines),
+    }
+}
+
+/// Compile `source` with `prelude` (which declares `transform`) in front
+fn create_shader(device: &Device, prelude: &str, source: &str) -> ShaderModule {
+    device.create_shader_module(ShaderModuleDescriptor {
+        label: Some("Shader"),
+        source: ShaderSource::Wgsl(format!("{prelude}{source}").into()),
+    })
+}
+
+/// Build the pipelines for drawing with `material` into a target with `format`, using `shader` (the material's own or the scene's)
+fn build_material_pipelines(
+    device: &Device,
+    layout: &PipelineLayout,
+    shader: &ShaderModule,
+    material: &MaterialDescriptor,
+    format: TextureFormat,
+    sample_count: u32,
+    depth_stencil: &DepthStencilState,
+) -> MaterialPipelines {
+    // Its own stencil state takes the place of the scene's, the depth test stays the same
+    let depth_stencil = match &material.stencil {
+        Some(stencil) => DepthStencilState {
+            stencil: stencil.clone(),
+            ..depth_stencil.clone()
+        },
+        None => depth_stencil.clone(),
+    };
+    let build = |depth_pass| {
+        let variant = PipelineVariant {
+            cull_mode: material.cull_mode,
+            polygon_mode: PolygonMode::Fill,
+            blend_mode: material.blend_mode,
+            depth_pass,
+            debug_view: DebugView::Lit,
+        };
+        create_render_pipeline(
+            device,
+            layout,
+            shader,
+            format,
+            sample_count,
+            &depth_stencil,
+            variant,
+        )
+    };
+    MaterialPipelines {
+        fill: build(DepthPass::Normal),
+        after_prepass: build(DepthPass::AfterPrepass),
+    }
+}
+
+/// Compile `source` and build `gbuffer`'s pipelines with it, one for each of `CULL_MODES`, returning the error if either step fails validation
+fn build_gbuffer_pipelines(
+    device: &Device,
+    layout: &PipelineLayout,
+    prelude: &str,
+    source: &str,
+    gbuffer: &GBuffer,
+    depth_stencil: &DepthStencilState,
+) -> Result<[RenderPipeline; CULL_MODES.len()], wgpu::Error> {
+    device.push_error_scope(ErrorFilter::Validation);
+    let shader = device.create_shader_module(ShaderModuleDescriptor {
+        label: Some("Shader"),
+        source: ShaderSource::Wgsl(format!("{prelude}{source}").into()),
+    });
+    let pipelines = CULL_MODES.map(|cull_mode| {
+        gbuffer.create_pipeline(device, layout, &shader, cull_mode, depth_stencil)
+    });
+    match pollster::block_on(device.pop_error_scope()) {
+        Some(err) => Err(err),
+        None => Ok(pipelines),
+    }
+}
+
+/// Compile `source` and build the shadow map's pipeline with it, returning the error if either step fails validation
+fn build_shadow_pipeline(
+    device: &Device,
+    layout: &PipelineLayout,
+    prelude: &str,
+    source: &str,
+    bias: DepthBiasState,
+) -> Result<RenderPipeline, wgpu::Error> {
+    device.push_error_scope(ErrorFilter::Validation);
+    let shader = device.create_shader_module(ShaderModuleDescriptor {
+        label: Some("Shader"),
+        source: ShaderSource::Wgsl(format!("{prelude}{source}").into()),
+    });
+    let pipeline = shadow::create_pipeline(device, layout, &shader, bias);
+    match pollster::block_on(device.pop_error_scope()) {
+        Some(err) => Err(err),
+        None => Ok(pipeline),
+    }
+}
+
+/// `wgpu::Error`'s `Display` just says "Validation Error", the actual message (e.g. which line of WGSL is wrong) is in the description
+pub fn error_description(err: &wgpu::Error) -> String {
+    match err {
+        wgpu::Error::Validation { description, .. } => description.clone(),
+        wgpu::Error::OutOfMemory { .. } => err.to_string(),
+    }
+}
+
+/// How the depth buffer is used by a pipeline
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DepthPass {
+    /// Draw a fragment if it's closer than what's already there, and store its depth
+    Normal,
+    /// Only store depth, without drawing any colour
+    Prepass,
+    /// Draw a fragment only if it's exactly what the prepass stored, i.e. it's the closest one
+    AfterPrepass,
+}
+
+/// What differs between the pipelines in `Pipelines`
+#[derive(Debug, Clone, Copy)]
+struct PipelineVariant {
+    /// Which triangles get culled, one of `CULL_MODES`
+    cull_mode: Option<Face>,
+    polygon_mode: PolygonMode,
+    blend_mode: BlendMode,
+    depth_pass: DepthPass,
+    /// Picks the fragment shader's entry point
+    debug_view: DebugView,
+}
+
+/// Build the pipeline that draws our vertices with `shader`
+fn create_render_pipeline(
+    device: &Device,
+    layout: &PipelineLayout,
+    shader: &ShaderModule,
+    format: TextureFormat,
+    sample_count: u32,
+    depth_stencil: &DepthStencilState,
+    variant: PipelineVariant,
+) -> RenderPipeline {
+    let PipelineVariant {
+        cull_mode,
+        polygon_mode,
+        blend_mode,
+        depth_pass,
+        debug_view,
+    } = variant;
+    let label = format!(
+        "{blend_mode:?} {polygon_mode:?} {depth_pass:?} {debug_view:?} Cull {cull_mode:?} Render Pipeline"
+    );
+    // Tells `wgpu` what colour outputs it should set up
+    // We only need one for the `surface`
+    let color_targets = [Some(ColorTargetState {
+        // We copy `surface`'s format so that copying to it is easy
+        format,
+        // How to combine new pixel data with the old
+        blend: Some(blend_mode.blend_state()),
+        // Write to all colours
+        write_mask: ColorWrites::ALL,
+    })];
+    device.create_render_pipeline(&RenderPipelineDescriptor {
+        label: Some(&label),
+        layout: Some(layout),
+        vertex: VertexState {
+            module: shader,
+            // The function we marked with `@vertex`
+            entry_point: "vs_main",
+            // Tells `wgpu` what type of vertices we want to pass to the vertex shader
+            buffers: &[Vertex::desc(), Instance::desc()],
+        },
+        // Technically optional, and the depth prepass doesn't need one since it only writes depth
+        fragment: (depth_pass != DepthPass::Prepass).then_some(FragmentState {
+            module: shader,
+            // The function we marked with `@fragment`, which is `fs_main` unless it's a debug view
+            entry_point: debug_view.entry_point(),
+            targets: &color_targets,
+        }),
+        primitive: PrimitiveState {
+            // Every 3 vertices will correspond to 1 trongle
+            topology: PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            // How to determine whether a triangle is facing forwards (if its counter-clockwise)
+            front_face: FrontFace::Ccw,
+            // Cull any triangles facing backwards, unless `State::set_cull_mode()` says otherwise
+            cull_mode,
+            // `PolygonMode::Line` requires `Features::POLYGON_MODE_LINE`
+            polygon_mode,
+            // Requires `Features::DEPTH_CLIP_CONTROL`
+            unclipped_depth: false,
+            // Requires `Features::CONSERVATIVE_RASTERIZATION`
+            conservative: false,
+        },
+        depth_stencil: Some(DepthStencilState {
+            // Store the depth of each fragment we draw, unless the prepass already did
+            depth_write_enabled: depth_pass != DepthPass::AfterPrepass,
+            // After the prepass, draw a fragment only if it's the one the prepass kept
+            depth_compare: match depth_pass {
+                DepthPass::Normal | DepthPass::Prepass => depth_stencil.depth_compare,
+                DepthPass::AfterPrepass => CompareFunction::Equal,
+            },
+            ..depth_stencil.clone()
+        }),
+        multisample: MultisampleState {
+            // How many samples the pipeline will use, has to match the render attachments
+            count: sample_count,
+            // Which samples should be active
+            mask: !0,
+            // To do with anti-aliasing
+            alpha_to_coverage_enabled: false,
+        },
+        // How many array layers the render attachments can have, we won't be rendering to array textures
+        multiview: None,
+    })
+}
+
+/// The built-in texture, drawn until another one's loaded
+fn create_checker_texture(device: &Device, queue: &Queue, options: TextureOptions) -> Texture {
+    Texture::from_bytes(
+        device,
+        queue,
+        include_bytes!("checker.png"),
+        Some("Checker Texture"),
+        options,
+    )
+    .expect("the built-in texture should be a valid PNG")
+}
+
+/// The layout of `@group(0)`: the globals and the light, then the shadow uniform, the shadow map and its comparison sampler
+fn create_globals_bind_group_layout(device: &Device) -> BindGroupLayout {
+    let uniform = |binding, visibility| BindGroupLayoutEntry {
+        binding,
+        visibility,
+        ty: BindingType::Buffer {
+            ty: BufferBindingType::Uniform,
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        },
+        count: None,
+    };
+    device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+        label: Some("Globals Bind Group Layout"),
+        entries: &[
+            uniform(0, ShaderStages::VERTEX | ShaderStages::FRAGMENT),
+            uniform(1, ShaderStages::VERTEX | ShaderStages::FRAGMENT),
+            uniform(2, ShaderStages::FRAGMENT),
+            BindGroupLayoutEntry {
+                binding: 3,
+                visibility: ShaderStages::FRAGMENT,
+                ty: BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: TextureViewDimension::D2,
+                    sample_type: TextureSampleType::Depth,
+                },
+                count: None,
+            },
+            BindGroupLayoutEntry {
+                binding: 4,
+                visibility: ShaderStages::FRAGMENT,
+                ty: BindingType::Sampler(SamplerBindingType::Comparison),
+                count: None,
+            },
+        ],
+    })
+}
+
+/// Bind `buffers` at 0, 1 and 2 and `shadow_map` at 3 and 4, matching `create_globals_bind_group_layout()`
+fn create_globals_bind_group(
+    device: &Device,
+    layout: &BindGroupLayout,
+    buffers: [&Buffer; 3],
+    shadow_map: &Texture,
+) -> BindGroup {
+    let mut entries: Vec<_> = buffers
+        .iter()
+        .zip(0..)
+        .map(|(buffer, binding)| BindGroupEntry {
+            binding,
+            resource: buffer.as_entire_binding(),
+        })
+        .collect();
+    entries.push(BindGroupEntry {
+        binding: 3,
+        resource: BindingResource::TextureView(&shadow_map.view),
+    });
+    entries.push(BindGroupEntry {
+        binding: 4,
+        resource: BindingResource::Sampler(&shadow_map.sampler),
+    });
+    device.create_bind_group(&BindGroupDescriptor {
+        label: Some("Globals Bind Group"),
+        layout,
+        entries: &entries,
+    })
+}
+
+/// Create a bind group with each of `buffers` as a uniform, the first at `@binding(0)` and so on, along with its layout
+fn create_uniform_bind_group(
+    device: &Device,
+    name: &str,
+    buffers: &[&Buffer],
+    visibility: ShaderStages,
+) -> (BindGroupLayout, BindGroup) {
+    let layout_entries: Vec<_> = (0..buffers.len() as u32)
+        .map(|binding| BindGroupLayoutEntry {
+            // Corresponds to `@binding(n)` in the shader
+            binding,
+            visibility,
+            ty: BindingType::Buffer {
+                ty: BufferBindingType::Uniform,
+                // The uniform never moves around within the buffer
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            // Not an array of buffers
+            count: None,
+        })
+        .collect();
+    // Describes the shape of the bind group, so pipelines can be created without a specific bind group
+    let layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+        label: Some(&format!("{name} Bind Group Layout")),
+        entries: &layout_entries,
+    });
+    let entries: Vec<_> = buffers
+        .iter()
+        .zip(0..)
+        .map(|(buffer, binding)| BindGroupEntry {
+            binding,
+            resource: buffer.as_entire_binding(),
+        })
+        .collect();
+    let bind_group = device.create_bind_group(&BindGroupDescriptor {
+        label: Some(&format!("{name} Bind Group")),
+        layout: &layout,
+        entries: &entries,
+    });
+    (layout, bind_group)
+}
+
+/// The first format in `formats` that is (or isn't) sRGB depending on `srgb`, falling back to the first format if none match
+///
+/// Surfaces list their formats in whatever order the platform likes, so just taking the first gives inconsistent gamma
+fn pick_surface_format(formats: &[TextureFormat], srgb: bool) -> Option<TextureFormat> {
+    formats
+        .iter()
+        .find(|format| format.describe().srgb == srgb)
+        .or_else(|| formats.first())
+        .copied()
+}
+
+/// Ask `adapter` for the `Device` and `Queue` we do all our work with
+///
+/// `OPTIONAL_FEATURES` get added to `features` if the adapter supports them
+async fn request_device(
+    adapter: &Adapter,
+    features: Features,
+    mut limits: Limits,
+) -> Result<(Device, Queue), RequestDeviceError> {
+    // Turn on whichever optional features the adapter happens to support
+    let features = features | (adapter.features() & OPTIONAL_FEATURES);
+    if features.contains(Features::PUSH_CONSTANTS) {
+        limits.max_push_constant_size = limits.max_push_constant_size.max(PUSH_CONSTANT_SIZE);
+    }
+    adapter
+        .request_device(
+            &DeviceDescriptor {
+                features,
+                limits,
+                label: None,
+            },
+            None,
+        )
+        .await
+}
+
+/// Find an adapter that can draw to `compatible_surface`, trying `power_preference` first
+///
+/// Some machines don't expose their discrete GPU to the surface, so if there isn't one we try the other preference, and then a software adapter
+async fn request_adapter(
+    instance: &wgpu::Instance,
+    power_preference: PowerPreference,
+    compatible_surface: Option<&Surface>,
+) -> Option<Adapter> {
+    let other_preference = match power_preference {
+        PowerPreference::HighPerformance => PowerPreference::LowPower,
+        PowerPreference::LowPower => PowerPreference::HighPerformance,
+    };
+    let tiers = [
+        (power_preference, false),
+        (other_preference, false),
+        (power_preference, true),
+    ];
+    for (power_preference, force_fallback_adapter) in tiers {
+        let adapter = instance
+            .request_adapter(&RequestAdapterOptions {
+                power_preference,
+                compatible_surface,
+                force_fallback_adapter,
+            })
+            .await;
+        let tier = if force_fallback_adapter {
+            "fallback".to_owned()
+        } else {
+            format!("{power_preference:?}")
+        };
+        match adapter {
+            Some(adapter) => {
+                log::info!("Got an adapter from the {tier} tier");
+                return Some(adapter);
+            }
+            None => log::warn!("Couldn't get a {tier} adapter"),
+        }
+    }
+    None
+}
+
+/// The adapter at `index` in `State::list_adapters()`, as long as it can draw to `compatible_surface`
+#[cfg(not(target_arch = "wasm32"))]
+fn adapter_by_index(
+    instance: &wgpu::Instance,
+    index: usize,
+    compatible_surface: Option<&Surface>,
+) -> Result<Adapter, StateInitError> {
+    // The instance only has the backends it was created with, so this lists the same adapters as `list_adapters()`
+    let adapter = instance
+        .enumerate_adapters(Backends::all())
+        .nth(index)
+        .ok_or(StateInitError::NoAdapterAtIndex(index))?;
+    if let Some(surface) = compatible_surface {
+        if !adapter.is_surface_supported(surface) {
+            return Err(StateInitError::UnsupportedSurface);
+        }
+    }
+    log::info!("Using adapter {index}, as asked");
+    Ok(adapter)
+}
+
+/// Upload the model matrices of `instances` to the GPU
+fn create_instance_buffer(device: &Device, instances: &[Instance]) -> Buffer {
+    let raw: Vec<_> = instances.iter().map(Instance::to_raw).collect();
+    device.create_buffer_init(&BufferInitDescriptor {
+        label: Some("Instance Buffer"),
+        contents: bytemuck::cast_slice(&raw),
+        // `COPY_DST` so `set_instances()` can overwrite it in place
+        usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
+    })
+}
+
+/// Upload `indices` to the GPU, or `None` if there aren't any since empty buffers can't be bound
+fn create_index_buffer(device: &Device, indices: &[u16]) -> Option<Buffer> {
+    if indices.is_empty() {
+        return None;
+    }
+    Some(device.create_buffer_init(&BufferInitDescriptor {
+        label: Some("Index Buffer"),
+        contents: bytemuck::cast_slice(indices),
+        usage: BufferUsages::INDEX,
+    }))
+}
+
+/// Whether the adapter can draw the scene in `format` with `sample_count` samples, and filter it afterwards
+///
+/// With multisampling the scene's resolved straight into the texture post-processing reads from, so both are in `format`
+fn supports_hdr(adapter: &Adapter, format: TextureFormat, sample_count: u32) -> bool {
+    let features = adapter.get_texture_format_features(format);
+    features
+        .allowed_usages
+        .contains(TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING)
+        && features
+            .flags
+            .contains(TextureFormatFeatureFlags::FILTERABLE)
+        && (sample_count == 1
+            || features
+                .flags
+                .contains(TextureFormatFeatureFlags::MULTISAMPLE))
+}
+
+/// Whether `format` can hold colours brighter than 1, i.e. it's one of the floating point colour formats
+///
+/// Formats like `Rgba8Unorm` are renderable and filterable too, but clip everything at 1
+fn is_float_format(format: TextureFormat) -> bool {
+    matches!(
+        format,
+        TextureFormat::R16Float
+            | TextureFormat::Rg16Float
+            | TextureFormat::Rgba16Float
+            | TextureFormat::R32Float
+            | TextureFormat::Rg32Float
+            | TextureFormat::Rgba32Float
+            | TextureFormat::Rg11b10Float
+    )
+}
+
+/// Indirect args that draw all `(index_count, instance_count)` indices and instances
+fn draw_everything((index_count, instance_count): (u32, u32)) -> DrawIndexedIndirectArgs {
+    DrawIndexedIndirectArgs {
+        index_count,
+        instance_count,
+        ..DrawIndexedIndirectArgs::default()
+    }
+}
+
+/// Use `requested` samples if the adapter can multisample `format` (and the depth buffer), otherwise fall back to no multisampling
+///
+/// The same check as `State::set_sample_count()`, so the count we start with is one it would have accepted
+fn pick_sample_count(adapter: &Adapter, format: TextureFormat, requested: u32) -> u32 {
+    if requested <= 1 {
+        return requested;
+    }
+    match find_unmultisampleable(adapter, std::iter::once(format)) {
+        Some(format) => {
+            log::warn!("{format:?} can't be multisampled on this adapter, disabling MSAA");
+            1
+        }
+        None => requested,
+    }
+}
+
+/// The first of `DEPTH_FORMAT` and `formats` the adapter can't multisample, if there is one
+fn find_unmultisampleable(
+    adapter: &Adapter,
+    formats: impl IntoIterator<Item = TextureFormat>,
+) -> Option<TextureFormat> {
+    std::iter::once(DEPTH_FORMAT).chain(formats).find(|format| {
+        !adapter
+            .get_texture_format_features(*format)
+            .flags
+            .contains(TextureFormatFeatureFlags::MULTISAMPLE)
+    })
 }
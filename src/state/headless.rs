@@ -0,0 +1,170 @@
+use std::{
+    num::NonZeroU32,
+    sync::{mpsc, Arc},
+};
+
+use image::RgbaImage;
+use wgpu::{
+    util::backend_bits_from_env, Backends, Buffer, BufferDescriptor, BufferUsages,
+    CompositeAlphaMode, Extent3d, Features, ImageCopyBuffer, ImageCopyTexture, ImageDataLayout,
+    Instance, Maintain, MapMode, Origin3d, PowerPreference, PresentMode, SurfaceConfiguration,
+    TextureAspect, TextureFormat, TextureUsages, COPY_BYTES_PER_ROW_ALIGNMENT,
+};
+
+use super::{request_adapter, request_device, State, StateInitError};
+use crate::limits_profile::LimitsProfile;
+use crate::texture;
+
+/// The format we render in when there's no surface to match
+const HEADLESS_FORMAT: TextureFormat = TextureFormat::Rgba8UnormSrgb;
+
+impl State {
+    /// Create a `State` that renders into an offscreen texture instead of a window, e.g. for screenshot tests
+    ///
+    /// The backend can still be picked with `WGPU_BACKEND`
+    pub async fn new_headless(width: u32, height: u32) -> Result<Self, StateInitError> {
+        let instance = Arc::new(Instance::new(
+            backend_bits_from_env().unwrap_or_else(Backends::all),
+        ));
+        // No surface, so any adapter will do
+        let adapter = request_adapter(&instance, PowerPreference::HighPerformance, None)
+            .await
+            .ok_or(StateInitError::NoAdapter)?;
+        let (device, queue) = request_device(
+            &adapter,
+            Features::empty(),
+            LimitsProfile::Downlevel.limits(&adapter),
+        )
+        .await?;
+
+        // There's no surface to configure, but the rest of `State` still uses this to know what it's drawing into
+        let config = SurfaceConfiguration {
+            usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::COPY_SRC,
+            format: HEADLESS_FORMAT,
+            width,
+            height,
+            present_mode: PresentMode::Fifo,
+            alpha_mode: CompositeAlphaMode::Auto,
+        };
+
+        // With no surface, the target makes an offscreen texture to draw into instead
+        Self::with_device(instance, adapter, device, queue, None, None, config)
+    }
+
+    /// The old name for `capture_frame()`, from before it worked with a window too
+    #[deprecated(note = "renamed to `capture_frame()`")]
+    pub fn render_to_image(&mut self) -> RgbaImage {
+        self.capture_frame()
+    }
+
+    /// Render a frame and copy it back to the CPU, e.g. for screenshots
+    ///
+    /// Surface textures can't be copied from, so when we have a window a temporary target is created for the frame
+    pub fn capture_frame(&mut self) -> RgbaImage {
+        self.prepare_transforms();
+        self.prepare_shadows();
+        self.prepare_skybox(&self.camera.clone());
+        self.prepare_deferred();
+        self.prepare_sprites();
+        self.prepare_materials();
+        self.prepare_particles();
+        self.prepare_debug_lines();
+        self.prepare_draw_indirect();
+        let mut staging_belt = self
+            .staging_belt
+            .take()
+            .expect("the staging belt is only taken out during render()");
+        let primary = self.primary_target();
+        let config = &primary.config;
+        let temporary_target;
+        let target = match &primary.offscreen_target {
+            Some(target) => target,
+            None => {
+                temporary_target = texture::create_render_target(&self.device, config);
+                &temporary_target
+            }
+        };
+        let (width, height) = (config.width, config.height);
+
+        // `copy_texture_to_buffer()` needs every row to start on a multiple of 256 bytes, so we pad them out
+        let bytes_per_pixel = config.format.describe().block_size as u32;
+        let unpadded_bytes_per_row = width * bytes_per_pixel;
+        let padded_bytes_per_row = unpadded_bytes_per_row.div_ceil(COPY_BYTES_PER_ROW_ALIGNMENT)
+            * COPY_BYTES_PER_ROW_ALIGNMENT;
+        let output_buffer = self.device.create_buffer(&BufferDescriptor {
+            label: Some("Readback Buffer"),
+            size: (padded_bytes_per_row * height) as u64,
+            usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = self.create_encoder();
+        self.write_uniforms(&mut staging_belt, &mut encoder, primary.size);
+        self.record_passes(&mut encoder, primary, &target.view);
+        encoder.copy_texture_to_buffer(
+            ImageCopyTexture {
+                texture: &target.texture,
+                mip_level: 0,
+                origin: Origin3d::ZERO,
+                aspect: TextureAspect::All,
+            },
+            ImageCopyBuffer {
+                buffer: &output_buffer,
+                layout: ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: NonZeroU32::new(padded_bytes_per_row),
+                    rows_per_image: NonZeroU32::new(height),
+                },
+            },
+            Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+        );
+        staging_belt.finish();
+        self.queue.submit(std::iter::once(encoder.finish()));
+        staging_belt.recall();
+
+        let padded = self.read_buffer(&output_buffer);
+        let mut pixels = Vec::with_capacity((unpadded_bytes_per_row * height) as usize);
+        for row in padded.chunks(padded_bytes_per_row as usize) {
+            pixels.extend_from_slice(&row[..unpadded_bytes_per_row as usize]);
+        }
+
+        // Surfaces are often BGRA, but `RgbaImage` is, well, RGBA
+        if matches!(
+            config.format,
+            TextureFormat::Bgra8Unorm | TextureFormat::Bgra8UnormSrgb
+        ) {
+            for pixel in pixels.chunks_exact_mut(4) {
+                pixel.swap(0, 2);
+            }
+        }
+        // Its buffers were mapped again while waiting for the readback
+        self.staging_belt = Some(staging_belt);
+
+        RgbaImage::from_raw(width, height, pixels)
+            .expect("the buffer should hold exactly `width * height` pixels")
+    }
+
+    /// Map `buffer` and copy out its contents, waiting for the GPU to finish with it first
+    ///
+    /// `buffer` needs `BufferUsages::MAP_READ`
+    pub(super) fn read_buffer(&self, buffer: &Buffer) -> Vec<u8> {
+        // Mapping happens asynchronously, so we wait for the GPU to finish before reading
+        let buffer_slice = buffer.slice(..);
+        let (sender, receiver) = mpsc::channel();
+        buffer_slice.map_async(MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+        self.poll(Maintain::Wait);
+        receiver
+            .recv()
+            .expect("the map callback should have run after waiting on the device")
+            .expect("failed to map the readback buffer");
+        let data = buffer_slice.get_mapped_range().to_vec();
+        buffer.unmap();
+        data
+    }
+}
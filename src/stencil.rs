@@ -0,0 +1,125 @@
+use wgpu::{
+    CompareFunction, DepthBiasState, DepthStencilState, Device, MultisampleState,
+    PipelineLayoutDescriptor, PrimitiveState, RenderPass, RenderPipeline, RenderPipelineDescriptor,
+    ShaderModuleDescriptor, ShaderSource, StencilFaceState, StencilOperation, StencilState,
+    VertexState,
+};
+use winit::dpi::PhysicalSize;
+
+use crate::texture::DEPTH_FORMAT;
+
+/// A rectangle of the target in physical pixels, (0, 0) is the top left
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MaskRect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl MaskRect {
+    /// The part of the rectangle that's inside a target that's `size` big, `None` if none of it is
+    ///
+    /// Scissor rects outside the target fail validation
+    pub fn clamp_to(self, size: PhysicalSize<u32>) -> Option<Self> {
+        let x = self.x.min(size.width);
+        let y = self.y.min(size.height);
+        let width = self.width.min(size.width - x);
+        let height = self.height.min(size.height - y);
+        (width > 0 && height > 0).then_some(Self {
+            x,
+            y,
+            width,
+            height,
+        })
+    }
+}
+
+/// The stencil test that only passes where the mask was drawn with `reference`, i.e. masking to the inside of it
+pub fn inside_mask() -> StencilState {
+    test_mask(CompareFunction::Equal)
+}
+
+/// The stencil test that only passes where the mask wasn't drawn, i.e. cutting a hole in the scene
+pub fn outside_mask() -> StencilState {
+    test_mask(CompareFunction::NotEqual)
+}
+
+/// Compare against the reference value without ever writing to the stencil
+fn test_mask(compare: CompareFunction) -> StencilState {
+    let face = StencilFaceState {
+        compare,
+        fail_op: StencilOperation::Keep,
+        depth_fail_op: StencilOperation::Keep,
+        pass_op: StencilOperation::Keep,
+    };
+    StencilState {
+        front: face,
+        back: face,
+        read_mask: 0xff,
+        write_mask: 0,
+    }
+}
+
+/// Build the pipeline that writes the stencil reference value into a `MaskRect`, in a pass of its own before the scene
+pub fn create_mask_pipeline(device: &Device, sample_count: u32) -> RenderPipeline {
+    let shader = device.create_shader_module(ShaderModuleDescriptor {
+        label: Some("Stencil Mask Shader"),
+        source: ShaderSource::Wgsl(include_str!("stencil_mask.wgsl").into()),
+    });
+    let layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+        label: Some("Stencil Mask Pipeline Layout"),
+        bind_group_layouts: &[],
+        push_constant_ranges: &[],
+    });
+    // Every fragment that gets drawn replaces the stencil with the reference value
+    let face = StencilFaceState {
+        compare: CompareFunction::Always,
+        fail_op: StencilOperation::Keep,
+        depth_fail_op: StencilOperation::Keep,
+        pass_op: StencilOperation::Replace,
+    };
+    device.create_render_pipeline(&RenderPipelineDescriptor {
+        label: Some("Stencil Mask Pipeline"),
+        layout: Some(&layout),
+        vertex: VertexState {
+            module: &shader,
+            entry_point: "vs_main",
+            // The triangle is made up in the vertex shader
+            buffers: &[],
+        },
+        // Only writes the stencil
+        fragment: None,
+        primitive: PrimitiveState::default(),
+        depth_stencil: Some(DepthStencilState {
+            format: DEPTH_FORMAT,
+            depth_write_enabled: false,
+            depth_compare: CompareFunction::Always,
+            stencil: StencilState {
+                front: face,
+                back: face,
+                read_mask: 0xff,
+                write_mask: 0xff,
+            },
+            bias: DepthBiasState::default(),
+        }),
+        multisample: MultisampleState {
+            count: sample_count,
+            ..MultisampleState::default()
+        },
+        multiview: None,
+    })
+}
+
+/// Write `reference` into the stencil wherever `rect` covers, using the pipeline from `create_mask_pipeline()`
+pub fn draw_mask<'a>(
+    render_pass: &mut RenderPass<'a>,
+    pipeline: &'a RenderPipeline,
+    rect: MaskRect,
+    reference: u32,
+) {
+    render_pass.set_pipeline(pipeline);
+    render_pass.set_scissor_rect(rect.x, rect.y, rect.width, rect.height);
+    render_pass.set_stencil_reference(reference);
+    render_pass.draw(0..3, 0..1);
+}
@@ -0,0 +1,64 @@
+use wgpu::{util::StagingBelt, CommandEncoder, Device, TextureFormat, TextureView};
+use wgpu_glyph::{ab_glyph::FontArc, GlyphBrush, GlyphBrushBuilder, Section, Text};
+use winit::dpi::PhysicalSize;
+
+/// How big the staging belt's chunks are, plenty for a few lines of text
+const STAGING_CHUNK_SIZE: u64 = 1024;
+
+/// Draws text on top of whatever's already in a texture
+pub struct TextBrush {
+    brush: GlyphBrush<()>,
+    /// Used to upload the glyph vertices, has to be told when the frame is submitted
+    staging_belt: StagingBelt,
+}
+
+impl TextBrush {
+    /// Create a brush for drawing into textures with `format`, using the font bundled in src/fonts
+    pub fn new(device: &Device, format: TextureFormat) -> Self {
+        let font = FontArc::try_from_slice(include_bytes!("fonts/DejaVuSansMono.ttf"))
+            .expect("the built-in font should be a valid TTF");
+        Self {
+            brush: GlyphBrushBuilder::using_font(font).build(device, format),
+            staging_belt: StagingBelt::new(STAGING_CHUNK_SIZE),
+        }
+    }
+
+    /// Record drawing `text` in the top-left corner of `view`, which is `size` big
+    ///
+    /// Has to be followed by `submitted()` once the encoder has been submitted
+    pub fn draw(
+        &mut self,
+        device: &Device,
+        encoder: &mut CommandEncoder,
+        view: &TextureView,
+        size: PhysicalSize<u32>,
+        text: &str,
+    ) {
+        self.brush.queue(Section {
+            screen_position: (10.0, 10.0),
+            // Wrap at the edge of the target, so it follows the window when it's resized
+            bounds: (size.width as f32 - 20.0, size.height as f32 - 20.0),
+            text: vec![Text::new(text)
+                .with_color([1.0, 1.0, 1.0, 1.0])
+                .with_scale(20.0)],
+            ..Section::default()
+        });
+        if let Err(err) = self.brush.draw_queued(
+            device,
+            &mut self.staging_belt,
+            encoder,
+            view,
+            size.width,
+            size.height,
+        ) {
+            log::error!("Failed to draw text: {err}");
+        }
+        // No more writes to the belt this frame
+        self.staging_belt.finish();
+    }
+
+    /// Let the staging belt reuse its buffers, once the commands from `draw()` have been submitted
+    pub fn submitted(&mut self) {
+        self.staging_belt.recall();
+    }
+}
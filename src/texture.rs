@@ -0,0 +1,698 @@
+use std::{
+    fs,
+    num::{NonZeroU32, NonZeroU8},
+    path::Path,
+};
+
+use image::{
+    error::{LimitError, LimitErrorKind, ParameterError, ParameterErrorKind},
+    DynamicImage, GenericImageView, ImageError, ImageResult,
+};
+use wgpu::{
+    Adapter, AddressMode, BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout,
+    BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingResource, BindingType, CompareFunction,
+    Device, DownlevelFlags, Extent3d, FilterMode, ImageCopyTexture, ImageDataLayout, Origin3d,
+    Queue, Sampler, SamplerBindingType, SamplerDescriptor, ShaderStages, SurfaceConfiguration,
+    TextureAspect, TextureDescriptor, TextureDimension, TextureFormat, TextureSampleType,
+    TextureUsages, TextureView, TextureViewDescriptor, TextureViewDimension,
+};
+use winit::dpi::PhysicalSize;
+
+use crate::compressed_texture::{self, CompressedImage, CompressedTextureError};
+use crate::mipmap;
+
+/// The format used for all depth buffers, with 8 bits of stencil alongside the depth
+pub const DEPTH_FORMAT: TextureFormat = TextureFormat::Depth24PlusStencil8;
+/// What the scene gets drawn in when post-processing, so colours can go past 1 until they're tonemapped
+pub const HDR_FORMAT: TextureFormat = TextureFormat::Rgba16Float;
+
+/// The most anisotropic filtering wgpu lets a sampler ask for
+pub const MAX_ANISOTROPY: u8 = 16;
+
+/// How a texture gets uploaded and sampled, see `Texture::load()`
+///
+/// The default is an sRGB texture with mipmaps and no anisotropic filtering
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TextureOptions {
+    srgb: bool,
+    generate_mipmaps: bool,
+    anisotropy: u8,
+    /// Whether the adapter `anisotropy` was set for has `DownlevelFlags::ANISOTROPIC_FILTERING`
+    anisotropic_filtering: bool,
+}
+
+impl Default for TextureOptions {
+    fn default() -> Self {
+        Self {
+            srgb: true,
+            generate_mipmaps: true,
+            anisotropy: 1,
+            anisotropic_filtering: false,
+        }
+    }
+}
+
+impl TextureOptions {
+    /// `true` for anything that holds colours, and `false` for data like normal maps
+    pub fn srgb(mut self, srgb: bool) -> Self {
+        self.srgb = srgb;
+        self
+    }
+
+    /// Fill in a full mip chain, which stops it shimmering when it's far away (or otherwise shrunk)
+    ///
+    /// Compressed textures keep whatever levels the file has instead
+    pub fn generate_mipmaps(mut self, generate_mipmaps: bool) -> Self {
+        self.generate_mipmaps = generate_mipmaps;
+        self
+    }
+
+    /// How many samples to take along surfaces seen at a grazing angle (1, 2, 4, 8 or 16), which keeps floors sharp
+    ///
+    /// It's rounded down to one of those, and only does anything with mipmaps and if `adapter` supports it
+    pub fn anisotropy(mut self, anisotropy: u8, adapter: &Adapter) -> Self {
+        self.anisotropy = anisotropy;
+        self.anisotropic_filtering = adapter
+            .get_downlevel_capabilities()
+            .flags
+            .contains(DownlevelFlags::ANISOTROPIC_FILTERING);
+        self
+    }
+}
+
+/// A GPU texture along with a view into it and a sampler to read it with
+pub struct Texture {
+    pub texture: wgpu::Texture,
+    pub view: TextureView,
+    pub sampler: Sampler,
+}
+
+impl Texture {
+    /// Load an image file (only PNGs for now) into a texture, set up with `options`
+    pub fn load(
+        device: &Device,
+        queue: &Queue,
+        path: impl AsRef<Path>,
+        options: TextureOptions,
+    ) -> ImageResult<Self> {
+        let path = path.as_ref();
+        let image = image::open(path)?;
+        Self::from_image(device, queue, &image, path.to_str(), options)
+    }
+
+    /// Decode an image that's already in memory, e.g. from `include_bytes!()`
+    pub fn from_bytes(
+        device: &Device,
+        queue: &Queue,
+        bytes: &[u8],
+        label: Option<&str>,
+        options: TextureOptions,
+    ) -> ImageResult<Self> {
+        let image = image::load_from_memory(bytes)?;
+        Self::from_image(device, queue, &image, label, options)
+    }
+
+    /// Upload `image` to the GPU
+    ///
+    /// Fails with `ImageError::Limits` if it's bigger than the device's `max_texture_dimension_2d`
+    pub fn from_image(
+        device: &Device,
+        queue: &Queue,
+        image: &DynamicImage,
+        label: Option<&str>,
+        options: TextureOptions,
+    ) -> ImageResult<Self> {
+        let (width, height) = image.dimensions();
+        // wgpu would only tell us with a validation panic
+        let max_dimension = device.limits().max_texture_dimension_2d;
+        if width > max_dimension || height > max_dimension {
+            log::error!(
+                "{} is {width}x{height}, but this device only supports textures up to {max_dimension}x{max_dimension}",
+                label.unwrap_or("The image")
+            );
+            return Err(ImageError::Limits(LimitError::from_kind(
+                LimitErrorKind::DimensionError,
+            )));
+        }
+        let rgba = image.to_rgba8();
+        let size = Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        };
+
+        // Colours in image files are almost always sRGB, and sampling an `Srgb` texture converts them to linear for us
+        let format = if options.srgb {
+            TextureFormat::Rgba8UnormSrgb
+        } else {
+            TextureFormat::Rgba8Unorm
+        };
+        let mip_level_count = if options.generate_mipmaps {
+            mipmap::mip_level_count(width, height)
+        } else {
+            1
+        };
+        let texture = device.create_texture(&TextureDescriptor {
+            label,
+            size,
+            mip_level_count,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format,
+            // `TEXTURE_BINDING` so we can use it in shaders, `COPY_DST` so we can copy the image (and any mip levels) into it
+            usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+        });
+        queue.write_texture(
+            ImageCopyTexture {
+                texture: &texture,
+                mip_level: 0,
+                origin: Origin3d::ZERO,
+                aspect: TextureAspect::All,
+            },
+            &rgba,
+            ImageDataLayout {
+                offset: 0,
+                // Unlike `copy_buffer_to_texture()`, `write_texture()` doesn't need rows padded to 256 bytes
+                bytes_per_row: NonZeroU32::new(4 * width),
+                rows_per_image: NonZeroU32::new(height),
+            },
+            size,
+        );
+        mipmap::generate_mipmaps(device, queue, &texture, size, format, mip_level_count);
+
+        let view = texture.create_view(&TextureViewDescriptor::default());
+        let sampler = create_sampler(device, label, mip_level_count, options);
+
+        Ok(Self {
+            texture,
+            view,
+            sampler,
+        })
+    }
+
+    /// Load a DDS or KTX2 file of BC or ASTC compressed blocks, see `from_compressed()`
+    pub fn load_compressed(
+        device: &Device,
+        queue: &Queue,
+        path: impl AsRef<Path>,
+        options: TextureOptions,
+    ) -> Result<Self, CompressedTextureError> {
+        let path = path.as_ref();
+        let image = CompressedImage::from_bytes(&fs::read(path)?)?;
+        Self::from_compressed(device, queue, &image, path.to_str(), options)
+    }
+
+    /// Upload `image` without decompressing it, a quarter of the memory (or less) of the same image in RGBA
+    ///
+    /// Devices without the feature for its format (e.g. `Features::TEXTURE_COMPRESSION_BC`) get it decoded to RGBA first, with mipmaps generated if it had any
+    /// So do images that aren't a whole number of blocks across and down, which wgpu won't accept
+    /// `options` are the same as for `load()`, except the levels come from the file rather than `generate_mipmaps()`,
+    /// and its sRGB setting wins over whatever the file says
+    pub fn from_compressed(
+        device: &Device,
+        queue: &Queue,
+        image: &CompressedImage,
+        label: Option<&str>,
+        options: TextureOptions,
+    ) -> Result<Self, CompressedTextureError> {
+        let format = compressed_texture::with_srgb(image.format, options.srgb);
+        let info = format.describe();
+        let (block_width, block_height) = info.block_dimensions;
+        let decode_reason = if !device.features().contains(info.required_features) {
+            Some(format!("the device doesn't support {format:?}"))
+        } else if !image.width.is_multiple_of(block_width as u32)
+            || !image.height.is_multiple_of(block_height as u32)
+        {
+            Some(format!(
+                "it's {}x{}, which isn't a whole number of {block_width}x{block_height} blocks",
+                image.width, image.height
+            ))
+        } else {
+            None
+        };
+        if let Some(reason) = decode_reason {
+            log::info!(
+                "Decoding {} since {reason}",
+                label.unwrap_or("a compressed texture")
+            );
+            let rgba = DynamicImage::ImageRgba8(image.decode()?);
+            return Ok(Self::from_image(
+                device,
+                queue,
+                &rgba,
+                label,
+                options.generate_mipmaps(image.levels.len() > 1),
+            )?);
+        }
+        let max_dimension = device.limits().max_texture_dimension_2d;
+        if image.width > max_dimension || image.height > max_dimension {
+            log::error!(
+                "{} is {}x{}, but this device only supports textures up to {max_dimension}x{max_dimension}",
+                label.unwrap_or("The image"),
+                image.width,
+                image.height
+            );
+            return Err(CompressedTextureError::Image(ImageError::Limits(
+                LimitError::from_kind(LimitErrorKind::DimensionError),
+            )));
+        }
+        let mip_level_count = image.levels.len() as u32;
+        let texture = device.create_texture(&TextureDescriptor {
+            label,
+            size: image.level_size(0),
+            mip_level_count,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format,
+            // Compressed formats can't be rendered to, so the mipmaps have to come from the file
+            usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+        });
+        for (level, data) in (0..).zip(&image.levels) {
+            // Levels smaller than a block still take up a whole one
+            let size = image.level_size(level).physical_size(format);
+            queue.write_texture(
+                ImageCopyTexture {
+                    texture: &texture,
+                    mip_level: level,
+                    origin: Origin3d::ZERO,
+                    aspect: TextureAspect::All,
+                },
+                data,
+                ImageDataLayout {
+                    offset: 0,
+                    // A row of blocks, not pixels
+                    bytes_per_row: NonZeroU32::new(
+                        size.width / block_width as u32 * info.block_size as u32,
+                    ),
+                    rows_per_image: None,
+                },
+                size,
+            );
+        }
+
+        let view = texture.create_view(&TextureViewDescriptor::default());
+        let sampler = create_sampler(device, label, mip_level_count, options);
+
+        Ok(Self {
+            texture,
+            view,
+            sampler,
+        })
+    }
+
+    /// Upload `images` as the layers of one 2D array texture, so a shader can pick between them without another bind group
+    ///
+    /// They all have to be the same size, and there can't be more than the device's `max_texture_array_layers`
+    /// There aren't any mipmaps, like sprites don't have
+    /// Only `SpriteBatch` binds these, the mesh shader's `t_diffuse` is a plain `texture_2d`
+    pub fn array_from_images(
+        device: &Device,
+        queue: &Queue,
+        images: &[DynamicImage],
+        label: Option<&str>,
+        srgb: bool,
+    ) -> ImageResult<Self> {
+        let (width, height) = match images.first() {
+            Some(image) => image.dimensions(),
+            None => {
+                log::error!("{} has no layers", label.unwrap_or("The texture array"));
+                return Err(ImageError::Parameter(ParameterError::from_kind(
+                    ParameterErrorKind::DimensionMismatch,
+                )));
+            }
+        };
+        if images
+            .iter()
+            .any(|image| image.dimensions() != (width, height))
+        {
+            log::error!(
+                "The layers of {} have to be the same size",
+                label.unwrap_or("a texture array")
+            );
+            return Err(ImageError::Parameter(ParameterError::from_kind(
+                ParameterErrorKind::DimensionMismatch,
+            )));
+        }
+        let limits = device.limits();
+        let max_dimension = limits.max_texture_dimension_2d;
+        let max_layers = limits.max_texture_array_layers;
+        if width > max_dimension || height > max_dimension || images.len() as u32 > max_layers {
+            log::error!(
+                "{} is {width}x{height} with {} layers, but this device only supports textures up to {max_dimension}x{max_dimension} with {max_layers} layers",
+                label.unwrap_or("The texture array"),
+                images.len(),
+            );
+            return Err(ImageError::Limits(LimitError::from_kind(
+                LimitErrorKind::DimensionError,
+            )));
+        }
+        // wgpu's GLES backend (`Backends::GL` and WebGL2) has to pick the GL texture target up front, and guesses it from the size:
+        // one layer makes a `TEXTURE_2D`, and square ones in multiples of six a cubemap (array)
+        // Neither can be viewed as a `texture_2d_array`, so there's an extra unused layer in those cases, the other backends don't need it
+        let mut layers = images.len() as u32;
+        if layers == 1 || (width == height && layers.is_multiple_of(6)) {
+            layers += 1;
+        }
+        let size = Extent3d {
+            width,
+            height,
+            depth_or_array_layers: layers,
+        };
+        let format = if srgb {
+            TextureFormat::Rgba8UnormSrgb
+        } else {
+            TextureFormat::Rgba8Unorm
+        };
+        let texture = device.create_texture(&TextureDescriptor {
+            label,
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format,
+            usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+        });
+        for (layer, image) in (0..).zip(images) {
+            queue.write_texture(
+                ImageCopyTexture {
+                    texture: &texture,
+                    mip_level: 0,
+                    origin: Origin3d {
+                        x: 0,
+                        y: 0,
+                        z: layer,
+                    },
+                    aspect: TextureAspect::All,
+                },
+                &image.to_rgba8(),
+                ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: NonZeroU32::new(4 * width),
+                    rows_per_image: NonZeroU32::new(height),
+                },
+                Extent3d {
+                    depth_or_array_layers: 1,
+                    ..size
+                },
+            );
+        }
+        let view = texture.create_view(&TextureViewDescriptor {
+            label,
+            dimension: Some(TextureViewDimension::D2Array),
+            ..TextureViewDescriptor::default()
+        });
+        // The same as `from_image()` without mipmaps
+        let sampler = device.create_sampler(&SamplerDescriptor {
+            label,
+            address_mode_u: AddressMode::Repeat,
+            address_mode_v: AddressMode::Repeat,
+            address_mode_w: AddressMode::Repeat,
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Nearest,
+            mipmap_filter: FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        Ok(Self {
+            texture,
+            view,
+            sampler,
+        })
+    }
+
+    /// The layout for a bind group with the texture at `@binding(0)` and its sampler at `@binding(1)`
+    pub fn bind_group_layout(device: &Device) -> BindGroupLayout {
+        create_bind_group_layout(
+            device,
+            "Texture Bind Group Layout",
+            TextureViewDimension::D2,
+        )
+    }
+
+    /// The same as `bind_group_layout()`, but for a texture from `array_from_images()`
+    pub fn array_bind_group_layout(device: &Device) -> BindGroupLayout {
+        create_bind_group_layout(
+            device,
+            "Texture Array Bind Group Layout",
+            TextureViewDimension::D2Array,
+        )
+    }
+
+    /// Create a bind group for this texture that matches `Texture::bind_group_layout()` (or `array_bind_group_layout()` for an array)
+    pub fn bind_group(&self, device: &Device, layout: &BindGroupLayout) -> BindGroup {
+        device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Texture Bind Group"),
+            layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::TextureView(&self.view),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::Sampler(&self.sampler),
+                },
+            ],
+        })
+    }
+}
+
+/// A bind group layout with a filterable texture viewed as `view_dimension` at `@binding(0)` and its sampler at `@binding(1)`
+fn create_bind_group_layout(
+    device: &Device,
+    label: &str,
+    view_dimension: TextureViewDimension,
+) -> BindGroupLayout {
+    device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+        label: Some(label),
+        entries: &[
+            BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::FRAGMENT,
+                ty: BindingType::Texture {
+                    sample_type: TextureSampleType::Float { filterable: true },
+                    view_dimension,
+                    multisampled: false,
+                },
+                count: None,
+            },
+            BindGroupLayoutEntry {
+                binding: 1,
+                visibility: ShaderStages::FRAGMENT,
+                // Has to agree with the `filterable` above
+                ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                count: None,
+            },
+        ],
+    })
+}
+
+/// The sampler for a texture loaded from a file, which repeats outside of 0..1
+fn create_sampler(
+    device: &Device,
+    label: Option<&str>,
+    mip_level_count: u32,
+    options: TextureOptions,
+) -> Sampler {
+    // With mipmaps, blend between pixels and between levels when minified, so it's smooth at any distance
+    // Otherwise just pick the nearest pixel, since there's nothing to blend towards
+    let min_filter = if mip_level_count > 1 {
+        FilterMode::Linear
+    } else {
+        FilterMode::Nearest
+    };
+    device.create_sampler(&SamplerDescriptor {
+        label,
+        // What to do with texture coordinates outside of 0..1
+        address_mode_u: AddressMode::Repeat,
+        address_mode_v: AddressMode::Repeat,
+        address_mode_w: AddressMode::Repeat,
+        // Blend between pixels when magnified
+        mag_filter: FilterMode::Linear,
+        min_filter,
+        mipmap_filter: min_filter,
+        anisotropy_clamp: NonZeroU8::new(clamp_anisotropy(options, mip_level_count > 1, label)),
+        ..Default::default()
+    })
+}
+
+/// Round the anisotropy in `options` down to something wgpu accepts, or 1 (off) if there are no mipmaps for it to work with
+/// or the adapter can't do anisotropic filtering at all
+fn clamp_anisotropy(options: TextureOptions, mipmapped: bool, label: Option<&str>) -> u8 {
+    let anisotropy = options.anisotropy;
+    if anisotropy <= 1 {
+        return 1;
+    }
+    if !options.anisotropic_filtering {
+        log::info!(
+            "{} can't use anisotropic filtering, the adapter doesn't support it",
+            label.unwrap_or("The texture")
+        );
+        return 1;
+    }
+    if !mipmapped {
+        log::warn!(
+            "{} has no mipmaps, so it can't use anisotropic filtering",
+            label.unwrap_or("The texture")
+        );
+        return 1;
+    }
+    // The highest power of 2 that isn't more than `anisotropy`
+    (1 << (u8::BITS - 1 - anisotropy.leading_zeros())).min(MAX_ANISOTROPY)
+}
+
+/// Scale `size` down (keeping its aspect ratio) so it fits within the device's `max_texture_dimension_2d`
+///
+/// For the textures that have to match a window, since a big window on a `downlevel_defaults()` device can go past the limit
+/// It doesn't log anything since it runs on every resize, `RenderTarget` warns when the size it ends up with changes
+pub fn clamp_size(device: &Device, size: PhysicalSize<u32>) -> PhysicalSize<u32> {
+    let max_dimension = device.limits().max_texture_dimension_2d;
+    let largest = size.width.max(size.height);
+    if largest <= max_dimension {
+        return size;
+    }
+    let scale = max_dimension as f64 / largest as f64;
+    PhysicalSize::new(
+        ((size.width as f64 * scale) as u32).clamp(1, max_dimension),
+        ((size.height as f64 * scale) as u32).clamp(1, max_dimension),
+    )
+}
+
+/// A plain linear sampler that clamps to the edges, for sampling textures we rendered ourselves
+pub(crate) fn create_clamped_sampler(device: &Device, compare: Option<CompareFunction>) -> Sampler {
+    device.create_sampler(&SamplerDescriptor {
+        address_mode_u: AddressMode::ClampToEdge,
+        address_mode_v: AddressMode::ClampToEdge,
+        address_mode_w: AddressMode::ClampToEdge,
+        mag_filter: FilterMode::Linear,
+        min_filter: FilterMode::Linear,
+        mipmap_filter: FilterMode::Nearest,
+        // Only depth textures want a comparison sampler
+        compare,
+        ..Default::default()
+    })
+}
+/// Create a depth texture that is `size` big, which should be the size of the surface it's used with
+///
+/// `sample_count` has to match the colour attachment it's used alongside
+pub fn create_depth_texture(
+    device: &Device,
+    size: PhysicalSize<u32>,
+    sample_count: u32,
+) -> Texture {
+    let texture = device.create_texture(&TextureDescriptor {
+        label: Some("Depth Texture"),
+        // Has to match the colour attachments exactly, otherwise the render pass won't accept it
+        size: Extent3d {
+            width: size.width,
+            height: size.height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count,
+        dimension: TextureDimension::D2,
+        format: DEPTH_FORMAT,
+        // We render to it, and might want to read from it in a shader later
+        usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+    });
+    let view = texture.create_view(&TextureViewDescriptor::default());
+    // Lets us compare against the depth in a shader later on, which is how shadow maps work
+    let sampler = create_clamped_sampler(device, Some(CompareFunction::LessEqual));
+    Texture {
+        texture,
+        view,
+        sampler,
+    }
+}
+
+/// Create the multisampled colour texture we render into before it gets resolved to the surface (or scene texture)
+///
+/// `format` is what it gets resolved into, returns `None` when `sample_count` is 1, since then we can just render straight into that
+pub fn create_msaa_texture(
+    device: &Device,
+    config: &SurfaceConfiguration,
+    format: TextureFormat,
+    sample_count: u32,
+) -> Option<Texture> {
+    if sample_count <= 1 {
+        return None;
+    }
+    let texture = device.create_texture(&TextureDescriptor {
+        label: Some("MSAA Texture"),
+        size: Extent3d {
+            width: config.width,
+            height: config.height,
+            depth_or_array_layers: 1,
+        },
+        // Multisampled textures can't have mipmaps
+        mip_level_count: 1,
+        sample_count,
+        dimension: TextureDimension::D2,
+        // Has to match what it gets resolved into
+        format,
+        usage: TextureUsages::RENDER_ATTACHMENT,
+    });
+    let view = texture.create_view(&TextureViewDescriptor::default());
+    // Multisampled textures can't be filtered, but every `Texture` needs a sampler
+    let sampler = create_clamped_sampler(device, None);
+    Some(Texture {
+        texture,
+        view,
+        sampler,
+    })
+}
+
+/// Create a texture with the same size and format as the surface that we can render into and then copy out of
+pub fn create_render_target(device: &Device, config: &SurfaceConfiguration) -> Texture {
+    let texture = device.create_texture(&TextureDescriptor {
+        label: Some("Render Target"),
+        size: Extent3d {
+            width: config.width,
+            height: config.height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: TextureDimension::D2,
+        format: config.format,
+        // `COPY_SRC` so we can copy it into a buffer and read it back on the CPU
+        usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::COPY_SRC,
+    });
+    let view = texture.create_view(&TextureViewDescriptor::default());
+    let sampler = create_clamped_sampler(device, None);
+    Texture {
+        texture,
+        view,
+        sampler,
+    }
+}
+
+/// Create a texture the size of the surface to draw the scene into, for post-processing to sample from
+pub fn create_scene_texture(
+    device: &Device,
+    config: &SurfaceConfiguration,
+    format: TextureFormat,
+) -> Texture {
+    let texture = device.create_texture(&TextureDescriptor {
+        label: Some("Scene Texture"),
+        size: Extent3d {
+            width: config.width,
+            height: config.height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        // When multisampling the scene gets resolved into this, same as it would into the surface
+        sample_count: 1,
+        dimension: TextureDimension::D2,
+        format,
+        // Drawn into by the scene's pass, then sampled by the post-processing pass
+        usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+    });
+    let view = texture.create_view(&TextureViewDescriptor::default());
+    let sampler = create_clamped_sampler(device, None);
+    Texture {
+        texture,
+        view,
+        sampler,
+    }
+}
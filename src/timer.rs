@@ -0,0 +1,59 @@
+use std::{collections::VecDeque, time::Duration};
+
+// `std::time::Instant` panics in the browser
+use instant::Instant;
+
+/// How far back the FPS average looks
+const WINDOW: Duration = Duration::from_secs(1);
+/// Any gap between frames longer than this is treated as a pause (e.g. the window was minimized) rather than a slow frame
+const MAX_FRAME_GAP: Duration = Duration::from_secs(1);
+
+/// Keeps track of how long recent frames took
+#[derive(Debug, Default)]
+pub struct FrameTimer {
+    last_frame: Option<Instant>,
+    /// Durations of the frames within the last `WINDOW`, oldest first
+    frame_times: VecDeque<Duration>,
+    /// Sum of `frame_times`, so we don't have to add them all up every time
+    total: Duration,
+}
+
+impl FrameTimer {
+    /// Record that a frame has just started
+    pub fn tick(&mut self) {
+        let now = Instant::now();
+        if let Some(last_frame) = self.last_frame {
+            let dt = now - last_frame;
+            if dt > MAX_FRAME_GAP {
+                // We were paused, so the old frames don't tell us anything about how fast we are now
+                self.reset();
+            } else {
+                self.frame_times.push_back(dt);
+                self.total += dt;
+                while self.total > WINDOW {
+                    match self.frame_times.pop_front() {
+                        Some(oldest) => self.total -= oldest,
+                        None => break,
+                    }
+                }
+            }
+        }
+        self.last_frame = Some(now);
+    }
+
+    /// Forget all recorded frames
+    pub fn reset(&mut self) {
+        self.last_frame = None;
+        self.frame_times.clear();
+        self.total = Duration::ZERO;
+    }
+
+    /// Average frames-per-second over roughly the last second, 0 if we don't know yet
+    pub fn fps(&self) -> f32 {
+        if self.total.is_zero() {
+            0.0
+        } else {
+            self.frame_times.len() as f32 / self.total.as_secs_f32()
+        }
+    }
+}
@@ -0,0 +1,274 @@
+use egui::{ClippedPrimitive, Context, PlatformOutput, RawInput, TexturesDelta};
+use egui_wgpu::renderer::{Renderer, ScreenDescriptor};
+use wgpu::{
+    CommandEncoder, Device, LoadOp, Operations, Queue, RenderPassColorAttachment,
+    RenderPassDescriptor, TextureFormat, TextureView,
+};
+use winit::{dpi::PhysicalSize, event::WindowEvent, window::Window};
+
+use crate::anti_aliasing::AntiAliasing;
+use crate::camera::Projection;
+use crate::color;
+use crate::debug_view::DebugView;
+use crate::post_process::{PostEffect, Tonemap};
+use crate::render_target::CULL_MODES;
+use crate::state::State;
+
+/// Lets callers add their own widgets, called every `State::update()` while the UI is visible
+pub type UiCallback = Box<dyn FnMut(&Context, &mut State)>;
+
+/// An egui debug UI, drawn on top of the main window
+pub struct Ui {
+    pub context: Context,
+    /// Turns winit events into egui input
+    winit_state: egui_winit::State,
+    renderer: Renderer,
+    /// Taken by `begin_frame()`, waiting for the next `State::update()`
+    raw_input: Option<RawInput>,
+    /// Cursor changes and the like from the last run, which need the window to apply
+    platform_output: Option<PlatformOutput>,
+    /// What the last run drew, kept so frames without an update still show the UI
+    paint_jobs: Vec<ClippedPrimitive>,
+    /// Font atlas (and other texture) changes from the last run, only uploaded once
+    textures_delta: TexturesDelta,
+}
+
+impl Ui {
+    /// Create a UI drawing into textures with `format`
+    pub fn new(device: &Device, format: TextureFormat, pixels_per_point: f32) -> Self {
+        // No clipboard support, so there's no need for the Wayland display
+        let mut winit_state = egui_winit::State::new_with_wayland_display(None);
+        winit_state.set_max_texture_side(device.limits().max_texture_dimension_2d as usize);
+        winit_state.set_pixels_per_point(pixels_per_point);
+        Self {
+            context: Context::default(),
+            winit_state,
+            // Drawn straight onto the resolved output, so no depth or multisampling
+            renderer: Renderer::new(device, format, None, 1),
+            raw_input: None,
+            platform_output: None,
+            paint_jobs: Vec::new(),
+            textures_delta: TexturesDelta::default(),
+        }
+    }
+
+    /// Physical pixels per egui point, follows `ScaleFactorChanged`
+    pub fn pixels_per_point(&self) -> f32 {
+        self.winit_state.pixels_per_point()
+    }
+
+    pub fn set_pixels_per_point(&mut self, pixels_per_point: f32) {
+        self.winit_state.set_pixels_per_point(pixels_per_point);
+    }
+
+    /// Returns whether egui wants the event to itself, e.g. a click on a window
+    pub fn on_event(&mut self, event: &WindowEvent) -> bool {
+        self.winit_state.on_event(&self.context, event).consumed
+    }
+
+    /// Collect the input for the next run, and apply what the last run asked of the window
+    pub fn begin_frame(&mut self, window: &Window) {
+        if let Some(platform_output) = self.platform_output.take() {
+            self.winit_state
+                .handle_platform_output(window, &self.context, platform_output);
+        }
+        self.raw_input = Some(self.winit_state.take_egui_input(window));
+    }
+
+    /// Lay out the UI with `run_ui`, using the input from `begin_frame()`
+    ///
+    /// Without a `begin_frame()` (e.g. when headless) there's no new input, but it still runs
+    pub fn run(&mut self, run_ui: impl FnOnce(&Context)) {
+        let raw_input = self.raw_input.take().unwrap_or_default();
+        let output = self.context.run(raw_input, run_ui);
+        self.platform_output = Some(output.platform_output);
+        self.paint_jobs = self.context.tessellate(output.shapes);
+        self.textures_delta.append(output.textures_delta);
+    }
+
+    /// Record drawing the UI on top of `view`, which is `size` big
+    pub fn draw(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        encoder: &mut CommandEncoder,
+        view: &TextureView,
+        size: PhysicalSize<u32>,
+    ) {
+        let screen_descriptor = ScreenDescriptor {
+            size_in_pixels: [size.width, size.height],
+            pixels_per_point: self.pixels_per_point(),
+        };
+        let textures_delta = std::mem::take(&mut self.textures_delta);
+        for (id, image_delta) in &textures_delta.set {
+            self.renderer
+                .update_texture(device, queue, *id, image_delta);
+        }
+        // Only ever returns command buffers from paint callbacks, which we don't use
+        self.renderer
+            .update_buffers(device, queue, encoder, &self.paint_jobs, &screen_descriptor);
+        {
+            let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+                label: Some("UI Render Pass"),
+                color_attachments: &[Some(RenderPassColorAttachment {
+                    view,
+                    resolve_target: None,
+                    ops: Operations {
+                        // Keep the scene underneath
+                        load: LoadOp::Load,
+                        store: true,
+                    },
+                })],
+                depth_stencil_attachment: None,
+            });
+            self.renderer
+                .render(&mut render_pass, &self.paint_jobs, &screen_descriptor);
+        }
+        // Safe to free now, the commands using them are already recorded
+        for id in &textures_delta.free {
+            self.renderer.free_texture(id);
+        }
+    }
+}
+
+/// The built-in settings window, for the clear colour, post-processing and the camera
+pub fn settings_window(context: &Context, state: &mut State) {
+    egui::Window::new("Settings").show(context, |ui| {
+        ui.heading("Clear colour");
+        let mut clear_color = color::to_srgb(state.clear_color);
+        // Only written back when it changes, so it doesn't get rounded to 8 bits every frame
+        if ui
+            .color_edit_button_srgba_unmultiplied(&mut clear_color)
+            .changed()
+        {
+            let [r, g, b, a] = clear_color;
+            state.set_clear_color(color::from_srgb(r, g, b, a));
+        }
+
+        ui.heading("Post-processing");
+        let mut post_effect = state.post_effect();
+        egui::ComboBox::from_label("Effect")
+            .selected_text(format!("{post_effect:?}"))
+            .show_ui(ui, |ui| {
+                for effect in PostEffect::ALL {
+                    ui.selectable_value(&mut post_effect, effect, format!("{effect:?}"));
+                }
+            });
+        // Switching to or from `None` recreates the scene textures, so only when it actually changes
+        if post_effect != state.post_effect() {
+            state.set_post_effect(post_effect);
+        }
+        let mut bloom = state.bloom();
+        if ui
+            .add(egui::Slider::new(&mut bloom, 0.0..=4.0).text("Bloom"))
+            .changed()
+        {
+            state.set_bloom(bloom);
+        }
+        let mut tonemap = state.tonemap();
+        egui::ComboBox::from_label("Tonemapping")
+            .selected_text(format!("{tonemap:?}"))
+            .show_ui(ui, |ui| {
+                for operator in Tonemap::ALL {
+                    ui.selectable_value(&mut tonemap, operator, format!("{operator:?}"));
+                }
+            });
+        if tonemap != state.tonemap() {
+            state.set_tonemap(tonemap);
+        }
+        let mut exposure = state.exposure();
+        if ui
+            .add(
+                egui::Slider::new(&mut exposure, 0.1..=8.0)
+                    .logarithmic(true)
+                    .text("Exposure"),
+            )
+            .changed()
+        {
+            state.set_exposure(exposure);
+        }
+
+        ui.heading("Display");
+        let current_mode = state.primary_target().config.present_mode;
+        let mut present_mode = current_mode;
+        egui::ComboBox::from_label("Present mode")
+            .selected_text(format!("{present_mode:?}"))
+            .show_ui(ui, |ui| {
+                for &mode in state.supported_present_modes() {
+                    ui.selectable_value(&mut present_mode, mode, format!("{mode:?}"));
+                }
+            });
+        // Reconfigures the surfaces, so only when it actually changes
+        if present_mode != current_mode {
+            state.set_present_mode(present_mode);
+        }
+        let mut anti_aliasing = state.anti_aliasing();
+        egui::ComboBox::from_label("Anti-aliasing")
+            .selected_text(format!("{anti_aliasing:?}"))
+            .show_ui(ui, |ui| {
+                for option in AntiAliasing::ALL {
+                    ui.selectable_value(&mut anti_aliasing, option, format!("{option:?}"));
+                }
+            });
+        // Rebuilds the pipelines and textures, so only when it actually changes
+        if anti_aliasing != state.anti_aliasing() {
+            state.set_anti_aliasing(anti_aliasing);
+        }
+
+        let mut debug_view = state.debug_view();
+        egui::ComboBox::from_label("Debug view")
+            .selected_text(format!("{debug_view:?}"))
+            .show_ui(ui, |ui| {
+                for view in DebugView::ALL {
+                    ui.selectable_value(&mut debug_view, view, format!("{view:?}"));
+                }
+            });
+        state.set_debug_view(debug_view);
+        let mut cull_mode = state.cull_mode();
+        egui::ComboBox::from_label("Cull mode")
+            .selected_text(format!("{cull_mode:?}"))
+            .show_ui(ui, |ui| {
+                for mode in CULL_MODES {
+                    ui.selectable_value(&mut cull_mode, mode, format!("{mode:?}"));
+                }
+            });
+        state.set_cull_mode(cull_mode);
+
+        ui.heading("Camera");
+        match &mut state.camera.projection {
+            Projection::Perspective { fovy } => {
+                let mut degrees = fovy.to_degrees();
+                if ui
+                    .add(egui::Slider::new(&mut degrees, 10.0..=120.0).text("FOV (degrees)"))
+                    .changed()
+                {
+                    *fovy = degrees.to_radians();
+                }
+            }
+            Projection::Orthographic { height } => {
+                ui.add(
+                    egui::Slider::new(height, 0.1..=100.0)
+                        .logarithmic(true)
+                        .text("Height"),
+                );
+            }
+        }
+        ui.add(egui::Slider::new(&mut state.camera_controller.speed, 0.1..=20.0).text("Speed"));
+        ui.add(
+            egui::Slider::new(&mut state.camera_controller.sensitivity, 0.001..=0.02)
+                .text("Sensitivity"),
+        );
+        let mut frustum_culling = state.frustum_culling();
+        if ui
+            .checkbox(&mut frustum_culling, "Frustum culling")
+            .changed()
+        {
+            state.set_frustum_culling(frustum_culling);
+        }
+        let stats = state.culling_stats();
+        ui.label(format!(
+            "{} instances drawn, {} culled",
+            stats.drawn, stats.culled
+        ));
+    });
+}
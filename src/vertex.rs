@@ -0,0 +1,162 @@
+use std::{borrow::Cow, error::Error, fmt, mem};
+
+use bytemuck::{Pod, Zeroable};
+use wgpu::{vertex_attr_array, BufferAddress, VertexAttribute, VertexBufferLayout, VertexStepMode};
+
+use crate::state::error_description;
+
+/// A single vertex as it is laid out in the vertex buffer
+// `Pod` and `Zeroable` let us cast a `&[Vertex]` to a `&[u8]` for uploading to the GPU
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+pub struct Vertex {
+    pub position: [f32; 3],
+    pub color: [f32; 3],
+    /// Where on the texture this vertex is, (0, 0) is the top left
+    pub tex_coords: [f32; 2],
+    /// Which way the surface faces, should be normalised
+    pub normal: [f32; 3],
+}
+
+impl Vertex {
+    // `@location(0)` is the position, `@location(1)` is the colour, `@location(2)` is the texture coordinates, and `@location(3)` is the normal
+    const ATTRIBUTES: [VertexAttribute; 4] =
+        vertex_attr_array![0 => Float32x3, 1 => Float32x3, 2 => Float32x2, 3 => Float32x3];
+
+    /// Describes to the pipeline how a `Vertex` is laid out in the buffer
+    pub fn desc<'a>() -> VertexBufferLayout<'a> {
+        VertexBufferLayout {
+            // How wide a single vertex is, so the shader knows how far to skip to get to the next one
+            array_stride: mem::size_of::<Vertex>() as BufferAddress,
+            // Each element of the buffer is a vertex (rather than an instance)
+            step_mode: VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+}
+
+/// How the vertex colour is blended across each triangle, see `State::set_color_interpolation()`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ColorInterpolation {
+    /// A gradient between the colours of the triangle's corners
+    #[default]
+    Smooth,
+    /// The whole triangle is the colour of its provoking vertex, which is the first of its three, for a low-poly look
+    Flat,
+}
+
+impl ColorInterpolation {
+    /// Both of them, e.g. for listing them in a UI
+    pub const ALL: [ColorInterpolation; 2] = [ColorInterpolation::Smooth, ColorInterpolation::Flat];
+
+    /// How the shader's `VertexOutput` declares its colour when it's interpolated this way
+    pub fn declaration(self) -> &'static str {
+        match self {
+            ColorInterpolation::Smooth => "@interpolate(perspective) color:",
+            ColorInterpolation::Flat => "@interpolate(flat) color:",
+        }
+    }
+
+    /// `source` with the colour declared this way, left alone if it's already declared this way (or not declared either way)
+    pub fn apply(self, source: &str) -> Cow<'_, str> {
+        let other = match self {
+            ColorInterpolation::Smooth => ColorInterpolation::Flat,
+            ColorInterpolation::Flat => ColorInterpolation::Smooth,
+        };
+        if source.contains(other.declaration()) {
+            Cow::Owned(source.replace(other.declaration(), self.declaration()))
+        } else {
+            Cow::Borrowed(source)
+        }
+    }
+}
+
+/// Everything that can go wrong in `State::set_color_interpolation()`
+#[derive(Debug)]
+pub enum ColorInterpolationError {
+    /// The shader doesn't declare its colour with `ColorInterpolation::declaration()`, so there's nothing to switch
+    NotDeclared(ColorInterpolation),
+    /// The pipelines couldn't be rebuilt with the colour declared the new way
+    Pipeline(wgpu::Error),
+}
+
+impl fmt::Display for ColorInterpolationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ColorInterpolationError::NotDeclared(interpolation) => write!(
+                f,
+                "the shader doesn't declare its colour with `{}`",
+                interpolation.declaration()
+            ),
+            ColorInterpolationError::Pipeline(err) => {
+                write!(
+                    f,
+                    "failed to rebuild the pipelines: {}",
+                    error_description(err)
+                )
+            }
+        }
+    }
+}
+
+impl Error for ColorInterpolationError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ColorInterpolationError::NotDeclared(_) => None,
+            ColorInterpolationError::Pipeline(err) => Some(err),
+        }
+    }
+}
+
+/// The same triangle the vertex shader used to generate by itself
+pub const TRIANGLE: &[Vertex] = &[
+    Vertex {
+        position: [0.5, -0.5, 0.0],
+        color: [0.3, 0.2, 0.1],
+        tex_coords: [1.0, 1.0],
+        normal: [0.0, 0.0, 1.0],
+    },
+    Vertex {
+        position: [0.0, 0.5, 0.0],
+        color: [0.3, 0.2, 0.1],
+        tex_coords: [0.5, 0.0],
+        normal: [0.0, 0.0, 1.0],
+    },
+    Vertex {
+        position: [-0.5, -0.5, 0.0],
+        color: [0.3, 0.2, 0.1],
+        tex_coords: [0.0, 1.0],
+        normal: [0.0, 0.0, 1.0],
+    },
+];
+
+/// The corners of a square, meant to be drawn with `QUAD_INDICES`
+pub const QUAD_VERTICES: &[Vertex] = &[
+    Vertex {
+        position: [-0.5, -0.5, 0.0],
+        color: [0.3, 0.2, 0.1],
+        tex_coords: [0.0, 1.0],
+        normal: [0.0, 0.0, 1.0],
+    },
+    Vertex {
+        position: [0.5, -0.5, 0.0],
+        color: [0.1, 0.3, 0.2],
+        tex_coords: [1.0, 1.0],
+        normal: [0.0, 0.0, 1.0],
+    },
+    Vertex {
+        position: [0.5, 0.5, 0.0],
+        color: [0.2, 0.1, 0.3],
+        tex_coords: [1.0, 0.0],
+        normal: [0.0, 0.0, 1.0],
+    },
+    Vertex {
+        position: [-0.5, 0.5, 0.0],
+        color: [0.3, 0.3, 0.1],
+        tex_coords: [0.0, 0.0],
+        normal: [0.0, 0.0, 1.0],
+    },
+];
+
+/// Two counter-clockwise trongles that share the diagonal of `QUAD_VERTICES`
+pub const QUAD_INDICES: &[u16] = &[0, 1, 2, 0, 2, 3];